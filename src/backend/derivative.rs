@@ -0,0 +1,349 @@
+use std::f64::consts::{LN_10, LN_2};
+
+use super::array_length;
+use builtins;
+use environment::Environment;
+use syntax::{
+    mentions, Array, Atom, BinaryOp, Conditional, Expr, FunctionCall, Index, Integral, Lambda, Op,
+    Series, SeriesKind,
+};
+
+/// Symbolically differentiate a type checked expression with respect to a
+/// float variable, so `solve()` can use Newton's method.
+///
+/// Returns `None` if part of the expression has no derivative we can write
+/// down, like a host function or anything involving complex numbers.
+/// Functions which are piecewise constant (e.g. `floor()`) are treated as
+/// having a slope of zero.
+pub fn differentiate(expr: &Expr, variable: &str, env: &Environment) -> Option<Expr> {
+    Differentiator { variable, env }.derivative(expr)
+}
+
+struct Differentiator<'a> {
+    variable: &'a str,
+    env: &'a Environment,
+}
+
+impl<'a> Differentiator<'a> {
+    fn derivative(&self, expr: &Expr) -> Option<Expr> {
+        if !mentions(expr, self.variable) {
+            return Some(zero_like(expr));
+        }
+
+        match *expr {
+            // the only atom which mentions the variable is the variable
+            Expr::Atom(..) => Some(number(1.0)),
+            Expr::BinaryOp(ref op) => self.binary_op(op),
+            Expr::Conditional(ref cond) => {
+                let if_true = self.derivative(&cond.if_true)?;
+                let if_false = self.derivative(&cond.if_false)?;
+                Some(Conditional::new(cond.condition.clone(), if_true, if_false).into())
+            }
+            Expr::FunctionCall(ref call) => self.function_call(call),
+            Expr::Array(ref array) => {
+                let elements = array
+                    .elements
+                    .iter()
+                    .map(|element| self.derivative(element))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(Array::new(elements).into())
+            }
+            Expr::Index(ref index) => {
+                let array = self.derivative(&index.array)?;
+                Some(Index::new(array, index.index.clone()).into())
+            }
+            // the variable is shadowed, and integer bounds can't depend on it
+            Expr::Series(ref series) if series.variable == self.variable => Some(number(0.0)),
+            Expr::Series(ref series) if series.kind == SeriesKind::Sum => {
+                let body = self.derivative(&series.body)?;
+                let series = Series::new(
+                    series.kind,
+                    series.variable.clone(),
+                    series.start.clone(),
+                    series.end.clone(),
+                    body,
+                );
+                Some(series.into())
+            }
+            Expr::Integral(ref integral) => self.integral(integral),
+            // the body's derivative depends on the value's
+            Expr::Let(ref binding) => self.derivative(&binding.inlined()),
+            _ => None,
+        }
+    }
+
+    fn binary_op(&self, op: &BinaryOp) -> Option<Expr> {
+        let (u, v) = (&op.left, &op.right);
+        let du = self.derivative(u)?;
+        let dv = self.derivative(v)?;
+
+        let derivative = match op.op {
+            Op::Add => add(du, dv),
+            Op::Subtract => sub(du, dv),
+            Op::Multiply => add(mul(du, v.clone()), mul(u.clone(), dv)),
+            Op::Divide => {
+                let numerator = mul(u.clone(), dv);
+                sub(
+                    div(du, v.clone()),
+                    div(numerator, mul(v.clone(), v.clone())),
+                )
+            }
+            _ => return None,
+        };
+
+        Some(derivative)
+    }
+
+    /// Integrals use Leibniz's rule, so the bounds may depend on the
+    /// variable as well as the body.
+    fn integral(&self, integral: &Integral) -> Option<Expr> {
+        // the body can only depend on the variable if it isn't shadowed
+        let inside = if integral.variable == self.variable {
+            number(0.0)
+        } else {
+            match self.derivative(&integral.body)? {
+                ref body if is_number(body, 0.0) => number(0.0),
+                body => Integral::new(
+                    body,
+                    integral.variable.clone(),
+                    integral.lower.clone(),
+                    integral.upper.clone(),
+                ).into(),
+            }
+        };
+
+        let body = Lambda::new(integral.variable.clone(), integral.body.clone());
+        let upper = mul(body.apply(&integral.upper), self.derivative(&integral.upper)?);
+        let lower = mul(body.apply(&integral.lower), self.derivative(&integral.lower)?);
+
+        Some(sub(add(inside, upper), lower))
+    }
+
+    /// Built-ins use the chain rule, `d/dx f(u) = f'(u) * du/dx`.
+    fn function_call(&self, call: &FunctionCall) -> Option<Expr> {
+        if self.env.function(&call.name).is_some() {
+            return None;
+        }
+        if builtins::reduction(&call.name, call.arguments.len()).is_some() {
+            return self.reduction(call);
+        }
+
+        let args = &call.arguments;
+        let u = args[0].clone();
+        let du = self.derivative(&u)?;
+
+        let slope = match (call.name.as_str(), args.len()) {
+            ("sqrt", 1) => div(number(0.5), call1("sqrt", u)),
+            ("sin", 1) => call1("cos", u),
+            ("cos", 1) => sub(number(0.0), call1("sin", u)),
+            ("exp", 1) => call1("exp", u),
+            ("ln", 1) => div(number(1.0), u),
+            ("log2", 1) => div(number(1.0 / LN_2), u),
+            ("log10", 1) => div(number(1.0 / LN_10), u),
+            ("abs", 1) => {
+                let negative = BinaryOp::new(u, number(0.0), Op::LessThan);
+                Conditional::new(negative.into(), number(-1.0), number(1.0)).into()
+            }
+            ("floor", 1) | ("ceil", 1) | ("round", 1) => return Some(number(0.0)),
+            ("pow", 2) => return self.pow(&u, &args[1], du),
+            ("min", 2) | ("max", 2) => {
+                let dv = self.derivative(&args[1])?;
+                let op = if call.name == "min" {
+                    Op::LessThan
+                } else {
+                    Op::GreaterThan
+                };
+                let first = BinaryOp::new(u, args[1].clone(), op);
+                return Some(Conditional::new(first.into(), du, dv).into());
+            }
+            _ => return None,
+        };
+
+        Some(mul(du, slope))
+    }
+
+    fn pow(&self, base: &Expr, exponent: &Expr, d_base: Expr) -> Option<Expr> {
+        if !mentions(exponent, self.variable) {
+            // n * u^(n - 1)
+            let reduced = sub(exponent.clone(), number(1.0));
+            let slope = mul(exponent.clone(), call2("pow", base.clone(), reduced));
+            return Some(mul(d_base, slope));
+        }
+
+        // u^v * (v' * ln(u) + v * u' / u)
+        let d_exponent = self.derivative(exponent)?;
+        let log = mul(d_exponent, call1("ln", base.clone()));
+        let ratio = div(mul(exponent.clone(), d_base), base.clone());
+        let power = call2("pow", base.clone(), exponent.clone());
+
+        Some(mul(power, add(log, ratio)))
+    }
+
+    fn reduction(&self, call: &FunctionCall) -> Option<Expr> {
+        match call.name.as_str() {
+            "sum" | "mean" => {
+                let array = self.derivative(&call.arguments[0])?;
+                Some(FunctionCall::new(call.name.clone(), vec![array]).into())
+            }
+            "dot" => {
+                let (a, b) = (&call.arguments[0], &call.arguments[1]);
+                let da = self.derivative(a)?;
+                let db = self.derivative(b)?;
+                let left = FunctionCall::new("dot", vec![da, b.clone()]);
+                let right = FunctionCall::new("dot", vec![a.clone(), db]);
+                Some(add(left.into(), right.into()))
+            }
+            // min() and max() pick an element we don't know until runtime
+            _ => None,
+        }
+    }
+}
+
+fn number(n: f64) -> Expr {
+    Atom::Number(n).into()
+}
+
+/// A zero with the same shape as `expr`, so arrays stay arrays.
+fn zero_like(expr: &Expr) -> Expr {
+    match array_length(expr) {
+        Some(len) => Array::new(vec![number(0.0); len]).into(),
+        None => number(0.0),
+    }
+}
+
+fn is_number(expr: &Expr, value: f64) -> bool {
+    match *expr {
+        Expr::Atom(Atom::Number(n), _) => n == value,
+        _ => false,
+    }
+}
+
+/// Is `expr` a zero we can drop without losing track of an array's
+/// length?
+fn is_scalar_zero(expr: &Expr, other: &Expr) -> bool {
+    is_number(expr, 0.0) && array_length(other).is_none()
+}
+
+// The helpers below skip the obvious no-ops, otherwise the derivative of
+// even a simple expression would be mostly multiplying by zero.
+
+fn add(left: Expr, right: Expr) -> Expr {
+    if is_number(&left, 0.0) {
+        right
+    } else if is_number(&right, 0.0) {
+        left
+    } else {
+        BinaryOp::add(left, right).into()
+    }
+}
+
+fn sub(left: Expr, right: Expr) -> Expr {
+    if is_number(&right, 0.0) {
+        left
+    } else {
+        BinaryOp::sub(left, right).into()
+    }
+}
+
+fn mul(left: Expr, right: Expr) -> Expr {
+    if is_scalar_zero(&left, &right) || is_scalar_zero(&right, &left) {
+        number(0.0)
+    } else if is_number(&left, 1.0) {
+        right
+    } else if is_number(&right, 1.0) {
+        left
+    } else {
+        BinaryOp::mult(left, right).into()
+    }
+}
+
+fn div(left: Expr, right: Expr) -> Expr {
+    if is_scalar_zero(&left, &right) {
+        number(0.0)
+    } else {
+        BinaryOp::div(left, right).into()
+    }
+}
+
+fn call1(name: &str, arg: Expr) -> Expr {
+    FunctionCall::new(name, vec![arg]).into()
+}
+
+fn call2(name: &str, first: Expr, second: Expr) -> Expr {
+    FunctionCall::new(name, vec![first, second]).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interval::IntervalEvaluator;
+
+    /// Differentiate `src` with respect to `x`, then evaluate the
+    /// derivative at a point.
+    fn slope(src: &str, x: f64) -> Option<f64> {
+        let env = Environment::new();
+        let ast = ::syntax::parse(&format!("solve(({}) == 0, x, 0)", src)).unwrap();
+        let (ast, _) = ::sema::type_check(&ast, &env).unwrap();
+
+        let left = match ast {
+            Expr::Solve(ref solve) => match solve.equation {
+                Expr::BinaryOp(ref op) => op.left.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        let derivative = differentiate(&left, "x", &env)?;
+
+        let got = IntervalEvaluator::new()
+            .with_input("x", [x, x])
+            .evaluate(&derivative)
+            .unwrap();
+        Some((got.lo() + got.hi()) / 2.0)
+    }
+
+    #[test]
+    fn differentiate_using_the_usual_rules() {
+        let inputs = vec![
+            ("x", 3.0, 1.0),
+            ("x * x * x - 2 / x", 2.0, 12.5),
+            ("sin(x) * exp(x)", 0.0, 1.0),
+            ("pow(x, 3) + pow(2, x)", 1.0, 3.0 + 2.0 * LN_2),
+            ("pow(x, x)", 1.0, 1.0),
+            (
+                "sqrt(x) + ln(x) + log10(x)",
+                4.0,
+                0.25 + 0.25 + 0.25 / LN_10,
+            ),
+            ("if x > 1 then abs(x - 3) else cos(x)", 2.0, -1.0),
+            ("max(x, 2) + floor(x)", 3.0, 1.0),
+            ("sum([1, 2] * x) + [x, x * x][1]", 2.0, 7.0),
+            ("sum(k, 1, 3, k * x) + sum(x, 1, 3, x)", 5.0, 6.0),
+            (
+                "integrate(t * x, t, 0, 1) + integrate(x, x, 0, 1)",
+                5.0,
+                0.5,
+            ),
+            ("integrate(t * t, t, 0, x) + integrate(x, x, x, 3)", 2.0, 2.0),
+            ("integrate(t * x, t, 1, x * x) + integrate(1, t, 0, x)", 2.0, 32.0 + 7.5 + 1.0),
+        ];
+
+        for (src, x, should_be) in inputs {
+            let got = slope(src, x).unwrap();
+            assert!((got - should_be).abs() < 1e-9, "{} = {}", src, got);
+        }
+    }
+
+    #[test]
+    fn some_expressions_have_no_derivative() {
+        let inputs = vec![
+            "re(x + 1i)",
+            "max([x, 1])",
+            "prod(k, 1, 3, x)",
+            "integrate(abs(x + 1i), t, 0, x)",
+        ];
+
+        for src in inputs {
+            assert!(slope(src, 1.0).is_none(), "{}", src);
+        }
+    }
+}
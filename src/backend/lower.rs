@@ -0,0 +1,418 @@
+//! Walking a type checked AST, independently of what it gets turned into.
+
+use std::cell::RefCell;
+
+use syntax::{Array, Atom, BinaryOp, Cast, Expr, FunctionCall, Index, Integral, Let, Op, Series,
+             Solve, Span, UnaryOp};
+
+/// The operations an expression is lowered to.
+///
+/// A `Backend` only decides what each operation turns into (an LLVM
+/// instruction, an exact number, a line of C, ...). The order things are
+/// evaluated in, how variables are scoped and which operations need control
+/// flow are all decided by [`lower()`].
+///
+/// [`lower()`]: fn.lower.html
+pub trait Backend {
+    /// The result of lowering an expression.
+    type Value: Clone;
+    /// Errors which can happen while lowering an expression.
+    type Error;
+
+    /// The variables bound by any enclosing sums, products or `Let`s, with
+    /// the innermost last.
+    fn scope(&self) -> &RefCell<Vec<(String, Self::Value)>>;
+
+    /// A literal, or an identifier which isn't bound by an enclosing sum,
+    /// product or `Let`.
+    fn atom(&self, atom: &Atom, span: Span) -> Result<Self::Value, Self::Error>;
+
+    /// Arithmetic or a comparison. Both operands have already been
+    /// evaluated and have the same type.
+    ///
+    /// `and` and `or` never get here, they are lowered to conditionals so
+    /// every backend short-circuits.
+    fn binary_op(
+        &self,
+        op: &BinaryOp,
+        left: Self::Value,
+        right: Self::Value,
+    ) -> Result<Self::Value, Self::Error>;
+
+    /// Apply a unary operator.
+    fn unary_op(&self, op: &UnaryOp, value: Self::Value) -> Result<Self::Value, Self::Error>;
+
+    /// Convert a value from `cast.from` to `cast.to`.
+    fn cast(&self, cast: &Cast, value: Self::Value) -> Result<Self::Value, Self::Error>;
+
+    /// Call a built-in or host function.
+    fn call(
+        &self,
+        call: &FunctionCall,
+        args: Vec<Self::Value>,
+    ) -> Result<Self::Value, Self::Error>;
+
+    /// Evaluate exactly one of the branches, depending on a boolean.
+    fn conditional<T, F>(
+        &self,
+        condition: Self::Value,
+        if_true: T,
+        if_false: F,
+    ) -> Result<Self::Value, Self::Error>
+    where
+        T: FnOnce() -> Result<Self::Value, Self::Error>,
+        F: FnOnce() -> Result<Self::Value, Self::Error>;
+
+    /// Add (or multiply) the `term` for every integer from `start` to `end`
+    /// inclusive. A series with no terms is `0` (or `1`).
+    fn series<F>(
+        &self,
+        series: &Series,
+        start: Self::Value,
+        end: Self::Value,
+        term: F,
+    ) -> Result<Self::Value, Self::Error>
+    where
+        F: FnMut(Self::Value) -> Result<Self::Value, Self::Error>;
+
+    /// Lower the `body` with the variable bound to `value`. However many
+    /// times the body uses the variable, the value must only be calculated
+    /// once.
+    fn bind<F>(
+        &self,
+        binding: &Let,
+        value: Self::Value,
+        body: F,
+    ) -> Result<Self::Value, Self::Error>
+    where
+        F: FnOnce(Self::Value) -> Result<Self::Value, Self::Error>;
+
+    /// Create an array from its elements.
+    fn array(&self, array: &Array, elements: Vec<Self::Value>) -> Result<Self::Value, Self::Error>;
+
+    /// Get an element of an array, checking it's in bounds.
+    fn index(
+        &self,
+        index: &Index,
+        array: Self::Value,
+        position: Self::Value,
+    ) -> Result<Self::Value, Self::Error>;
+
+    /// Integrals evaluate their body in ways which differ too much between
+    /// backends (e.g. as a separate function), so the backend lowers the
+    /// sub-expressions itself.
+    fn integral(&self, integral: &Integral) -> Result<Self::Value, Self::Error>;
+
+    /// Like `integral()`, the backend lowers the equation and guess itself.
+    fn solve(&self, solve: &Solve) -> Result<Self::Value, Self::Error>;
+}
+
+/// The `Backend::Error` for backends which can lower every expression.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Never {}
+
+/// Lower an expression which has already been through
+/// [`sema::type_check()`] using a `Backend`.
+///
+/// [`sema::type_check()`]: ../sema/fn.type_check.html
+pub fn lower<B: Backend>(backend: &B, expr: &Expr) -> Result<B::Value, B::Error> {
+    match *expr {
+        Expr::Atom(Atom::Ident(ref name), span) => {
+            let bound = backend
+                .scope()
+                .borrow()
+                .iter()
+                .rev()
+                .find(|&&(ref variable, _)| variable == name)
+                .map(|&(_, ref value)| value.clone());
+
+            match bound {
+                Some(value) => Ok(value),
+                None => backend.atom(&Atom::Ident(name.clone()), span),
+            }
+        }
+        Expr::Atom(ref atom, span) => backend.atom(atom, span),
+        Expr::BinaryOp(ref op) => lower_binary_op(backend, op),
+        Expr::UnaryOp(ref op) => {
+            let value = lower(backend, &op.value)?;
+            backend.unary_op(op, value)
+        }
+        Expr::Conditional(ref cond) => {
+            let condition = lower(backend, &cond.condition)?;
+            backend.conditional(
+                condition,
+                || lower(backend, &cond.if_true),
+                || lower(backend, &cond.if_false),
+            )
+        }
+        Expr::Cast(ref cast) => {
+            let value = lower(backend, &cast.value)?;
+            backend.cast(cast, value)
+        }
+        Expr::FunctionCall(ref call) => {
+            let mut args = Vec::new();
+            for arg in &call.arguments {
+                args.push(lower(backend, arg)?);
+            }
+            backend.call(call, args)
+        }
+        Expr::Array(ref array) => {
+            let mut elements = Vec::new();
+            for element in &array.elements {
+                elements.push(lower(backend, element)?);
+            }
+            backend.array(array, elements)
+        }
+        Expr::Index(ref index) => {
+            let array = lower(backend, &index.array)?;
+            let position = lower(backend, &index.index)?;
+            backend.index(index, array, position)
+        }
+        Expr::Series(ref series) => lower_series(backend, series),
+        Expr::Let(ref binding) => lower_let(backend, binding),
+        Expr::Integral(ref integral) => backend.integral(integral),
+        Expr::Solve(ref solve) => backend.solve(solve),
+        Expr::UnitAnnotation(_) => unreachable!("Units are erased before lowering"),
+        Expr::Lambda(_) => unreachable!("Lambdas are inlined before lowering"),
+    }
+}
+
+/// `a and b` is `if a then b else false`, and `a or b` is
+/// `if a then true else b`.
+fn lower_binary_op<B: Backend>(backend: &B, op: &BinaryOp) -> Result<B::Value, B::Error> {
+    let left = lower(backend, &op.left)?;
+
+    match op.op {
+        Op::And => backend.conditional(
+            left,
+            || lower(backend, &op.right),
+            || backend.atom(&Atom::Boolean(false), op.span),
+        ),
+        Op::Or => backend.conditional(
+            left,
+            || backend.atom(&Atom::Boolean(true), op.span),
+            || lower(backend, &op.right),
+        ),
+        _ => {
+            let right = lower(backend, &op.right)?;
+            backend.binary_op(op, left, right)
+        }
+    }
+}
+
+/// The series' variable is in scope while each term is lowered.
+fn lower_series<B: Backend>(backend: &B, series: &Series) -> Result<B::Value, B::Error> {
+    let start = lower(backend, &series.start)?;
+    let end = lower(backend, &series.end)?;
+
+    backend.series(series, start, end, |counter| {
+        backend
+            .scope()
+            .borrow_mut()
+            .push((series.variable.clone(), counter));
+        let term = lower(backend, &series.body);
+        backend.scope().borrow_mut().pop();
+
+        term
+    })
+}
+
+/// The variable is in scope while the body is lowered.
+fn lower_let<B: Backend>(backend: &B, binding: &Let) -> Result<B::Value, B::Error> {
+    let value = lower(backend, &binding.value)?;
+
+    backend.bind(binding, value, |value| {
+        backend
+            .scope()
+            .borrow_mut()
+            .push((binding.variable.clone(), value));
+        let body = lower(backend, &binding.body);
+        backend.scope().borrow_mut().pop();
+
+        body
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use environment::Environment;
+    use sema;
+    use syntax;
+
+    /// Writes out each operation as an S-expression, unrolling series.
+    /// Integrals and `solve()` aren't printed.
+    #[derive(Default)]
+    struct Printer {
+        scope: RefCell<Vec<(String, String)>>,
+    }
+
+    impl Backend for Printer {
+        type Value = String;
+        type Error = Unsupported;
+
+        fn scope(&self) -> &RefCell<Vec<(String, String)>> {
+            &self.scope
+        }
+
+        fn atom(&self, atom: &Atom, _: Span) -> Result<String, Unsupported> {
+            match *atom {
+                Atom::Number(n) => Ok(n.to_string()),
+                Atom::Integer(n) => Ok(n.to_string()),
+                Atom::Boolean(b) => Ok(b.to_string()),
+                ref other => panic!("Unexpected atom, {:?}", other),
+            }
+        }
+
+        fn binary_op(
+            &self,
+            op: &BinaryOp,
+            left: String,
+            right: String,
+        ) -> Result<String, Unsupported> {
+            Ok(format!("({} {} {})", op.op, left, right))
+        }
+
+        fn unary_op(&self, op: &UnaryOp, value: String) -> Result<String, Unsupported> {
+            Ok(format!("({} {})", op.op, value))
+        }
+
+        fn cast(&self, cast: &Cast, value: String) -> Result<String, Unsupported> {
+            Ok(format!("({} {})", cast.to, value))
+        }
+
+        fn call(&self, call: &FunctionCall, args: Vec<String>) -> Result<String, Unsupported> {
+            Ok(format!("({} {})", call.name, args.join(" ")))
+        }
+
+        fn conditional<T, F>(
+            &self,
+            condition: String,
+            if_true: T,
+            if_false: F,
+        ) -> Result<String, Unsupported>
+        where
+            T: FnOnce() -> Result<String, Unsupported>,
+            F: FnOnce() -> Result<String, Unsupported>,
+        {
+            Ok(format!("(if {} {} {})", condition, if_true()?, if_false()?))
+        }
+
+        fn series<F>(
+            &self,
+            series: &Series,
+            start: String,
+            end: String,
+            mut term: F,
+        ) -> Result<String, Unsupported>
+        where
+            F: FnMut(String) -> Result<String, Unsupported>,
+        {
+            let (start, end): (i64, i64) = (start.parse().unwrap(), end.parse().unwrap());
+            let mut terms = Vec::new();
+            for i in start..=end {
+                terms.push(term(i.to_string())?);
+            }
+
+            Ok(format!("({} {})", series.kind, terms.join(" ")))
+        }
+
+        fn bind<F>(&self, binding: &Let, value: String, body: F) -> Result<String, Unsupported>
+        where
+            F: FnOnce(String) -> Result<String, Unsupported>,
+        {
+            let body = body(binding.variable.clone())?;
+            Ok(format!("(let {} {} {})", binding.variable, value, body))
+        }
+
+        fn array(&self, _: &Array, elements: Vec<String>) -> Result<String, Unsupported> {
+            Ok(format!("[{}]", elements.join(" ")))
+        }
+
+        fn index(&self, _: &Index, array: String, position: String) -> Result<String, Unsupported> {
+            Ok(format!("{}[{}]", array, position))
+        }
+
+        fn integral(&self, integral: &Integral) -> Result<String, Unsupported> {
+            Err(Unsupported(integral.span))
+        }
+
+        fn solve(&self, solve: &Solve) -> Result<String, Unsupported> {
+            Err(Unsupported(solve.span))
+        }
+    }
+
+    /// Where the `Printer` found an integral or `solve()`.
+    #[derive(Debug, PartialEq)]
+    struct Unsupported(Span);
+
+    fn try_print(src: &str) -> Result<String, Unsupported> {
+        let ast = syntax::parse(src).unwrap();
+        let (ast, _) = sema::type_check(&ast, &Environment::new()).unwrap();
+
+        lower(&Printer::default(), &ast)
+    }
+
+    fn print(src: &str) -> String {
+        try_print(src).unwrap()
+    }
+
+    #[test]
+    fn logical_operators_become_conditionals() {
+        let inputs = vec![
+            ("true and false", "(if true false false)"),
+            ("1 < 2 or not true", "(if (< 1 2) true (not true))"),
+        ];
+
+        for (src, should_be) in inputs {
+            assert_eq!(print(src), should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn series_bind_their_variable_for_each_term() {
+        let inputs = vec![
+            (
+                "sum(k, 1, 3, k * 0.5)",
+                "(sum (* (float 1) 0.5) (* (float 2) 0.5) (* (float 3) 0.5))",
+            ),
+            ("prod(i, 1, 2, sum(i, 1, i, i))", "(prod (sum (float 1)) (sum (float 1) (float 2)))"),
+        ];
+
+        for (src, should_be) in inputs {
+            assert_eq!(print(src), should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn bound_values_are_only_lowered_once() {
+        let inputs = vec![
+            (
+                "sum(map(x -> x * x, [1, 2] * 3))",
+                "(sum [(float (let x (* 1 3) (* x x))) (float (let x (* 2 3) (* x x)))])",
+            ),
+            (
+                "sum(map(x -> x, [1, 2] + sqrt(2)))",
+                "(sum (let '0 (sqrt (float 2)) [(+ (float 1) '0) (+ (float 2) '0)]))",
+            ),
+        ];
+
+        for (src, should_be) in inputs {
+            assert_eq!(print(src), should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn errors_from_the_backend_stop_lowering() {
+        let inputs = vec![
+            ("1 + integrate(x, x, 0, 1)", "integrate(x, x, 0, 1)"),
+            ("if false then solve(x == 1, x, 0) else 2", "solve(x == 1, x, 0)"),
+            ("sum(k, 1, 2, k * integrate(x, x, 0, k))", "integrate(x, x, 0, k)"),
+        ];
+
+        for (src, text) in inputs {
+            let Unsupported(span) = try_print(src).unwrap_err();
+            assert_eq!(&src[span.start..span.end], text, "{}", src);
+        }
+    }
+}
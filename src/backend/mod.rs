@@ -0,0 +1,306 @@
+//! The interface shared by the code generators.
+//!
+//! `calc` can generate machine code using either LLVM (the `llvm` feature,
+//! enabled by default) or [Cranelift] (the `cranelift` feature). Both
+//! implement [`Jit`] and produce an [`Executable`], so code which only
+//! wants to run an expression doesn't need to care which one it's using.
+//!
+//! ```rust
+//! use calc::backend::{Executable, Jit};
+//! use calc::environment::Environment;
+//!
+//! /// Evaluate an expression using whichever JIT compiler we're given.
+//! fn evaluate<J: Jit>(jit: J, src: &str) -> f64 {
+//!     let ast = calc::syntax::parse(src).unwrap();
+//!     let (ast, _) = calc::sema::type_check(&ast, &Environment::new()).unwrap();
+//!
+//!     jit.build(&ast).unwrap().call().unwrap()
+//! }
+//! ```
+//!
+//! Where `jit` is either a `calc::trans::Compiler` or a
+//! `calc::cranelift::Compiler`.
+//!
+//! Walking the AST is done once, by [`lower()`], which calls into a
+//! [`Backend`] for each operation. Both JIT compilers, the [`bytecode`]
+//! compiler, the [`c`] emitter and the [`interpreter`] are `Backend`s, so
+//! adding a feature to the language means extending the walk in one place
+//! and implementing a method on each backend. The [`interval`] evaluator
+//! is the exception, because it needs its inputs to stay as identifiers
+//! instead of being inlined by the type checker.
+//!
+//! Compiled code from either backend exposes the same entrypoint,
+//! `calc_main`, which has the [`CalcMain`] signature and reports failures
+//! using the [`Status`] codes.
+//!
+//! [Cranelift]: https://github.com/CraneStation/cranelift
+//! [`Backend`]: trait.Backend.html
+//! [`Executable`]: trait.Executable.html
+//! [`Jit`]: trait.Jit.html
+//! [`bytecode`]: ../bytecode/index.html
+//! [`c`]: ../c/index.html
+//! [`interpreter`]: ../interpreter/index.html
+//! [`interval`]: ../interval/index.html
+//! [`lower()`]: fn.lower.html
+//! [`CalcMain`]: type.CalcMain.html
+//! [`Status`]: enum.Status.html
+
+#[cfg(any(feature = "llvm", feature = "cranelift"))]
+mod derivative;
+mod lower;
+#[cfg(any(feature = "llvm", feature = "cranelift"))]
+pub(crate) mod quadrature;
+
+#[cfg(any(feature = "llvm", feature = "cranelift"))]
+pub(crate) use self::derivative::differentiate;
+pub use self::lower::{lower, Backend, Never};
+
+use failure::Error;
+use num_complex::Complex64;
+
+use syntax::{Expr, Span};
+
+/// A code generator which turns a type checked `Expr` into machine code
+/// which can be executed.
+pub trait Jit {
+    /// The compiled program.
+    type Program: Executable;
+
+    /// Compile an expression which has already been through
+    /// [`sema::type_check()`].
+    ///
+    /// [`sema::type_check()`]: ../sema/fn.type_check.html
+    fn build(self, ast: &Expr) -> Result<Self::Program, Error>;
+}
+
+/// A compiled expression which is ready to be run.
+pub trait Executable {
+    /// Run the program, returning its result as a complex number.
+    fn call_complex(&self) -> Result<Complex64, RuntimeError>;
+
+    /// Run the program, only returning the real part of the result.
+    fn call(&self) -> Result<f64, RuntimeError> {
+        self.call_complex().map(|z| z.re)
+    }
+}
+
+/// The signature used for `calc`'s entrypoint, `"calc_main"`.
+///
+/// The result is written to the first pointer as a complex number (with an
+/// imaginary part of zero for real results), and the return value is a
+/// [`Status`] code indicating whether the calculation succeeded. If it
+/// failed, the location of the offending expression is written to the
+/// second pointer.
+///
+/// [`Status`]: enum.Status.html
+pub type CalcMain = unsafe extern "C" fn(*mut Complex64, *mut ErrorLocation) -> u32;
+
+/// The name of the function every backend generates for the expression.
+pub const CALC_ENTRYPOINT: &str = "calc_main";
+
+/// The span of the expression which caused `calc_main` to fail.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub struct ErrorLocation {
+    /// The index of the first byte.
+    pub start: u64,
+    /// The index one past the last byte.
+    pub end: u64,
+}
+
+impl From<ErrorLocation> for Span {
+    fn from(other: ErrorLocation) -> Span {
+        Span::new(other.start as usize, other.end as usize)
+    }
+}
+
+/// The status codes returned by `calc_main`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(u32)]
+pub enum Status {
+    /// The calculation succeeded.
+    Ok = 0,
+    /// An integer operation overflowed.
+    IntegerOverflow = 1,
+    /// Division or modulo by zero.
+    DivideByZero = 2,
+    /// A function was called with an argument outside its domain (e.g.
+    /// `sqrt(-1)`).
+    DomainError = 3,
+    /// An array was indexed past its end.
+    IndexOutOfBounds = 4,
+    /// `solve()` couldn't find a value which satisfies the equation.
+    NoSolution = 5,
+    /// A closure registered with the `Environment` panicked.
+    HostFunctionPanicked = 6,
+    /// An integral couldn't be approximated accurately enough.
+    NoConvergence = 7,
+}
+
+impl Status {
+    /// Get the `Status` corresponding to a code returned from `calc_main`.
+    pub fn from_code(code: u32) -> Option<Status> {
+        match code {
+            0 => Some(Status::Ok),
+            1 => Some(Status::IntegerOverflow),
+            2 => Some(Status::DivideByZero),
+            3 => Some(Status::DomainError),
+            4 => Some(Status::IndexOutOfBounds),
+            5 => Some(Status::NoSolution),
+            6 => Some(Status::HostFunctionPanicked),
+            7 => Some(Status::NoConvergence),
+            _ => None,
+        }
+    }
+}
+
+/// Call a compiled `calc_main` (given the pointers it should write to),
+/// translating any error status into a `RuntimeError`.
+pub(crate) fn call_entrypoint<F>(calc_main: F) -> Result<Complex64, RuntimeError>
+where
+    F: FnOnce(*mut Complex64, *mut ErrorLocation) -> u32,
+{
+    let mut result = Complex64::new(0.0, 0.0);
+    let mut location = ErrorLocation::default();
+
+    let code = calc_main(&mut result, &mut location);
+    let span = Span::from(location);
+
+    match Status::from_code(code) {
+        Some(Status::Ok) => Ok(result),
+        Some(Status::IntegerOverflow) => Err(RuntimeError::IntegerOverflow { span }),
+        Some(Status::DivideByZero) => Err(RuntimeError::DivideByZero { span }),
+        Some(Status::DomainError) => Err(RuntimeError::DomainError { span }),
+        Some(Status::IndexOutOfBounds) => Err(RuntimeError::IndexOutOfBounds { span }),
+        Some(Status::NoSolution) => Err(RuntimeError::NoSolution { span }),
+        Some(Status::HostFunctionPanicked) => Err(RuntimeError::HostFunctionPanicked { span }),
+        Some(Status::NoConvergence) => Err(RuntimeError::NoConvergence { span }),
+        None => Err(RuntimeError::UnknownStatus(code)),
+    }
+}
+
+/// Errors which can happen while running compiled code.
+#[derive(Debug, Copy, Clone, PartialEq, Fail)]
+pub enum RuntimeError {
+    /// An integer operation overflowed.
+    #[fail(display = "Integer overflow at {}", span)]
+    IntegerOverflow {
+        /// The operation which overflowed.
+        span: Span,
+    },
+    /// Division or modulo by zero.
+    #[fail(display = "Division by zero at {}", span)]
+    DivideByZero {
+        /// The division.
+        span: Span,
+    },
+    /// A function was called with an argument it isn't defined for.
+    #[fail(display = "Argument outside the function's domain at {}", span)]
+    DomainError {
+        /// The function call.
+        span: Span,
+    },
+    /// An array was indexed past its end.
+    #[fail(display = "Index out of bounds at {}", span)]
+    IndexOutOfBounds {
+        /// The indexing operation.
+        span: Span,
+    },
+    /// `solve()` couldn't find a value which satisfies the equation.
+    #[fail(display = "No solution found for the equation at {}", span)]
+    NoSolution {
+        /// The call to `solve()`.
+        span: Span,
+    },
+    /// A closure registered with the `Environment` panicked.
+    #[fail(display = "The host function called at {} panicked", span)]
+    HostFunctionPanicked {
+        /// The function call.
+        span: Span,
+    },
+    /// An integral couldn't be approximated accurately enough (e.g. because
+    /// the integrand has a singularity).
+    #[fail(display = "The integral at {} didn't converge", span)]
+    NoConvergence {
+        /// The integral.
+        span: Span,
+    },
+    /// `calc_main` returned a status code we don't know about.
+    #[fail(display = "Unknown status code, {}", _0)]
+    UnknownStatus(u32),
+}
+
+impl RuntimeError {
+    /// The `Status` code this error corresponds to.
+    pub fn status(&self) -> Option<Status> {
+        match *self {
+            RuntimeError::IntegerOverflow { .. } => Some(Status::IntegerOverflow),
+            RuntimeError::DivideByZero { .. } => Some(Status::DivideByZero),
+            RuntimeError::DomainError { .. } => Some(Status::DomainError),
+            RuntimeError::IndexOutOfBounds { .. } => Some(Status::IndexOutOfBounds),
+            RuntimeError::NoSolution { .. } => Some(Status::NoSolution),
+            RuntimeError::HostFunctionPanicked { .. } => Some(Status::HostFunctionPanicked),
+            RuntimeError::NoConvergence { .. } => Some(Status::NoConvergence),
+            RuntimeError::UnknownStatus(_) => None,
+        }
+    }
+
+    /// The location of the expression which failed.
+    pub fn span(&self) -> Option<Span> {
+        match *self {
+            RuntimeError::IntegerOverflow { span }
+            | RuntimeError::DivideByZero { span }
+            | RuntimeError::DomainError { span }
+            | RuntimeError::IndexOutOfBounds { span }
+            | RuntimeError::NoSolution { span }
+            | RuntimeError::HostFunctionPanicked { span }
+            | RuntimeError::NoConvergence { span } => Some(span),
+            RuntimeError::UnknownStatus(_) => None,
+        }
+    }
+
+    /// Get the text of the offending sub-expression from the program's
+    /// source code.
+    pub fn source_text<'src>(&self, src: &'src str) -> Option<&'src str> {
+        self.span().and_then(|span| src.get(span.start..span.end))
+    }
+}
+
+/// The number of elements in an array-valued expression, or `None` if it
+/// isn't an array.
+#[cfg(any(feature = "llvm", feature = "cranelift"))]
+pub(crate) fn array_length(expr: &Expr) -> Option<usize> {
+    match *expr {
+        Expr::Array(ref array) => Some(array.elements.len()),
+        Expr::BinaryOp(ref op) => array_length(&op.left).or_else(|| array_length(&op.right)),
+        Expr::Conditional(ref cond) => array_length(&cond.if_true),
+        Expr::Let(ref binding) => array_length(&binding.body),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe extern "C" fn divide_by_zero(_: *mut Complex64, location: *mut ErrorLocation) -> u32 {
+        *location = ErrorLocation { start: 4, end: 9 };
+        Status::DivideByZero as u32
+    }
+
+    unsafe extern "C" fn unknown_status(_: *mut Complex64, _: *mut ErrorLocation) -> u32 {
+        42
+    }
+
+    #[test]
+    fn translate_status_codes() {
+        let err = call_entrypoint(|out, location| unsafe { divide_by_zero(out, location) })
+            .unwrap_err();
+        assert_eq!(err.status(), Some(Status::DivideByZero));
+        assert_eq!(err.source_text("1 + 1 / 0"), Some("1 / 0"));
+
+        let err = call_entrypoint(|out, location| unsafe { unknown_status(out, location) })
+            .unwrap_err();
+        assert_eq!(err, RuntimeError::UnknownStatus(42));
+    }
+}
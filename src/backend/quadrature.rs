@@ -0,0 +1,38 @@
+//! The Gauss–Kronrod rule both JIT compilers use for `integrate()`.
+//!
+//! Each panel is integrated with the 15-point Kronrod rule, and the
+//! difference from the embedded 7-point Gauss rule estimates its error.
+
+/// The Kronrod points in `(0, 1]`, where the odd-numbered points are shared
+/// with the 7-point Gauss rule. Each point is mirrored around the centre of
+/// the panel, and the centre itself has the last weight.
+pub(crate) const KRONROD_NODES: [f64; 7] = [
+    0.991_455_371_120_812_6,
+    0.949_107_912_342_758_5,
+    0.864_864_423_359_769_1,
+    0.741_531_185_599_394_4,
+    0.586_087_235_467_691_1,
+    0.405_845_151_377_397_2,
+    0.207_784_955_007_898_5,
+];
+pub(crate) const KRONROD_WEIGHTS: [f64; 8] = [
+    0.022_935_322_010_529_22,
+    0.063_092_092_629_978_55,
+    0.104_790_010_322_250_2,
+    0.140_653_259_715_525_9,
+    0.169_004_726_639_267_9,
+    0.190_350_578_064_785_4,
+    0.204_432_940_075_298_9,
+    0.209_482_141_084_727_8,
+];
+pub(crate) const GAUSS_WEIGHTS: [f64; 4] = [
+    0.129_484_966_168_869_7,
+    0.279_705_391_489_276_7,
+    0.381_830_050_505_118_9,
+    0.417_959_183_673_469_4,
+];
+/// The relative (or, for answers near zero, absolute) error an integral is
+/// refined to.
+pub(crate) const INTEGRAL_TOLERANCE: f64 = 1e-10;
+/// The most panels an integral will be split into.
+pub(crate) const MAX_PANELS: u64 = 1 << 16;
@@ -0,0 +1,300 @@
+//! Evaluate a `calc` expression from the command line, optionally writing
+//! the code generated for it to disk, or print how it was parsed.
+
+extern crate calc;
+#[macro_use]
+extern crate failure;
+extern crate inkwell;
+
+use calc::environment::Environment;
+use calc::jit::{Program, RuntimeError};
+use calc::syntax::dump::{dump, Format};
+use calc::syntax::Type;
+use calc::trans::{self, Compiler, Emit};
+use failure::Error;
+use inkwell::context::Context;
+use inkwell::OptimizationLevel;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+const USAGE: &str = "Usage: calc [OPTIONS] <EXPR>
+       calc parse [--format <FORMAT>] <EXPR>
+
+Options:
+    --emit <KINDS>     Write the generated code to disk, where KINDS is a
+                       comma-separated list of ir, bc, asm and obj
+    -o, --output <NAME>
+                       Where to write outputs, without the extension
+                       [default: calc]
+    -O <LEVEL>         The optimisation level, 0 to 3 [default: 2]
+    -g                 Attach debug info to the generated code, so gdb and
+                       perf can map it back to EXPR
+    -h, --help         Print this message
+
+Parse options:
+    --format <FORMAT>  How to print the AST, one of tree, sexpr, json or
+                       dot [default: tree]
+
+Emitting \"ir\" writes the IR from before and after optimisation, to
+NAME.ll and NAME.opt.ll respectively.";
+
+fn main() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            process::exit(1);
+        }
+    };
+
+    if let Err(e) = run(&args) {
+        eprintln!("Error: {}", e);
+
+        if let Some(text) = e
+            .downcast_ref::<RuntimeError>()
+            .and_then(|e| e.source_text(&args.src))
+        {
+            eprintln!("    {}", text);
+        }
+
+        process::exit(1);
+    }
+}
+
+fn run(args: &Args) -> Result<(), Error> {
+    let env = Environment::new();
+    let ast = calc::syntax::parse(&args.src)?;
+
+    if let Command::Parse(format) = args.command {
+        let text = dump(&ast, format);
+        if text.ends_with('\n') {
+            print!("{}", text);
+        } else {
+            println!("{}", text);
+        }
+        return Ok(());
+    }
+
+    let (ast, ty) = calc::sema::type_check(&ast, &env)?;
+
+    let ctx = Context::create();
+    let mut compiler = Compiler::new(&ctx)
+        .with_runtime_checks(true)
+        .with_environment(&env);
+    if args.debug_info {
+        let path = trans::write_source_file(&args.src)?;
+        compiler = compiler.with_debug_info(&path, &args.src);
+    }
+    let compiled = compiler.compile(&ast);
+
+    if args.emit.contains(&Emit::Ir) {
+        trans::write_output(&compiled.module, Emit::Ir, &args.output_path("ll"))?;
+    }
+
+    trans::optimize(&compiled.module, args.opt_level);
+
+    for &emit in &args.emit {
+        let path = match emit {
+            Emit::Ir => args.output_path("opt.ll"),
+            other => args.output_path(other.extension()),
+        };
+        trans::write_output(&compiled.module, emit, &path)?;
+    }
+
+    let program = Program::new(&compiled)?;
+    if ty == Type::Complex {
+        println!("{}", program.call_complex()?);
+    } else {
+        println!("{}", program.call()?);
+    }
+
+    Ok(())
+}
+
+/// The parsed command-line arguments.
+#[derive(Debug, Clone, PartialEq)]
+struct Args {
+    command: Command,
+    src: String,
+    emit: Vec<Emit>,
+    output: PathBuf,
+    opt_level: OptimizationLevel,
+    debug_info: bool,
+}
+
+impl Args {
+    /// Parse the command-line arguments, returning `None` if the user asked
+    /// for help.
+    fn parse<I>(args: I) -> Result<Option<Args>, Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = args.into_iter().peekable();
+        let mut command = Command::Run;
+        if args.peek().map(|arg| arg == "parse") == Some(true) {
+            args.next();
+            command = Command::Parse(Format::Tree);
+        }
+
+        let mut src = None;
+        let mut emit = Vec::new();
+        let mut output = PathBuf::from("calc");
+        let mut opt_level = OptimizationLevel::Default;
+        let mut debug_info = false;
+
+        while let Some(arg) = args.next() {
+            // options can be given as either "--emit=ir" or "--emit ir"
+            let (flag, inline_value) = match arg.find('=') {
+                Some(ix) if arg.starts_with("--") => (arg[..ix].to_string(), Some(&arg[ix + 1..])),
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .map(String::from)
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format_err!("{} expects a value", flag))
+            };
+
+            match flag.as_str() {
+                "-h" | "--help" => return Ok(None),
+                "--emit" => {
+                    for kind in value()?.split(',') {
+                        let kind = kind.parse()?;
+                        if !emit.contains(&kind) {
+                            emit.push(kind);
+                        }
+                    }
+                }
+                "--format" => match command {
+                    Command::Parse(ref mut format) => *format = value()?.parse()?,
+                    Command::Run => return Err(format_err!("--format only works with \"parse\"")),
+                },
+                "-o" | "--output" => output = PathBuf::from(value()?),
+                "-O" => opt_level = parse_opt_level(&value()?)?,
+                "-g" => debug_info = true,
+                _ if flag.starts_with("-O") => opt_level = parse_opt_level(&flag[2..])?,
+                _ if flag.starts_with('-') => {
+                    return Err(format_err!("Unknown option, \"{}\"", flag));
+                }
+                _ if src.is_some() => return Err(format_err!("Unexpected argument, \"{}\"", arg)),
+                _ => src = Some(arg.clone()),
+            }
+        }
+
+        let src = src.ok_or_else(|| format_err!("No expression was provided"))?;
+
+        Ok(Some(Args {
+            command,
+            src,
+            emit,
+            output,
+            opt_level,
+            debug_info,
+        }))
+    }
+
+    fn output_path(&self, extension: &str) -> PathBuf {
+        let mut name = self.output.clone().into_os_string();
+        name.push(".");
+        name.push(extension);
+        PathBuf::from(name)
+    }
+}
+
+/// What to do with the expression.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Command {
+    /// Compile and run it.
+    Run,
+    /// Print its AST.
+    Parse(Format),
+}
+
+fn parse_opt_level(level: &str) -> Result<OptimizationLevel, Error> {
+    match level {
+        "0" => Ok(OptimizationLevel::None),
+        "1" => Ok(OptimizationLevel::Less),
+        "2" => Ok(OptimizationLevel::Default),
+        "3" => Ok(OptimizationLevel::Aggressive),
+        _ => Err(format_err!("Invalid optimisation level, \"{}\"", level)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, Error> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_emit_flags() {
+        let got = parse(&["--emit=ir,asm", "-O3", "-g", "-o", "out/formula", "1 + 2"])
+            .unwrap()
+            .unwrap();
+
+        let should_be = Args {
+            command: Command::Run,
+            src: String::from("1 + 2"),
+            emit: vec![Emit::Ir, Emit::Assembly],
+            output: PathBuf::from("out/formula"),
+            opt_level: OptimizationLevel::Aggressive,
+            debug_info: true,
+        };
+        assert_eq!(got, should_be);
+        assert_eq!(
+            got.output_path("opt.ll"),
+            PathBuf::from("out/formula.opt.ll")
+        );
+
+        let got = parse(&["--emit", "obj", "--emit=bc,obj", "-O", "0", "1"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(got.emit, vec![Emit::Object, Emit::Bitcode]);
+        assert_eq!(got.opt_level, OptimizationLevel::None);
+        assert!(!got.debug_info);
+    }
+
+    #[test]
+    fn parse_subcommand() {
+        let got = parse(&["parse", "--format=sexpr", "1 + 2"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(got.command, Command::Parse(Format::SExpr));
+        assert_eq!(got.src, "1 + 2");
+
+        let got = parse(&["parse", "x"]).unwrap().unwrap();
+        assert_eq!(got.command, Command::Parse(Format::Tree));
+
+        // "parse" is only a subcommand when it comes first
+        let got = parse(&["-O1", "parse"]).unwrap().unwrap();
+        assert_eq!(got.command, Command::Run);
+        assert_eq!(got.src, "parse");
+    }
+
+    #[test]
+    fn invalid_arguments_are_rejected() {
+        let inputs: Vec<&[&str]> = vec![
+            &[],
+            &["--emit=exe", "1"],
+            &["--emit"],
+            &["-O7", "1"],
+            &["--verbose", "1"],
+            &["1", "2"],
+            &["--format=json", "1"],
+            &["parse", "--format", "yaml", "1"],
+        ];
+
+        for args in inputs {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+
+        assert_eq!(parse(&["1", "--help"]).unwrap(), None);
+    }
+}
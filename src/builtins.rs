@@ -0,0 +1,175 @@
+//! The functions and constants which are built into the language.
+
+use std::f64::consts;
+
+/// A built-in function.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Builtin {
+    /// The name used to call the function.
+    pub name: &'static str,
+    /// The number of arguments it accepts.
+    pub arity: usize,
+    /// The LLVM intrinsic used to implement it for real numbers, if it is
+    /// defined for them.
+    pub intrinsic: Option<&'static str>,
+    /// The real values the first argument may take.
+    pub domain: Domain,
+    /// What happens when the function is given a complex number.
+    pub complex: ComplexSupport,
+}
+
+/// The range of inputs a function is defined for.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Domain {
+    /// Every number.
+    Any,
+    /// Zero and above (e.g. `sqrt()`).
+    NonNegative,
+    /// Strictly greater than zero (e.g. `ln()`).
+    Positive,
+}
+
+impl Domain {
+    /// Is the function defined for this input?
+    pub fn contains(&self, value: f64) -> bool {
+        match *self {
+            Domain::Any => true,
+            Domain::NonNegative => value >= 0.0,
+            Domain::Positive => value > 0.0,
+        }
+    }
+}
+
+/// How a function treats complex arguments.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ComplexSupport {
+    /// Only real numbers are accepted.
+    RealOnly,
+    /// Complex numbers are accepted and the result is real (e.g. `abs()`).
+    ToReal,
+    /// Complex numbers are accepted and the result is complex (e.g.
+    /// `sqrt()`).
+    ToComplex,
+}
+
+macro_rules! builtin {
+    ($name:expr, $arity:expr, $intrinsic:expr) => {
+        builtin!($name, $arity, $intrinsic, Domain::Any)
+    };
+    ($name:expr, $arity:expr, $intrinsic:expr, $domain:expr) => {
+        builtin!($name, $arity, $intrinsic, $domain, ComplexSupport::RealOnly)
+    };
+    ($name:expr, $arity:expr, $intrinsic:expr, $domain:expr, $complex:expr) => {
+        Builtin {
+            name: $name,
+            arity: $arity,
+            intrinsic: Some($intrinsic),
+            domain: $domain,
+            complex: $complex,
+        }
+    };
+}
+
+/// A function which only makes sense for complex numbers, so real arguments
+/// are always promoted.
+macro_rules! complex_builtin {
+    ($name:expr, $complex:expr) => {
+        Builtin {
+            name: $name,
+            arity: 1,
+            intrinsic: None,
+            domain: Domain::Any,
+            complex: $complex,
+        }
+    };
+}
+
+/// Every built-in function.
+pub const BUILTINS: &[Builtin] = &[
+    builtin!(
+        "sqrt",
+        1,
+        "llvm.sqrt.f64",
+        Domain::NonNegative,
+        ComplexSupport::ToComplex
+    ),
+    builtin!("sin", 1, "llvm.sin.f64"),
+    builtin!("cos", 1, "llvm.cos.f64"),
+    builtin!("exp", 1, "llvm.exp.f64", Domain::Any, ComplexSupport::ToComplex),
+    builtin!("ln", 1, "llvm.log.f64", Domain::Positive),
+    builtin!("log2", 1, "llvm.log2.f64", Domain::Positive),
+    builtin!("log10", 1, "llvm.log10.f64", Domain::Positive),
+    builtin!("abs", 1, "llvm.fabs.f64", Domain::Any, ComplexSupport::ToReal),
+    builtin!("floor", 1, "llvm.floor.f64"),
+    builtin!("ceil", 1, "llvm.ceil.f64"),
+    builtin!("round", 1, "llvm.round.f64"),
+    builtin!("pow", 2, "llvm.pow.f64"),
+    builtin!("min", 2, "llvm.minnum.f64"),
+    builtin!("max", 2, "llvm.maxnum.f64"),
+    complex_builtin!("arg", ComplexSupport::ToReal),
+    complex_builtin!("re", ComplexSupport::ToReal),
+    complex_builtin!("im", ComplexSupport::ToReal),
+    complex_builtin!("conj", ComplexSupport::ToComplex),
+];
+
+/// Find the built-in function with this name.
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name == name)
+}
+
+/// A built-in function which reduces arrays to a single number.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Reduction {
+    /// The name used to call the function.
+    pub name: &'static str,
+    /// The number of arrays it accepts.
+    pub arity: usize,
+}
+
+/// Every built-in reduction. These may share a name with a normal built-in
+/// function (e.g. `min()`) as long as they accept a different number of
+/// arguments.
+pub const REDUCTIONS: &[Reduction] = &[
+    Reduction { name: "sum", arity: 1 },
+    Reduction { name: "mean", arity: 1 },
+    Reduction { name: "min", arity: 1 },
+    Reduction { name: "max", arity: 1 },
+    Reduction { name: "dot", arity: 2 },
+];
+
+/// Find the reduction with this name which accepts `arity` arrays.
+pub fn reduction(name: &str, arity: usize) -> Option<&'static Reduction> {
+    REDUCTIONS
+        .iter()
+        .find(|r| r.name == name && r.arity == arity)
+}
+
+/// A named constant which is inlined wherever it is used.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Constant {
+    /// The constant's name.
+    pub name: &'static str,
+    /// Its value.
+    pub value: f64,
+}
+
+/// Every built-in constant.
+pub const CONSTANTS: &[Constant] = &[
+    Constant {
+        name: "pi",
+        value: consts::PI,
+    },
+    Constant {
+        name: "e",
+        value: consts::E,
+    },
+    Constant {
+        name: "tau",
+        value: 2.0 * consts::PI,
+    },
+];
+
+/// Find the built-in constant with this name.
+pub fn constant(name: &str) -> Option<&'static Constant> {
+    CONSTANTS.iter().find(|c| c.name == name)
+}
@@ -0,0 +1,320 @@
+use std::cell::{Cell, RefCell};
+
+use super::{Bytecode, BytecodeError, Instruction};
+use backend::{self, Backend};
+use syntax::{Array, Atom, BinaryOp, Cast, Expr, FunctionCall, Index, Integral, Let, Op, Series,
+             SeriesKind, Solve, Span, Type, UnaryOp};
+
+/// Compile an expression which has already been type checked.
+pub(super) fn compile(ast: &Expr) -> Result<Bytecode, BytecodeError> {
+    let compiler = Compiler::default();
+    let code = backend::lower(&compiler, ast)?;
+
+    Ok(Bytecode {
+        instructions: code.instructions,
+        spans: code.spans,
+        functions: compiler.functions.into_inner(),
+        locals: compiler.locals.get(),
+    })
+}
+
+#[derive(Default)]
+struct Compiler {
+    /// The variables bound by any sums, products or `Let`s we're currently
+    /// inside.
+    scope: RefCell<Vec<(String, Code)>>,
+    /// The name of every function which gets called.
+    functions: RefCell<Vec<String>>,
+    /// The number of slots allocated so far.
+    locals: Cell<u32>,
+}
+
+impl Compiler {
+    fn allocate_slot(&self) -> u32 {
+        let slot = self.locals.get();
+        self.locals.set(slot + 1);
+        slot
+    }
+
+    fn function_index(&self, name: &str) -> u32 {
+        let mut functions = self.functions.borrow_mut();
+
+        let index = match functions.iter().position(|f| f == name) {
+            Some(index) => index,
+            None => {
+                functions.push(name.to_string());
+                functions.len() - 1
+            }
+        };
+
+        index as u32
+    }
+}
+
+/// Each operation becomes the instructions which leave its value on top of
+/// the stack.
+impl Backend for Compiler {
+    type Value = Code;
+    type Error = BytecodeError;
+
+    fn scope(&self) -> &RefCell<Vec<(String, Code)>> {
+        &self.scope
+    }
+
+    fn atom(&self, atom: &Atom, span: Span) -> Result<Code, BytecodeError> {
+        let instruction = match *atom {
+            Atom::Number(n) => Instruction::PushFloat(n),
+            Atom::Integer(n) => Instruction::PushInteger(n),
+            Atom::Boolean(b) => Instruction::PushBool(b),
+            Atom::Imaginary(_) => return unsupported("complex numbers", span),
+            Atom::Ident(_) => {
+                unreachable!("The type checker inlines everything except bound variables")
+            }
+        };
+
+        Ok(Code::single(instruction, span))
+    }
+
+    fn binary_op(&self, op: &BinaryOp, left: Code, right: Code) -> Result<Code, BytecodeError> {
+        let mut code = left;
+        code.append(right);
+        code.push(Instruction::BinaryOp(op.op), op.span);
+        Ok(code)
+    }
+
+    fn unary_op(&self, op: &UnaryOp, value: Code) -> Result<Code, BytecodeError> {
+        let mut code = value;
+        code.push(Instruction::Not, op.span);
+        Ok(code)
+    }
+
+    fn cast(&self, cast: &Cast, value: Code) -> Result<Code, BytecodeError> {
+        let mut code = value;
+
+        match (cast.from, cast.to) {
+            (_, Type::Complex) => return unsupported("complex numbers", cast.span),
+            (from, to) if from == to => {}
+            (_, Type::Float) => {
+                code.push(Instruction::ToFloat, cast.span);
+            }
+            (_, Type::Integer) => {
+                code.push(Instruction::ToInteger, cast.span);
+            }
+            (from, to) => unreachable!("Can't convert a {} to a {}", from, to),
+        }
+
+        Ok(code)
+    }
+
+    /// Functions are called by name, with the name looked up when the
+    /// bytecode is run.
+    fn call(&self, call: &FunctionCall, args: Vec<Code>) -> Result<Code, BytecodeError> {
+        let mut code = Code::default();
+        for arg in args {
+            code.append(arg);
+        }
+
+        code.push(
+            Instruction::Call {
+                function: self.function_index(&call.name),
+                arity: call.arguments.len() as u8,
+            },
+            call.span,
+        );
+        Ok(code)
+    }
+
+    /// Jump over the `true` branch when the condition is false, and over
+    /// the `false` branch at the end of the `true` branch.
+    fn conditional<T, F>(
+        &self,
+        condition: Code,
+        if_true: T,
+        if_false: F,
+    ) -> Result<Code, BytecodeError>
+    where
+        T: FnOnce() -> Result<Code, BytecodeError>,
+        F: FnOnce() -> Result<Code, BytecodeError>,
+    {
+        // jumps can't fail, so they're attributed to the condition
+        let span = condition.span();
+        let mut code = condition;
+
+        let jump_to_false = code.push(Instruction::JumpIfFalse(0), span);
+        code.append(if_true()?);
+        let jump_to_end = code.push(Instruction::Jump(0), span);
+
+        let if_false_position = code.next_position();
+        code.patch(jump_to_false, if_false_position);
+        code.append(if_false()?);
+
+        let end = code.next_position();
+        code.patch(jump_to_end, end);
+
+        Ok(code)
+    }
+
+    /// Sums and products are lowered to a counted loop, with the counter,
+    /// upper bound and running total each kept in their own slot.
+    fn series<F>(
+        &self,
+        series: &Series,
+        start: Code,
+        end: Code,
+        mut term: F,
+    ) -> Result<Code, BytecodeError>
+    where
+        F: FnMut(Code) -> Result<Code, BytecodeError>,
+    {
+        let span = series.span;
+        let counter = self.allocate_slot();
+        let last = self.allocate_slot();
+        let total = self.allocate_slot();
+
+        let mut code = start;
+        code.push(Instruction::Store(counter), span);
+        code.append(end);
+        code.push(Instruction::Store(last), span);
+        let identity = match series.kind {
+            SeriesKind::Sum => 0.0,
+            SeriesKind::Product => 1.0,
+        };
+        code.push(Instruction::PushFloat(identity), span);
+        code.push(Instruction::Store(total), span);
+
+        // while counter <= last
+        let header = code.next_position();
+        code.push(Instruction::Load(counter), span);
+        code.push(Instruction::Load(last), span);
+        code.push(Instruction::BinaryOp(Op::LessThanOrEqual), span);
+        let exit = code.push(Instruction::JumpIfFalse(0), span);
+
+        code.push(Instruction::Load(total), span);
+        code.append(term(Code::single(Instruction::Load(counter), span))?);
+        let op = match series.kind {
+            SeriesKind::Sum => Op::Add,
+            SeriesKind::Product => Op::Multiply,
+        };
+        code.push(Instruction::BinaryOp(op), span);
+        code.push(Instruction::Store(total), span);
+
+        // stop before incrementing, otherwise `last == i64::MAX` would
+        // overflow
+        code.push(Instruction::Load(counter), span);
+        code.push(Instruction::Load(last), span);
+        code.push(Instruction::BinaryOp(Op::Equal), span);
+        let not_last = code.push(Instruction::JumpIfFalse(0), span);
+        let finished = code.push(Instruction::Jump(0), span);
+
+        let increment = code.next_position();
+        code.patch(not_last, increment);
+        code.push(Instruction::Load(counter), span);
+        code.push(Instruction::PushInteger(1), span);
+        code.push(Instruction::BinaryOp(Op::Add), span);
+        code.push(Instruction::Store(counter), span);
+        code.push(Instruction::Jump(header), span);
+
+        let after = code.next_position();
+        code.patch(exit, after);
+        code.patch(finished, after);
+        code.push(Instruction::Load(total), span);
+
+        Ok(code)
+    }
+
+    /// The value is stored in a slot, and the body loads it from there.
+    fn bind<F>(&self, binding: &Let, value: Code, body: F) -> Result<Code, BytecodeError>
+    where
+        F: FnOnce(Code) -> Result<Code, BytecodeError>,
+    {
+        let slot = self.allocate_slot();
+
+        let mut code = value;
+        code.push(Instruction::Store(slot), binding.span);
+        code.append(body(Code::single(Instruction::Load(slot), binding.span))?);
+
+        Ok(code)
+    }
+
+    fn array(&self, array: &Array, _: Vec<Code>) -> Result<Code, BytecodeError> {
+        unsupported("arrays", array.span)
+    }
+
+    fn index(&self, index: &Index, _: Code, _: Code) -> Result<Code, BytecodeError> {
+        unsupported("arrays", index.span)
+    }
+
+    fn integral(&self, integral: &Integral) -> Result<Code, BytecodeError> {
+        unsupported("integrals", integral.span)
+    }
+
+    fn solve(&self, solve: &Solve) -> Result<Code, BytecodeError> {
+        unsupported("solve()", solve.span)
+    }
+}
+
+/// The instructions for part of an expression.
+///
+/// Jump targets are relative to the start of the `Code` they're in, and get
+/// moved along when it's appended to something else.
+#[derive(Debug, Clone, Default)]
+struct Code {
+    instructions: Vec<Instruction>,
+    spans: Vec<Span>,
+}
+
+impl Code {
+    fn single(instruction: Instruction, span: Span) -> Code {
+        let mut code = Code::default();
+        code.push(instruction, span);
+        code
+    }
+
+    /// Append an instruction, returning its position.
+    fn push(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.instructions.push(instruction);
+        self.spans.push(span);
+        self.instructions.len() - 1
+    }
+
+    /// Add another piece of code to the end of this one.
+    fn append(&mut self, other: Code) {
+        let offset = self.next_position();
+
+        for instruction in other.instructions {
+            let instruction = match instruction {
+                Instruction::Jump(target) => Instruction::Jump(target + offset),
+                Instruction::JumpIfFalse(target) => Instruction::JumpIfFalse(target + offset),
+                other => other,
+            };
+            self.instructions.push(instruction);
+        }
+
+        self.spans.extend(other.spans);
+    }
+
+    fn next_position(&self) -> u32 {
+        self.instructions.len() as u32
+    }
+
+    /// The location of the expression this code calculates.
+    fn span(&self) -> Span {
+        self.spans.last().cloned().unwrap_or_default()
+    }
+
+    /// Point the jump at `position` to `target`, once we know where it
+    /// should go.
+    fn patch(&mut self, position: usize, target: u32) {
+        match self.instructions[position] {
+            Instruction::Jump(ref mut t) | Instruction::JumpIfFalse(ref mut t) => *t = target,
+            other => unreachable!("Tried to patch {}, which isn't a jump", other),
+        }
+    }
+}
+
+fn unsupported<T>(feature: &str, span: Span) -> Result<T, BytecodeError> {
+    Err(BytecodeError::Unsupported {
+        feature: feature.to_string(),
+        span,
+    })
+}
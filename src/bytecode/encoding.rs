@@ -0,0 +1,363 @@
+//! The binary format used to save `Bytecode`.
+//!
+//! Everything is little-endian. The file starts with a header:
+//!
+//! | Bytes | Contents                                   |
+//! |-------|--------------------------------------------|
+//! | 4     | The magic number, `calc`                   |
+//! | 2     | The format version                         |
+//! | 4     | The CRC-32 of everything after the header  |
+//!
+//! Followed by the number of variable slots (`u32`), the function names
+//! (a `u32` count, then a `u32` length and UTF-8 bytes for each name) and
+//! the instructions (a `u32` count, then for each one an opcode byte, its
+//! operands and the start and end of its span as `u32`s).
+
+use super::{Bytecode, BytecodeError, Instruction};
+use syntax::{Op, Span};
+
+/// The version of the binary format written by `Bytecode::to_bytes()`.
+///
+/// This is bumped whenever the encoding changes, so older versions of
+/// `calc` reject the bytecode instead of misinterpreting it.
+pub const FORMAT_VERSION: u16 = 1;
+
+const MAGIC: &[u8; 4] = b"calc";
+const HEADER_LENGTH: usize = 10;
+
+/// Every operator, in the order of the byte used to encode it.
+const OPERATORS: [Op; 14] = [
+    Op::Add,
+    Op::Subtract,
+    Op::Multiply,
+    Op::Divide,
+    Op::IntegerDivide,
+    Op::Modulo,
+    Op::LessThan,
+    Op::LessThanOrEqual,
+    Op::Equal,
+    Op::NotEqual,
+    Op::GreaterThan,
+    Op::GreaterThanOrEqual,
+    Op::And,
+    Op::Or,
+];
+
+impl Bytecode {
+    /// Encode the bytecode so it can be saved and loaded again later.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+
+        write_int(&mut body, u64::from(self.locals), 4);
+
+        write_int(&mut body, self.functions.len() as u64, 4);
+        for name in &self.functions {
+            write_int(&mut body, name.len() as u64, 4);
+            body.extend_from_slice(name.as_bytes());
+        }
+
+        write_int(&mut body, self.instructions.len() as u64, 4);
+        for (instruction, span) in self.instructions.iter().zip(&self.spans) {
+            write_instruction(&mut body, *instruction);
+            write_int(&mut body, span.start as u64, 4);
+            write_int(&mut body, span.end as u64, 4);
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_LENGTH + body.len());
+        bytes.extend_from_slice(MAGIC);
+        write_int(&mut bytes, u64::from(FORMAT_VERSION), 2);
+        write_int(&mut bytes, u64::from(crc32(&body)), 4);
+        bytes.extend_from_slice(&body);
+
+        bytes
+    }
+
+    /// Load bytecode saved by `to_bytes()`.
+    ///
+    /// The header and checksum are checked before anything is decoded.
+    /// Bytecode which decodes successfully may still be nonsensical, which
+    /// the `Vm` reports when it gets to the offending instruction.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Bytecode, BytecodeError> {
+        if bytes.len() < HEADER_LENGTH || &bytes[..4] != MAGIC {
+            return Err(BytecodeError::InvalidHeader);
+        }
+
+        let mut header = Reader {
+            bytes: &bytes[..HEADER_LENGTH],
+            offset: 4,
+        };
+        let version = header.int(2)? as u16;
+        if version != FORMAT_VERSION {
+            return Err(BytecodeError::UnsupportedVersion { version });
+        }
+
+        let checksum = header.int(4)? as u32;
+        let body = &bytes[HEADER_LENGTH..];
+        if crc32(body) != checksum {
+            return Err(BytecodeError::ChecksumMismatch);
+        }
+
+        let mut reader = Reader {
+            bytes,
+            offset: HEADER_LENGTH,
+        };
+        let locals = reader.int(4)? as u32;
+
+        let mut functions = Vec::new();
+        for _ in 0..reader.int(4)? {
+            let length = reader.int(4)? as usize;
+            let offset = reader.offset;
+            let name = String::from_utf8(reader.take(length)?.to_vec())
+                .map_err(|_| BytecodeError::Corrupt { offset })?;
+            functions.push(name);
+        }
+
+        let mut instructions = Vec::new();
+        let mut spans = Vec::new();
+        for _ in 0..reader.int(4)? {
+            instructions.push(reader.instruction()?);
+            let start = reader.int(4)? as usize;
+            let end = reader.int(4)? as usize;
+            spans.push(Span::new(start, end));
+        }
+
+        if reader.offset != bytes.len() {
+            return Err(BytecodeError::Corrupt {
+                offset: reader.offset,
+            });
+        }
+
+        let code = Bytecode {
+            instructions,
+            spans,
+            functions,
+            locals,
+        };
+        if code.locals > code.slots_used() {
+            return Err(BytecodeError::Corrupt {
+                offset: HEADER_LENGTH,
+            });
+        }
+
+        Ok(code)
+    }
+}
+
+fn write_instruction(buffer: &mut Vec<u8>, instruction: Instruction) {
+    match instruction {
+        Instruction::PushFloat(n) => {
+            buffer.push(0);
+            write_int(buffer, n.to_bits(), 8);
+        }
+        Instruction::PushInteger(n) => {
+            buffer.push(1);
+            write_int(buffer, n as u64, 8);
+        }
+        Instruction::PushBool(b) => buffer.extend_from_slice(&[2, b as u8]),
+        Instruction::Load(slot) => {
+            buffer.push(3);
+            write_int(buffer, u64::from(slot), 4);
+        }
+        Instruction::Store(slot) => {
+            buffer.push(4);
+            write_int(buffer, u64::from(slot), 4);
+        }
+        Instruction::BinaryOp(op) => {
+            let code = OPERATORS.iter().position(|&o| o == op).unwrap();
+            buffer.extend_from_slice(&[5, code as u8]);
+        }
+        Instruction::Not => buffer.push(6),
+        Instruction::ToFloat => buffer.push(7),
+        Instruction::ToInteger => buffer.push(8),
+        Instruction::Call { function, arity } => {
+            buffer.push(9);
+            write_int(buffer, u64::from(function), 4);
+            buffer.push(arity);
+        }
+        Instruction::Jump(target) => {
+            buffer.push(10);
+            write_int(buffer, u64::from(target), 4);
+        }
+        Instruction::JumpIfFalse(target) => {
+            buffer.push(11);
+            write_int(buffer, u64::from(target), 4);
+        }
+    }
+}
+
+/// Write the lowest `width` bytes of an integer.
+fn write_int(buffer: &mut Vec<u8>, value: u64, width: usize) {
+    for i in 0..width {
+        buffer.push((value >> (8 * i)) as u8);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], BytecodeError> {
+        let corrupt = BytecodeError::Corrupt {
+            offset: self.offset,
+        };
+        let end = self.offset.checked_add(length).ok_or_else(|| corrupt.clone())?;
+        let taken = self.bytes.get(self.offset..end).ok_or(corrupt)?;
+
+        self.offset = end;
+        Ok(taken)
+    }
+
+    fn int(&mut self, width: usize) -> Result<u64, BytecodeError> {
+        let bytes = self.take(width)?;
+        Ok(bytes
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| (value << 8) | u64::from(byte)))
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, BytecodeError> {
+        let offset = self.offset;
+        let corrupt = BytecodeError::Corrupt { offset };
+
+        let instruction = match self.int(1)? {
+            0 => Instruction::PushFloat(f64::from_bits(self.int(8)?)),
+            1 => Instruction::PushInteger(self.int(8)? as i64),
+            2 => match self.int(1)? {
+                0 => Instruction::PushBool(false),
+                1 => Instruction::PushBool(true),
+                _ => return Err(corrupt),
+            },
+            3 => Instruction::Load(self.int(4)? as u32),
+            4 => Instruction::Store(self.int(4)? as u32),
+            5 => {
+                let op = OPERATORS.get(self.int(1)? as usize).ok_or(corrupt)?;
+                Instruction::BinaryOp(*op)
+            }
+            6 => Instruction::Not,
+            7 => Instruction::ToFloat,
+            8 => Instruction::ToInteger,
+            9 => Instruction::Call {
+                function: self.int(4)? as u32,
+                arity: self.int(1)? as u8,
+            },
+            10 => Instruction::Jump(self.int(4)? as u32),
+            11 => Instruction::JumpIfFalse(self.int(4)? as u32),
+            _ => return Err(corrupt),
+        };
+
+        Ok(instruction)
+    }
+}
+
+/// The CRC-32 used by zlib and PNG.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0_u32;
+
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            // xor with the polynomial whenever the low bit is set
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use environment::Environment;
+    use syntax;
+
+    fn compile(src: &str) -> Bytecode {
+        let ast = syntax::parse(src).unwrap();
+        Bytecode::compile(&ast, &Environment::new()).unwrap()
+    }
+
+    #[test]
+    fn known_checksum() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn round_trip_through_bytes() {
+        let inputs = vec![
+            "1 + 2.5 * 3",
+            "if true and not false then 0x10 // 3 else 2 - 3",
+            "sum(k, 1, 10, sqrt(k)) + pow(2, 0.5) + max(1, 2)",
+            "0.1 + 1e-300 + 6.02e23 + pi",
+        ];
+
+        for src in inputs {
+            let code = compile(src);
+            let bytes = code.to_bytes();
+            assert_eq!(&bytes[..6], b"calc\x01\x00", "{}", src);
+
+            let got = Bytecode::from_bytes(&bytes).unwrap();
+            assert_eq!(got, code, "{}", src);
+        }
+    }
+
+    #[test]
+    fn damaged_bytecode_is_rejected() {
+        let bytes = compile("sqrt(2) * 3").to_bytes();
+
+        assert_eq!(
+            Bytecode::from_bytes(b"not bytecode").unwrap_err(),
+            BytecodeError::InvalidHeader
+        );
+
+        let mut newer = bytes.clone();
+        newer[4] = 2;
+        assert_eq!(
+            Bytecode::from_bytes(&newer).unwrap_err(),
+            BytecodeError::UnsupportedVersion { version: 2 }
+        );
+
+        let mut flipped = bytes.clone();
+        let last = flipped.len() - 1;
+        flipped[last] ^= 0x80;
+        assert_eq!(
+            Bytecode::from_bytes(&flipped).unwrap_err(),
+            BytecodeError::ChecksumMismatch
+        );
+
+        assert_eq!(
+            Bytecode::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            BytecodeError::ChecksumMismatch
+        );
+    }
+
+    #[test]
+    fn corrupt_bodies_are_rejected() {
+        let with_checksum = |body: &[u8]| {
+            let mut bytes = b"calc\x01\x00".to_vec();
+            write_int(&mut bytes, u64::from(crc32(body)), 4);
+            bytes.extend_from_slice(body);
+            bytes
+        };
+
+        let inputs: Vec<(&[u8], usize)> = vec![
+            // a truncated instruction count
+            (&[0, 0, 0, 0, 0, 0, 0, 0, 1, 0], 18),
+            // an unknown opcode
+            (&[0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 42], 22),
+            // a function name which isn't UTF-8
+            (&[0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0xFF], 22),
+            // trailing bytes
+            (&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], 22),
+            // more locals than the instructions use
+            (&[0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0], 10),
+        ];
+
+        for (body, offset) in inputs {
+            let got = Bytecode::from_bytes(&with_checksum(body)).unwrap_err();
+            assert_eq!(got, BytecodeError::Corrupt { offset }, "{:?}", body);
+        }
+    }
+}
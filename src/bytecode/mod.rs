@@ -0,0 +1,315 @@
+//! Compile expressions to a compact bytecode which is evaluated by a small
+//! stack machine.
+//!
+//! Bytecode is a middle ground between the [`interpreter`] and the JIT.
+//! Compiling it is cheap and doesn't need LLVM, and evaluating it avoids
+//! walking the AST. A compiled `Bytecode` can be saved with `to_bytes()`
+//! and loaded again with `from_bytes()`, so a formula can be compiled once
+//! and evaluated wherever it's needed.
+//!
+//! Only real numbers, integers and booleans are supported, so complex
+//! numbers, arrays, integrals and `solve()` are reported as an error when
+//! compiling. Like the JIT with runtime checks enabled, division by zero
+//! and calling a function outside its domain are errors instead of giving
+//! `inf` or `NaN`.
+//!
+//! ```rust
+//! use calc::bytecode::{Bytecode, Vm};
+//! use calc::environment::Environment;
+//!
+//! let ast = calc::syntax::parse("sum(k, 1, 4, k * k) / 2").unwrap();
+//! let code = Bytecode::compile(&ast, &Environment::new()).unwrap();
+//!
+//! let blob = code.to_bytes();
+//! let loaded = Bytecode::from_bytes(&blob).unwrap();
+//!
+//! assert_eq!(Vm::new().run(&loaded).unwrap(), 15.0);
+//! ```
+//!
+//! [`interpreter`]: ../interpreter/index.html
+
+mod compile;
+mod encoding;
+mod vm;
+
+pub use self::encoding::FORMAT_VERSION;
+pub use self::vm::{Vm, DEFAULT_INSTRUCTION_LIMIT};
+
+use failure::Error;
+use std::fmt::{self, Display, Formatter};
+
+use environment::Environment;
+use sema;
+use syntax::{Expr, Op, Span};
+
+/// A compiled expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode {
+    instructions: Vec<Instruction>,
+    /// The location of the expression each instruction came from.
+    spans: Vec<Span>,
+    /// The name of every function which gets called.
+    functions: Vec<String>,
+    /// The number of slots needed for variables.
+    locals: u32,
+}
+
+impl Bytecode {
+    /// Type check an expression and compile it to bytecode.
+    ///
+    /// Functions registered with the `Environment` are called by name, so
+    /// the [`Vm`] needs an `Environment` which provides them.
+    ///
+    /// [`Vm`]: struct.Vm.html
+    pub fn compile(ast: &Expr, env: &Environment) -> Result<Bytecode, Error> {
+        let (ast, _) = sema::type_check(ast, env)?;
+        Ok(compile::compile(&ast)?)
+    }
+
+    /// The instructions to execute, in order.
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// Where the instruction at `position` came from in the source code.
+    pub fn span(&self, position: usize) -> Option<Span> {
+        self.spans.get(position).cloned()
+    }
+
+    /// The name of a function used by a `Call` instruction.
+    pub fn function(&self, index: u32) -> Option<&str> {
+        self.functions.get(index as usize).map(|name| name.as_str())
+    }
+
+    /// The number of variable slots used by `Load` and `Store`.
+    pub fn locals(&self) -> u32 {
+        self.locals
+    }
+
+    /// One more than the highest slot used by a `Load` or `Store`. Bytecode
+    /// never needs more locals than this, so anything claiming to is corrupt
+    /// (and could make the `Vm` allocate an absurd amount of memory).
+    fn slots_used(&self) -> u32 {
+        self.instructions
+            .iter()
+            .filter_map(|instruction| match *instruction {
+                Instruction::Load(slot) | Instruction::Store(slot) => Some(slot.saturating_add(1)),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// The disassembly, one instruction per line.
+impl Display for Bytecode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        writeln!(f, "; calc bytecode v{}, {} locals", FORMAT_VERSION, self.locals)?;
+
+        for (position, instruction) in self.instructions.iter().enumerate() {
+            write!(f, "{:04}  ", position)?;
+
+            match *instruction {
+                Instruction::Call { function, arity } => match self.function(function) {
+                    Some(name) => writeln!(f, "call {}/{}", name, arity)?,
+                    None => writeln!(f, "{}", instruction)?,
+                },
+                other => writeln!(f, "{}", other)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A single operation for the stack machine.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Instruction {
+    /// Push a float onto the stack.
+    PushFloat(f64),
+    /// Push an integer onto the stack.
+    PushInteger(i64),
+    /// Push a boolean onto the stack.
+    PushBool(bool),
+    /// Push the value of a variable onto the stack.
+    Load(u32),
+    /// Pop a value off the stack and save it in a variable.
+    Store(u32),
+    /// Pop the right operand, then the left operand, and push the result.
+    BinaryOp(Op),
+    /// Logical negation of the top of the stack.
+    Not,
+    /// Convert the top of the stack to a float.
+    ToFloat,
+    /// Convert the top of the stack to an integer, rounding towards zero.
+    ToInteger,
+    /// Pop `arity` arguments (the last argument is on top) and call a
+    /// function, pushing its result.
+    Call {
+        /// The index of the function's name.
+        function: u32,
+        /// The number of arguments.
+        arity: u8,
+    },
+    /// Continue from another instruction.
+    Jump(u32),
+    /// Pop a boolean and continue from another instruction if it's false.
+    JumpIfFalse(u32),
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Instruction::PushFloat(n) => write!(f, "push_float {:?}", n),
+            Instruction::PushInteger(n) => write!(f, "push_int {}", n),
+            Instruction::PushBool(b) => write!(f, "push_bool {}", b),
+            Instruction::Load(slot) => write!(f, "load {}", slot),
+            Instruction::Store(slot) => write!(f, "store {}", slot),
+            Instruction::BinaryOp(op) => write!(f, "binop {}", op),
+            Instruction::Not => write!(f, "not"),
+            Instruction::ToFloat => write!(f, "to_float"),
+            Instruction::ToInteger => write!(f, "to_int"),
+            Instruction::Call { function, arity } => write!(f, "call #{}/{}", function, arity),
+            Instruction::Jump(target) => write!(f, "jump {:04}", target),
+            Instruction::JumpIfFalse(target) => write!(f, "jump_if_false {:04}", target),
+        }
+    }
+}
+
+/// Errors from compiling, loading or running bytecode.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum BytecodeError {
+    /// Part of the expression can't be expressed as bytecode.
+    #[fail(display = "Bytecode doesn't support {} (at {})", feature, span)]
+    Unsupported {
+        /// What isn't supported.
+        feature: String,
+        /// The offending expression.
+        span: Span,
+    },
+    /// Division or modulo by zero.
+    #[fail(display = "Division by zero at {}", span)]
+    DivideByZero {
+        /// The division.
+        span: Span,
+    },
+    /// An integer operation overflowed.
+    #[fail(display = "Integer overflow at {}", span)]
+    IntegerOverflow {
+        /// The operation which overflowed.
+        span: Span,
+    },
+    /// A function was called with an argument it isn't defined for.
+    #[fail(display = "Argument outside the function's domain at {}", span)]
+    DomainError {
+        /// The function call.
+        span: Span,
+    },
+    /// A function which is neither a built-in nor provided by the `Vm`'s
+    /// `Environment`.
+    #[fail(display = "Unknown function, \"{}\" at {}", name, span)]
+    UnknownFunction {
+        /// The function's name.
+        name: String,
+        /// The function call.
+        span: Span,
+    },
+    /// A closure registered with the `Vm`'s `Environment` panicked.
+    #[fail(display = "The host function called at {} panicked", span)]
+    HostFunctionPanicked {
+        /// The function call.
+        span: Span,
+    },
+    /// An instruction which can't be executed, e.g. because it pops more
+    /// values than are on the stack.
+    #[fail(display = "Malformed bytecode at instruction {}", position)]
+    Malformed {
+        /// The index of the instruction.
+        position: usize,
+    },
+    /// The `Vm` executed its limit of instructions without finishing, e.g.
+    /// because of a jump which loops forever.
+    #[fail(display = "Gave up after executing {} instructions", limit)]
+    InstructionLimitExceeded {
+        /// The `Vm`'s limit.
+        limit: u64,
+    },
+    /// An instruction tried to push more values than the `Vm`'s stack can
+    /// hold.
+    #[fail(display = "Stack overflow at instruction {}", position)]
+    StackOverflow {
+        /// The index of the instruction.
+        position: usize,
+    },
+    /// The data doesn't start with the bytecode header.
+    #[fail(display = "The data isn't calc bytecode")]
+    InvalidHeader,
+    /// The bytecode was saved by an incompatible version of `calc`.
+    #[fail(display = "Unsupported bytecode version {}", version)]
+    UnsupportedVersion {
+        /// The version in the header.
+        version: u16,
+    },
+    /// The checksum in the header doesn't match the data.
+    #[fail(display = "The bytecode's checksum doesn't match")]
+    ChecksumMismatch,
+    /// The data couldn't be decoded.
+    #[fail(display = "The bytecode is corrupt at byte {}", offset)]
+    Corrupt {
+        /// Where decoding failed.
+        offset: usize,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntax;
+
+    fn compile(src: &str) -> Bytecode {
+        let ast = syntax::parse(src).unwrap();
+        Bytecode::compile(&ast, &Environment::new()).unwrap()
+    }
+
+    #[test]
+    fn disassemble_a_conditional() {
+        let got = compile("if 1 < 2 then sqrt(4) else 0").to_string();
+        let should_be = "\
+; calc bytecode v1, 0 locals
+0000  push_int 1
+0001  push_int 2
+0002  binop <
+0003  jump_if_false 0008
+0004  push_int 4
+0005  to_float
+0006  call sqrt/1
+0007  jump 0010
+0008  push_int 0
+0009  to_float
+";
+
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn some_expressions_can_not_be_compiled() {
+        let inputs = vec![
+            ("1 + 2i", "complex numbers"),
+            ("sum([1, 2])", "arrays"),
+            ("integrate(x, x, 0, 1)", "integrals"),
+            ("solve(x == 1, x, 0)", "solve()"),
+        ];
+
+        for (src, feature) in inputs {
+            let ast = syntax::parse(src).unwrap();
+            let err = Bytecode::compile(&ast, &Environment::new()).unwrap_err();
+
+            match err.downcast::<BytecodeError>() {
+                Ok(BytecodeError::Unsupported { feature: ref got, .. }) => {
+                    assert_eq!(got, feature, "{}", src)
+                }
+                other => panic!("Unexpected result for {}: {:?}", src, other),
+            }
+        }
+    }
+}
@@ -0,0 +1,453 @@
+use super::{Bytecode, BytecodeError, Instruction};
+use builtins;
+use environment::Environment;
+use syntax::{Op, Span};
+
+/// The number of instructions a `Vm` executes before giving up, unless
+/// it's changed with `with_instruction_limit()`.
+pub const DEFAULT_INSTRUCTION_LIMIT: u64 = 1 << 32;
+
+/// The most values the stack can hold. Compiled bytecode only needs one
+/// slot per level of nesting in the expression.
+const MAX_STACK_DEPTH: usize = 1 << 16;
+
+/// A stack machine which runs `Bytecode`.
+///
+/// Bytecode may have been loaded from anywhere, so every instruction is
+/// checked before it's executed and problems (e.g. popping from an empty
+/// stack) are reported as `BytecodeError::Malformed` instead of panicking.
+/// Jumps can go backwards, so the number of instructions executed and the
+/// size of the stack are both limited as well.
+#[derive(Debug, Clone)]
+pub struct Vm {
+    env: Environment,
+    instruction_limit: u64,
+}
+
+impl Vm {
+    /// Create a `Vm` which only knows about the built-in functions.
+    pub fn new() -> Vm {
+        Vm {
+            env: Environment::new(),
+            instruction_limit: DEFAULT_INSTRUCTION_LIMIT,
+        }
+    }
+
+    /// Provide the host functions called by the bytecode.
+    pub fn with_environment(mut self, env: &Environment) -> Vm {
+        self.env = env.clone();
+        self
+    }
+
+    /// Stop with `BytecodeError::InstructionLimitExceeded` after executing
+    /// this many instructions, instead of `DEFAULT_INSTRUCTION_LIMIT`.
+    pub fn with_instruction_limit(mut self, limit: u64) -> Vm {
+        self.instruction_limit = limit;
+        self
+    }
+
+    /// Run the bytecode, returning the value left on the stack. Integers and
+    /// booleans are converted to a float, the same way `Program::call()`
+    /// does.
+    pub fn run(&self, code: &Bytecode) -> Result<f64, BytecodeError> {
+        if code.locals > code.slots_used() {
+            return Err(BytecodeError::Malformed { position: 0 });
+        }
+
+        let mut stack = Vec::new();
+        let mut locals = vec![Value::Integer(0); code.locals as usize];
+        let mut position = 0;
+        let mut executed = 0;
+
+        while position < code.instructions.len() {
+            if executed == self.instruction_limit {
+                return Err(BytecodeError::InstructionLimitExceeded {
+                    limit: self.instruction_limit,
+                });
+            }
+            executed += 1;
+
+            let instruction = code.instructions[position];
+            let span = code.spans.get(position).cloned().unwrap_or_default();
+            let malformed = BytecodeError::Malformed { position };
+            let overflow = BytecodeError::StackOverflow { position };
+            position += 1;
+
+            match instruction {
+                Instruction::PushFloat(n) => stack.push(Value::Float(n)),
+                Instruction::PushInteger(n) => stack.push(Value::Integer(n)),
+                Instruction::PushBool(b) => stack.push(Value::Bool(b)),
+                Instruction::Load(slot) => {
+                    let value = locals.get(slot as usize).ok_or_else(|| malformed.clone())?;
+                    stack.push(*value);
+                }
+                Instruction::Store(slot) => {
+                    let value = stack.pop().ok_or_else(|| malformed.clone())?;
+                    let local = locals.get_mut(slot as usize).ok_or(malformed)?;
+                    *local = value;
+                }
+                Instruction::BinaryOp(op) => {
+                    let right = stack.pop();
+                    let left = stack.pop();
+                    let result = match (left, right) {
+                        (Some(left), Some(right)) => binary_op(op, left, right, span)?,
+                        _ => None,
+                    };
+                    stack.push(result.ok_or(malformed)?);
+                }
+                Instruction::Not => match stack.pop() {
+                    Some(Value::Bool(b)) => stack.push(Value::Bool(!b)),
+                    _ => return Err(malformed),
+                },
+                Instruction::ToFloat => {
+                    let value = stack.pop().ok_or(malformed)?;
+                    stack.push(Value::Float(value.to_f64()));
+                }
+                Instruction::ToInteger => {
+                    let value = match stack.pop() {
+                        Some(Value::Float(f)) => f as i64,
+                        Some(Value::Integer(i)) => i,
+                        Some(Value::Bool(b)) => b as i64,
+                        None => return Err(malformed),
+                    };
+                    stack.push(Value::Integer(value));
+                }
+                Instruction::Call { function, arity } => {
+                    let name = code.function(function).ok_or_else(|| malformed.clone())?;
+                    let split = stack
+                        .len()
+                        .checked_sub(arity as usize)
+                        .ok_or_else(|| malformed.clone())?;
+                    let mut args = Vec::new();
+                    for arg in stack.drain(split..) {
+                        match arg {
+                            Value::Float(f) => args.push(f),
+                            _ => return Err(malformed),
+                        }
+                    }
+
+                    let result = self.call_function(name, &args, span)?;
+                    stack.push(Value::Float(result.ok_or(malformed)?));
+                }
+                Instruction::Jump(target) => {
+                    if target as usize > code.instructions.len() {
+                        return Err(malformed);
+                    }
+                    position = target as usize;
+                }
+                Instruction::JumpIfFalse(target) => {
+                    if target as usize > code.instructions.len() {
+                        return Err(malformed);
+                    }
+                    match stack.pop() {
+                        Some(Value::Bool(true)) => {}
+                        Some(Value::Bool(false)) => position = target as usize,
+                        _ => return Err(malformed),
+                    }
+                }
+            }
+
+            if stack.len() > MAX_STACK_DEPTH {
+                return Err(overflow);
+            }
+        }
+
+        match (stack.pop(), stack.is_empty()) {
+            (Some(value), true) => Ok(value.to_f64()),
+            _ => Err(BytecodeError::Malformed { position }),
+        }
+    }
+
+    /// Call a host function or built-in, returning `None` if it was given
+    /// the wrong number of arguments.
+    fn call_function(
+        &self,
+        name: &str,
+        args: &[f64],
+        span: Span,
+    ) -> Result<Option<f64>, BytecodeError> {
+        if let Some(host) = self.env.function(name) {
+            if host.arity() != args.len() {
+                return Ok(None);
+            }
+            return host
+                .call(args)
+                .map(Some)
+                .ok_or(BytecodeError::HostFunctionPanicked { span });
+        }
+
+        let builtin = match builtins::lookup(name) {
+            Some(builtin) => builtin,
+            None => {
+                return Err(BytecodeError::UnknownFunction {
+                    name: name.to_string(),
+                    span,
+                })
+            }
+        };
+
+        if builtin.arity == args.len() && !builtin.domain.contains(args[0]) {
+            return Err(BytecodeError::DomainError { span });
+        }
+
+        Ok(call_builtin(name, args))
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Vm {
+        Vm::new()
+    }
+}
+
+/// A value on the stack.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Value {
+    Float(f64),
+    Integer(i64),
+    Bool(bool),
+}
+
+impl Value {
+    fn to_f64(self) -> f64 {
+        match self {
+            Value::Float(f) => f,
+            Value::Integer(i) => i as f64,
+            Value::Bool(b) => if b { 1.0 } else { 0.0 },
+        }
+    }
+}
+
+/// Apply a binary operator, returning `None` if the operands have the wrong
+/// types.
+fn binary_op(
+    op: Op,
+    left: Value,
+    right: Value,
+    span: Span,
+) -> Result<Option<Value>, BytecodeError> {
+    let value = match (left, right) {
+        (Value::Float(l), Value::Float(r)) => match op {
+            Op::Add => Value::Float(l + r),
+            Op::Subtract => Value::Float(l - r),
+            Op::Multiply => Value::Float(l * r),
+            Op::Divide if r == 0.0 => return Err(BytecodeError::DivideByZero { span }),
+            Op::Divide => Value::Float(l / r),
+            Op::LessThan => Value::Bool(l < r),
+            Op::LessThanOrEqual => Value::Bool(l <= r),
+            Op::Equal => Value::Bool(l == r),
+            Op::NotEqual => Value::Bool(l != r),
+            Op::GreaterThan => Value::Bool(l > r),
+            Op::GreaterThanOrEqual => Value::Bool(l >= r),
+            Op::IntegerDivide | Op::Modulo | Op::And | Op::Or => return Ok(None),
+        },
+        (Value::Integer(l), Value::Integer(r)) => {
+            let checked = match op {
+                Op::Add => l.checked_add(r),
+                Op::Subtract => l.checked_sub(r),
+                Op::Multiply => l.checked_mul(r),
+                Op::IntegerDivide | Op::Modulo if r == 0 => {
+                    return Err(BytecodeError::DivideByZero { span })
+                }
+                // integer operations truncate towards zero
+                Op::IntegerDivide => l.checked_div(r),
+                Op::Modulo => l.checked_rem(r),
+                Op::LessThan => return Ok(Some(Value::Bool(l < r))),
+                Op::LessThanOrEqual => return Ok(Some(Value::Bool(l <= r))),
+                Op::Equal => return Ok(Some(Value::Bool(l == r))),
+                Op::NotEqual => return Ok(Some(Value::Bool(l != r))),
+                Op::GreaterThan => return Ok(Some(Value::Bool(l > r))),
+                Op::GreaterThanOrEqual => return Ok(Some(Value::Bool(l >= r))),
+                Op::Divide | Op::And | Op::Or => return Ok(None),
+            };
+
+            match checked {
+                Some(n) => Value::Integer(n),
+                None => return Err(BytecodeError::IntegerOverflow { span }),
+            }
+        }
+        // `and` and `or` are compiled to jumps, so they never get here
+        (Value::Bool(l), Value::Bool(r)) => match op {
+            Op::Equal => Value::Bool(l == r),
+            Op::NotEqual => Value::Bool(l != r),
+            _ => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+
+    Ok(Some(value))
+}
+
+/// Evaluate a built-in function using the `f64` method which matches the
+/// LLVM intrinsic the JIT would use.
+fn call_builtin(name: &str, args: &[f64]) -> Option<f64> {
+    let value = match (name, args) {
+        ("sqrt", &[x]) => x.sqrt(),
+        ("sin", &[x]) => x.sin(),
+        ("cos", &[x]) => x.cos(),
+        ("exp", &[x]) => x.exp(),
+        ("ln", &[x]) => x.ln(),
+        ("log2", &[x]) => x.log2(),
+        ("log10", &[x]) => x.log10(),
+        ("abs", &[x]) => x.abs(),
+        ("floor", &[x]) => x.floor(),
+        ("ceil", &[x]) => x.ceil(),
+        ("round", &[x]) => x.round(),
+        ("pow", &[x, y]) => x.powf(y),
+        ("min", &[x, y]) => x.min(y),
+        ("max", &[x, y]) => x.max(y),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntax;
+
+    fn run(src: &str) -> Result<f64, BytecodeError> {
+        let mut env = Environment::new();
+        env.register_closure("double", 1, |args| args[0] * 2.0)
+            .register_closure("oops", 1, |_| panic!("Oops"));
+
+        let ast = syntax::parse(src).unwrap();
+        let code = Bytecode::compile(&ast, &env).unwrap();
+        Vm::new().with_environment(&env).run(&code)
+    }
+
+    #[test]
+    fn evaluate_expressions() {
+        let inputs = vec![
+            ("1 + 2 * 3", 7.0),
+            ("7 // 2 + 7 % 2 + (0 - 7) // 2", 1.0),
+            ("1 / 4", 0.25),
+            ("if 1 < 2 and not false then 10 else 20", 10.0),
+            ("if 1.5 >= 2 or 3 == 4 then 10 else 20", 20.0),
+            ("1 > 2 and 1 // 0 == 0 or 1 < 2 or 1 // 0 == 0", 1.0),
+            ("2 > 1", 1.0),
+            ("sqrt(16) + pow(2, 10) + max(1, 2)", 1030.0),
+            ("round(2.5) + floor(0 - 0.5)", 2.0),
+            ("sum(k, 1, 100, k)", 5050.0),
+            ("prod(i, 1, 5, i)", 120.0),
+            ("sum(i, 1, 3, sum(j, 1, i, j))", 10.0),
+            ("sum(k, 5, 1, k)", 0.0),
+            ("sum(i, 9223372036854775806, 9223372036854775807, 1)", 2.0),
+            ("double(21)", 42.0),
+            ("pi", ::std::f64::consts::PI),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = run(src).unwrap();
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn runtime_errors() {
+        let inputs = vec![
+            ("1 / 0", "Division by zero at 0..5"),
+            ("2 * (1 % 0)", "Division by zero at 5..10"),
+            ("9223372036854775807 + 1", "Integer overflow at 0..23"),
+            ("1 + ln(0)", "Argument outside the function's domain at 4..9"),
+            ("1 + oops(2)", "The host function called at 4..11 panicked"),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = run(src).unwrap_err();
+            assert_eq!(got.to_string(), should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn host_functions_must_be_provided() {
+        let mut env = Environment::new();
+        env.register_closure("f", 0, |_| 1.0);
+        let ast = syntax::parse("f()").unwrap();
+        let code = Bytecode::compile(&ast, &env).unwrap();
+
+        assert_eq!(Vm::new().with_environment(&env).run(&code).unwrap(), 1.0);
+        assert_eq!(
+            Vm::new().run(&code).unwrap_err(),
+            BytecodeError::UnknownFunction {
+                name: String::from("f"),
+                span: Span::new(0, 3),
+            }
+        );
+    }
+
+    #[test]
+    fn bytecode_which_never_finishes_is_an_error() {
+        use super::Instruction::*;
+
+        let code = Bytecode {
+            instructions: vec![Jump(0)],
+            spans: vec![Span::default()],
+            functions: Vec::new(),
+            locals: 0,
+        };
+        assert_eq!(
+            Vm::new().with_instruction_limit(1000).run(&code).unwrap_err(),
+            BytecodeError::InstructionLimitExceeded { limit: 1000 }
+        );
+
+        let code = Bytecode {
+            instructions: vec![PushFloat(1.0), Jump(0)],
+            spans: vec![Span::default(); 2],
+            functions: Vec::new(),
+            locals: 0,
+        };
+        match Vm::new().run(&code) {
+            Err(BytecodeError::StackOverflow { position: 0 }) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let ast = syntax::parse("sum(k, 1, 100, k)").unwrap();
+        let code = Bytecode::compile(&ast, &Environment::new()).unwrap();
+        assert!(Vm::new().with_instruction_limit(100).run(&code).is_err());
+        assert_eq!(Vm::new().run(&code).unwrap(), 5050.0);
+    }
+
+    #[test]
+    fn malformed_bytecode_is_an_error() {
+        use super::Instruction::*;
+
+        let inputs = vec![
+            vec![BinaryOp(Op::Add)],
+            vec![PushBool(true), PushInteger(1), BinaryOp(Op::Add)],
+            vec![PushBool(true), PushBool(false), BinaryOp(Op::And)],
+            vec![PushFloat(1.0), Jump(7)],
+            vec![Load(0)],
+            vec![PushFloat(1.0), Call { function: 0, arity: 1 }],
+            vec![PushFloat(1.0), PushFloat(2.0)],
+            vec![],
+        ];
+
+        for instructions in inputs {
+            let code = Bytecode {
+                spans: vec![Span::default(); instructions.len()],
+                instructions,
+                functions: Vec::new(),
+                locals: 0,
+            };
+
+            match Vm::new().run(&code) {
+                Err(BytecodeError::Malformed { .. }) => {}
+                other => panic!("Unexpected result for {:?}: {:?}", code, other),
+            }
+        }
+
+        // far more locals than the instructions use
+        let code = Bytecode {
+            instructions: vec![PushFloat(1.0), Store(0)],
+            spans: vec![Span::default(); 2],
+            functions: Vec::new(),
+            locals: u32::max_value(),
+        };
+        assert_eq!(
+            Vm::new().run(&code).unwrap_err(),
+            BytecodeError::Malformed { position: 0 }
+        );
+    }
+}
@@ -0,0 +1,867 @@
+//! Generate C source code for targets which can't run LLVM or Cranelift.
+//!
+//! The expression becomes a self-contained C99 function which only needs
+//! `<math.h>` and `<stdint.h>`. It takes the same parameters as the
+//! JIT compiled `calc_main` (a pointer the result is written to, then a
+//! pointer to the location of any error) and returns the same [`Status`]
+//! codes.
+//!
+//! ```rust
+//! use calc::c::Emitter;
+//! use calc::environment::Environment;
+//!
+//! let ast = calc::syntax::parse("(1.5 + 2.5) * sqrt(2)").unwrap();
+//! let (ast, _) = calc::sema::type_check(&ast, &Environment::new()).unwrap();
+//!
+//! let src = Emitter::new().emit(&ast).unwrap();
+//! assert!(src.contains("result[0] = (1.5 + 2.5) * sqrt(2.0);"));
+//! ```
+//!
+//! Only real numbers, integers and booleans are supported. Complex numbers,
+//! arrays, integrals, `solve()` and host functions registered as closures
+//! are reported as [`Unsupported`].
+//!
+//! [`Status`]: ../backend/enum.Status.html
+//! [`Unsupported`]: struct.Unsupported.html
+
+use failure::Error;
+use std::cell::{Cell, RefCell};
+use std::f64;
+
+use backend::{self, Backend, Status, CALC_ENTRYPOINT};
+use builtins::{self, Domain};
+use environment::Environment;
+use syntax::{Array, Atom, BinaryOp, Cast, Expr, FunctionCall, Index, Integral, Let, Op,
+             Series, SeriesKind, Solve, Span, Type, UnaryOp, UnaryOperator};
+
+/// Turns a type checked `Expr` into a C function.
+#[derive(Debug, Clone)]
+pub struct Emitter {
+    name: String,
+    runtime_checks: bool,
+    env: Environment,
+    /// The statements making up the block currently being emitted.
+    lines: RefCell<Vec<String>>,
+    /// Prototypes for any host functions which get called.
+    prototypes: RefCell<Vec<String>>,
+    /// The variables bound by any sums, products or `Let`s we're currently
+    /// inside.
+    scope: RefCell<Vec<(String, Code)>>,
+    /// The number of temporaries and loop counters declared so far.
+    names: Cell<usize>,
+}
+
+impl Emitter {
+    /// Create an `Emitter` which generates a function called `calc_main`,
+    /// with runtime checks disabled.
+    pub fn new() -> Emitter {
+        Emitter {
+            name: CALC_ENTRYPOINT.to_string(),
+            runtime_checks: false,
+            env: Environment::new(),
+            lines: RefCell::new(Vec::new()),
+            prototypes: RefCell::new(Vec::new()),
+            scope: RefCell::new(Vec::new()),
+            names: Cell::new(0),
+        }
+    }
+
+    /// Use a different name for the generated function, so several
+    /// expressions can be linked into the same program.
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Emitter {
+        self.name = name.into();
+        self
+    }
+
+    /// Guard floating point division and calls to functions which are only
+    /// defined for some inputs, the same as
+    /// `trans::Compiler::with_runtime_checks()`.
+    ///
+    /// Integer overflow is always checked, regardless of this setting.
+    pub fn with_runtime_checks(mut self, enabled: bool) -> Emitter {
+        self.runtime_checks = enabled;
+        self
+    }
+
+    /// Let the expression call extern functions registered with an
+    /// `Environment`. They are declared in the generated code, and need to
+    /// be provided when it is linked.
+    pub fn with_environment(mut self, env: &Environment) -> Emitter {
+        self.env = env.clone();
+        self
+    }
+
+    /// Generate C for an expression which has already been through
+    /// [`sema::type_check()`].
+    ///
+    /// [`sema::type_check()`]: ../sema/fn.type_check.html
+    pub fn emit(self, ast: &Expr) -> Result<String, Error> {
+        let value = backend::lower(&self, ast)?;
+        self.statement(format!("result[0] = {};", to_double(value).code));
+        self.statement("result[1] = 0.0;");
+        self.statement(format!("return {};", Status::Ok as u32));
+
+        let mut src = String::new();
+        src.push_str("#include <math.h>\n");
+        src.push_str("#include <stdint.h>\n\n");
+        src.push_str("#ifndef CALC_ERROR_LOCATION\n");
+        src.push_str("#define CALC_ERROR_LOCATION\n");
+        src.push_str("struct calc_error_location {\n");
+        src.push_str("    uint64_t start;\n");
+        src.push_str("    uint64_t end;\n");
+        src.push_str("};\n");
+        src.push_str("#endif\n\n");
+
+        for prototype in self.prototypes.borrow().iter() {
+            src.push_str(prototype);
+            src.push('\n');
+        }
+        if !self.prototypes.borrow().is_empty() {
+            src.push('\n');
+        }
+
+        src.push_str(&format!(
+            "uint32_t {}(double result[2], struct calc_error_location *location) {{\n",
+            self.name
+        ));
+        for line in self.lines.borrow().iter() {
+            src.push_str("    ");
+            src.push_str(line);
+            src.push('\n');
+        }
+        src.push_str("}\n");
+
+        Ok(src)
+    }
+
+    fn statement<S: Into<String>>(&self, line: S) {
+        self.lines.borrow_mut().push(line.into());
+    }
+
+    /// Run `f`, returning the statements it emits instead of adding them to
+    /// the current block.
+    fn block<F, T>(&self, f: F) -> (Vec<String>, T)
+    where
+        F: FnOnce() -> T,
+    {
+        let outer = self.lines.replace(Vec::new());
+        let value = f();
+        let inner = self.lines.replace(outer);
+
+        (inner, value)
+    }
+
+    /// Emit a nested block, indented one level deeper.
+    fn nested(&self, lines: Vec<String>) {
+        for line in lines {
+            self.statement(format!("    {}", line));
+        }
+    }
+
+    fn fresh_name(&self, prefix: &str) -> String {
+        let n = self.names.get() + 1;
+        self.names.set(n);
+        format!("{}{}", prefix, n)
+    }
+
+    /// Store a value in a variable so it can be used more than once without
+    /// being recalculated.
+    ///
+    /// Literals get a variable too, otherwise the compiler warns about
+    /// overflow or division by zero in code our checks make unreachable.
+    fn temporary(&self, value: Code) -> Code {
+        let is_variable = value
+            .code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !value.code.starts_with(|c: char| c.is_ascii_digit());
+        if is_variable {
+            return value;
+        }
+
+        let name = self.fresh_name("t");
+        self.statement(format!("const {} {} = {};", c_type(value.ty), name, value.code));
+        Code::primary(name, value.ty)
+    }
+
+    /// Return `status` from the function when `condition` is true, recording
+    /// the offending expression's location.
+    fn bail_if(&self, condition: &str, status: Status, span: Span) {
+        self.statement(format!("if ({}) {{", condition));
+        self.statement(format!("    location->start = {};", span.start));
+        self.statement(format!("    location->end = {};", span.end));
+        self.statement(format!("    return {};", status as u32));
+        self.statement("}");
+    }
+
+    fn float_binary_op(&self, op: &BinaryOp, left: Code, right: Code) -> Code {
+        let (symbol, precedence) = match op.op {
+            Op::Add => ("+", Precedence::Additive),
+            Op::Subtract => ("-", Precedence::Additive),
+            Op::Multiply => ("*", Precedence::Multiplicative),
+            Op::Divide => {
+                let right = if self.runtime_checks {
+                    let right = self.temporary(right);
+                    self.bail_if(&format!("{} == 0.0", right.code), Status::DivideByZero, op.span);
+                    right
+                } else {
+                    right
+                };
+
+                return binary(left, "/", Precedence::Multiplicative, right, Type::Float);
+            }
+            Op::IntegerDivide | Op::Modulo => unreachable!("{} only accepts integers", op.op),
+            Op::And | Op::Or => unreachable!("Logical operators are lowered to conditionals"),
+            _ => return comparison(op.op, left, right),
+        };
+
+        binary(left, symbol, precedence, right, Type::Float)
+    }
+
+    /// Integer arithmetic is checked before it's done, because signed
+    /// overflow is undefined behaviour in C.
+    fn int_binary_op(&self, op: &BinaryOp, left: Code, right: Code) -> Code {
+        let (symbol, precedence) = match op.op {
+            Op::Add => ("+", Precedence::Additive),
+            Op::Subtract => ("-", Precedence::Additive),
+            Op::Multiply => ("*", Precedence::Multiplicative),
+            Op::IntegerDivide => ("/", Precedence::Multiplicative),
+            Op::Modulo => ("%", Precedence::Multiplicative),
+            Op::Divide => unreachable!("Division is always done using floats"),
+            Op::And | Op::Or => unreachable!("Logical operators are lowered to conditionals"),
+            _ => return comparison(op.op, left, right),
+        };
+
+        let left = self.temporary(left);
+        let right = self.temporary(right);
+        let (l, r) = (&left.code, &right.code);
+
+        let overflows = match op.op {
+            Op::Add => format!(
+                "({r} > 0 && {l} > INT64_MAX - {r}) || ({r} < 0 && {l} < INT64_MIN - {r})",
+                l = l,
+                r = r
+            ),
+            Op::Subtract => format!(
+                "({r} < 0 && {l} > INT64_MAX + {r}) || ({r} > 0 && {l} < INT64_MIN + {r})",
+                l = l,
+                r = r
+            ),
+            Op::Multiply => format!(
+                "{l} > 0 ? ({r} > 0 ? {l} > INT64_MAX / {r} : {r} < INT64_MIN / {l}) \
+                 : ({r} > 0 ? {l} < INT64_MIN / {r} : {l} != 0 && {r} < INT64_MAX / {l})",
+                l = l,
+                r = r
+            ),
+            _ => {
+                // C leaves `x / 0` and `INT64_MIN / -1` undefined
+                self.bail_if(&format!("{} == 0", r), Status::DivideByZero, op.span);
+                format!("{} == INT64_MIN && {} == -1", l, r)
+            }
+        };
+        self.bail_if(&overflows, Status::IntegerOverflow, op.span);
+
+        binary(left, symbol, precedence, right, Type::Integer)
+    }
+
+    fn check_domain(&self, domain: Domain, value: Code, span: Span) -> Code {
+        let comparison = match domain {
+            Domain::Any => return value,
+            Domain::NonNegative => "<",
+            Domain::Positive => "<=",
+        };
+
+        let value = self.temporary(value);
+        self.bail_if(
+            &format!("{} {} 0.0", value.code, comparison),
+            Status::DomainError,
+            span,
+        );
+        value
+    }
+}
+
+impl Default for Emitter {
+    fn default() -> Emitter {
+        Emitter::new()
+    }
+}
+
+/// Each operation becomes a C expression. Anything which needs to be checked
+/// first (or evaluated conditionally) is emitted as statements beforehand.
+impl Backend for Emitter {
+    type Value = Code;
+    type Error = Unsupported;
+
+    fn scope(&self) -> &RefCell<Vec<(String, Code)>> {
+        &self.scope
+    }
+
+    fn atom(&self, atom: &Atom, span: Span) -> Result<Code, Unsupported> {
+        match *atom {
+            Atom::Number(n) => Ok(float_literal(n)),
+            Atom::Integer(n) => Ok(Code::primary(format!("INT64_C({})", n), Type::Integer)),
+            Atom::Boolean(b) => Ok(Code::primary(if b { "1" } else { "0" }, Type::Bool)),
+            Atom::Imaginary(_) => Err(Unsupported::new("complex numbers", span)),
+            Atom::Ident(_) => {
+                unreachable!("The type checker inlines everything except bound variables")
+            }
+        }
+    }
+
+    fn binary_op(&self, op: &BinaryOp, left: Code, right: Code) -> Result<Code, Unsupported> {
+        match left.ty {
+            Type::Float => Ok(self.float_binary_op(op, left, right)),
+            Type::Integer => Ok(self.int_binary_op(op, left, right)),
+            Type::Bool => Ok(comparison(op.op, left, right)),
+            Type::Complex => Err(Unsupported::new("complex numbers", op.span)),
+            Type::Array(_) => Err(Unsupported::new("arrays", op.span)),
+        }
+    }
+
+    fn unary_op(&self, op: &UnaryOp, value: Code) -> Result<Code, Unsupported> {
+        match op.op {
+            UnaryOperator::Not => Ok(Code {
+                code: format!("!{}", value.wrap_below(Precedence::Unary)),
+                ty: Type::Bool,
+                precedence: Precedence::Unary,
+            }),
+        }
+    }
+
+    fn cast(&self, cast: &Cast, value: Code) -> Result<Code, Unsupported> {
+        match (cast.from, cast.to) {
+            (from, to) if from == to => Ok(value),
+            (Type::Integer, Type::Float) if integer_literal(&value).is_some() => {
+                let n = integer_literal(&value).unwrap();
+                Ok(float_literal(n as f64))
+            }
+            (_, Type::Float) => Ok(to_double(value)),
+            (_, Type::Integer) => Ok(Code {
+                code: format!("(int64_t){}", value.wrap_below(Precedence::Unary)),
+                ty: Type::Integer,
+                precedence: Precedence::Unary,
+            }),
+            (_, Type::Complex) => Err(Unsupported::new("complex numbers", cast.span)),
+            (_, Type::Array(_)) => Err(Unsupported::new("arrays", cast.span)),
+            (from, to) => unreachable!("Can't convert a {} to a {}", from, to),
+        }
+    }
+
+    /// Built-in functions map to their `<math.h>` equivalent.
+    fn call(&self, call: &FunctionCall, mut args: Vec<Code>) -> Result<Code, Unsupported> {
+        let name = if let Some(host) = self.env.function(&call.name) {
+            if host.closure_address().is_some() {
+                return Err(Unsupported::new("closures", call.span));
+            }
+
+            let params = vec!["double"; host.arity()].join(", ");
+            let prototype = format!("double {}({});", call.name, params);
+            if !self.prototypes.borrow().contains(&prototype) {
+                self.prototypes.borrow_mut().push(prototype);
+            }
+            call.name.as_str()
+        } else {
+            let builtin = builtins::lookup(&call.name)
+                .ok_or_else(|| Unsupported::new("arrays", call.span))?;
+
+            if self.runtime_checks {
+                args[0] = self.check_domain(builtin.domain, args[0].clone(), call.span);
+            }
+
+            match builtin.name {
+                "ln" => "log",
+                "abs" => "fabs",
+                "min" => "fmin",
+                "max" => "fmax",
+                _ if builtin.intrinsic.is_none() => {
+                    return Err(Unsupported::new("complex numbers", call.span))
+                }
+                other => other,
+            }
+        };
+
+        let args: Vec<String> = args.into_iter().map(|arg| arg.code).collect();
+        Ok(Code::primary(
+            format!("{}({})", name, args.join(", ")),
+            Type::Float,
+        ))
+    }
+
+    /// Conditionals become `?:`, `&&` or `||` where possible. If either
+    /// branch needs statements, an `if` statement assigns to a temporary
+    /// instead.
+    fn conditional<T, F>(
+        &self,
+        condition: Code,
+        if_true: T,
+        if_false: F,
+    ) -> Result<Code, Unsupported>
+    where
+        T: FnOnce() -> Result<Code, Unsupported>,
+        F: FnOnce() -> Result<Code, Unsupported>,
+    {
+        let (true_lines, true_value) = self.block(if_true);
+        let true_value = true_value?;
+        let (false_lines, false_value) = self.block(if_false);
+        let false_value = false_value?;
+
+        if true_lines.is_empty() && false_lines.is_empty() {
+            let is = |value: &Code, literal: &str| value.ty == Type::Bool && value.code == literal;
+
+            if is(&false_value, "0") {
+                return Ok(binary(condition, "&&", Precedence::And, true_value, Type::Bool));
+            } else if is(&true_value, "1") {
+                return Ok(binary(condition, "||", Precedence::Or, false_value, Type::Bool));
+            }
+
+            return Ok(Code {
+                code: format!(
+                    "{} ? {} : {}",
+                    condition.wrap_below(Precedence::Or),
+                    true_value.code,
+                    false_value.wrap_below(Precedence::Conditional),
+                ),
+                ty: true_value.ty,
+                precedence: Precedence::Conditional,
+            });
+        }
+
+        let ty = true_value.ty;
+        let name = self.fresh_name("t");
+        self.statement(format!("{} {};", c_type(ty), name));
+        self.statement(format!("if ({}) {{", condition.code));
+        self.nested(true_lines);
+        self.statement(format!("    {} = {};", name, true_value.code));
+        self.statement("} else {");
+        self.nested(false_lines);
+        self.statement(format!("    {} = {};", name, false_value.code));
+        self.statement("}");
+
+        Ok(Code::primary(name, ty))
+    }
+
+    /// Sums and products become a `for` loop which updates a running total.
+    fn series<F>(
+        &self,
+        series: &Series,
+        start: Code,
+        end: Code,
+        mut term: F,
+    ) -> Result<Code, Unsupported>
+    where
+        F: FnMut(Code) -> Result<Code, Unsupported>,
+    {
+        let start = self.temporary(start);
+        let end = self.temporary(end);
+
+        let total = self.fresh_name("t");
+        let (identity, update) = match series.kind {
+            SeriesKind::Sum => ("0.0", "+="),
+            SeriesKind::Product => ("1.0", "*="),
+        };
+        self.statement(format!("double {} = {};", total, identity));
+
+        // the variable's name isn't always a valid C identifier (e.g. `k'`
+        // after a lambda's parameter has been renamed)
+        let counter = self.fresh_name("i");
+        let (lines, value) = self.block(|| term(Code::primary(counter.clone(), Type::Integer)));
+        let value = value?;
+
+        self.statement(format!(
+            "for (int64_t {c} = {}; {c} <= {}; {c}++) {{",
+            start.code,
+            end.code,
+            c = counter
+        ));
+        self.nested(lines);
+        self.statement(format!("    {} {} {};", total, update, value.code));
+        // stop before incrementing, otherwise `end == INT64_MAX` would
+        // overflow
+        self.statement(format!("    if ({} == {}) break;", counter, end.code));
+        self.statement("}");
+
+        Ok(Code::primary(total, Type::Float))
+    }
+
+    /// The value is stored in a temporary which the body can use as often
+    /// as it likes.
+    fn bind<F>(&self, _: &Let, value: Code, body: F) -> Result<Code, Unsupported>
+    where
+        F: FnOnce(Code) -> Result<Code, Unsupported>,
+    {
+        body(self.temporary(value))
+    }
+
+    fn array(&self, array: &Array, _: Vec<Code>) -> Result<Code, Unsupported> {
+        Err(Unsupported::new("arrays", array.span))
+    }
+
+    fn index(&self, index: &Index, _: Code, _: Code) -> Result<Code, Unsupported> {
+        Err(Unsupported::new("arrays", index.span))
+    }
+
+    fn integral(&self, integral: &Integral) -> Result<Code, Unsupported> {
+        Err(Unsupported::new("integrals", integral.span))
+    }
+
+    fn solve(&self, solve: &Solve) -> Result<Code, Unsupported> {
+        Err(Unsupported::new("solve()", solve.span))
+    }
+}
+
+/// A C expression without side effects.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Code {
+    code: String,
+    ty: Type,
+    precedence: Precedence,
+}
+
+impl Code {
+    fn primary<S: Into<String>>(code: S, ty: Type) -> Code {
+        Code {
+            code: code.into(),
+            ty,
+            precedence: Precedence::Primary,
+        }
+    }
+
+    /// The C source code.
+    pub fn as_str(&self) -> &str {
+        &self.code
+    }
+
+    /// The code, in parentheses if it binds less tightly than `precedence`.
+    fn wrap_below(&self, precedence: Precedence) -> String {
+        if self.precedence < precedence {
+            format!("({})", self.code)
+        } else {
+            self.code.clone()
+        }
+    }
+}
+
+/// C's operator precedence, from loosest to tightest.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Conditional,
+    Or,
+    And,
+    Equality,
+    Relational,
+    Additive,
+    Multiplicative,
+    Unary,
+    Primary,
+}
+
+/// A left-associative binary operator.
+fn binary(left: Code, symbol: &str, precedence: Precedence, right: Code, ty: Type) -> Code {
+    // compilers warn about `a < b == c` and `a && b || c` without
+    // parentheses, even though they're correct
+    let loosest = match precedence {
+        Precedence::Equality | Precedence::Relational => Precedence::Additive,
+        Precedence::Or => Precedence::Equality,
+        other => other,
+    };
+    let wrap_left = left.precedence < loosest || left.code.starts_with('!') && loosest > precedence;
+    let left = if wrap_left {
+        format!("({})", left.code)
+    } else {
+        left.code
+    };
+    let right = if right.precedence <= precedence || right.precedence < loosest {
+        format!("({})", right.code)
+    } else {
+        right.code
+    };
+
+    Code {
+        code: format!("{} {} {}", left, symbol, right),
+        ty,
+        precedence,
+    }
+}
+
+fn comparison(op: Op, left: Code, right: Code) -> Code {
+    let (symbol, precedence) = match op {
+        Op::LessThan => ("<", Precedence::Relational),
+        Op::LessThanOrEqual => ("<=", Precedence::Relational),
+        Op::GreaterThan => (">", Precedence::Relational),
+        Op::GreaterThanOrEqual => (">=", Precedence::Relational),
+        Op::Equal => ("==", Precedence::Equality),
+        Op::NotEqual => ("!=", Precedence::Equality),
+        other => unreachable!("{} isn't a comparison", other),
+    };
+
+    binary(left, symbol, precedence, right, Type::Bool)
+}
+
+fn to_double(value: Code) -> Code {
+    match value.ty {
+        Type::Float => value,
+        _ => Code {
+            code: format!("(double){}", value.wrap_below(Precedence::Unary)),
+            ty: Type::Float,
+            precedence: Precedence::Unary,
+        },
+    }
+}
+
+/// Write a float so it's parsed as exactly the same `double`.
+fn float_literal(n: f64) -> Code {
+    if n.is_nan() {
+        return Code::primary("NAN", Type::Float);
+    } else if n == f64::INFINITY {
+        return Code::primary("INFINITY", Type::Float);
+    } else if n == f64::NEG_INFINITY {
+        return Code {
+            code: "-INFINITY".to_string(),
+            ty: Type::Float,
+            precedence: Precedence::Unary,
+        };
+    }
+
+    let mut code = format!("{:?}", n);
+    if !code.contains(&['.', 'e'][..]) {
+        code.push_str(".0");
+    }
+
+    Code::primary(code, Type::Float)
+}
+
+fn integer_literal(value: &Code) -> Option<i64> {
+    if value.code.starts_with("INT64_C(") && value.code.ends_with(')') {
+        value.code["INT64_C(".len()..value.code.len() - 1].parse().ok()
+    } else {
+        None
+    }
+}
+
+fn c_type(ty: Type) -> &'static str {
+    match ty {
+        Type::Float => "double",
+        Type::Integer => "int64_t",
+        Type::Bool => "int",
+        Type::Complex | Type::Array(_) => unreachable!("{} is never emitted", ty),
+    }
+}
+
+/// Part of an expression which can't be turned into C.
+#[derive(Debug, Clone, PartialEq, Fail)]
+#[fail(display = "Can't generate C for {} (at {})", feature, span)]
+pub struct Unsupported {
+    /// What isn't supported.
+    pub feature: String,
+    /// The offending expression.
+    pub span: Span,
+}
+
+impl Unsupported {
+    fn new(feature: &str, span: Span) -> Unsupported {
+        Unsupported {
+            feature: feature.to_string(),
+            span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interpreter::Interpreter;
+    use sema;
+    use std::env;
+    use std::fs;
+    use std::process::Command;
+    use syntax;
+
+    const HARNESS: &str = r#"
+#include <stdio.h>
+
+int main(void) {
+    double result[2] = {0.0, 0.0};
+    struct calc_error_location location = {0, 0};
+    uint32_t status = calc_main(result, &location);
+    printf("%u %.17g %llu %llu\n", (unsigned) status, result[0],
+           (unsigned long long) location.start, (unsigned long long) location.end);
+    return 0;
+}
+"#;
+
+    fn emit(src: &str, runtime_checks: bool) -> String {
+        emit_expr(&syntax::parse(src).unwrap(), runtime_checks)
+    }
+
+    fn emit_expr(ast: &Expr, runtime_checks: bool) -> String {
+        let mut env = Environment::new();
+        env.register_constant("inf", f64::INFINITY)
+            .register_constant("ninf", f64::NEG_INFINITY);
+        let (ast, _) = sema::type_check(ast, &env).unwrap();
+
+        Emitter::new()
+            .with_runtime_checks(runtime_checks)
+            .emit(&ast)
+            .unwrap()
+    }
+
+    fn run(name: &str, src: &str) -> (u32, f64, u64, u64) {
+        run_expr(name, &syntax::parse(src).unwrap())
+    }
+
+    /// Compile the generated C with the system's C compiler and run it,
+    /// returning the status code, result and error location.
+    fn run_expr(name: &str, ast: &Expr) -> (u32, f64, u64, u64) {
+        let code = emit_expr(ast, true);
+        let dir = env::temp_dir().join(format!("calc-c-{}-{}", name, ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("main.c");
+        let exe = dir.join("main");
+        fs::write(&source, code.clone() + HARNESS).unwrap();
+
+        let compiled = Command::new("cc")
+            .arg("-std=c99")
+            .arg("-Wall")
+            .arg("-Werror")
+            .arg("-o")
+            .arg(&exe)
+            .arg(&source)
+            .arg("-lm")
+            .output()
+            .unwrap();
+        assert!(
+            compiled.status.success(),
+            "Couldn't compile {:?}\n{}\n{}",
+            ast,
+            code,
+            String::from_utf8_lossy(&compiled.stderr)
+        );
+
+        let output = Command::new(&exe).output().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let words: Vec<&str> = stdout.split_whitespace().collect();
+        (
+            words[0].parse().unwrap(),
+            words[1].parse().unwrap(),
+            words[2].parse().unwrap(),
+            words[3].parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn precedence_only_adds_the_parentheses_c_needs() {
+        let inputs = vec![
+            ("1.5 - (2.5 - 3.5)", "1.5 - (2.5 - 3.5)"),
+            ("(1.5 - 2.5) - 3.5", "1.5 - 2.5 - 3.5"),
+            ("1.5 * (2.5 + 3.5) / 4.5", "1.5 * (2.5 + 3.5) / 4.5"),
+            ("1.5 / (2.5 * 3.5)", "1.5 / (2.5 * 3.5)"),
+            ("pow(2, 1.5 + 1)", "pow(2.0, 1.5 + 1.0)"),
+            (
+                "if (1.5 < 2.5) == true and not (1 > 2) then 1 else 2",
+                "(double)((1.5 < 2.5) == 1 && !(INT64_C(1) > INT64_C(2)) \
+                 ? INT64_C(1) : INT64_C(2))",
+            ),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = emit(src, false);
+            let expected = format!("result[0] = {};", should_be);
+            assert!(got.contains(&expected), "{}\n{}", expected, got);
+        }
+    }
+
+    #[test]
+    fn results_match_the_interpreter() {
+        let inputs = vec![
+            "1 + 2 * 3 - 4 / 8",
+            "1.5 - (2.5 - 3.5) * 2",
+            "7 // 2 + 7 % 2 + (0 - 7) // 2 + (0 - 7) % 2",
+            "if 1 < 2 and not false then 10 else 20",
+            "if 1.5 >= 2 or 3 == 4 then 10 else 20",
+            "1 > 2 and 1 // 0 == 0 or 1 < 2 or 1 // 0 == 0",
+            "if 1 < 2 then 3 // 2 else 4 // 0",
+            "abs(0 - 3) + floor(2.5) + min(1, 2) + max(1, 2) + pow(2, 10)",
+            "sum(k, 1, 100, k)",
+            "prod(i, 1, 5, i)",
+            "sum(i, 1, 3, sum(j, 1, i, j // 2))",
+            "sum(k, 3, 1, k)",
+            "sum(i, 9223372036854775806, 9223372036854775807, 1)",
+            "0.1 + 0.2",
+            "1e300 * 10 / 1e301",
+        ];
+
+        for (i, src) in inputs.into_iter().enumerate() {
+            let ast = syntax::parse(src).unwrap();
+            let should_be = Interpreter::new()
+                .with_precision(10)
+                .evaluate(&ast)
+                .unwrap()
+                .to_f64();
+
+            let (status, got, _, _) = run(&format!("results-{}", i), src);
+
+            assert_eq!(status, Status::Ok as u32, "{}", src);
+            assert!((got - should_be).abs() < 1e-9, "{}: {} != {}", src, got, should_be);
+        }
+
+        // the interpreter rejects infinity, so these are checked directly
+        let inputs = vec![
+            ("inf", f64::INFINITY),
+            ("ninf", f64::NEG_INFINITY),
+            ("if ninf < 0 then 1 else 0", 1.0),
+        ];
+
+        for (i, (src, should_be)) in inputs.into_iter().enumerate() {
+            let (status, got, _, _) = run(&format!("infinite-{}", i), src);
+
+            assert_eq!((status, got), (Status::Ok as u32, should_be), "{}", src);
+        }
+    }
+
+    #[test]
+    fn runtime_errors_are_reported_with_their_location() {
+        let inputs = vec![
+            ("1 + 1 / 0", Status::DivideByZero, 4, 9),
+            ("2 + 7 % (3 - 3)", Status::DivideByZero, 4, 15),
+            ("9223372036854775807 + 1", Status::IntegerOverflow, 0, 23),
+            ("3037000500 * 3037000500", Status::IntegerOverflow, 0, 23),
+            ("1 + sqrt(1 - 2)", Status::DomainError, 4, 15),
+            ("sum(k, 0, 2, ln(k))", Status::DomainError, 13, 18),
+        ];
+
+        for (i, (src, status, start, end)) in inputs.into_iter().enumerate() {
+            let got = run(&format!("errors-{}", i), src);
+
+            assert_eq!((got.0, got.2, got.3), (status as u32, start, end), "{}", src);
+        }
+    }
+
+    #[test]
+    fn series_variables_dont_need_to_be_valid_c_identifiers() {
+        // what `k` is renamed to when it would capture a lambda's argument,
+        // e.g. `sum(k, 1, 2, sum(map(x -> sum(k, 1, 3, k * x), [k])))`
+        let term = BinaryOp::mult(Atom::from("k'").into(), Atom::from(2).into());
+        let (start, end) = (Atom::from(1).into(), Atom::from(3).into());
+        let series = Series::new(SeriesKind::Sum, "k'", start, end, term.into());
+
+        let got = run_expr("renamed", &series.into());
+
+        assert_eq!(got, (Status::Ok as u32, 12.0, 0, 0));
+    }
+
+    #[test]
+    fn unsupported_expressions_are_an_error() {
+        let inputs = vec!["1 + 2i", "[1, 2][0]", "solve(x * x == 4, x, 1)"];
+
+        for src in inputs {
+            let ast = syntax::parse(src).unwrap();
+            let (ast, _) = sema::type_check(&ast, &Environment::new()).unwrap();
+
+            let err = Emitter::new().emit(&ast).unwrap_err();
+            assert!(err.downcast::<Unsupported>().is_ok(), "{}", src);
+        }
+    }
+}
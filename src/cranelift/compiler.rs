@@ -0,0 +1,1503 @@
+use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::immediates::Ieee64;
+use cranelift_codegen::ir::types::{B1, F64, I32, I64, I8};
+use cranelift_codegen::ir::{AbiParam, FuncRef, InstBuilder, MemFlags, Signature, StackSlot,
+                            StackSlotData, StackSlotKind, Type, Value};
+use cranelift_codegen::isa::CallConv;
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_module::{FuncId, Linkage, Module};
+use cranelift_simplejit::{SimpleJITBackend, SimpleJITBuilder};
+use failure::Error;
+use std::cell::{Cell, RefCell};
+use std::fmt::Debug;
+use std::mem;
+
+use super::Program;
+use backend::quadrature::{GAUSS_WEIGHTS, INTEGRAL_TOLERANCE, KRONROD_NODES, KRONROD_WEIGHTS,
+                          MAX_PANELS};
+use backend::{self, differentiate, Backend, CalcMain, Jit, Status, CALC_ENTRYPOINT};
+use builtins::{self, Domain};
+use environment::{self, Environment, HostFunction};
+use solve;
+use syntax::{Array, Atom, BinaryOp, Cast, Expr, FunctionCall, Index, Integral, Let, Op,
+             Series, SeriesKind, Solve, Span, UnaryOp, UnaryOperator};
+
+/// Compiles a type checked `Expr` to machine code using Cranelift.
+#[derive(Debug, Clone, Default)]
+pub struct Compiler {
+    runtime_checks: bool,
+    env: Environment,
+}
+
+impl Compiler {
+    /// Create a new `Compiler` with runtime checks disabled.
+    pub fn new() -> Compiler {
+        Compiler::default()
+    }
+
+    /// Guard floating point division and calls to functions which are only
+    /// defined for some inputs, the same as
+    /// `trans::Compiler::with_runtime_checks()`.
+    ///
+    /// Integer overflow is always checked, regardless of this setting.
+    pub fn with_runtime_checks(mut self, enabled: bool) -> Compiler {
+        self.runtime_checks = enabled;
+        self
+    }
+
+    /// Let the compiled code call functions registered with an
+    /// `Environment`.
+    pub fn with_environment(mut self, env: &Environment) -> Compiler {
+        self.env = env.clone();
+        self
+    }
+
+    /// Compile an expression which has already been through
+    /// [`sema::type_check()`] to a `calc_main` function, plus any
+    /// integrands and residuals it uses.
+    ///
+    /// [`sema::type_check()`]: ../sema/fn.type_check.html
+    pub fn compile(self, ast: &Expr) -> Result<Program, Error> {
+        let module = RefCell::new(Module::new(SimpleJITBuilder::new()));
+
+        // `fn(*mut Complex64, *mut ErrorLocation) -> u32`, like the LLVM backend
+        let pointer = module.borrow().target_config().pointer_type();
+        let mut sig = module.borrow().make_signature();
+        sig.params.push(AbiParam::new(pointer));
+        sig.params.push(AbiParam::new(pointer));
+        sig.returns.push(AbiParam::new(I32));
+
+        let id = self.define_function(
+            &module,
+            CALC_ENTRYPOINT,
+            Linkage::Export,
+            sig,
+            |translator, params| translator.translate(ast, params[0]),
+        )?;
+
+        let mut module = module.into_inner();
+        module.finalize_definitions();
+
+        let code = module.get_finalized_function(id);
+        let calc_main = unsafe { mem::transmute::<*const u8, CalcMain>(code) };
+
+        Ok(Program {
+            calc_main,
+            _module: module,
+            env: self.env,
+        })
+    }
+
+    /// Declare a function, then compile it using a fresh `Translator` which
+    /// `body` uses to emit everything after the entry block.
+    ///
+    /// Every function we generate takes a pointer to write the location of
+    /// an error to as its second parameter.
+    fn define_function<F>(
+        &self,
+        module: &RefCell<Module<SimpleJITBackend>>,
+        name: &str,
+        linkage: Linkage,
+        signature: Signature,
+        body: F,
+    ) -> Result<FuncId, Error>
+    where
+        F: FnOnce(&Translator, &[Value]) -> Result<(), Error>,
+    {
+        // declared up front, so nested functions can't take the same name
+        let id = module
+            .borrow_mut()
+            .declare_function(name, linkage, &signature)
+            .map_err(|e| format_err!("Unable to declare {}: {}", name, e))?;
+
+        let mut ctx = module.borrow().make_context();
+        ctx.func.signature = signature;
+        let mut builder_ctx = FunctionBuilderContext::new();
+
+        {
+            let pointer = module.borrow().target_config().pointer_type();
+            let call_conv = ctx.func.signature.call_conv;
+            let builder = FunctionBuilder::new(&mut ctx.func, &mut builder_ctx);
+            let translator = Translator {
+                builder: RefCell::new(builder),
+                compiler: self,
+                module,
+                pointer,
+                call_conv,
+                location: Cell::new(None),
+                scope: RefCell::new(Vec::new()),
+                variables: Cell::new(0),
+            };
+
+            let params = translator.start_function();
+            translator.location.set(Some(params[1]));
+            body(&translator, &params)?;
+            translator.builder.into_inner().finalize();
+        }
+
+        let mut module = module.borrow_mut();
+        module
+            .define_function(id, &mut ctx)
+            .map_err(|e| format_err!("Unable to compile {}: {}", name, e))?;
+        module.clear_context(&mut ctx);
+
+        Ok(id)
+    }
+}
+
+impl Jit for Compiler {
+    type Program = Program;
+
+    fn build(self, ast: &Expr) -> Result<Program, Error> {
+        self.compile(ast)
+    }
+}
+
+/// How a `calc` value is made up from Cranelift values.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Operand<V = Value> {
+    /// An integer, float or boolean.
+    Scalar(V),
+    /// The real and imaginary parts of a complex number, both `f64`s.
+    Complex(V, V),
+    /// The address of an array's elements, which are `f64`s stored in a
+    /// stack slot, and how many there are.
+    Array(V, usize),
+}
+
+impl<V: Copy + Debug> Operand<V> {
+    fn scalar(self) -> V {
+        match self {
+            Operand::Scalar(value) => value,
+            other => unreachable!("The type checker ensures {:?} is a number", other),
+        }
+    }
+
+    fn array(self) -> (V, usize) {
+        match self {
+            Operand::Array(address, len) => (address, len),
+            other => unreachable!("The type checker ensures {:?} is an array", other),
+        }
+    }
+
+    fn parts(self) -> Vec<V> {
+        match self {
+            Operand::Scalar(value) | Operand::Array(value, _) => vec![value],
+            Operand::Complex(re, im) => vec![re, im],
+        }
+    }
+
+    /// Transform each part, keeping the same shape.
+    fn map<U, F: FnMut(V) -> U>(self, mut f: F) -> Operand<U> {
+        match self {
+            Operand::Scalar(value) => Operand::Scalar(f(value)),
+            Operand::Complex(re, im) => {
+                let re = f(re);
+                Operand::Complex(re, f(im))
+            }
+            Operand::Array(address, len) => Operand::Array(f(address), len),
+        }
+    }
+}
+
+/// Emits Cranelift IR for a single function.
+///
+/// Integers are `i64`, floats are `f64` and booleans are `b1`. Complex
+/// numbers and arrays are made up of several values, see `Operand`.
+struct Translator<'a> {
+    builder: RefCell<FunctionBuilder<'a>>,
+    compiler: &'a Compiler,
+    /// Where integrands and residuals get compiled to.
+    module: &'a RefCell<Module<SimpleJITBackend>>,
+    pointer: Type,
+    call_conv: CallConv,
+    /// Where the location of a failed operation gets written.
+    location: Cell<Option<Value>>,
+    /// The variables bound by any sums, products, integrals or equations
+    /// we're currently inside.
+    scope: RefCell<Vec<(String, Operand)>>,
+    /// The number of variables declared so far.
+    variables: Cell<usize>,
+}
+
+impl<'a> Translator<'a> {
+    /// Create the entry block, returning the function's parameters.
+    fn start_function(&self) -> Vec<Value> {
+        let mut builder = self.builder.borrow_mut();
+        let entry = builder.create_ebb();
+        builder.append_ebb_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+        builder.ebb_params(entry).to_vec()
+    }
+
+    fn translate(&self, ast: &Expr, out: Value) -> Result<(), Error> {
+        let result = backend::lower(self, ast)?;
+
+        let mut builder = self.builder.borrow_mut();
+        let (re, im) = match result {
+            Operand::Complex(re, im) => (re, im),
+            Operand::Scalar(value) => {
+                let re = self.to_float(&mut builder, value);
+                (re, builder.ins().f64const(Ieee64::with_float(0.0)))
+            }
+            Operand::Array(..) => unreachable!("The type checker doesn't allow array results"),
+        };
+
+        builder.ins().store(MemFlags::new(), re, out, 0);
+        builder.ins().store(MemFlags::new(), im, out, 8);
+        self.return_status(&mut builder, Status::Ok);
+        Ok(())
+    }
+
+    fn float_binary_op(
+        &self,
+        builder: &mut FunctionBuilder,
+        op: &BinaryOp,
+        left: Value,
+        right: Value,
+    ) -> Value {
+        let condition = match op.op {
+            Op::Add => return builder.ins().fadd(left, right),
+            Op::Subtract => return builder.ins().fsub(left, right),
+            Op::Multiply => return builder.ins().fmul(left, right),
+            Op::Divide => {
+                if self.compiler.runtime_checks {
+                    let zero = builder.ins().f64const(Ieee64::with_float(0.0));
+                    let is_zero = builder.ins().fcmp(FloatCC::Equal, right, zero);
+                    self.bail_if(builder, is_zero, Status::DivideByZero, op.span);
+                }
+
+                return builder.ins().fdiv(left, right);
+            }
+            Op::IntegerDivide | Op::Modulo => unreachable!("{} only accepts integers", op.op),
+            Op::LessThan => FloatCC::LessThan,
+            Op::LessThanOrEqual => FloatCC::LessThanOrEqual,
+            Op::Equal => FloatCC::Equal,
+            // true if either side is NaN, the same as `!=` in Rust and C
+            Op::NotEqual => FloatCC::NotEqual,
+            Op::GreaterThan => FloatCC::GreaterThan,
+            Op::GreaterThanOrEqual => FloatCC::GreaterThanOrEqual,
+            Op::And | Op::Or => unreachable!("Logical operators are lowered to conditionals"),
+        };
+
+        builder.ins().fcmp(condition, left, right)
+    }
+
+    fn int_binary_op(
+        &self,
+        builder: &mut FunctionBuilder,
+        op: &BinaryOp,
+        left: Value,
+        right: Value,
+    ) -> Value {
+        let condition = match op.op {
+            Op::Add | Op::Subtract | Op::Multiply => {
+                return self.checked_arithmetic(builder, op, left, right)
+            }
+            Op::IntegerDivide | Op::Modulo => {
+                return self.checked_division(builder, op, left, right)
+            }
+            Op::Divide => unreachable!("Division is always done using floats"),
+            Op::And | Op::Or => unreachable!("Logical operators are lowered to conditionals"),
+            Op::LessThan => IntCC::SignedLessThan,
+            Op::LessThanOrEqual => IntCC::SignedLessThanOrEqual,
+            Op::Equal => IntCC::Equal,
+            Op::NotEqual => IntCC::NotEqual,
+            Op::GreaterThan => IntCC::SignedGreaterThan,
+            Op::GreaterThanOrEqual => IntCC::SignedGreaterThanOrEqual,
+        };
+
+        builder.ins().icmp(condition, left, right)
+    }
+
+    /// Cranelift doesn't have LLVM's `*.with.overflow` intrinsics, so the
+    /// overflow flag is calculated from the operands and result.
+    fn checked_arithmetic(
+        &self,
+        builder: &mut FunctionBuilder,
+        op: &BinaryOp,
+        left: Value,
+        right: Value,
+    ) -> Value {
+        let (result, overflowed) = match op.op {
+            Op::Add => {
+                // overflow happens when both operands have a different sign
+                // to the result
+                let sum = builder.ins().iadd(left, right);
+                let a = builder.ins().bxor(left, sum);
+                let b = builder.ins().bxor(right, sum);
+                let both = builder.ins().band(a, b);
+                let overflowed = builder.ins().icmp_imm(IntCC::SignedLessThan, both, 0);
+                (sum, overflowed)
+            }
+            Op::Subtract => {
+                // overflow happens when the operands have different signs
+                // and the result's sign is different to the left operand's
+                let difference = builder.ins().isub(left, right);
+                let a = builder.ins().bxor(left, right);
+                let b = builder.ins().bxor(left, difference);
+                let both = builder.ins().band(a, b);
+                let overflowed = builder.ins().icmp_imm(IntCC::SignedLessThan, both, 0);
+                (difference, overflowed)
+            }
+            Op::Multiply => {
+                // the high half of the 128-bit product must just be the sign
+                // extension of the low half
+                let low = builder.ins().imul(left, right);
+                let high = builder.ins().smulhi(left, right);
+                let sign = builder.ins().sshr_imm(low, 63);
+                let overflowed = builder.ins().icmp(IntCC::NotEqual, high, sign);
+                (low, overflowed)
+            }
+            other => unreachable!("{} can't overflow", other),
+        };
+
+        self.bail_if(builder, overflowed, Status::IntegerOverflow, op.span);
+        result
+    }
+
+    /// Cranelift traps on division by zero and `i64::MIN / -1`, so both are
+    /// always checked.
+    fn checked_division(
+        &self,
+        builder: &mut FunctionBuilder,
+        op: &BinaryOp,
+        left: Value,
+        right: Value,
+    ) -> Value {
+        let is_zero = builder.ins().icmp_imm(IntCC::Equal, right, 0);
+        self.bail_if(builder, is_zero, Status::DivideByZero, op.span);
+
+        let is_min = builder.ins().icmp_imm(IntCC::Equal, left, ::std::i64::MIN);
+        let is_minus_one = builder.ins().icmp_imm(IntCC::Equal, right, -1);
+        let overflows = builder.ins().band(is_min, is_minus_one);
+        self.bail_if(builder, overflows, Status::IntegerOverflow, op.span);
+
+        match op.op {
+            Op::IntegerDivide => builder.ins().sdiv(left, right),
+            _ => builder.ins().srem(left, right),
+        }
+    }
+
+    fn bool_binary_op(
+        &self,
+        builder: &mut FunctionBuilder,
+        op: &BinaryOp,
+        left: Value,
+        right: Value,
+    ) -> Value {
+        match op.op {
+            Op::Equal | Op::NotEqual => {
+                let left = builder.ins().bint(I64, left);
+                let right = builder.ins().bint(I64, right);
+                let condition = if op.op == Op::Equal {
+                    IntCC::Equal
+                } else {
+                    IntCC::NotEqual
+                };
+                builder.ins().icmp(condition, left, right)
+            }
+            other => unreachable!("{} doesn't accept bools", other),
+        }
+    }
+
+    /// Complex arithmetic, done component-wise.
+    fn complex_binary_op(
+        &self,
+        builder: &mut FunctionBuilder,
+        op: &BinaryOp,
+        (a, b): (Value, Value),
+        (c, d): (Value, Value),
+    ) -> Operand {
+        let (re, im) = match op.op {
+            Op::Add => (builder.ins().fadd(a, c), builder.ins().fadd(b, d)),
+            Op::Subtract => (builder.ins().fsub(a, c), builder.ins().fsub(b, d)),
+            Op::Multiply => {
+                // (a + bi)(c + di) = (ac - bd) + (ad + bc)i
+                let ac = builder.ins().fmul(a, c);
+                let bd = builder.ins().fmul(b, d);
+                let ad = builder.ins().fmul(a, d);
+                let bc = builder.ins().fmul(b, c);
+                (builder.ins().fsub(ac, bd), builder.ins().fadd(ad, bc))
+            }
+            Op::Divide => {
+                // (a + bi)/(c + di) = ((ac + bd) + (bc - ad)i) / (c² + d²)
+                let cc = builder.ins().fmul(c, c);
+                let dd = builder.ins().fmul(d, d);
+                let denominator = builder.ins().fadd(cc, dd);
+
+                if self.compiler.runtime_checks {
+                    let zero = builder.ins().f64const(Ieee64::with_float(0.0));
+                    let re_is_zero = builder.ins().fcmp(FloatCC::Equal, c, zero);
+                    let im_is_zero = builder.ins().fcmp(FloatCC::Equal, d, zero);
+                    let is_zero = builder.ins().band(re_is_zero, im_is_zero);
+                    self.bail_if(builder, is_zero, Status::DivideByZero, op.span);
+                }
+
+                let ac = builder.ins().fmul(a, c);
+                let bd = builder.ins().fmul(b, d);
+                let bc = builder.ins().fmul(b, c);
+                let ad = builder.ins().fmul(a, d);
+                let re = builder.ins().fadd(ac, bd);
+                let im = builder.ins().fsub(bc, ad);
+                (
+                    builder.ins().fdiv(re, denominator),
+                    builder.ins().fdiv(im, denominator),
+                )
+            }
+            Op::Equal => {
+                let re_equal = builder.ins().fcmp(FloatCC::Equal, a, c);
+                let im_equal = builder.ins().fcmp(FloatCC::Equal, b, d);
+                return Operand::Scalar(builder.ins().band(re_equal, im_equal));
+            }
+            Op::NotEqual => {
+                let re_differs = builder.ins().fcmp(FloatCC::NotEqual, a, c);
+                let im_differs = builder.ins().fcmp(FloatCC::NotEqual, b, d);
+                return Operand::Scalar(builder.ins().bor(re_differs, im_differs));
+            }
+            other => unreachable!("The type checker doesn't allow {} on complex numbers", other),
+        };
+
+        Operand::Complex(re, im)
+    }
+
+    /// Element-wise arithmetic, done in a loop. A number used alongside an
+    /// array is combined with every element.
+    fn array_binary_op(
+        &self,
+        builder: &mut FunctionBuilder,
+        op: &BinaryOp,
+        left: Operand,
+        right: Operand,
+    ) -> Operand {
+        let len = match (left, right) {
+            (Operand::Array(_, len), _) | (_, Operand::Array(_, len)) => len,
+            _ => unreachable!("At least one operand is an array"),
+        };
+        let result = self.allocate_array(builder, len);
+
+        let count = builder.ins().iconst(I64, len as i64);
+        self.build_loop(builder, count, |builder, i| {
+            let l = self.element_or_scalar(builder, left, i);
+            let r = self.element_or_scalar(builder, right, i);
+            let value = self.float_binary_op(builder, op, l, r);
+            self.store_element(builder, result, i, value);
+        });
+
+        Operand::Array(result, len)
+    }
+
+    fn element_or_scalar(&self, builder: &mut FunctionBuilder, value: Operand, i: Value) -> Value {
+        match value {
+            Operand::Array(array, _) => self.load_element(builder, array, i),
+            other => other.scalar(),
+        }
+    }
+
+    /// Arrays are stored as a buffer of doubles on the stack.
+    fn allocate_array(&self, builder: &mut FunctionBuilder, len: usize) -> Value {
+        let size = 8 * len as u32;
+        let slot = builder.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+        builder.ins().stack_addr(self.pointer, slot, 0)
+    }
+
+    fn element_address(&self, builder: &mut FunctionBuilder, array: Value, i: Value) -> Value {
+        let offset = builder.ins().imul_imm(i, 8);
+        builder.ins().iadd(array, offset)
+    }
+
+    fn load_element(&self, builder: &mut FunctionBuilder, array: Value, i: Value) -> Value {
+        let address = self.element_address(builder, array, i);
+        builder.ins().load(F64, MemFlags::new(), address, 0)
+    }
+
+    fn store_element(&self, builder: &mut FunctionBuilder, array: Value, i: Value, value: Value) {
+        let address = self.element_address(builder, array, i);
+        builder.ins().store(MemFlags::new(), value, address, 0);
+    }
+
+    /// Emit a loop which runs `body` once for every index in `0..len`.
+    ///
+    /// Arrays are never empty (and integrals have at least one panel), so
+    /// the exit condition is only checked after the first iteration.
+    fn build_loop<F>(&self, builder: &mut FunctionBuilder, len: Value, mut body: F)
+    where
+        F: FnMut(&mut FunctionBuilder, Value),
+    {
+        let index = self.declare_variable(builder, I64);
+        let zero = builder.ins().iconst(I64, 0);
+        builder.def_var(index, zero);
+
+        let header = builder.create_ebb();
+        let exit = builder.create_ebb();
+        builder.ins().jump(header, &[]);
+
+        // the header can't be sealed until we've added the back edge
+        builder.switch_to_block(header);
+        let i = builder.use_var(index);
+        body(builder, i);
+
+        let next = builder.ins().iadd_imm(i, 1);
+        builder.def_var(index, next);
+        let done = builder.ins().icmp(IntCC::Equal, next, len);
+        builder.ins().brnz(done, exit, &[]);
+        builder.ins().jump(header, &[]);
+        builder.seal_block(header);
+
+        builder.switch_to_block(exit);
+        builder.seal_block(exit);
+    }
+
+    /// Built-ins which accept a complex number are written in terms of real
+    /// instructions and the Rust implementations of real functions.
+    fn complex_function_call(
+        &self,
+        builder: &mut FunctionBuilder,
+        name: &str,
+        re: Value,
+        im: Value,
+    ) -> Operand {
+        match name {
+            "re" => Operand::Scalar(re),
+            "im" => Operand::Scalar(im),
+            "conj" => Operand::Complex(re, builder.ins().fneg(im)),
+            "abs" => Operand::Scalar(self.call_builtin(builder, "hypot", &[re, im])),
+            "arg" => Operand::Scalar(self.call_builtin(builder, "atan2", &[im, re])),
+            "exp" => {
+                // e^(a + bi) = e^a (cos b + i sin b)
+                let magnitude = self.call_builtin(builder, "exp", &[re]);
+                let cos = self.call_builtin(builder, "cos", &[im]);
+                let sin = self.call_builtin(builder, "sin", &[im]);
+                Operand::Complex(
+                    builder.ins().fmul(magnitude, cos),
+                    builder.ins().fmul(magnitude, sin),
+                )
+            }
+            "sqrt" => {
+                // the principal square root, which has a non-negative real
+                // part and an imaginary part with the same sign as the input
+                let two = builder.ins().f64const(Ieee64::with_float(2.0));
+                let modulus = self.call_builtin(builder, "hypot", &[re, im]);
+
+                let sum = builder.ins().fadd(modulus, re);
+                let half_sum = builder.ins().fdiv(sum, two);
+                let new_re = builder.ins().sqrt(half_sum);
+
+                let difference = builder.ins().fsub(modulus, re);
+                let half_difference = builder.ins().fdiv(difference, two);
+                let magnitude = builder.ins().sqrt(half_difference);
+                let new_im = builder.ins().fcopysign(magnitude, im);
+
+                Operand::Complex(new_re, new_im)
+            }
+            other => unreachable!("The type checker ensures {}() accepts complex numbers", other),
+        }
+    }
+
+    /// Reductions loop over their arguments, keeping a running total in a
+    /// variable.
+    fn reduction(
+        &self,
+        builder: &mut FunctionBuilder,
+        call: &FunctionCall,
+        args: &[Operand],
+    ) -> Value {
+        let (array, len) = args[0].array();
+
+        let initial = match call.name.as_str() {
+            "min" | "max" => {
+                let first = builder.ins().iconst(I64, 0);
+                self.load_element(builder, array, first)
+            }
+            _ => builder.ins().f64const(Ieee64::with_float(0.0)),
+        };
+        let total = self.declare_variable(builder, F64);
+        builder.def_var(total, initial);
+
+        let count = builder.ins().iconst(I64, len as i64);
+        self.build_loop(builder, count, |builder, i| {
+            let previous = builder.use_var(total);
+            let element = self.load_element(builder, array, i);
+
+            let next = match call.name.as_str() {
+                "sum" | "mean" => builder.ins().fadd(previous, element),
+                // Rust's `f64::min()` and `f64::max()` ignore NaNs, the same
+                // as `llvm.minnum.f64` and `llvm.maxnum.f64`
+                "min" | "max" => self.call_builtin(builder, &call.name, &[previous, element]),
+                "dot" => {
+                    let (other, _) = args[1].array();
+                    let other = self.load_element(builder, other, i);
+                    let product = builder.ins().fmul(element, other);
+                    builder.ins().fadd(previous, product)
+                }
+                other => unreachable!("Unknown reduction, {}()", other),
+            };
+
+            builder.def_var(total, next);
+        });
+
+        let total = builder.use_var(total);
+
+        if call.name == "mean" {
+            let len = builder.ins().f64const(Ieee64::with_float(len as f64));
+            builder.ins().fdiv(total, len)
+        } else {
+            total
+        }
+    }
+
+    /// Extern functions are called directly. Closures are called through
+    /// the same trampoline as the LLVM backend, with the arguments passed
+    /// in a buffer on the stack and the result written to another stack
+    /// slot.
+    fn call_host_function(
+        &self,
+        builder: &mut FunctionBuilder,
+        call: &FunctionCall,
+        host: &HostFunction,
+        args: &[Value],
+    ) -> Value {
+        if let Some(address) = host.extern_address() {
+            return self.call_address(builder, address, args);
+        }
+
+        let closure = host.closure_address()
+            .expect("Host functions are either extern functions or closures");
+
+        let size = 8 * args.len().max(1) as u32;
+        let slot = builder.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+        for (i, &arg) in args.iter().enumerate() {
+            builder.ins().stack_store(arg, slot, 8 * i as i32);
+        }
+
+        let out = builder.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8));
+
+        let closure = builder.ins().iconst(self.pointer, closure as i64);
+        let buffer = builder.ins().stack_addr(self.pointer, slot, 0);
+        let len = builder.ins().iconst(I64, args.len() as i64);
+        let result = builder.ins().stack_addr(self.pointer, out, 0);
+
+        let mut sig = Signature::new(self.call_conv);
+        sig.params.push(AbiParam::new(self.pointer));
+        sig.params.push(AbiParam::new(self.pointer));
+        sig.params.push(AbiParam::new(I64));
+        sig.params.push(AbiParam::new(self.pointer));
+        sig.returns.push(AbiParam::new(I32));
+
+        let trampoline = environment::call_closure as *const () as usize;
+        let status = self.call_indirect(builder, sig, trampoline, &[closure, buffer, len, result]);
+        let panicked = builder
+            .ins()
+            .icmp_imm(IntCC::NotEqual, status, Status::Ok as i64);
+        self.bail_if(builder, panicked, Status::HostFunctionPanicked, call.span);
+
+        builder.ins().stack_load(F64, out, 0)
+    }
+
+    /// Call the Rust implementation of a real function.
+    fn call_builtin(&self, builder: &mut FunctionBuilder, name: &str, args: &[Value]) -> Value {
+        let address = builtin_address(name)
+            .unwrap_or_else(|| panic!("{}() is implemented in Rust", name));
+        self.call_address(builder, address, args)
+    }
+
+    /// Call a `extern "C" fn(f64, ...) -> f64` at a known address.
+    fn call_address(&self, builder: &mut FunctionBuilder, address: usize, args: &[Value]) -> Value {
+        let mut sig = Signature::new(self.call_conv);
+        for _ in args {
+            sig.params.push(AbiParam::new(F64));
+        }
+        sig.returns.push(AbiParam::new(F64));
+
+        self.call_indirect(builder, sig, address, args)
+    }
+
+    fn call_indirect(
+        &self,
+        builder: &mut FunctionBuilder,
+        sig: Signature,
+        address: usize,
+        args: &[Value],
+    ) -> Value {
+        let sig = builder.import_signature(sig);
+        let callee = builder.ins().iconst(self.pointer, address as i64);
+        let call = builder.ins().call_indirect(sig, callee, args);
+
+        builder.inst_results(call)[0]
+    }
+
+    /// Copy the value of every variable in scope to a buffer on the stack,
+    /// so the functions we generate for integrands and residuals can use
+    /// them. Returns the buffer's address and the type of each variable's
+    /// parts, for `unpack_captures()`.
+    fn capture_scope(
+        &self,
+        builder: &mut FunctionBuilder,
+    ) -> (Value, Vec<(String, Operand<Type>)>) {
+        let scope = self.scope.borrow();
+        let parts: usize = scope.iter().map(|&(_, value)| value.parts().len()).sum();
+        let size = 8 * parts.max(1) as u32;
+        let slot = builder.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+
+        let mut offset = 0;
+        let mut layout = Vec::new();
+        for &(ref name, value) in scope.iter() {
+            let types = value.map(|part| {
+                let ty = type_of(builder, part);
+                // booleans don't have a size, so they're stored as integers
+                let part = match ty {
+                    B1 => builder.ins().bint(I64, part),
+                    _ => part,
+                };
+                builder.ins().stack_store(part, slot, offset);
+                offset += 8;
+                ty
+            });
+            layout.push((name.clone(), types));
+        }
+
+        (builder.ins().stack_addr(self.pointer, slot, 0), layout)
+    }
+
+    /// Load the variables `capture_scope()` copied into `captures`.
+    fn unpack_captures(
+        &self,
+        builder: &mut FunctionBuilder,
+        layout: &[(String, Operand<Type>)],
+        captures: Value,
+    ) -> Vec<(String, Operand)> {
+        let mut offset = 0;
+        let mut scope = Vec::new();
+
+        for &(ref name, types) in layout {
+            let value = types.map(|ty| {
+                let part = match ty {
+                    B1 => {
+                        let int = builder.ins().load(I64, MemFlags::new(), captures, offset);
+                        builder.ins().icmp_imm(IntCC::NotEqual, int, 0)
+                    }
+                    _ => builder.ins().load(ty, MemFlags::new(), captures, offset),
+                };
+                offset += 8;
+                part
+            });
+            scope.push((name.clone(), value));
+        }
+
+        scope
+    }
+
+    /// Compile `body` to a function of `variable` with the
+    /// `solve::Residual` signature, which integrands use as well. The
+    /// variables in scope are passed in a buffer made by `capture_scope()`.
+    fn compile_function_of(
+        &self,
+        name: &str,
+        body: &Expr,
+        variable: &str,
+        layout: &[(String, Operand<Type>)],
+    ) -> Result<FuncId, Error> {
+        let mut sig = Signature::new(self.call_conv);
+        for &ty in &[self.pointer, self.pointer, F64, self.pointer] {
+            sig.params.push(AbiParam::new(ty));
+        }
+        sig.returns.push(AbiParam::new(I32));
+
+        let name = (0..)
+            .map(|i| format!("{}.{}", name, i))
+            .find(|name| self.module.borrow().get_name(name).is_none())
+            .unwrap();
+
+        self.compiler.define_function(
+            self.module,
+            &name,
+            Linkage::Local,
+            sig,
+            |translator, params| {
+                let (out, x, captures) = (params[0], params[2], params[3]);
+                {
+                    let mut builder = translator.builder.borrow_mut();
+                    let mut scope = translator.unpack_captures(&mut builder, layout, captures);
+                    scope.push((variable.to_string(), Operand::Scalar(x)));
+                    translator.scope.replace(scope);
+                }
+
+                let value = backend::lower(translator, body)?.scalar();
+
+                let mut builder = translator.builder.borrow_mut();
+                builder.ins().store(MemFlags::new(), value, out, 0);
+                translator.return_status(&mut builder, Status::Ok);
+                Ok(())
+            },
+        )
+    }
+
+    fn import_function(&self, builder: &mut FunctionBuilder, id: FuncId) -> FuncRef {
+        self.module
+            .borrow()
+            .declare_func_in_func(id, builder.func)
+    }
+
+    /// Evaluate the integrand at `x`, passing on any error it returns.
+    fn call_integrand(
+        &self,
+        builder: &mut FunctionBuilder,
+        integrand: FuncRef,
+        out: StackSlot,
+        x: Value,
+        captures: Value,
+    ) -> Value {
+        let result = builder.ins().stack_addr(self.pointer, out, 0);
+        let location = self.location.get().expect("The entry block has been created");
+        let call = builder
+            .ins()
+            .call(integrand, &[result, location, x, captures]);
+        let status = builder.inst_results(call)[0];
+        // the integrand has already recorded where it failed
+        self.propagate_failure(builder, status);
+
+        builder.ins().stack_load(F64, out, 0)
+    }
+
+    fn check_domain(
+        &self,
+        builder: &mut FunctionBuilder,
+        domain: Domain,
+        value: Value,
+        span: Span,
+    ) {
+        let condition = match domain {
+            Domain::Any => return,
+            Domain::NonNegative => FloatCC::LessThan,
+            Domain::Positive => FloatCC::LessThanOrEqual,
+        };
+
+        let zero = builder.ins().f64const(Ieee64::with_float(0.0));
+        let outside = builder.ins().fcmp(condition, value, zero);
+        self.bail_if(builder, outside, Status::DomainError, span);
+    }
+
+    /// Return `status` from the current function when `condition` is true,
+    /// recording the offending expression's location. Otherwise continue in
+    /// a fresh block.
+    fn bail_if(&self, builder: &mut FunctionBuilder, condition: Value, status: Status, span: Span) {
+        let bail = builder.create_ebb();
+        let next = builder.create_ebb();
+        builder.ins().brnz(condition, bail, &[]);
+        builder.ins().jump(next, &[]);
+
+        builder.switch_to_block(bail);
+        builder.seal_block(bail);
+        let location = self.location.get().expect("The entry block has been created");
+        let start = builder.ins().iconst(I64, span.start as i64);
+        builder.ins().store(MemFlags::new(), start, location, 0);
+        let end = builder.ins().iconst(I64, span.end as i64);
+        builder.ins().store(MemFlags::new(), end, location, 8);
+        self.return_status(builder, status);
+
+        builder.switch_to_block(next);
+        builder.seal_block(next);
+    }
+
+    /// Return `status` from the current function if it isn't `Status::Ok`.
+    fn propagate_failure(&self, builder: &mut FunctionBuilder, status: Value) {
+        let failed = builder
+            .ins()
+            .icmp_imm(IntCC::NotEqual, status, Status::Ok as i64);
+        let bail = builder.create_ebb();
+        let next = builder.create_ebb();
+        builder.ins().brnz(failed, bail, &[]);
+        builder.ins().jump(next, &[]);
+
+        builder.switch_to_block(bail);
+        builder.seal_block(bail);
+        builder.ins().return_(&[status]);
+
+        builder.switch_to_block(next);
+        builder.seal_block(next);
+    }
+
+    fn return_status(&self, builder: &mut FunctionBuilder, status: Status) {
+        let code = builder.ins().iconst(I32, status as i64);
+        builder.ins().return_(&[code]);
+    }
+
+    /// Convert an integer or boolean to a float.
+    fn to_float(&self, builder: &mut FunctionBuilder, value: Value) -> Value {
+        match type_of(builder, value) {
+            F64 => value,
+            I64 => builder.ins().fcvt_from_sint(F64, value),
+            B1 => {
+                let int = builder.ins().bint(I64, value);
+                builder.ins().fcvt_from_sint(F64, int)
+            }
+            other => unreachable!("calc never produces a {}", other),
+        }
+    }
+
+    fn declare_variable(&self, builder: &mut FunctionBuilder, ty: Type) -> Variable {
+        let variable = Variable::new(self.variables.get());
+        self.variables.set(self.variables.get() + 1);
+        builder.declare_var(variable, ty);
+        variable
+    }
+}
+
+/// Every operation is emitted into the block the builder is currently in.
+/// The builder is only borrowed while emitting, never while lowering
+/// sub-expressions.
+impl<'a> Backend for Translator<'a> {
+    type Value = Operand;
+    type Error = Error;
+
+    fn scope(&self) -> &RefCell<Vec<(String, Operand)>> {
+        &self.scope
+    }
+
+    fn atom(&self, atom: &Atom, _: Span) -> Result<Operand, Error> {
+        let mut builder = self.builder.borrow_mut();
+
+        let value = match *atom {
+            Atom::Number(n) => builder.ins().f64const(Ieee64::with_float(n)),
+            Atom::Integer(n) => builder.ins().iconst(I64, n),
+            Atom::Boolean(b) => builder.ins().bconst(B1, b),
+            Atom::Imaginary(n) => {
+                let re = builder.ins().f64const(Ieee64::with_float(0.0));
+                let im = builder.ins().f64const(Ieee64::with_float(n));
+                return Ok(Operand::Complex(re, im));
+            }
+            Atom::Ident(_) => {
+                unreachable!("The type checker inlines everything except bound variables")
+            }
+        };
+
+        Ok(Operand::Scalar(value))
+    }
+
+    fn binary_op(&self, op: &BinaryOp, left: Operand, right: Operand) -> Result<Operand, Error> {
+        let mut builder = self.builder.borrow_mut();
+
+        let value = match (left, right) {
+            (Operand::Scalar(l), Operand::Scalar(r)) => {
+                let value = match type_of(&builder, l) {
+                    F64 => self.float_binary_op(&mut builder, op, l, r),
+                    I64 => self.int_binary_op(&mut builder, op, l, r),
+                    B1 => self.bool_binary_op(&mut builder, op, l, r),
+                    other => unreachable!("calc never produces a {}", other),
+                };
+                Operand::Scalar(value)
+            }
+            (Operand::Complex(a, b), Operand::Complex(c, d)) => {
+                self.complex_binary_op(&mut builder, op, (a, b), (c, d))
+            }
+            (Operand::Array(..), _) | (_, Operand::Array(..)) => {
+                self.array_binary_op(&mut builder, op, left, right)
+            }
+            _ => unreachable!("The type checker ensures both operands have the same type"),
+        };
+
+        Ok(value)
+    }
+
+    fn unary_op(&self, op: &UnaryOp, value: Operand) -> Result<Operand, Error> {
+        match op.op {
+            UnaryOperator::Not => {
+                // x86 can't encode a `bnot` of a `b1`, so compare its integer
+                // form against zero instead
+                let mut builder = self.builder.borrow_mut();
+                let int = builder.ins().bint(I8, value.scalar());
+                Ok(Operand::Scalar(builder.ins().icmp_imm(IntCC::Equal, int, 0)))
+            }
+        }
+    }
+
+    fn cast(&self, cast: &Cast, value: Operand) -> Result<Operand, Error> {
+        let mut builder = self.builder.borrow_mut();
+
+        let converted = match (value, cast.to) {
+            (value, to) if cast.from == to => value,
+            (Operand::Scalar(value), ::syntax::Type::Complex) => {
+                let re = self.to_float(&mut builder, value);
+                let im = builder.ins().f64const(Ieee64::with_float(0.0));
+                Operand::Complex(re, im)
+            }
+            (Operand::Scalar(value), to) => {
+                let converted = match (type_of(&builder, value), cranelift_type(to)) {
+                    (from, to) if from == to => value,
+                    (_, F64) => self.to_float(&mut builder, value),
+                    (B1, I64) => builder.ins().bint(I64, value),
+                    (F64, I64) => builder.ins().fcvt_to_sint_sat(I64, value),
+                    (from, to) => unreachable!("Can't convert a {} to a {}", from, to),
+                };
+                Operand::Scalar(converted)
+            }
+            (_, to) => unreachable!("Can't convert a {} to a {}", cast.from, to),
+        };
+
+        Ok(converted)
+    }
+
+    /// Built-in functions without an equivalent Cranelift instruction are
+    /// implemented in Rust and called through their address, the same way
+    /// as host functions.
+    fn call(&self, call: &FunctionCall, args: Vec<Operand>) -> Result<Operand, Error> {
+        let mut builder = self.builder.borrow_mut();
+
+        match args.first() {
+            Some(&Operand::Complex(re, im)) => {
+                return Ok(self.complex_function_call(&mut builder, &call.name, re, im))
+            }
+            Some(&Operand::Array(..)) => {
+                return Ok(Operand::Scalar(self.reduction(&mut builder, call, &args)))
+            }
+            _ => {}
+        }
+
+        let args: Vec<Value> = args.into_iter().map(Operand::scalar).collect();
+
+        if let Some(host) = self.compiler.env.function(&call.name) {
+            let value = self.call_host_function(&mut builder, call, host, &args);
+            return Ok(Operand::Scalar(value));
+        }
+
+        let builtin = builtins::lookup(&call.name)
+            .expect("The type checker ensures only known functions are called");
+
+        if self.compiler.runtime_checks {
+            self.check_domain(&mut builder, builtin.domain, args[0], call.span);
+        }
+
+        let value = match (call.name.as_str(), args.as_slice()) {
+            ("sqrt", &[x]) => builder.ins().sqrt(x),
+            ("abs", &[x]) => builder.ins().fabs(x),
+            ("floor", &[x]) => builder.ins().floor(x),
+            ("ceil", &[x]) => builder.ins().ceil(x),
+            (name, _) => {
+                let address = builtin_address(name)
+                    .expect("The type checker promotes the arguments of complex-only functions");
+                self.call_address(&mut builder, address, &args)
+            }
+        };
+
+        Ok(Operand::Scalar(value))
+    }
+
+    /// Only the selected branch gets evaluated, with its value passed to
+    /// the block after the conditional.
+    fn conditional<T, F>(
+        &self,
+        condition: Operand,
+        if_true: T,
+        if_false: F,
+    ) -> Result<Operand, Error>
+    where
+        T: FnOnce() -> Result<Operand, Error>,
+        F: FnOnce() -> Result<Operand, Error>,
+    {
+        let (if_false_ebb, merge) = {
+            let mut builder = self.builder.borrow_mut();
+            let if_true_ebb = builder.create_ebb();
+            let if_false_ebb = builder.create_ebb();
+            let merge = builder.create_ebb();
+
+            builder.ins().brz(condition.scalar(), if_false_ebb, &[]);
+            builder.ins().jump(if_true_ebb, &[]);
+
+            builder.switch_to_block(if_true_ebb);
+            builder.seal_block(if_true_ebb);
+            (if_false_ebb, merge)
+        };
+
+        let true_value = if_true()?;
+        let types = {
+            let mut builder = self.builder.borrow_mut();
+            let types = true_value.map(|part| type_of(&builder, part));
+            for ty in types.parts() {
+                builder.append_ebb_param(merge, ty);
+            }
+            builder.ins().jump(merge, &true_value.parts());
+
+            builder.switch_to_block(if_false_ebb);
+            builder.seal_block(if_false_ebb);
+            types
+        };
+
+        let false_value = if_false()?;
+        let mut builder = self.builder.borrow_mut();
+        builder.ins().jump(merge, &false_value.parts());
+
+        builder.switch_to_block(merge);
+        builder.seal_block(merge);
+        let mut params = builder.ebb_params(merge).to_vec().into_iter();
+        Ok(types.map(|_| params.next().expect("Both branches have the same type")))
+    }
+
+    /// Sums and products are lowered to a counted loop, with the loop
+    /// counter and running total stored in variables.
+    fn series<F>(
+        &self,
+        series: &Series,
+        start: Operand,
+        end: Operand,
+        mut term: F,
+    ) -> Result<Operand, Error>
+    where
+        F: FnMut(Operand) -> Result<Operand, Error>,
+    {
+        let end = end.scalar();
+        let (counter, total, header, exit, i) = {
+            let mut builder = self.builder.borrow_mut();
+
+            let counter = self.declare_variable(&mut builder, I64);
+            builder.def_var(counter, start.scalar());
+            let identity = match series.kind {
+                SeriesKind::Sum => 0.0,
+                SeriesKind::Product => 1.0,
+            };
+            let total = self.declare_variable(&mut builder, F64);
+            let identity = builder.ins().f64const(Ieee64::with_float(identity));
+            builder.def_var(total, identity);
+
+            let header = builder.create_ebb();
+            let body = builder.create_ebb();
+            let exit = builder.create_ebb();
+            builder.ins().jump(header, &[]);
+
+            // the header can't be sealed until we've added the back edge
+            builder.switch_to_block(header);
+            let i = builder.use_var(counter);
+            let finished = builder.ins().icmp(IntCC::SignedGreaterThan, i, end);
+            builder.ins().brnz(finished, exit, &[]);
+            builder.ins().jump(body, &[]);
+
+            builder.switch_to_block(body);
+            builder.seal_block(body);
+            let i = builder.use_var(counter);
+            (counter, total, header, exit, i)
+        };
+
+        let term = term(Operand::Scalar(i))?.scalar();
+
+        let mut builder = self.builder.borrow_mut();
+        let previous = builder.use_var(total);
+        let next_total = match series.kind {
+            SeriesKind::Sum => builder.ins().fadd(previous, term),
+            SeriesKind::Product => builder.ins().fmul(previous, term),
+        };
+        builder.def_var(total, next_total);
+        // stop before incrementing, otherwise `end == i64::MAX` would wrap
+        // around and never finish
+        let i = builder.use_var(counter);
+        let last = builder.ins().icmp(IntCC::Equal, i, end);
+        builder.ins().brnz(last, exit, &[]);
+        let next = builder.ins().iadd_imm(i, 1);
+        builder.def_var(counter, next);
+        builder.ins().jump(header, &[]);
+        builder.seal_block(header);
+
+        builder.switch_to_block(exit);
+        builder.seal_block(exit);
+        Ok(Operand::Scalar(builder.use_var(total)))
+    }
+
+    /// A Cranelift value is only calculated once, wherever it's used.
+    fn bind<F>(&self, _: &Let, value: Operand, body: F) -> Result<Operand, Error>
+    where
+        F: FnOnce(Operand) -> Result<Operand, Error>,
+    {
+        body(value)
+    }
+
+    fn array(&self, _: &Array, elements: Vec<Operand>) -> Result<Operand, Error> {
+        let mut builder = self.builder.borrow_mut();
+        let array = self.allocate_array(&mut builder, elements.len());
+
+        for (i, element) in elements.iter().enumerate() {
+            let address = builder.ins().iadd_imm(array, 8 * i as i64);
+            builder
+                .ins()
+                .store(MemFlags::new(), element.scalar(), address, 0);
+        }
+
+        Ok(Operand::Array(array, elements.len()))
+    }
+
+    /// Indexing is always bounds checked, regardless of whether runtime
+    /// checks are enabled, because reading past the end of the buffer would
+    /// be undefined behaviour.
+    fn index(&self, index: &Index, array: Operand, position: Operand) -> Result<Operand, Error> {
+        let (array, len) = array.array();
+        let position = position.scalar();
+        let mut builder = self.builder.borrow_mut();
+
+        // negative indices wrap around to huge unsigned numbers, so a single
+        // comparison catches both ends
+        let out_of_bounds =
+            builder
+                .ins()
+                .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, position, len as i64);
+        self.bail_if(&mut builder, out_of_bounds, Status::IndexOutOfBounds, index.span);
+
+        Ok(Operand::Scalar(self.load_element(&mut builder, array, position)))
+    }
+
+    /// Integrals use the same Gauss–Kronrod scheme as the LLVM backend. The
+    /// integrand is compiled to its own function, which is evaluated at the
+    /// 15 Kronrod points of each panel, and the number of panels is doubled
+    /// until the Kronrod estimate agrees with the embedded 7-point Gauss
+    /// estimate. If that still hasn't happened with `MAX_PANELS` panels, we
+    /// bail with `Status::NoConvergence`.
+    fn integral(&self, integral: &Integral) -> Result<Operand, Error> {
+        let lower = backend::lower(self, &integral.lower)?.scalar();
+        let upper = backend::lower(self, &integral.upper)?.scalar();
+
+        let mut builder = self.builder.borrow_mut();
+        let (captures, layout) = self.capture_scope(&mut builder);
+        let integrand =
+            self.compile_function_of("integrand", &integral.body, &integral.variable, &layout)?;
+        let integrand = self.import_function(&mut builder, integrand);
+        let out = builder.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8));
+
+        let panels = self.declare_variable(&mut builder, I64);
+        let kronrod = self.declare_variable(&mut builder, F64);
+        let error = self.declare_variable(&mut builder, F64);
+        let one = builder.ins().iconst(I64, 1);
+        builder.def_var(panels, one);
+
+        let refine = builder.create_ebb();
+        let not_converged = builder.create_ebb();
+        let exit = builder.create_ebb();
+        builder.ins().jump(refine, &[]);
+
+        // refining jumps back here, so this is sealed last
+        builder.switch_to_block(refine);
+        let zero = builder.ins().f64const(Ieee64::with_float(0.0));
+        builder.def_var(kronrod, zero);
+        builder.def_var(error, zero);
+
+        let n = builder.use_var(panels);
+        let n_float = builder.ins().fcvt_from_sint(F64, n);
+        let width = builder.ins().fsub(upper, lower);
+        let step = builder.ins().fdiv(width, n_float);
+        let half = builder.ins().f64const(Ieee64::with_float(0.5));
+        let half = builder.ins().fmul(step, half);
+
+        self.build_loop(&mut builder, n, |builder, i| {
+            let i = builder.ins().fcvt_from_sint(F64, i);
+            let offset = builder.ins().fmul(i, step);
+            let left = builder.ins().fadd(lower, offset);
+            let centre = builder.ins().fadd(left, half);
+
+            let value = self.call_integrand(builder, integrand, out, centre, captures);
+            let weight = builder
+                .ins()
+                .f64const(Ieee64::with_float(KRONROD_WEIGHTS[7]));
+            let mut k = builder.ins().fmul(value, weight);
+            let weight = builder
+                .ins()
+                .f64const(Ieee64::with_float(GAUSS_WEIGHTS[3]));
+            let mut g = builder.ins().fmul(value, weight);
+
+            for (j, (&node, &weight)) in KRONROD_NODES.iter().zip(&KRONROD_WEIGHTS).enumerate() {
+                let node = builder.ins().f64const(Ieee64::with_float(node));
+                let dx = builder.ins().fmul(half, node);
+                let x = builder.ins().fsub(centre, dx);
+                let below = self.call_integrand(builder, integrand, out, x, captures);
+                let x = builder.ins().fadd(centre, dx);
+                let above = self.call_integrand(builder, integrand, out, x, captures);
+                let pair = builder.ins().fadd(below, above);
+
+                let weight = builder.ins().f64const(Ieee64::with_float(weight));
+                let term = builder.ins().fmul(pair, weight);
+                k = builder.ins().fadd(k, term);
+
+                // every second Kronrod point is also a Gauss point
+                if j % 2 == 1 {
+                    let weight = builder
+                        .ins()
+                        .f64const(Ieee64::with_float(GAUSS_WEIGHTS[j / 2]));
+                    let term = builder.ins().fmul(pair, weight);
+                    g = builder.ins().fadd(g, term);
+                }
+            }
+
+            let difference = builder.ins().fsub(k, g);
+            let difference = builder.ins().fabs(difference);
+
+            let panel = builder.ins().fmul(k, half);
+            let previous = builder.use_var(kronrod);
+            let total = builder.ins().fadd(previous, panel);
+            builder.def_var(kronrod, total);
+
+            let panel_error = builder.ins().fmul(difference, half);
+            let previous = builder.use_var(error);
+            let total_error = builder.ins().fadd(previous, panel_error);
+            builder.def_var(error, total_error);
+        });
+
+        let total = builder.use_var(kronrod);
+        let error = builder.use_var(error);
+
+        // stop once the error is small relative to the answer
+        let magnitude = builder.ins().fabs(total);
+        let tolerance = builder
+            .ins()
+            .f64const(Ieee64::with_float(INTEGRAL_TOLERANCE));
+        let relative = builder.ins().fmul(magnitude, tolerance);
+        let tolerance = self.call_builtin(&mut builder, "max", &[relative, tolerance]);
+        let converged = builder
+            .ins()
+            .fcmp(FloatCC::LessThanOrEqual, error, tolerance);
+        builder.ins().brnz(converged, exit, &[]);
+        builder.ins().jump(not_converged, &[]);
+
+        builder.switch_to_block(not_converged);
+        builder.seal_block(not_converged);
+        let exhausted = builder
+            .ins()
+            .icmp_imm(IntCC::SignedGreaterThanOrEqual, n, MAX_PANELS as i64);
+        self.bail_if(&mut builder, exhausted, Status::NoConvergence, integral.span);
+
+        let doubled = builder.ins().imul_imm(n, 2);
+        builder.def_var(panels, doubled);
+        builder.ins().jump(refine, &[]);
+        builder.seal_block(refine);
+
+        builder.switch_to_block(exit);
+        builder.seal_block(exit);
+        Ok(Operand::Scalar(total))
+    }
+
+    /// Equations are solved by the [`solve`] module at runtime, the same as
+    /// with the LLVM backend. We compile `left - right` (and its
+    /// derivative, if there is one) to functions the solver can call,
+    /// passing it their addresses and a buffer holding the values of any
+    /// variables in scope.
+    ///
+    /// [`solve`]: ../solve/index.html
+    fn solve(&self, solve: &Solve) -> Result<Operand, Error> {
+        let guess = backend::lower(self, &solve.guess)?.scalar();
+
+        let residual: Expr = match solve.equation {
+            Expr::BinaryOp(ref op) => BinaryOp::sub(op.left.clone(), op.right.clone()).into(),
+            _ => unreachable!("The type checker only accepts equations"),
+        };
+        let derivative = differentiate(&residual, &solve.variable, &self.compiler.env);
+
+        let mut builder = self.builder.borrow_mut();
+        let (captures, layout) = self.capture_scope(&mut builder);
+
+        let residual =
+            self.compile_function_of("residual", &residual, &solve.variable, &layout)?;
+        let residual = self.import_function(&mut builder, residual);
+        let residual = builder.ins().func_addr(self.pointer, residual);
+
+        let derivative = match derivative {
+            Some(ref derivative) => {
+                let derivative =
+                    self.compile_function_of("derivative", derivative, &solve.variable, &layout)?;
+                let derivative = self.import_function(&mut builder, derivative);
+                builder.ins().func_addr(self.pointer, derivative)
+            }
+            None => builder.ins().iconst(self.pointer, 0),
+        };
+
+        let root = builder.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8));
+        let root_address = builder.ins().stack_addr(self.pointer, root, 0);
+        let location = self.location.get().expect("The entry block has been created");
+
+        let mut sig = Signature::new(self.call_conv);
+        for &ty in &[self.pointer, self.pointer, self.pointer, F64, self.pointer, self.pointer] {
+            sig.params.push(AbiParam::new(ty));
+        }
+        sig.returns.push(AbiParam::new(I32));
+
+        let solver = solve::calc_solve as *const () as usize;
+        let args = [residual, derivative, captures, guess, root_address, location];
+        let status = self.call_indirect(&mut builder, sig, solver, &args);
+
+        let no_solution = builder
+            .ins()
+            .icmp_imm(IntCC::Equal, status, Status::NoSolution as i64);
+        self.bail_if(&mut builder, no_solution, Status::NoSolution, solve.span);
+        // any other error came from the residual, which recorded its location
+        self.propagate_failure(&mut builder, status);
+
+        Ok(Operand::Scalar(builder.ins().stack_load(F64, root, 0)))
+    }
+}
+
+fn type_of(builder: &FunctionBuilder, value: Value) -> Type {
+    builder.func.dfg.value_type(value)
+}
+
+fn cranelift_type(ty: ::syntax::Type) -> Type {
+    match ty {
+        ::syntax::Type::Bool => B1,
+        ::syntax::Type::Integer => I64,
+        ::syntax::Type::Float => F64,
+        other => unreachable!("A {} isn't a single Cranelift value", other),
+    }
+}
+
+/// The address of the Rust implementation of a built-in function, or of
+/// one of the real functions complex numbers are written in terms of.
+fn builtin_address(name: &str) -> Option<usize> {
+    extern "C" fn sin(x: f64) -> f64 {
+        x.sin()
+    }
+    extern "C" fn cos(x: f64) -> f64 {
+        x.cos()
+    }
+    extern "C" fn exp(x: f64) -> f64 {
+        x.exp()
+    }
+    extern "C" fn ln(x: f64) -> f64 {
+        x.ln()
+    }
+    extern "C" fn log2(x: f64) -> f64 {
+        x.log2()
+    }
+    extern "C" fn log10(x: f64) -> f64 {
+        x.log10()
+    }
+    extern "C" fn round(x: f64) -> f64 {
+        // rounds half-way cases away from zero, like `llvm.round.f64`
+        x.round()
+    }
+    extern "C" fn pow(x: f64, y: f64) -> f64 {
+        x.powf(y)
+    }
+    extern "C" fn min(x: f64, y: f64) -> f64 {
+        x.min(y)
+    }
+    extern "C" fn max(x: f64, y: f64) -> f64 {
+        x.max(y)
+    }
+    extern "C" fn hypot(x: f64, y: f64) -> f64 {
+        x.hypot(y)
+    }
+    extern "C" fn atan2(y: f64, x: f64) -> f64 {
+        y.atan2(x)
+    }
+
+    let address = match name {
+        "sin" => sin as *const () as usize,
+        "cos" => cos as *const () as usize,
+        "exp" => exp as *const () as usize,
+        "ln" => ln as *const () as usize,
+        "log2" => log2 as *const () as usize,
+        "log10" => log10 as *const () as usize,
+        "round" => round as *const () as usize,
+        "pow" => pow as *const () as usize,
+        "min" => min as *const () as usize,
+        "max" => max as *const () as usize,
+        "hypot" => hypot as *const () as usize,
+        "atan2" => atan2 as *const () as usize,
+        _ => return None,
+    };
+
+    Some(address)
+}
@@ -0,0 +1,246 @@
+//! Generate machine code using [Cranelift] instead of LLVM.
+//!
+//! Cranelift is written in Rust and pulled in like any other crate, so this
+//! backend is available on machines where LLVM can't be installed.
+//! Enable it with the `cranelift` feature, optionally turning off the
+//! default `llvm` feature.
+//!
+//! ```rust
+//! use calc::backend::Executable;
+//! use calc::cranelift::Compiler;
+//! use calc::environment::Environment;
+//!
+//! let ast = calc::syntax::parse("sum(k, 1, 10, k * k) / 5").unwrap();
+//! let (ast, _) = calc::sema::type_check(&ast, &Environment::new()).unwrap();
+//!
+//! let program = Compiler::new().with_runtime_checks(true).compile(&ast).unwrap();
+//! assert_eq!(program.call().unwrap(), 77.0);
+//! ```
+//!
+//! The generated `calc_main` has the same signature and status codes as
+//! the LLVM backend's, and the whole language is supported. Integrands and
+//! the residuals passed to `solve()` are compiled to their own functions in
+//! the same module, and equations are solved by the same [`solve`] module.
+//!
+//! [Cranelift]: https://github.com/CraneStation/cranelift
+//! [`solve`]: ../solve/index.html
+
+mod compiler;
+
+pub use self::compiler::Compiler;
+
+use cranelift_module::Module;
+use cranelift_simplejit::SimpleJITBackend;
+use num_complex::Complex64;
+use std::fmt::{self, Debug, Formatter};
+
+use backend::{self, CalcMain, Executable, RuntimeError};
+use environment::Environment;
+
+/// A `calc_main` compiled by Cranelift, which is ready to be executed.
+pub struct Program {
+    calc_main: CalcMain,
+    // owns the memory the compiled code lives in
+    _module: Module<SimpleJITBackend>,
+    // keeps any closures alive for as long as the compiled code can call them
+    env: Environment,
+}
+
+impl Program {
+    /// Run the program, translating any error status from `calc_main` into a
+    /// `RuntimeError`.
+    pub fn call(&self) -> Result<f64, RuntimeError> {
+        self.call_complex().map(|z| z.re)
+    }
+
+    /// Run the program, returning its result as a complex number.
+    pub fn call_complex(&self) -> Result<Complex64, RuntimeError> {
+        let calc_main = self.calc_main;
+        backend::call_entrypoint(|result, location| unsafe { calc_main(result, location) })
+    }
+}
+
+impl Executable for Program {
+    fn call_complex(&self) -> Result<Complex64, RuntimeError> {
+        Program::call_complex(self)
+    }
+}
+
+impl Debug for Program {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Program")
+            .field("calc_main", &(self.calc_main as usize))
+            .field("env", &self.env)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::{Jit, Status};
+    use sema;
+    use syntax;
+
+    extern "C" fn hypotenuse(a: f64, b: f64) -> f64 {
+        a.hypot(b)
+    }
+
+    fn try_execute(src: &str) -> Result<f64, RuntimeError> {
+        try_execute_complex(src).map(|z| z.re)
+    }
+
+    fn try_execute_complex(src: &str) -> Result<Complex64, RuntimeError> {
+        let mut env = Environment::new();
+        env.register_function("hypotenuse", hypotenuse as extern "C" fn(f64, f64) -> f64)
+            .register_closure("sum3", 3, |args| args.iter().sum())
+            .register_closure("oops", 1, |_| panic!("Oops"))
+            .register_constant("nan", ::std::f64::NAN);
+
+        let ast = syntax::parse(src).unwrap();
+        let (ast, _) = sema::type_check(&ast, &env).unwrap();
+
+        Compiler::new()
+            .with_runtime_checks(true)
+            .with_environment(&env)
+            .build(&ast)
+            .unwrap()
+            .call_complex()
+    }
+
+    #[test]
+    fn execute_expressions() {
+        let inputs = vec![
+            ("1 + 2 * 3", 7.0),
+            ("7 // 2 + 7 % 2 + (0 - 7) // 2", 1.0),
+            ("1 / 4", 0.25),
+            ("if 1 < 2 and not false then 10 else 20", 10.0),
+            ("if 1.5 >= 2 or 3 == 4 then 10 else 20", 20.0),
+            ("1 > 2 and 1 // 0 == 0 or 1 < 2 or 1 // 0 == 0", 1.0),
+            ("2 > 1", 1.0),
+            ("sqrt(16) + pow(2, 10) + max(1, 2) + abs(0 - 3)", 1033.0),
+            ("round(2.5) + floor(0 - 0.5) + ceil(0.5)", 3.0),
+            ("sum(k, 1, 100, k)", 5050.0),
+            ("prod(i, 1, 5, i)", 120.0),
+            ("sum(i, 1, 3, sum(j, 1, i, j))", 10.0),
+            ("sum(k, 5, 1, k)", 0.0),
+            ("sum(i, 9223372036854775806, 9223372036854775807, 1)", 2.0),
+            ("hypotenuse(3, 4) + sum3(1, 2, 3)", 11.0),
+            ("pi", ::std::f64::consts::PI),
+            ("nan != nan", 1.0),
+            ("nan == nan", 0.0),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = try_execute(src).unwrap();
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn runtime_errors_have_the_same_status_as_llvm() {
+        let inputs = vec![
+            ("1 + 1 // 0", Status::DivideByZero, "1 // 0"),
+            ("1 % (2 - 2)", Status::DivideByZero, "1 % (2 - 2)"),
+            ("2 * (100 / 0)", Status::DivideByZero, "100 / 0"),
+            ("sqrt(4) + sqrt(0 - 1)", Status::DomainError, "sqrt(0 - 1)"),
+            ("ln(0.0)", Status::DomainError, "ln(0.0)"),
+            ("9223372036854775807 + 1", Status::IntegerOverflow, "9223372036854775807 + 1"),
+            (
+                "(0 - 9223372036854775807 - 1) // (0 - 1)",
+                Status::IntegerOverflow,
+                "(0 - 9223372036854775807 - 1) // (0 - 1)",
+            ),
+            ("4294967296 * 4294967296", Status::IntegerOverflow, "4294967296 * 4294967296"),
+            ("1 + oops(2)", Status::HostFunctionPanicked, "oops(2)"),
+            ("sum(i, 0, 2, [1, 2][i])", Status::IndexOutOfBounds, "[1, 2][i]"),
+            ("sum(i, 0 - 1, 0, [1, 2][i])", Status::IndexOutOfBounds, "[1, 2][i]"),
+            ("abs(1i / (0 * 1i))", Status::DivideByZero, "1i / (0 * 1i)"),
+            ("1 + integrate(sqrt(x), x, 0 - 1, 1)", Status::DomainError, "sqrt(x)"),
+            (
+                "2 * integrate(1 / x, x, 0, 1)",
+                Status::NoConvergence,
+                "integrate(1 / x, x, 0, 1)",
+            ),
+            (
+                "1 + solve(x * x == 0 - 1, x, 1)",
+                Status::NoSolution,
+                "solve(x * x == 0 - 1, x, 1)",
+            ),
+            ("solve(x == ln(0 - x * x), x, 1)", Status::DomainError, "ln(0 - x * x)"),
+        ];
+
+        for (src, status, text) in inputs {
+            let err = try_execute(src).unwrap_err();
+            assert_eq!(err.status(), Some(status), "{}", src);
+            assert_eq!(err.source_text(src), Some(text), "{}", src);
+        }
+    }
+
+    #[test]
+    fn execute_complex_arithmetic() {
+        let inputs = vec![
+            ("3 + 4i", Complex64::new(3.0, 4.0)),
+            ("(1 + 2i) * (3 - 1i)", Complex64::new(5.0, 5.0)),
+            ("(1 + 1i) / (1 - 1i)", Complex64::new(0.0, 1.0)),
+            ("conj(2 + 3i) + re(1i) + im(1i)", Complex64::new(3.0, -3.0)),
+            ("sqrt(0 - 4i * 1i) + sqrt(2i)", Complex64::new(3.0, 1.0)),
+            ("abs(3 + 4i) + arg(1i) * 0", Complex64::new(5.0, 0.0)),
+            ("if 1i * 1i == 0 - 1 then 1i else 0", Complex64::new(0.0, 1.0)),
+            ("if 1i != 1i then 1 else 2", Complex64::new(2.0, 0.0)),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = try_execute_complex(src).unwrap();
+            assert!((got - should_be).norm() < 1e-12, "{} gave {}", src, got);
+        }
+
+        let got = try_execute_complex("exp(1i * pi) + 1").unwrap();
+        assert!(got.norm() < 1e-12);
+    }
+
+    #[test]
+    fn execute_arrays() {
+        let inputs = vec![
+            ("[1, 2, 3][1]", 2.0),
+            ("([1, 2, 3] + [10, 20, 30])[2]", 33.0),
+            ("(2 * [1.5, 2.5])[1]", 5.0),
+            ("sum([1, 2, 3] / 2)", 3.0),
+            ("mean([1, 2, 3, 4])", 2.5),
+            ("min([3, 1, 2]) + max([3, 1, 2])", 4.0),
+            ("dot([1, 2, 3], [4, 5, 6])", 32.0),
+            ("sum(if 1 < 2 then [1, 2] else [3, 4])", 3.0),
+            ("sum(map(x -> x * x, [1, 2, 3] + 1))", 29.0),
+            ("sum(i, 0, 100000, [1, 2][i % 2])", 150001.0),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = try_execute(src).unwrap();
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn execute_integrals_and_solve() {
+        let inputs = vec![
+            ("integrate(x * x, x, 0, 3)", 9.0),
+            ("integrate(t -> sin(t), 0, pi)", 2.0),
+            ("integrate(x, x, 1, 0)", -0.5),
+            ("integrate(integrate(x * y, y, 0, 1), x, 0, 2)", 1.0),
+            ("sum(k, 1, 3, integrate(pow(x, k), x, 0, 1))", 1.0 / 2.0 + 1.0 / 3.0 + 1.0 / 4.0),
+            ("sum(map(x -> integrate(t * x, t, 0, 2), [1, 2]))", 6.0),
+            ("integrate(exp(0 - x * x), x, 0 - 10, 10)", ::std::f64::consts::PI.sqrt()),
+            ("solve(x * x == 2, x, 1)", 2.0_f64.sqrt()),
+            ("solve(x -> cos(x) == x, 1)", 0.739_085_133_215_160_6),
+            ("solve(floor(x) + x / 4 == 2.6, x, 0)", 2.4),
+            ("sum(k, 1, 3, solve(x * k == 1, x, 5))", 1.0 + 1.0 / 2.0 + 1.0 / 3.0),
+            ("solve(integrate(t, t, 0, x) == 8, x, 1)", 4.0),
+            ("solve(solve(y * x == 2, y, 1) == 4, x, 1)", 0.5),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = try_execute(src).unwrap();
+            assert!((got - should_be).abs() < 1e-8, "{} = {}", src, got);
+        }
+    }
+}
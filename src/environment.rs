@@ -0,0 +1,324 @@
+//! Things the host application makes available to `calc` programs.
+//!
+//! Functions can be registered either as a plain `extern "C"` function or as
+//! a closure. Constants are known at compile time and get inlined into the
+//! program as if they were literals. Anything registered takes precedence
+//! over a built-in with the same name.
+//!
+//! ```rust
+//! use calc::environment::Environment;
+//!
+//! extern "C" fn interest(rate: f64, years: f64) -> f64 {
+//!     (1.0 + rate).powf(years)
+//! }
+//!
+//! let mut env = Environment::new();
+//! env.register_function("interest", interest as extern "C" fn(f64, f64) -> f64)
+//!     .register_closure("double", 1, |args| args[0] * 2.0)
+//!     .register_constant("gst", 0.1);
+//!
+//! assert_eq!(env.function("interest").unwrap().arity(), 2);
+//! assert_eq!(env.constant("gst"), Some(0.1));
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::slice;
+
+use backend::Status;
+
+/// The symbol JIT compiled code uses to invoke a closure.
+pub(crate) const CLOSURE_TRAMPOLINE: &str = "calc.call_closure";
+
+/// A closure which can be called by `calc` code.
+pub type Closure = Box<Fn(&[f64]) -> f64>;
+
+/// The functions and values available to a `calc` program.
+#[derive(Default, Clone)]
+pub struct Environment {
+    functions: HashMap<String, HostFunction>,
+    constants: HashMap<String, f64>,
+}
+
+impl Environment {
+    /// Create an empty `Environment`.
+    pub fn new() -> Environment {
+        Environment::default()
+    }
+
+    /// Register an `extern "C"` function which accepts some number of `f64`
+    /// arguments and returns an `f64`.
+    pub fn register_function<S, F>(&mut self, name: S, func: F) -> &mut Environment
+    where
+        S: Into<String>,
+        F: ExternFunction,
+    {
+        let host = HostFunction {
+            arity: func.arity(),
+            kind: Kind::Extern(func.address()),
+        };
+        self.functions.insert(name.into(), host);
+        self
+    }
+
+    /// Register a closure which will be passed exactly `arity` arguments.
+    ///
+    /// If the closure panics, the panic is caught and the calculation fails
+    /// with an error pointing at the function call.
+    pub fn register_closure<S, F>(&mut self, name: S, arity: usize, func: F) -> &mut Environment
+    where
+        S: Into<String>,
+        F: Fn(&[f64]) -> f64 + 'static,
+    {
+        let host = HostFunction {
+            arity,
+            kind: Kind::Closure(Rc::new(Box::new(func))),
+        };
+        self.functions.insert(name.into(), host);
+        self
+    }
+
+    /// Register a compile-time constant.
+    ///
+    /// The value is copied into the generated code, so changing it after a
+    /// program has been compiled has no effect on that program.
+    pub fn register_constant<S: Into<String>>(&mut self, name: S, value: f64) -> &mut Environment {
+        self.constants.insert(name.into(), value);
+        self
+    }
+
+    /// Look up a registered constant.
+    pub fn constant(&self, name: &str) -> Option<f64> {
+        self.constants.get(name).cloned()
+    }
+
+    /// Look up a registered function.
+    pub fn function(&self, name: &str) -> Option<&HostFunction> {
+        self.functions.get(name)
+    }
+
+    pub(crate) fn functions(&self) -> &HashMap<String, HostFunction> {
+        &self.functions
+    }
+}
+
+impl Debug for Environment {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Environment")
+            .field("functions", &self.functions)
+            .field("constants", &self.constants)
+            .finish()
+    }
+}
+
+/// A function registered with the `Environment`.
+#[derive(Clone)]
+pub struct HostFunction {
+    arity: usize,
+    kind: Kind,
+}
+
+#[derive(Clone)]
+enum Kind {
+    Extern(usize),
+    Closure(Rc<Closure>),
+}
+
+impl HostFunction {
+    /// The number of arguments this function accepts.
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// The name JIT compiled code uses when declaring the function.
+    pub(crate) fn symbol(name: &str) -> String {
+        format!("host.{}", name)
+    }
+
+    /// The address of an `extern "C"` function.
+    pub(crate) fn extern_address(&self) -> Option<usize> {
+        match self.kind {
+            Kind::Extern(address) => Some(address),
+            Kind::Closure(_) => None,
+        }
+    }
+
+    /// A pointer to the closure, which gets passed to the trampoline. The
+    /// closure stays alive for as long as any clone of this `HostFunction`.
+    pub(crate) fn closure_address(&self) -> Option<usize> {
+        match self.kind {
+            Kind::Closure(ref closure) => Some(&**closure as *const Closure as usize),
+            Kind::Extern(_) => None,
+        }
+    }
+
+    /// Call the function from Rust, for when there's no compiled code to do
+    /// it for us. Returns `None` if a closure panicked.
+    pub(crate) fn call(&self, args: &[f64]) -> Option<f64> {
+        assert_eq!(args.len(), self.arity, "Called with the wrong number of arguments");
+
+        match self.kind {
+            Kind::Closure(ref closure) => {
+                panic::catch_unwind(AssertUnwindSafe(|| closure(args))).ok()
+            }
+            // the address came from an `ExternFunction` with this arity
+            Kind::Extern(address) => Some(unsafe { call_extern(address, args) }),
+        }
+    }
+}
+
+impl Debug for HostFunction {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            Kind::Extern(_) => "extern",
+            Kind::Closure(_) => "closure",
+        };
+
+        f.debug_struct("HostFunction")
+            .field("arity", &self.arity)
+            .field("kind", &kind)
+            .finish()
+    }
+}
+
+/// The trampoline JIT compiled code calls to invoke a closure, passing the
+/// arguments as a pointer and length.
+///
+/// The closure's return value is written to `result`, and the trampoline
+/// returns a [`Status`] code so a panic can be reported as an error.
+///
+/// [`Status`]: ../backend/enum.Status.html
+pub(crate) extern "C" fn call_closure(
+    closure: usize,
+    args: *const f64,
+    len: u64,
+    result: *mut f64,
+) -> u32 {
+    let closure = unsafe { &*(closure as *const Closure) };
+    let args = unsafe { slice::from_raw_parts(args, len as usize) };
+
+    // unwinding into JIT compiled code is undefined behaviour
+    match panic::catch_unwind(AssertUnwindSafe(|| closure(args))) {
+        Ok(value) => {
+            unsafe { *result = value };
+            Status::Ok as u32
+        }
+        Err(_) => Status::HostFunctionPanicked as u32,
+    }
+}
+
+/// Call an `extern "C"` function which takes `args.len()` arguments.
+unsafe fn call_extern(address: usize, args: &[f64]) -> f64 {
+    type F0 = extern "C" fn() -> f64;
+    type F1 = extern "C" fn(f64) -> f64;
+    type F2 = extern "C" fn(f64, f64) -> f64;
+    type F3 = extern "C" fn(f64, f64, f64) -> f64;
+    type F4 = extern "C" fn(f64, f64, f64, f64) -> f64;
+    type F5 = extern "C" fn(f64, f64, f64, f64, f64) -> f64;
+    type F6 = extern "C" fn(f64, f64, f64, f64, f64, f64) -> f64;
+
+    match *args {
+        [] => mem::transmute::<usize, F0>(address)(),
+        [a] => mem::transmute::<usize, F1>(address)(a),
+        [a, b] => mem::transmute::<usize, F2>(address)(a, b),
+        [a, b, c] => mem::transmute::<usize, F3>(address)(a, b, c),
+        [a, b, c, d] => mem::transmute::<usize, F4>(address)(a, b, c, d),
+        [a, b, c, d, e] => mem::transmute::<usize, F5>(address)(a, b, c, d, e),
+        [a, b, c, d, e, f] => mem::transmute::<usize, F6>(address)(a, b, c, d, e, f),
+        _ => unreachable!("Extern functions take at most 6 arguments"),
+    }
+}
+
+/// An `extern "C"` function which can be called from `calc` code.
+///
+/// This is implemented for functions taking up to 6 `f64` arguments and
+/// returning an `f64`.
+pub trait ExternFunction: Copy {
+    /// The number of arguments accepted.
+    fn arity(&self) -> usize;
+    /// The function's address in memory.
+    fn address(&self) -> usize;
+}
+
+macro_rules! extern_function {
+    ($arity:expr, $( $arg:ty ),*) => {
+        impl ExternFunction for extern "C" fn($( $arg ),*) -> f64 {
+            fn arity(&self) -> usize {
+                $arity
+            }
+
+            fn address(&self) -> usize {
+                *self as usize
+            }
+        }
+    };
+}
+
+extern_function!(0,);
+extern_function!(1, f64);
+extern_function!(2, f64, f64);
+extern_function!(3, f64, f64, f64);
+extern_function!(4, f64, f64, f64, f64);
+extern_function!(5, f64, f64, f64, f64, f64);
+extern_function!(6, f64, f64, f64, f64, f64, f64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn add(a: f64, b: f64) -> f64 {
+        a + b
+    }
+
+    #[test]
+    fn extern_functions_know_their_arity() {
+        let mut env = Environment::new();
+        env.register_function("add", add as extern "C" fn(f64, f64) -> f64);
+
+        let got = env.function("add").unwrap();
+
+        assert_eq!(got.arity(), 2);
+        assert_eq!(got.extern_address(), Some(add as usize));
+    }
+
+    #[test]
+    fn call_a_closure_through_the_trampoline() {
+        let offset = 10.0;
+        let mut env = Environment::new();
+        env.register_closure("sum", 3, move |args| args.iter().sum::<f64>() + offset);
+
+        let address = env.function("sum").unwrap().closure_address().unwrap();
+        let args = [1.0, 2.0, 3.0];
+        let mut got = 0.0;
+        let status = call_closure(address, args.as_ptr(), args.len() as u64, &mut got);
+
+        assert_eq!(status, Status::Ok as u32);
+        assert_eq!(got, 16.0);
+    }
+
+    #[test]
+    fn panicking_closures_return_an_error_status() {
+        let mut env = Environment::new();
+        env.register_closure("oops", 0, |_| panic!("Oops"));
+
+        let address = env.function("oops").unwrap().closure_address().unwrap();
+        let mut got = 1.0;
+        let status = call_closure(address, [].as_ptr(), 0, &mut got);
+
+        assert_eq!(status, Status::HostFunctionPanicked as u32);
+        assert_eq!(got, 1.0);
+    }
+
+    #[test]
+    fn call_host_functions_directly() {
+        let mut env = Environment::new();
+        env.register_function("add", add as extern "C" fn(f64, f64) -> f64)
+            .register_closure("oops", 1, |_| panic!("Oops"));
+
+        assert_eq!(env.function("add").unwrap().call(&[1.0, 2.0]), Some(3.0));
+        assert_eq!(env.function("oops").unwrap().call(&[1.0]), None);
+    }
+}
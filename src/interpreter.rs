@@ -0,0 +1,1122 @@
+//! Evaluate `calc` expressions exactly, without going through LLVM.
+//!
+//! Every number is represented as an arbitrary-precision rational, so
+//! operations like `0.1 + 0.2` and `1 / 3 * 3` give the answer you'd expect
+//! from doing the calculation by hand and integers never overflow. The final
+//! result is rounded to a fixed number of decimal places.
+//!
+//! Built-in functions which can't be computed exactly (e.g. `sin()` or
+//! `sqrt()`) are reported as an error instead of silently losing precision.
+//! Integrals are the exception, being approximated using adaptive Simpson's
+//! rule until the estimated error is well below the requested precision.
+//! Complex numbers are also supported, with their real and imaginary parts
+//! both being rationals, as are arrays of rationals.
+//!
+//! ```rust
+//! use calc::interpreter::{Interpreter, Rounding};
+//!
+//! let ast = calc::syntax::parse("0.1 + 0.2 + 10 / 3").unwrap();
+//! let got = Interpreter::new()
+//!     .with_precision(4)
+//!     .with_rounding(Rounding::HalfEven)
+//!     .evaluate(&ast)
+//!     .unwrap();
+//!
+//! assert_eq!(got.to_string(), "3.6333");
+//! ```
+
+use failure::Error;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+use std::cell::RefCell;
+use std::fmt::{self, Display, Formatter};
+
+use backend::{self, Backend};
+use builtins;
+use environment::Environment;
+use sema;
+use syntax::{
+    Array, Atom, BinaryOp, Cast, Expr, FunctionCall, Index, Integral, Let, Op, Series,
+    SeriesKind, Solve, Span, Type, UnaryOp, UnaryOperator,
+};
+
+/// How to round a result which has more decimal places than requested.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Rounding {
+    /// Round to the nearest value, with ties going to the even neighbour
+    /// (banker's rounding).
+    HalfEven,
+    /// Round to the nearest value, with ties going away from zero.
+    HalfUp,
+    /// Truncate, always rounding towards zero.
+    TowardsZero,
+    /// Always round away from zero.
+    AwayFromZero,
+    /// Round towards negative infinity.
+    Floor,
+    /// Round towards positive infinity.
+    Ceiling,
+}
+
+impl Default for Rounding {
+    fn default() -> Rounding {
+        Rounding::HalfEven
+    }
+}
+
+/// An interpreter which evaluates expressions using exact arithmetic.
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    precision: u32,
+    rounding: Rounding,
+    env: Environment,
+    /// The value of each enclosing sum, product or integral's variable.
+    variables: RefCell<Vec<(String, Value)>>,
+}
+
+impl Interpreter {
+    /// Create an interpreter which rounds results to 10 decimal places
+    /// using banker's rounding.
+    pub fn new() -> Interpreter {
+        Interpreter {
+            precision: 10,
+            rounding: Rounding::default(),
+            env: Environment::new(),
+            variables: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The number of decimal places results are rounded to.
+    pub fn with_precision(mut self, decimal_places: u32) -> Interpreter {
+        self.precision = decimal_places;
+        self
+    }
+
+    /// How results are rounded.
+    pub fn with_rounding(mut self, rounding: Rounding) -> Interpreter {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Resolve constants using an `Environment`.
+    ///
+    /// Host functions can't be evaluated exactly, so calling one is an
+    /// error.
+    pub fn with_environment(mut self, env: &Environment) -> Interpreter {
+        self.env = env.clone();
+        self
+    }
+
+    /// Type check and evaluate an expression which gives a real number.
+    ///
+    /// Rounding is only applied to the final result. Intermediate values are
+    /// always exact.
+    pub fn evaluate(&self, ast: &Expr) -> Result<Decimal, Error> {
+        let (ast, ty) = sema::type_check(ast, &self.env)?;
+        if ty == Type::Complex {
+            return Err(EvalError::ComplexResult { span: ast.span() }.into());
+        }
+
+        let (re, _) = self.eval(&ast)?.into_complex();
+        Ok(Decimal::round(&re, self.precision, self.rounding))
+    }
+
+    /// Type check and evaluate an expression, returning the real and
+    /// imaginary parts of the result.
+    pub fn evaluate_complex(&self, ast: &Expr) -> Result<(Decimal, Decimal), Error> {
+        let (ast, _) = sema::type_check(ast, &self.env)?;
+
+        let (re, im) = self.eval(&ast)?.into_complex();
+        Ok((
+            Decimal::round(&re, self.precision, self.rounding),
+            Decimal::round(&im, self.precision, self.rounding),
+        ))
+    }
+
+    fn eval(&self, expr: &Expr) -> Result<Value, EvalError> {
+        backend::lower(self, expr)
+    }
+
+    fn eval_number(&self, expr: &Expr) -> Result<BigRational, EvalError> {
+        self.eval(expr).map(Value::into_number)
+    }
+
+    fn eval_binary_op(&self, op: &BinaryOp, left: Value, right: Value) -> Result<Value, EvalError> {
+        match (left, right) {
+            (Value::Number(l), Value::Number(r)) => real_binary_op(op, l, r),
+            (Value::Complex(a, b), Value::Complex(c, d)) => complex_binary_op(op, (a, b), (c, d)),
+            (Value::Bool(l), Value::Bool(r)) => Ok(Value::Bool(match op.op {
+                Op::Equal => l == r,
+                _ => l != r,
+            })),
+            (left @ Value::Array(_), right) | (left, right @ Value::Array(_)) => {
+                array_binary_op(op, left, right)
+            }
+            _ => unreachable!("The type checker ensures both operands have the same type"),
+        }
+    }
+
+    fn eval_index(&self, index: &Index, array: Value, position: Value) -> Result<Value, EvalError> {
+        let elements = match array {
+            Value::Array(elements) => elements,
+            _ => unreachable!("The type checker ensures only arrays are indexed"),
+        };
+        let position = position.into_number().to_integer();
+        let length = elements.len();
+
+        position
+            .to_usize()
+            .and_then(|i| elements.into_iter().nth(i))
+            .map(Value::Number)
+            .ok_or_else(|| EvalError::IndexOutOfBounds {
+                index: position,
+                length,
+                span: index.span,
+            })
+    }
+
+    /// The variable is always an integer, so the terms are just as exact as
+    /// everything else.
+    fn eval_series<F>(
+        &self,
+        series: &Series,
+        start: Value,
+        end: Value,
+        mut term: F,
+    ) -> Result<Value, EvalError>
+    where
+        F: FnMut(Value) -> Result<Value, EvalError>,
+    {
+        let start = bound(start, &series.start)?;
+        let end = bound(end, &series.end)?;
+
+        let mut total = match series.kind {
+            SeriesKind::Sum => BigRational::zero(),
+            SeriesKind::Product => BigRational::one(),
+        };
+
+        for i in start..=end {
+            let i = Value::Number(BigRational::from_integer(BigInt::from(i)));
+            let term = term(i)?.into_number();
+            total = match series.kind {
+                SeriesKind::Sum => total + term,
+                SeriesKind::Product => total * term,
+            };
+        }
+
+        Ok(Value::Number(total))
+    }
+
+    /// The integrand is evaluated exactly at each point, but the integral
+    /// itself is only accurate to a couple of decimal places past the
+    /// requested precision.
+    fn eval_integral(&self, integral: &Integral) -> Result<Value, EvalError> {
+        let a = self.eval_number(&integral.lower)?;
+        let b = self.eval_number(&integral.upper)?;
+        let f = |x: &BigRational| self.eval_integrand(integral, x);
+
+        let (fa, fb) = (f(&a)?, f(&b)?);
+        let whole = Panel::new(&f, a, b, fa, fb)?;
+        let tolerance = BigRational::new(BigInt::one(), pow10(self.precision + 2));
+
+        let mut evaluations = 3;
+        let value = adaptive_simpson(
+            &f,
+            &whole,
+            tolerance,
+            MAX_SUBDIVISIONS,
+            &mut evaluations,
+            integral.span,
+        )?;
+        Ok(Value::Number(value))
+    }
+
+    fn eval_integrand(
+        &self,
+        integral: &Integral,
+        x: &BigRational,
+    ) -> Result<BigRational, EvalError> {
+        self.variables
+            .borrow_mut()
+            .push((integral.variable.clone(), Value::Number(x.clone())));
+        let value = self.eval_number(&integral.body);
+        self.variables.borrow_mut().pop();
+
+        value
+    }
+
+    /// Only the built-in functions with an exact implementation are
+    /// supported. For complex numbers, that's `re()`, `im()` and `conj()`.
+    fn eval_function_call(
+        &self,
+        call: &FunctionCall,
+        args: Vec<Value>,
+    ) -> Result<Value, EvalError> {
+        let no_exact_implementation = || EvalError::NoExactImplementation {
+            function: call.name.clone(),
+            span: call.span,
+        };
+
+        if self.env.function(&call.name).is_some() {
+            return Err(no_exact_implementation());
+        }
+        if builtins::reduction(&call.name, call.arguments.len()).is_some() {
+            return Ok(eval_reduction(call, args));
+        }
+
+        let builtin = builtins::lookup(&call.name)
+            .expect("The type checker ensures only known functions are called");
+
+        let mut numbers = Vec::new();
+        for arg in args {
+            match arg {
+                Value::Number(n) => numbers.push(n),
+                Value::Complex(re, im) => {
+                    return match builtin.name {
+                        "re" => Ok(Value::Number(re)),
+                        "im" => Ok(Value::Number(im)),
+                        "conj" => Ok(Value::Complex(re, -im)),
+                        _ => Err(no_exact_implementation()),
+                    }
+                }
+                Value::Bool(_) | Value::Array(_) => {
+                    unreachable!("The type checker ensures this is a number")
+                }
+            }
+        }
+
+        let value = match builtin.name {
+            "abs" => numbers[0].abs(),
+            "floor" => numbers[0].floor(),
+            "ceil" => numbers[0].ceil(),
+            "round" => numbers[0].round(),
+            "min" => numbers[0].clone().min(numbers[1].clone()),
+            "max" => numbers[0].clone().max(numbers[1].clone()),
+            "pow" => pow(&numbers[0], &numbers[1], call)?,
+            _ => return Err(no_exact_implementation()),
+        };
+
+        Ok(Value::Number(value))
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+/// Evaluating is lowering an expression straight to its value.
+impl Backend for Interpreter {
+    type Value = Value;
+    type Error = EvalError;
+
+    fn scope(&self) -> &RefCell<Vec<(String, Value)>> {
+        &self.variables
+    }
+
+    fn atom(&self, atom: &Atom, span: Span) -> Result<Value, EvalError> {
+        eval_atom(atom, span)
+    }
+
+    fn binary_op(&self, op: &BinaryOp, left: Value, right: Value) -> Result<Value, EvalError> {
+        self.eval_binary_op(op, left, right)
+    }
+
+    fn unary_op(&self, op: &UnaryOp, value: Value) -> Result<Value, EvalError> {
+        match (op.op, value) {
+            (UnaryOperator::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+            _ => unreachable!("The type checker ensures this is a boolean"),
+        }
+    }
+
+    /// Integers and floats are both rationals, so only promoting to a
+    /// complex number does anything.
+    fn cast(&self, cast: &Cast, value: Value) -> Result<Value, EvalError> {
+        match (value, cast.to) {
+            (Value::Number(n), Type::Complex) => Ok(Value::Complex(n, BigRational::zero())),
+            (value, _) => Ok(value),
+        }
+    }
+
+    fn call(&self, call: &FunctionCall, args: Vec<Value>) -> Result<Value, EvalError> {
+        self.eval_function_call(call, args)
+    }
+
+    fn conditional<T, F>(
+        &self,
+        condition: Value,
+        if_true: T,
+        if_false: F,
+    ) -> Result<Value, EvalError>
+    where
+        T: FnOnce() -> Result<Value, EvalError>,
+        F: FnOnce() -> Result<Value, EvalError>,
+    {
+        match condition {
+            Value::Bool(true) => if_true(),
+            Value::Bool(false) => if_false(),
+            _ => unreachable!("The type checker ensures this is a boolean"),
+        }
+    }
+
+    fn series<F>(
+        &self,
+        series: &Series,
+        start: Value,
+        end: Value,
+        term: F,
+    ) -> Result<Value, EvalError>
+    where
+        F: FnMut(Value) -> Result<Value, EvalError>,
+    {
+        self.eval_series(series, start, end, term)
+    }
+
+    fn bind<F>(&self, _: &Let, value: Value, body: F) -> Result<Value, EvalError>
+    where
+        F: FnOnce(Value) -> Result<Value, EvalError>,
+    {
+        body(value)
+    }
+
+    fn array(&self, _: &Array, elements: Vec<Value>) -> Result<Value, EvalError> {
+        Ok(Value::Array(
+            elements.into_iter().map(Value::into_number).collect(),
+        ))
+    }
+
+    fn index(&self, index: &Index, array: Value, position: Value) -> Result<Value, EvalError> {
+        self.eval_index(index, array, position)
+    }
+
+    fn integral(&self, integral: &Integral) -> Result<Value, EvalError> {
+        self.eval_integral(integral)
+    }
+
+    /// The solution is almost never a rational number.
+    fn solve(&self, solve: &Solve) -> Result<Value, EvalError> {
+        Err(EvalError::NoExactImplementation {
+            function: String::from("solve"),
+            span: solve.span,
+        })
+    }
+}
+
+/// The exact value of an expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// An integer or real number.
+    Number(BigRational),
+    /// A complex number's real and imaginary parts.
+    Complex(BigRational, BigRational),
+    /// A boolean.
+    Bool(bool),
+    /// An array of real numbers.
+    Array(Vec<BigRational>),
+}
+
+impl Value {
+    fn into_number(self) -> BigRational {
+        match self {
+            Value::Number(n) => n,
+            _ => unreachable!("The type checker ensures this is a real number"),
+        }
+    }
+
+    /// Get the value as a complex number, treating booleans as `0` or `1`.
+    fn into_complex(self) -> (BigRational, BigRational) {
+        match self {
+            Value::Number(n) => (n, BigRational::zero()),
+            Value::Complex(re, im) => (re, im),
+            Value::Bool(b) => {
+                let n = if b {
+                    BigRational::one()
+                } else {
+                    BigRational::zero()
+                };
+                (n, BigRational::zero())
+            }
+            Value::Array(_) => unreachable!("The type checker doesn't allow array results"),
+        }
+    }
+}
+
+fn eval_reduction(call: &FunctionCall, args: Vec<Value>) -> Value {
+    let arrays: Vec<Vec<BigRational>> = args
+        .into_iter()
+        .map(|arg| match arg {
+            Value::Array(elements) => elements,
+            _ => unreachable!("The type checker ensures reductions are given arrays"),
+        })
+        .collect();
+
+    let first = &arrays[0];
+    let sum = |values: Vec<BigRational>| {
+        values
+            .into_iter()
+            .fold(BigRational::zero(), |acc, x| acc + x)
+    };
+
+    let value = match call.name.as_str() {
+        "sum" => sum(first.clone()),
+        "mean" => sum(first.clone()) / BigRational::from_integer(BigInt::from(first.len())),
+        "min" => first.iter().min().cloned().expect("Arrays are never empty"),
+        "max" => first.iter().max().cloned().expect("Arrays are never empty"),
+        "dot" => sum(first.iter().zip(&arrays[1]).map(|(a, b)| a * b).collect()),
+        other => unreachable!("Unknown reduction, {}", other),
+    };
+
+    Value::Number(value)
+}
+
+/// Bounds are limited to 64-bit integers, like they would be in compiled
+/// code.
+fn bound(value: Value, bound: &Expr) -> Result<i64, EvalError> {
+    let value = value.into_number().to_integer();
+
+    match value.to_i64() {
+        Some(i) => Ok(i),
+        None => Err(EvalError::BoundOutOfRange {
+            bound: value,
+            span: bound.span(),
+        }),
+    }
+}
+
+fn real_binary_op(
+    op: &BinaryOp,
+    left: BigRational,
+    right: BigRational,
+) -> Result<Value, EvalError> {
+    let value = match op.op {
+        Op::Add => left + right,
+        Op::Subtract => left - right,
+        Op::Multiply => left * right,
+        Op::Divide | Op::IntegerDivide | Op::Modulo if right.is_zero() => {
+            return Err(EvalError::DivideByZero { span: op.span })
+        }
+        Op::Divide => left / right,
+        // integer operations truncate towards zero, like `i64` does
+        Op::IntegerDivide => (left / right).trunc(),
+        Op::Modulo => {
+            let quotient = (&left / &right).trunc();
+            left - quotient * right
+        }
+        Op::LessThan => return Ok(Value::Bool(left < right)),
+        Op::LessThanOrEqual => return Ok(Value::Bool(left <= right)),
+        Op::Equal => return Ok(Value::Bool(left == right)),
+        Op::NotEqual => return Ok(Value::Bool(left != right)),
+        Op::GreaterThan => return Ok(Value::Bool(left > right)),
+        Op::GreaterThanOrEqual => return Ok(Value::Bool(left >= right)),
+        Op::And | Op::Or => unreachable!(),
+    };
+
+    Ok(Value::Number(value))
+}
+
+/// Element-wise arithmetic, where a number is combined with every element
+/// of the array.
+fn array_binary_op(op: &BinaryOp, left: Value, right: Value) -> Result<Value, EvalError> {
+    let pairs: Vec<(BigRational, BigRational)> = match (left, right) {
+        (Value::Array(l), Value::Array(r)) => l.into_iter().zip(r).collect(),
+        (Value::Array(l), Value::Number(r)) => l.into_iter().map(|a| (a, r.clone())).collect(),
+        (Value::Number(l), Value::Array(r)) => r.into_iter().map(|b| (l.clone(), b)).collect(),
+        _ => unreachable!("The type checker only allows arrays to be combined with numbers"),
+    };
+
+    let mut elements = Vec::new();
+    for (a, b) in pairs {
+        match real_binary_op(op, a, b)? {
+            Value::Number(n) => elements.push(n),
+            _ => unreachable!("The type checker only allows arithmetic on arrays"),
+        }
+    }
+
+    Ok(Value::Array(elements))
+}
+
+type Complex = (BigRational, BigRational);
+
+fn complex_binary_op(op: &BinaryOp, left: Complex, right: Complex) -> Result<Value, EvalError> {
+    let ((a, b), (c, d)) = (left, right);
+
+    let (re, im) = match op.op {
+        Op::Add => (a + c, b + d),
+        Op::Subtract => (a - c, b - d),
+        Op::Multiply => (&a * &c - &b * &d, a * d + b * c),
+        Op::Divide => {
+            let denominator = &c * &c + &d * &d;
+            if denominator.is_zero() {
+                return Err(EvalError::DivideByZero { span: op.span });
+            }
+            (
+                (&a * &c + &b * &d) / &denominator,
+                (b * c - a * d) / denominator,
+            )
+        }
+        Op::Equal => return Ok(Value::Bool(a == c && b == d)),
+        Op::NotEqual => return Ok(Value::Bool(a != c || b != d)),
+        _ => unreachable!(
+            "The type checker doesn't allow {} on complex numbers",
+            op.op
+        ),
+    };
+
+    Ok(Value::Complex(re, im))
+}
+
+/// How many times an integral's range may be halved before we give up.
+const MAX_SUBDIVISIONS: u32 = 50;
+/// How many times an integrand may be evaluated before we give up, so an
+/// integrand which needs subdividing everywhere doesn't take forever.
+const MAX_EVALUATIONS: usize = 1 << 14;
+
+/// Part of an integral's range, with the integrand evaluated at each end
+/// and the midpoint.
+struct Panel {
+    a: BigRational,
+    b: BigRational,
+    fa: BigRational,
+    fm: BigRational,
+    fb: BigRational,
+    /// Simpson's rule applied to the panel.
+    estimate: BigRational,
+}
+
+impl Panel {
+    fn new<F>(
+        f: &F,
+        a: BigRational,
+        b: BigRational,
+        fa: BigRational,
+        fb: BigRational,
+    ) -> Result<Panel, EvalError>
+    where
+        F: Fn(&BigRational) -> Result<BigRational, EvalError>,
+    {
+        let fm = f(&midpoint(&a, &b))?;
+        let weighted = &fa + &fm * BigRational::from_integer(BigInt::from(4)) + &fb;
+        let estimate = (&b - &a) * weighted / BigRational::from_integer(BigInt::from(6));
+
+        Ok(Panel {
+            a,
+            b,
+            fa,
+            fm,
+            fb,
+            estimate,
+        })
+    }
+
+    fn split<F>(&self, f: &F) -> Result<(Panel, Panel), EvalError>
+    where
+        F: Fn(&BigRational) -> Result<BigRational, EvalError>,
+    {
+        let m = midpoint(&self.a, &self.b);
+        let left = Panel::new(f, self.a.clone(), m.clone(), self.fa.clone(), self.fm.clone())?;
+        let right = Panel::new(f, m, self.b.clone(), self.fm.clone(), self.fb.clone())?;
+
+        Ok((left, right))
+    }
+}
+
+fn midpoint(a: &BigRational, b: &BigRational) -> BigRational {
+    (a + b) / BigRational::from_integer(BigInt::from(2))
+}
+
+/// Keep halving a panel until Simpson's rule gives (almost) the same answer
+/// for both halves as it did for the whole.
+///
+/// `evaluations` counts how many times the integrand has been evaluated for
+/// the whole integral.
+fn adaptive_simpson<F>(
+    f: &F,
+    panel: &Panel,
+    tolerance: BigRational,
+    depth: u32,
+    evaluations: &mut usize,
+    span: Span,
+) -> Result<BigRational, EvalError>
+where
+    F: Fn(&BigRational) -> Result<BigRational, EvalError>,
+{
+    // splitting evaluates the integrand at the middle of each half
+    *evaluations += 2;
+    if *evaluations > MAX_EVALUATIONS {
+        return Err(EvalError::IntegralDidNotConverge { span });
+    }
+
+    let (left, right) = panel.split(f)?;
+    let difference = &left.estimate + &right.estimate - &panel.estimate;
+    let fifteen = BigRational::from_integer(BigInt::from(15));
+
+    if difference.abs() <= &tolerance * &fifteen {
+        // the difference also tells us roughly how far off the halves are
+        return Ok(left.estimate + right.estimate + difference / fifteen);
+    }
+    if depth == 0 {
+        return Err(EvalError::IntegralDidNotConverge { span });
+    }
+
+    let tolerance = tolerance / BigRational::from_integer(BigInt::from(2));
+    let left = adaptive_simpson(f, &left, tolerance.clone(), depth - 1, evaluations, span)?;
+    let right = adaptive_simpson(f, &right, tolerance, depth - 1, evaluations, span)?;
+
+    Ok(left + right)
+}
+
+fn eval_atom(atom: &Atom, span: Span) -> Result<Value, EvalError> {
+    match *atom {
+        Atom::Integer(i) => Ok(Value::Number(BigRational::from_integer(BigInt::from(i)))),
+        // infinity and NaN can come from constants or unit conversions
+        Atom::Number(n) | Atom::Imaginary(n) if !n.is_finite() => {
+            Err(EvalError::NonFiniteNumber { span })
+        }
+        Atom::Number(n) => Ok(Value::Number(decimal_to_rational(n))),
+        Atom::Imaginary(n) => Ok(Value::Complex(BigRational::zero(), decimal_to_rational(n))),
+        Atom::Boolean(b) => Ok(Value::Bool(b)),
+        Atom::Ident(ref name) => Err(EvalError::UnknownIdentifier {
+            name: name.clone(),
+            span,
+        }),
+    }
+}
+
+/// Convert a float to the decimal number it was most likely written as.
+///
+/// The lexer has already parsed literals into `f64`s, so we recover the
+/// shortest decimal representation which round-trips (i.e. `0.1` instead of
+/// `0.1000000000000000055511151231257827...`).
+fn decimal_to_rational(n: f64) -> BigRational {
+    let repr = n.abs().to_string();
+    let mut parts = repr.splitn(2, '.');
+    let whole = parts.next().unwrap_or("0");
+    let fraction = parts.next().unwrap_or("");
+
+    let digits: BigInt = format!("{}{}", whole, fraction)
+        .parse()
+        .expect("A finite float is always printed as digits");
+    let value = BigRational::new(digits, pow10(fraction.len() as u32));
+
+    if n.is_sign_negative() {
+        -value
+    } else {
+        value
+    }
+}
+
+/// The most bits `pow()` will put in the numerator or denominator of its
+/// result, about 300,000 decimal digits.
+const MAX_POW_BITS: u64 = 1 << 20;
+
+/// Raise to an integer power. Fractional exponents (which may give an
+/// irrational result) and exponents too large to compute are an error.
+fn pow(
+    base: &BigRational,
+    exponent: &BigRational,
+    call: &FunctionCall,
+) -> Result<BigRational, EvalError> {
+    if !exponent.is_integer() {
+        return Err(EvalError::NoExactImplementation {
+            function: call.name.clone(),
+            span: call.span,
+        });
+    }
+
+    let too_large = EvalError::ResultTooLarge { span: call.span };
+    let exponent = exponent.to_integer().to_i64().ok_or_else(|| too_large.clone())?;
+    let magnitude = exponent.checked_abs().ok_or_else(|| too_large.clone())? as u64;
+
+    // 0, 1 and -1 stay the same size however many times they're multiplied
+    if !base.is_zero() && !base.abs().is_one() {
+        let bits = base.numer().bits().max(base.denom().bits()) as u64;
+        match bits.checked_mul(magnitude) {
+            Some(size) if size <= MAX_POW_BITS => {}
+            _ => return Err(too_large),
+        }
+    }
+
+    let value = ::num_traits::pow(base.clone(), magnitude as usize);
+
+    if exponent >= 0 {
+        Ok(value)
+    } else if value.is_zero() {
+        Err(EvalError::DivideByZero { span: call.span })
+    } else {
+        Ok(value.recip())
+    }
+}
+
+fn pow10(exponent: u32) -> BigInt {
+    let ten = BigInt::from(10);
+    (0..exponent).fold(BigInt::one(), |acc, _| acc * &ten)
+}
+
+/// The result of an exact calculation, rounded to a fixed number of decimal
+/// places.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decimal {
+    digits: BigInt,
+    decimal_places: u32,
+}
+
+impl Decimal {
+    fn round(value: &BigRational, decimal_places: u32, rounding: Rounding) -> Decimal {
+        let scaled = value * BigRational::from_integer(pow10(decimal_places));
+        let truncated = scaled.trunc();
+        let remainder = (&scaled - &truncated).abs();
+        let half = BigRational::new(BigInt::one(), BigInt::from(2));
+
+        let away_from_zero = !remainder.is_zero()
+            && match rounding {
+                Rounding::TowardsZero => false,
+                Rounding::AwayFromZero => true,
+                Rounding::Floor => scaled.is_negative(),
+                Rounding::Ceiling => scaled.is_positive(),
+                Rounding::HalfUp => remainder >= half,
+                Rounding::HalfEven => {
+                    let is_odd = !(truncated.to_integer() % BigInt::from(2)).is_zero();
+                    remainder > half || (remainder == half && is_odd)
+                }
+            };
+
+        let mut digits = truncated.to_integer();
+        if away_from_zero {
+            digits = digits + scaled.signum().to_integer();
+        }
+
+        Decimal {
+            digits,
+            decimal_places,
+        }
+    }
+
+    /// The number of digits after the decimal point.
+    pub fn decimal_places(&self) -> u32 {
+        self.decimal_places
+    }
+
+    /// The exact value as a fraction.
+    pub fn to_rational(&self) -> BigRational {
+        BigRational::new(self.digits.clone(), pow10(self.decimal_places))
+    }
+
+    /// The nearest `f64`.
+    pub fn to_f64(&self) -> f64 {
+        self.to_string()
+            .parse()
+            .expect("Decimals are always formatted as valid floats")
+    }
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let places = self.decimal_places as usize;
+        let digits = self.digits.abs().to_string();
+        let digits = format!("{:0>width$}", digits, width = places + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - places);
+
+        if self.digits.is_negative() {
+            write!(f, "-")?;
+        }
+
+        if fraction.is_empty() {
+            write!(f, "{}", whole)
+        } else {
+            write!(f, "{}.{}", whole, fraction)
+        }
+    }
+}
+
+/// Errors which can happen while evaluating an expression exactly.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum EvalError {
+    /// Division or modulo by zero.
+    #[fail(display = "Division by zero at {}", span)]
+    DivideByZero {
+        /// The division.
+        span: Span,
+    },
+    /// The function can't be evaluated without losing precision.
+    #[fail(display = "\"{}\" has no exact implementation (at {})", function, span)]
+    NoExactImplementation {
+        /// The function's name.
+        function: String,
+        /// The function call.
+        span: Span,
+    },
+    /// Calling `evaluate()` on an expression which gives a complex number.
+    #[fail(
+        display = "The expression at {} is complex, use evaluate_complex() instead",
+        span
+    )]
+    ComplexResult {
+        /// The expression.
+        span: Span,
+    },
+    /// Indexing past the end of an array.
+    #[fail(
+        display = "Index {} is out of bounds for an array of length {} at {}",
+        index, length, span
+    )]
+    IndexOutOfBounds {
+        /// The index.
+        index: BigInt,
+        /// The array's length.
+        length: usize,
+        /// The indexing operation.
+        span: Span,
+    },
+    /// The bound of a sum or product doesn't fit in a 64-bit integer.
+    #[fail(display = "The bound {} is too big (at {})", bound, span)]
+    BoundOutOfRange {
+        /// The bound's value.
+        bound: BigInt,
+        /// Where the bound is.
+        span: Span,
+    },
+    /// A result with too many digits to calculate exactly (e.g.
+    /// `pow(10, 1000000000)`).
+    #[fail(display = "The result at {} is too large to calculate exactly", span)]
+    ResultTooLarge {
+        /// The expression.
+        span: Span,
+    },
+    /// An integral which couldn't be approximated accurately enough (e.g.
+    /// because the integrand has a singularity).
+    #[fail(display = "The integral at {} didn't converge", span)]
+    IntegralDidNotConverge {
+        /// The integral.
+        span: Span,
+    },
+    /// Infinity or `NaN`, which have no exact value.
+    #[fail(display = "The number at {} isn't finite", span)]
+    NonFiniteNumber {
+        /// The number.
+        span: Span,
+    },
+    /// An identifier which wasn't resolved by the type checker.
+    #[fail(display = "Unknown identifier, \"{}\" at {}", name, span)]
+    UnknownIdentifier {
+        /// The identifier.
+        name: String,
+        /// Where it was used.
+        span: Span,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntax;
+
+    fn evaluate(src: &str, decimal_places: u32, rounding: Rounding) -> Result<Decimal, Error> {
+        let ast = syntax::parse(src).unwrap();
+        Interpreter::new()
+            .with_precision(decimal_places)
+            .with_rounding(rounding)
+            .evaluate(&ast)
+    }
+
+    #[test]
+    fn decimal_arithmetic_is_exact() {
+        let inputs = vec![
+            ("0.1 + 0.2", "0.30"),
+            ("1 / 3 * 3", "1.00"),
+            ("9223372036854775807 + 1", "9223372036854775808.00"),
+            ("100.10 - 0.01", "100.09"),
+            ("7 // 2 + 7 % 2 + (0 - 7) // 2", "1.00"),
+            ("if 0.1 + 0.2 == 0.3 then 1 else 0", "1.00"),
+            ("if 1 > 2 and 1 // 0 == 0 then 1 else 2", "2.00"),
+            ("pow(1.1, 2) + pow(2, 0 - 2)", "1.46"),
+            ("max(abs(0 - 2.5), floor(2.5)) + round(0.5)", "3.50"),
+            ("sum([0.1, 0.2] * 10 + 1)", "5.00"),
+            ("mean([1, 2, 4]) + max([1, 2] / 3)", "3.00"),
+            ("dot([1, 2], [3, 4]) - min([0.5, 0.25])", "10.75"),
+            ("[1, 2, 3][7 // 3]", "3.00"),
+            ("sum(k, 1, 99, 1 / (k * (k + 1)))", "0.99"),
+            ("prod(k, 1, 20, k) - 2432902008176640000", "0.00"),
+            ("sum(i, 1, 3, sum(i, 1, i, 0.1))", "0.60"),
+            ("sum(i, 3, 1, i) + prod(i, 3, 1, i)", "1.00"),
+            ("sum(map(x -> x * x, [0.1, 0.2]))", "0.05"),
+            ("sum(map(x -> x * x, [1, 2] * 3))", "45.00"),
+            ("sum(map(x -> x * x, map(y -> y + y, [1, 2] + 0.5)))", "34.00"),
+            ("sum(map(x -> x^2, [1, 2] * 3)) + 2^3^2 - 2 * 3^2", "539.00"),
+            ("integrate(x -> x^2, 0, 3) + 1.5 m^2 / 1 m^2", "10.50"),
+            ("integrate(x * x, x, 0, 3)", "9.00"),
+            ("integrate(t -> 1 / t, 1, 2)", "0.69"),
+            ("integrate(x, x, 0, 1) + integrate(abs(x - 1), x, 0, 2)", "1.50"),
+            ("sum(k, 1, 3, integrate(x * k, x, 1, 0))", "-3.00"),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = evaluate(src, 2, Rounding::HalfEven).unwrap();
+            assert_eq!(got.to_string(), should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn complex_arithmetic_is_exact() {
+        let inputs = vec![
+            ("(1 + 2i) * (3 - 1i)", ("5.00", "5.00")),
+            ("(1 + 1i) / (1 - 1i)", ("0.00", "1.00")),
+            ("conj(0.1 + 0.2i) + re(2i) + im(0.5i)", ("0.60", "-0.20")),
+            ("if 1i * 1i == 0 - 1 then 1 else 2", ("1.00", "0.00")),
+        ];
+
+        for (src, (re, im)) in inputs {
+            let ast = syntax::parse(src).unwrap();
+            let got = Interpreter::new()
+                .with_precision(2)
+                .evaluate_complex(&ast)
+                .unwrap();
+
+            assert_eq!(
+                (got.0.to_string(), got.1.to_string()),
+                (re.to_string(), im.to_string()),
+                "{}",
+                src
+            );
+        }
+
+        let err = evaluate("1 + 2i", 2, Rounding::HalfEven).unwrap_err();
+        match err.downcast::<EvalError>().unwrap() {
+            EvalError::ComplexResult { .. } => {}
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn results_are_rounded_to_the_requested_precision() {
+        let inputs = vec![
+            ("2.5", Rounding::HalfEven, "2"),
+            ("3.5", Rounding::HalfEven, "4"),
+            ("0 - 2.5", Rounding::HalfEven, "-2"),
+            ("2.5", Rounding::HalfUp, "3"),
+            ("0 - 2.5", Rounding::HalfUp, "-3"),
+            ("2.9", Rounding::TowardsZero, "2"),
+            ("2.1", Rounding::AwayFromZero, "3"),
+            ("0 - 2.1", Rounding::Floor, "-3"),
+            ("0 - 2.9", Rounding::Ceiling, "-2"),
+        ];
+
+        for (src, rounding, should_be) in inputs {
+            let got = evaluate(src, 0, rounding).unwrap();
+            assert_eq!(got.to_string(), should_be, "{} {:?}", src, rounding);
+        }
+
+        let got = evaluate("1 / 8", 2, Rounding::HalfEven).unwrap();
+        assert_eq!(got.to_string(), "0.12");
+        assert_eq!(got.to_f64(), 0.12);
+    }
+
+    #[test]
+    fn huge_powers_are_an_error() {
+        let inputs = vec![
+            "pow(10, 1000000000)",
+            "pow(2, 0 - 2147483648)",
+            "pow(1.5, 0 - 9223372036854775807 - 1)",
+            "pow(2, 9223372036854775807 * 2)",
+        ];
+
+        for src in inputs {
+            let err = evaluate(src, 2, Rounding::HalfEven).unwrap_err();
+            match err.downcast::<EvalError>() {
+                Ok(EvalError::ResultTooLarge { .. }) => {}
+                other => panic!("Unexpected result for {}: {:?}", src, other),
+            }
+        }
+
+        let got = evaluate("pow(0 - 1, 1000000001) + pow(2, 0 - 1000)", 2, Rounding::HalfEven);
+        assert_eq!(got.unwrap().to_string(), "-1.00");
+    }
+
+    #[test]
+    fn integrals_which_need_too_many_evaluations_are_an_error() {
+        // the number of panels grows with the fourth root of the precision
+        let src = "integrate(x * x * x * x * x * x, x, 0, 1)";
+        let err = evaluate(src, 30, Rounding::HalfEven).unwrap_err();
+
+        match err.downcast::<EvalError>() {
+            Ok(EvalError::IntegralDidNotConverge { span }) => assert_eq!(span.end, src.len()),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+        let got = evaluate(src, 4, Rounding::HalfEven);
+        assert_eq!(got.unwrap().to_string(), "0.1429");
+    }
+
+    #[test]
+    fn inexact_functions_are_an_error() {
+        let inputs = vec![
+            "sqrt(2)",
+            "sin(0)",
+            "pow(2, 0.5)",
+            "1 + ln(1)",
+            "abs(3 + 4i)",
+            "solve(x * x == 4, x, 1)",
+        ];
+
+        for src in inputs {
+            let err = evaluate(src, 2, Rounding::HalfEven).unwrap_err();
+            match err.downcast::<EvalError>() {
+                Ok(EvalError::NoExactImplementation { .. }) => {}
+                other => panic!("Unexpected result for {}: {:?}", src, other),
+            }
+        }
+    }
+
+    #[test]
+    fn non_finite_numbers_are_an_error() {
+        let mut env = Environment::new();
+        env.register_constant("nan", ::std::f64::NAN)
+            .register_constant("inf", ::std::f64::INFINITY);
+        let inputs = vec![("1 + nan", "nan"), ("2 * inf", "inf")];
+
+        for (src, text) in inputs {
+            let ast = syntax::parse(src).unwrap();
+            let err = Interpreter::new()
+                .with_environment(&env)
+                .evaluate(&ast)
+                .unwrap_err();
+
+            match err.downcast::<EvalError>() {
+                Ok(EvalError::NonFiniteNumber { span }) => {
+                    assert_eq!(&src[span.start..span.end], text, "{}", src)
+                }
+                other => panic!("Unexpected result for {}: {:?}", src, other),
+            }
+        }
+
+        // overflowing unit conversions are caught before evaluation
+        let err = evaluate("2 * 1e308 km", 2, Rounding::HalfEven).unwrap_err();
+        match err.downcast::<sema::TypeError>() {
+            Ok(sema::TypeError::Dimension(_)) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn indexing_past_the_end_of_an_array_is_an_error() {
+        let err = evaluate("[1, 2][3 - 1]", 2, Rounding::HalfEven).unwrap_err();
+
+        match err.downcast::<EvalError>().unwrap() {
+            EvalError::IndexOutOfBounds { index, length, .. } => {
+                assert_eq!((index, length), (BigInt::from(2), 2));
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        let err = evaluate("1 + 1 / (2 - 2)", 2, Rounding::HalfEven).unwrap_err();
+
+        match err.downcast::<EvalError>().unwrap() {
+            EvalError::DivideByZero { span } => assert_eq!((span.start, span.end), (4, 15)),
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+}
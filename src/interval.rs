@@ -0,0 +1,1064 @@
+//! Evaluate `calc` expressions over intervals to get guaranteed bounds on
+//! the result.
+//!
+//! Named inputs are given as a range of values (`[lo, hi]`) and every
+//! operation returns an interval which is guaranteed to contain every
+//! possible result. Because floating point operations round to the nearest
+//! representable number, results are widened outwards by one ULP wherever
+//! rounding may have happened.
+//!
+//! ```rust
+//! use calc::interval::{Interval, IntervalEvaluator};
+//!
+//! let ast = calc::syntax::parse("x * x - 2 * x").unwrap();
+//! let got = IntervalEvaluator::new()
+//!     .with_input("x", [1.0, 3.0])
+//!     .evaluate(&ast)
+//!     .unwrap();
+//!
+//! assert!(got.contains(-1.0) && got.contains(3.0));
+//! ```
+
+use failure::Error;
+use std::collections::HashMap;
+use std::f64::{self, consts};
+use std::fmt::{self, Display, Formatter};
+
+use builtins;
+use environment::Environment;
+use sema;
+use syntax::{
+    Atom, BinaryOp, Conditional, Expr, FunctionCall, Index, Integral, Let, Op, Series, SeriesKind,
+    Span, UnaryOp, UnaryOperator,
+};
+
+/// A closed range of real numbers, `[lo, hi]`. Either bound may be infinite.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Interval {
+    lo: f64,
+    hi: f64,
+}
+
+impl Interval {
+    /// Create a new `Interval`.
+    ///
+    /// A `NaN` bound (e.g. from `inf - inf`) could be anything, so it is
+    /// replaced with the matching infinity.
+    ///
+    /// # Panics
+    ///
+    /// The lower bound must not be greater than the upper bound.
+    pub fn new(lo: f64, hi: f64) -> Interval {
+        let lo = if lo.is_nan() { f64::NEG_INFINITY } else { lo };
+        let hi = if hi.is_nan() { f64::INFINITY } else { hi };
+        assert!(lo <= hi, "Invalid interval, [{}, {}]", lo, hi);
+        Interval { lo, hi }
+    }
+
+    /// An interval containing a single number.
+    pub fn point(value: f64) -> Interval {
+        Interval::new(value, value)
+    }
+
+    /// The interval containing every real number.
+    pub fn entire() -> Interval {
+        Interval::new(f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    /// The lower bound.
+    pub fn lo(&self) -> f64 {
+        self.lo
+    }
+
+    /// The upper bound.
+    pub fn hi(&self) -> f64 {
+        self.hi
+    }
+
+    /// The distance between the two bounds.
+    pub fn width(&self) -> f64 {
+        self.hi - self.lo
+    }
+
+    /// Is `value` inside this interval?
+    pub fn contains(&self, value: f64) -> bool {
+        self.lo <= value && value <= self.hi
+    }
+
+    /// The smallest interval containing both `self` and `other`.
+    pub fn hull(&self, other: Interval) -> Interval {
+        Interval::new(self.lo.min(other.lo), self.hi.max(other.hi))
+    }
+
+    /// Widen the interval by one ULP in each direction to account for
+    /// rounding errors.
+    fn widen(&self) -> Interval {
+        Interval::new(next_down(self.lo), next_up(self.hi))
+    }
+
+    /// Discard the part of the interval below `lo`, returning `None` if there
+    /// is nothing left.
+    fn clamp_below(&self, lo: f64) -> Option<Interval> {
+        if self.hi < lo {
+            None
+        } else {
+            Some(Interval::new(self.lo.max(lo), self.hi))
+        }
+    }
+
+    /// Apply a monotonically increasing function to both bounds.
+    fn increasing<F: Fn(f64) -> f64>(&self, func: F) -> Interval {
+        Interval::new(func(self.lo), func(self.hi))
+    }
+
+    fn add(&self, other: Interval) -> Interval {
+        Interval::new(self.lo + other.lo, self.hi + other.hi).widen()
+    }
+
+    fn sub(&self, other: Interval) -> Interval {
+        Interval::new(self.lo - other.hi, self.hi - other.lo).widen()
+    }
+
+    fn mul(&self, other: Interval) -> Interval {
+        let products = [
+            mul(self.lo, other.lo),
+            mul(self.lo, other.hi),
+            mul(self.hi, other.lo),
+            mul(self.hi, other.hi),
+        ];
+        bounds(&products).widen()
+    }
+
+    /// Division, where dividing by an interval containing zero gives the
+    /// smallest interval containing every possible quotient (which may be
+    /// unbounded). Returns `None` when the divisor is exactly zero.
+    fn div(&self, other: Interval) -> Option<Interval> {
+        if other.lo == 0.0 && other.hi == 0.0 {
+            return None;
+        }
+
+        if other.lo > 0.0 || other.hi < 0.0 {
+            let quotients = [
+                self.lo / other.lo,
+                self.lo / other.hi,
+                self.hi / other.lo,
+                self.hi / other.hi,
+            ];
+            return Some(bounds(&quotients).widen());
+        }
+
+        if self.contains(0.0) {
+            return Some(Interval::entire());
+        }
+
+        // the divisor touches zero from one side, so the quotient is
+        // unbounded in one direction
+        let got = if other.lo == 0.0 {
+            if self.lo > 0.0 {
+                Interval::new(self.lo / other.hi, f64::INFINITY)
+            } else {
+                Interval::new(f64::NEG_INFINITY, self.hi / other.hi)
+            }
+        } else if other.hi == 0.0 {
+            if self.lo > 0.0 {
+                Interval::new(f64::NEG_INFINITY, self.lo / other.lo)
+            } else {
+                Interval::new(self.hi / other.lo, f64::INFINITY)
+            }
+        } else {
+            Interval::entire()
+        };
+
+        Some(got.widen())
+    }
+
+    fn abs(&self) -> Interval {
+        if self.lo >= 0.0 {
+            *self
+        } else if self.hi <= 0.0 {
+            Interval::new(-self.hi, -self.lo)
+        } else {
+            Interval::new(0.0, self.hi.max(-self.lo))
+        }
+    }
+
+    /// `cos()` reaches its maximum at `2kπ` and its minimum at `π + 2kπ`, so
+    /// the bounds are either the value at an endpoint or one of those
+    /// extremes.
+    fn cos(&self) -> Interval {
+        if !self.lo.is_finite() || !self.hi.is_finite() || self.width() >= 2.0 * consts::PI {
+            return Interval::new(-1.0, 1.0);
+        }
+
+        let endpoints = [self.lo.cos(), self.hi.cos()];
+        let mut got = bounds(&endpoints).widen();
+
+        if self.contains_multiple_of_pi(0.0) {
+            got = got.hull(Interval::point(1.0));
+        }
+        if self.contains_multiple_of_pi(consts::PI) {
+            got = got.hull(Interval::point(-1.0));
+        }
+
+        Interval::new(got.lo.max(-1.0), got.hi.min(1.0))
+    }
+
+    fn sin(&self) -> Interval {
+        self.sub(Interval::point(consts::FRAC_PI_2)).cos()
+    }
+
+    /// Does the interval contain `offset + 2kπ` for some integer `k`? This
+    /// errs on the side of saying yes when the answer is borderline.
+    fn contains_multiple_of_pi(&self, offset: f64) -> bool {
+        let period = 2.0 * consts::PI;
+        let k = ((self.lo - offset) / period).floor();
+        let candidate = offset + k * period;
+
+        let tolerance = 1e-9 * (1.0 + self.lo.abs().max(self.hi.abs()));
+        candidate >= self.lo - tolerance || candidate + period <= self.hi + tolerance
+    }
+
+    /// Raise to a power. Integer exponents work for any base, otherwise the
+    /// base must be non-negative.
+    fn pow(&self, exponent: Interval) -> Option<Interval> {
+        if exponent.lo == exponent.hi && exponent.lo.fract() == 0.0 {
+            let n = exponent.lo;
+            let is_even = (n / 2.0).fract() == 0.0;
+
+            if n == 0.0 {
+                return Some(Interval::point(1.0));
+            } else if n < 0.0 {
+                return Interval::point(1.0).div(self.pow(Interval::point(-n))?);
+            } else if is_even {
+                return Some(self.abs().increasing(|x| x.powf(n)).widen());
+            } else {
+                return Some(self.increasing(|x| x.powf(n)).widen());
+            }
+        }
+
+        if self.lo < 0.0 {
+            return None;
+        }
+
+        // for a non-negative base, x^y is monotonic in each argument so the
+        // extremes are at the corners
+        let corners = [
+            self.lo.powf(exponent.lo),
+            self.lo.powf(exponent.hi),
+            self.hi.powf(exponent.lo),
+            self.hi.powf(exponent.hi),
+        ];
+        Some(bounds(&corners).widen())
+    }
+}
+
+impl From<[f64; 2]> for Interval {
+    fn from(other: [f64; 2]) -> Interval {
+        Interval::new(other[0], other[1])
+    }
+}
+
+impl From<f64> for Interval {
+    fn from(other: f64) -> Interval {
+        Interval::point(other)
+    }
+}
+
+impl Display for Interval {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "[{}, {}]", self.lo, self.hi)
+    }
+}
+
+/// Multiplication where `0 * inf` is `0`, as is conventional for interval
+/// arithmetic.
+fn mul(a: f64, b: f64) -> f64 {
+    if a == 0.0 || b == 0.0 {
+        0.0
+    } else {
+        a * b
+    }
+}
+
+fn bounds(values: &[f64]) -> Interval {
+    let lo = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let hi = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    Interval::new(lo, hi)
+}
+
+fn next_up(x: f64) -> f64 {
+    if x.is_nan() || x == f64::INFINITY {
+        x
+    } else if x == 0.0 {
+        f64::from_bits(1)
+    } else if x > 0.0 {
+        f64::from_bits(x.to_bits() + 1)
+    } else {
+        f64::from_bits(x.to_bits() - 1)
+    }
+}
+
+fn next_down(x: f64) -> f64 {
+    -next_up(-x)
+}
+
+/// Evaluates expressions over intervals.
+#[derive(Debug, Clone, Default)]
+pub struct IntervalEvaluator {
+    inputs: HashMap<String, Interval>,
+    env: Environment,
+}
+
+impl IntervalEvaluator {
+    /// Create a new `IntervalEvaluator` without any inputs.
+    pub fn new() -> IntervalEvaluator {
+        IntervalEvaluator::default()
+    }
+
+    /// Give a named input a range of values.
+    pub fn with_input<S, I>(mut self, name: S, value: I) -> IntervalEvaluator
+    where
+        S: Into<String>,
+        I: Into<Interval>,
+    {
+        self.inputs.insert(name.into(), value.into());
+        self
+    }
+
+    /// Resolve constants using an `Environment`.
+    ///
+    /// Nothing is known about how host functions behave, so calling one is
+    /// an error.
+    pub fn with_environment(mut self, env: &Environment) -> IntervalEvaluator {
+        self.env = env.clone();
+        self
+    }
+
+    /// Type check the expression, then find bounds for its result.
+    ///
+    /// Booleans are treated as `0` or `1`, so a condition which may go
+    /// either way evaluates to `[0, 1]`.
+    pub fn evaluate(&self, ast: &Expr) -> Result<Interval, Error> {
+        // inputs behave like float constants as far as the type checker is
+        // concerned
+        let mut env = self.env.clone();
+        for name in self.inputs.keys() {
+            env.register_constant(name.as_str(), 0.0);
+        }
+        sema::type_check(ast, &env)?;
+
+        // the inputs mustn't be inlined, so only inline lambdas and convert
+        // quantities to SI
+        let ast = sema::inline_lambdas(ast)?;
+        let (ast, _) = sema::check_dimensions(&ast)?;
+
+        let got = match self.eval(&ast)? {
+            Value::Number(n) => n,
+            Value::Bool(Truth::True) => Interval::point(1.0),
+            Value::Bool(Truth::False) => Interval::point(0.0),
+            Value::Bool(Truth::Unknown) => Interval::new(0.0, 1.0),
+            Value::Array(_) => unreachable!("The type checker doesn't allow array results"),
+        };
+
+        Ok(got)
+    }
+
+    fn eval(&self, expr: &Expr) -> Result<Value, IntervalError> {
+        match *expr {
+            Expr::Atom(ref atom, span) => self.eval_atom(atom, span),
+            Expr::BinaryOp(ref op) => self.eval_binary_op(op),
+            Expr::UnaryOp(ref op) => self.eval_unary_op(op),
+            Expr::Conditional(ref cond) => self.eval_conditional(cond),
+            Expr::FunctionCall(ref call) => self.eval_function_call(call).map(Value::Number),
+            // every number is already a float
+            Expr::Cast(ref cast) => self.eval(&cast.value),
+            Expr::UnitAnnotation(_) => unreachable!("Units are erased before evaluation"),
+            Expr::Array(ref array) => {
+                let mut elements = Vec::new();
+                for element in &array.elements {
+                    elements.push(self.eval_number(element)?);
+                }
+                Ok(Value::Array(elements))
+            }
+            Expr::Index(ref index) => self.eval_index(index),
+            Expr::Series(ref series) => self.eval_series(series).map(Value::Number),
+            Expr::Integral(ref integral) => self.eval_integral(integral).map(Value::Number),
+            Expr::Let(ref binding) => self.eval_let(binding),
+            Expr::Solve(ref solve) => Err(IntervalError::NoIntervalImplementation {
+                function: String::from("solve"),
+                span: solve.span,
+            }),
+            Expr::Lambda(_) => unreachable!("Lambdas are inlined before evaluation"),
+        }
+    }
+
+    fn eval_number(&self, expr: &Expr) -> Result<Interval, IntervalError> {
+        match self.eval(expr)? {
+            Value::Number(n) => Ok(n),
+            _ => unreachable!("The type checker ensures this is a number"),
+        }
+    }
+
+    fn eval_bool(&self, expr: &Expr) -> Result<Truth, IntervalError> {
+        match self.eval(expr)? {
+            Value::Bool(b) => Ok(b),
+            _ => unreachable!("The type checker ensures this is a boolean"),
+        }
+    }
+
+    /// Literals which may have been rounded when they were parsed, and
+    /// irrational constants, are widened so they contain the true value.
+    fn eval_atom(&self, atom: &Atom, span: Span) -> Result<Value, IntervalError> {
+        let value = match *atom {
+            Atom::Boolean(b) => return Ok(Value::Bool(Truth::from(b))),
+            // compare as i128, because converting back to i64 saturates
+            Atom::Integer(i) if (i as f64) as i128 == i128::from(i) => Interval::point(i as f64),
+            Atom::Integer(i) => Interval::point(i as f64).widen(),
+            Atom::Number(n) if n.fract() == 0.0 => Interval::point(n),
+            Atom::Number(n) => Interval::point(n).widen(),
+            Atom::Imaginary(_) => return Err(IntervalError::ComplexNumber { span }),
+            Atom::Ident(ref name) => {
+                if let Some(&input) = self.inputs.get(name) {
+                    input
+                } else if let Some(value) = self.env.constant(name) {
+                    Interval::point(value)
+                } else if let Some(constant) = builtins::constant(name) {
+                    Interval::point(constant.value).widen()
+                } else {
+                    return Err(IntervalError::UnknownIdentifier {
+                        name: name.clone(),
+                        span,
+                    });
+                }
+            }
+        };
+
+        Ok(Value::Number(value))
+    }
+
+    fn eval_binary_op(&self, op: &BinaryOp) -> Result<Value, IntervalError> {
+        if op.op.is_logical() {
+            let left = self.eval_bool(&op.left)?;
+            let right = self.eval_bool(&op.right)?;
+            let value = match op.op {
+                Op::And => left.and(right),
+                _ => left.or(right),
+            };
+            return Ok(Value::Bool(value));
+        }
+
+        let (left, right) = match (self.eval(&op.left)?, self.eval(&op.right)?) {
+            (Value::Number(l), Value::Number(r)) => (l, r),
+            (Value::Bool(l), Value::Bool(r)) => {
+                let equal = l.equals(r);
+                return Ok(Value::Bool(match op.op {
+                    Op::Equal => equal,
+                    _ => equal.not(),
+                }));
+            }
+            (left, right) => return array_binary_op(op, left, right),
+        };
+
+        if op.op.is_comparison() {
+            return Ok(Value::Bool(compare(op.op, left, right)));
+        }
+
+        Ok(Value::Number(arithmetic(op, left, right)?))
+    }
+
+    fn eval_unary_op(&self, op: &UnaryOp) -> Result<Value, IntervalError> {
+        match op.op {
+            UnaryOperator::Not => Ok(Value::Bool(self.eval_bool(&op.value)?.not())),
+        }
+    }
+
+    /// Any element the index may refer to could be the result, so take the
+    /// hull of them all. It's only an error when the index can't possibly be
+    /// inside the array.
+    fn eval_index(&self, index: &Index) -> Result<Value, IntervalError> {
+        let elements = match self.eval(&index.array)? {
+            Value::Array(elements) => elements,
+            _ => unreachable!("The type checker ensures only arrays are indexed"),
+        };
+        let position = self.eval_number(&index.index)?;
+
+        let first = position.lo.ceil().max(0.0);
+        let last = position.hi.floor().min(elements.len() as f64 - 1.0);
+        if first > last {
+            return Err(IntervalError::IndexOutOfBounds { span: index.span });
+        }
+
+        let candidates = &elements[first as usize..=last as usize];
+        let got = candidates
+            .iter()
+            .fold(candidates[0], |acc, &element| acc.hull(element));
+
+        Ok(Value::Number(got))
+    }
+
+    /// When we can't tell which branch will be taken, the result could come
+    /// from either.
+    fn eval_conditional(&self, cond: &Conditional) -> Result<Value, IntervalError> {
+        match self.eval_bool(&cond.condition)? {
+            Truth::True => self.eval(&cond.if_true),
+            Truth::False => self.eval(&cond.if_false),
+            Truth::Unknown => match (self.eval(&cond.if_true)?, self.eval(&cond.if_false)?) {
+                (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a.hull(b))),
+                (Value::Bool(a), Value::Bool(b)) => {
+                    Ok(Value::Bool(if a == b { a } else { Truth::Unknown }))
+                }
+                (Value::Array(a), Value::Array(b)) => Ok(Value::Array(
+                    a.iter().zip(&b).map(|(x, &y)| x.hull(y)).collect(),
+                )),
+                _ => unreachable!("The type checker ensures both branches have the same type"),
+            },
+        }
+    }
+
+    fn eval_function_call(&self, call: &FunctionCall) -> Result<Interval, IntervalError> {
+        if self.env.function(&call.name).is_some() {
+            return Err(IntervalError::NoIntervalImplementation {
+                function: call.name.clone(),
+                span: call.span,
+            });
+        }
+
+        if builtins::reduction(&call.name, call.arguments.len()).is_some() {
+            return self.eval_reduction(call);
+        }
+
+        let builtin = builtins::lookup(&call.name)
+            .expect("The type checker ensures only known functions are called");
+
+        let mut args = Vec::new();
+        for arg in &call.arguments {
+            args.push(self.eval_number(arg)?);
+        }
+
+        let domain_error = IntervalError::DomainError { span: call.span };
+        let arg = args[0];
+
+        let got = match builtin.name {
+            "sqrt" => arg
+                .clamp_below(0.0)
+                .ok_or(domain_error)?
+                .increasing(f64::sqrt)
+                .widen(),
+            "ln" | "log2" | "log10" => {
+                let log: fn(f64) -> f64 = match builtin.name {
+                    "ln" => f64::ln,
+                    "log2" => f64::log2,
+                    _ => f64::log10,
+                };
+                // the log of zero is negative infinity, which is the correct
+                // bound for arguments approaching zero
+                let arg = arg.clamp_below(0.0).ok_or(domain_error)?;
+                if arg.hi == 0.0 {
+                    return Err(IntervalError::DomainError { span: call.span });
+                }
+                arg.increasing(log).widen()
+            }
+            "exp" => arg.increasing(f64::exp).widen(),
+            "sin" => arg.sin(),
+            "cos" => arg.cos(),
+            "abs" => arg.abs(),
+            "floor" => arg.increasing(f64::floor),
+            "ceil" => arg.increasing(f64::ceil),
+            "round" => arg.increasing(f64::round),
+            "min" => Interval::new(arg.lo.min(args[1].lo), arg.hi.min(args[1].hi)),
+            "max" => Interval::new(arg.lo.max(args[1].lo), arg.hi.max(args[1].hi)),
+            "pow" => arg.pow(args[1]).ok_or(domain_error)?,
+            _ => {
+                return Err(IntervalError::NoIntervalImplementation {
+                    function: call.name.clone(),
+                    span: call.span,
+                })
+            }
+        };
+
+        Ok(got)
+    }
+
+    /// The terms are evaluated one at a time, so the number of terms needs
+    /// to be known exactly.
+    fn eval_series(&self, series: &Series) -> Result<Interval, IntervalError> {
+        let start = self.eval_bound(&series.start)?;
+        let end = self.eval_bound(&series.end)?;
+
+        let mut total = match series.kind {
+            SeriesKind::Sum => Interval::point(0.0),
+            SeriesKind::Product => Interval::point(1.0),
+        };
+
+        for i in start..=end {
+            let term = self.eval_number(&series.term(i))?;
+            total = match series.kind {
+                SeriesKind::Sum => total.add(term),
+                SeriesKind::Product => total.mul(term),
+            };
+        }
+
+        Ok(total)
+    }
+
+    /// Numbers are bound like an input, while anything else is substituted
+    /// into the body.
+    fn eval_let(&self, binding: &Let) -> Result<Value, IntervalError> {
+        match self.eval(&binding.value)? {
+            Value::Number(value) => {
+                let mut inner = self.clone();
+                inner.inputs.insert(binding.variable.clone(), value);
+                inner.eval(&binding.body)
+            }
+            _ => self.eval(&binding.inlined()),
+        }
+    }
+
+    /// An integral over `[a, b]` is somewhere between `b - a` times the
+    /// smallest and largest values of its integrand, so we split the range
+    /// into pieces and add up the bounds for each piece.
+    ///
+    /// When the bounds themselves are uncertain, the part of the range
+    /// which may or may not be included is bounded separately.
+    fn eval_integral(&self, integral: &Integral) -> Result<Interval, IntervalError> {
+        let lower = self.eval_number(&integral.lower)?;
+        let upper = self.eval_number(&integral.upper)?;
+        let integrand = |x: Interval| {
+            let mut inner = self.clone();
+            inner.inputs.insert(integral.variable.clone(), x);
+            inner.eval_number(&integral.body)
+        };
+
+        // the bounds overlap (or are backwards), so we can't split the range
+        if lower.hi() > upper.lo() {
+            return Ok(upper.sub(lower).mul(integrand(lower.hull(upper))?));
+        }
+
+        let mut total = Interval::point(0.0);
+        for &end in &[lower, upper] {
+            if end.width() > 0.0 {
+                let length = Interval::new(0.0, length(end.lo(), end.hi()).hi());
+                total = total.add(length.mul(integrand(end)?));
+            }
+        }
+
+        let (start, stop) = (lower.hi(), upper.lo());
+        let step = (stop - start) / INTEGRAL_PIECES as f64;
+        let mut left = start;
+
+        for i in 1..=INTEGRAL_PIECES {
+            let right = if i == INTEGRAL_PIECES {
+                stop
+            } else {
+                (start + step * i as f64).max(left).min(stop)
+            };
+
+            let piece = Interval::new(left, right);
+            total = total.add(length(left, right).mul(integrand(piece)?));
+            left = right;
+        }
+
+        Ok(total)
+    }
+
+    /// Bounds are always integers, so rounding errors don't matter as long
+    /// as there's only one integer the bound could be.
+    fn eval_bound(&self, bound: &Expr) -> Result<i64, IntervalError> {
+        let value = self.eval_number(bound)?;
+        let (lo, hi) = (value.lo().ceil(), value.hi().floor());
+
+        if lo == hi {
+            Ok(lo as i64)
+        } else {
+            Err(IntervalError::UncertainBound { span: bound.span() })
+        }
+    }
+
+    fn eval_reduction(&self, call: &FunctionCall) -> Result<Interval, IntervalError> {
+        let mut arrays = Vec::new();
+        for arg in &call.arguments {
+            match self.eval(arg)? {
+                Value::Array(elements) => arrays.push(elements),
+                _ => unreachable!("The type checker ensures reductions are given arrays"),
+            }
+        }
+
+        let first = &arrays[0];
+        let sum = |values: &[Interval]| {
+            values
+                .iter()
+                .fold(Interval::point(0.0), |acc, &x| acc.add(x))
+        };
+
+        let got = match call.name.as_str() {
+            "sum" => sum(first),
+            "mean" => sum(first)
+                .div(Interval::point(first.len() as f64))
+                .expect("Arrays are never empty"),
+            "min" => first.iter().fold(first[0], |acc, x| {
+                Interval::new(acc.lo.min(x.lo), acc.hi.min(x.hi))
+            }),
+            "max" => first.iter().fold(first[0], |acc, x| {
+                Interval::new(acc.lo.max(x.lo), acc.hi.max(x.hi))
+            }),
+            "dot" => {
+                let products: Vec<Interval> =
+                    first.iter().zip(&arrays[1]).map(|(a, &b)| a.mul(b)).collect();
+                sum(&products)
+            }
+            other => unreachable!("Unknown reduction, {}", other),
+        };
+
+        Ok(got)
+    }
+}
+
+/// Arithmetic on two numbers.
+fn arithmetic(op: &BinaryOp, left: Interval, right: Interval) -> Result<Interval, IntervalError> {
+    let divide_by_zero = IntervalError::DivideByZero { span: op.span };
+
+    let value = match op.op {
+        Op::Add => left.add(right),
+        Op::Subtract => left.sub(right),
+        Op::Multiply => left.mul(right),
+        Op::Divide => left.div(right).ok_or(divide_by_zero)?,
+        // truncation is monotonic, so truncate the bounds of the quotient
+        Op::IntegerDivide => left
+            .div(right)
+            .ok_or(divide_by_zero)?
+            .increasing(f64::trunc),
+        Op::Modulo => {
+            if right.lo == 0.0 && right.hi == 0.0 {
+                return Err(divide_by_zero);
+            }
+            // the remainder has the dividend's sign and is smaller in
+            // magnitude than both operands
+            let limit = right.abs().hi.min(left.abs().hi);
+            Interval::new(
+                if left.lo < 0.0 { -limit } else { 0.0 },
+                if left.hi > 0.0 { limit } else { 0.0 },
+            )
+        }
+        _ => unreachable!("{} isn't an arithmetic operator", op.op),
+    };
+
+    Ok(value)
+}
+
+/// Element-wise arithmetic, where a number is combined with every element
+/// of the array.
+fn array_binary_op(op: &BinaryOp, left: Value, right: Value) -> Result<Value, IntervalError> {
+    let pairs: Vec<(Interval, Interval)> = match (left, right) {
+        (Value::Array(l), Value::Array(r)) => l.into_iter().zip(r).collect(),
+        (Value::Array(l), Value::Number(r)) => l.into_iter().map(|a| (a, r)).collect(),
+        (Value::Number(l), Value::Array(r)) => r.into_iter().map(|b| (l, b)).collect(),
+        _ => unreachable!("The type checker ensures both operands have the same type"),
+    };
+
+    let mut elements = Vec::new();
+    for (a, b) in pairs {
+        elements.push(arithmetic(op, a, b)?);
+    }
+
+    Ok(Value::Array(elements))
+}
+
+/// A comparison is only definitely true (or false) if it holds for every
+/// pair of values from the two intervals.
+fn compare(op: Op, left: Interval, right: Interval) -> Truth {
+    let less = if left.hi < right.lo {
+        Truth::True
+    } else if left.lo >= right.hi {
+        Truth::False
+    } else {
+        Truth::Unknown
+    };
+    let greater = if left.lo > right.hi {
+        Truth::True
+    } else if left.hi <= right.lo {
+        Truth::False
+    } else {
+        Truth::Unknown
+    };
+    let equal = if left.lo == left.hi && left == right {
+        Truth::True
+    } else if left.hi < right.lo || right.hi < left.lo {
+        Truth::False
+    } else {
+        Truth::Unknown
+    };
+
+    match op {
+        Op::LessThan => less,
+        Op::LessThanOrEqual => greater.not(),
+        Op::Equal => equal,
+        Op::NotEqual => equal.not(),
+        Op::GreaterThan => greater,
+        Op::GreaterThanOrEqual => less.not(),
+        _ => unreachable!("{} isn't a comparison", op),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(Interval),
+    Bool(Truth),
+    Array(Vec<Interval>),
+}
+
+/// Three-valued logic, for when a comparison may go either way.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Truth {
+    True,
+    False,
+    Unknown,
+}
+
+impl Truth {
+    fn not(self) -> Truth {
+        match self {
+            Truth::True => Truth::False,
+            Truth::False => Truth::True,
+            Truth::Unknown => Truth::Unknown,
+        }
+    }
+
+    fn and(self, other: Truth) -> Truth {
+        match (self, other) {
+            (Truth::False, _) | (_, Truth::False) => Truth::False,
+            (Truth::True, Truth::True) => Truth::True,
+            _ => Truth::Unknown,
+        }
+    }
+
+    fn or(self, other: Truth) -> Truth {
+        self.not().and(other.not()).not()
+    }
+
+    fn equals(self, other: Truth) -> Truth {
+        match (self, other) {
+            (Truth::Unknown, _) | (_, Truth::Unknown) => Truth::Unknown,
+            (a, b) => Truth::from(a == b),
+        }
+    }
+}
+
+impl From<bool> for Truth {
+    fn from(other: bool) -> Truth {
+        if other {
+            Truth::True
+        } else {
+            Truth::False
+        }
+    }
+}
+
+/// How many pieces an integral's range is split into.
+const INTEGRAL_PIECES: usize = 64;
+
+/// The distance from `lo` to `hi`, accounting for rounding.
+fn length(lo: f64, hi: f64) -> Interval {
+    Interval::point(hi).sub(Interval::point(lo))
+}
+
+/// Errors which can happen while evaluating an expression over intervals.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum IntervalError {
+    /// The divisor was exactly zero.
+    #[fail(display = "Division by zero at {}", span)]
+    DivideByZero {
+        /// The division.
+        span: Span,
+    },
+    /// The entire argument is outside the function's domain.
+    #[fail(display = "Argument outside the function's domain at {}", span)]
+    DomainError {
+        /// The function call.
+        span: Span,
+    },
+    /// The index can't refer to any element of the array.
+    #[fail(display = "Index out of bounds at {}", span)]
+    IndexOutOfBounds {
+        /// The indexing operation.
+        span: Span,
+    },
+    /// The bound of a sum or product isn't a single integer (e.g. because it
+    /// depends on a condition which may go either way).
+    #[fail(display = "The bound at {} isn't known exactly", span)]
+    UncertainBound {
+        /// Where the bound is.
+        span: Span,
+    },
+    /// We don't know how to bound this function.
+    #[fail(
+        display = "\"{}\" can't be evaluated over an interval (at {})",
+        function, span
+    )]
+    NoIntervalImplementation {
+        /// The function's name.
+        function: String,
+        /// The function call.
+        span: Span,
+    },
+    /// Complex numbers don't have an ordering, so they can't be bounded by
+    /// an interval.
+    #[fail(display = "Complex numbers aren't supported (at {})", span)]
+    ComplexNumber {
+        /// The complex literal.
+        span: Span,
+    },
+    /// An identifier which isn't an input or a constant.
+    #[fail(display = "Unknown identifier, \"{}\" at {}", name, span)]
+    UnknownIdentifier {
+        /// The identifier.
+        name: String,
+        /// Where it was used.
+        span: Span,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntax;
+
+    fn evaluate(src: &str, x: [f64; 2]) -> Result<Interval, Error> {
+        let ast = syntax::parse(src).unwrap();
+        IntervalEvaluator::new().with_input("x", x).evaluate(&ast)
+    }
+
+    #[test]
+    fn bounds_enclose_every_possible_result() {
+        let inputs = vec![
+            ("x + 1", [1.0, 2.0], 2.0, 3.0),
+            ("x * x", [-2.0, 3.0], -6.0, 9.0),
+            ("pow(x, 2)", [-2.0, 3.0], 0.0, 9.0),
+            ("1 / x", [2.0, 4.0], 0.25, 0.5),
+            ("sqrt(x)", [4.0, 9.0], 2.0, 3.0),
+            ("abs(x)", [-3.0, 1.0], 0.0, 3.0),
+            ("sin(x)", [0.0, 3.0], 0.0, 1.0),
+            ("cos(x)", [-1.0, 4.0], -1.0, 1.0),
+            ("max(x, 0)", [-1.0, 2.0], 0.0, 2.0),
+            ("x * 0.1", [1.0, 1.0], 0.1, 0.1),
+        ];
+
+        for (src, x, lo, hi) in inputs {
+            let got = evaluate(src, x).unwrap();
+
+            assert!(got.lo() <= lo && hi <= got.hi(), "{} gave {}", src, got);
+            // the bounds shouldn't be much wider than they need to be
+            assert!(got.width() - (hi - lo) < 1e-9, "{} gave {}", src, got);
+        }
+    }
+
+    #[test]
+    fn dividing_by_an_interval_containing_zero() {
+        let got = evaluate("1 / x", [0.0, 2.0]).unwrap();
+        assert!(got.lo() <= 0.5 && got.hi() == f64::INFINITY);
+
+        let got = evaluate("1 / x", [-1.0, 2.0]).unwrap();
+        assert_eq!(got, Interval::entire());
+
+        let err = evaluate("1 / x", [0.0, 0.0]).unwrap_err();
+        match err.downcast::<IntervalError>().unwrap() {
+            IntervalError::DivideByZero { .. } => {}
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unknown_bounds_become_infinite() {
+        let got = evaluate("1e308 * 10 - 1e308 * 10", [0.0, 0.0]).unwrap();
+        assert_eq!(got, Interval::entire());
+
+        let got = evaluate("x - x", [f64::INFINITY, f64::INFINITY]).unwrap();
+        assert_eq!(got, Interval::entire());
+    }
+
+    #[test]
+    fn integers_which_cant_be_represented_exactly_are_widened() {
+        let got = evaluate("9223372036854775807", [0.0, 0.0]).unwrap();
+        assert!(got.lo() < 9_223_372_036_854_775_807.0 && got.width() > 0.0);
+
+        let got = evaluate("9007199254740992", [0.0, 0.0]).unwrap();
+        assert_eq!(got, Interval::point(9_007_199_254_740_992.0));
+    }
+
+    #[test]
+    fn uncertain_conditions_take_both_branches() {
+        let got = evaluate("if x > 1 then 10 else 20", [0.0, 0.5]).unwrap();
+        assert_eq!(got, Interval::point(20.0));
+
+        let got = evaluate("if x > 1 then 10 else 20", [0.0, 2.0]).unwrap();
+        assert_eq!(got, Interval::new(10.0, 20.0));
+
+        let got = evaluate("x > 1 or x < 3", [0.0, 2.0]).unwrap();
+        assert_eq!(got, Interval::point(1.0));
+    }
+
+    #[test]
+    fn functions_outside_their_domain() {
+        assert!(evaluate("ln(x)", [-2.0, -1.0]).is_err());
+        assert!(evaluate("pow(x, 0.5)", [-2.0, 1.0]).is_err());
+        assert!(evaluate("abs(x + 1i)", [0.0, 1.0]).is_err());
+
+        let got = evaluate("sqrt(x)", [-1.0, 4.0]).unwrap();
+        assert!(got.lo() <= 0.0 && got.hi() >= 2.0);
+    }
+
+    #[test]
+    fn arrays_are_evaluated_element_wise() {
+        let got = evaluate("sum([1, 2, 3] * x)", [1.0, 2.0]).unwrap();
+        assert!(got.contains(6.0) && got.contains(12.0));
+
+        let got = evaluate("max([x, 2 * x, 3])", [1.0, 2.0]).unwrap();
+        assert!(got.lo() == 3.0 && got.contains(4.0) && got.width() < 1.0 + 1e-9);
+
+        let got = evaluate("[10, 20, 30][3 // 2] + x", [0.5, 1.5]).unwrap();
+        assert!(got.contains(20.5) && got.contains(21.5));
+
+        assert!(evaluate("[1, 2][5 - 3]", [0.0, 1.0]).is_err());
+
+        let got = evaluate("sum(map(y -> y * y, [1, 2] * x))", [1.0, 2.0]).unwrap();
+        assert!(got.contains(5.0) && got.contains(20.0) && got.width() < 15.0 + 1e-9);
+
+        let src = "sum(map(y -> y, (if x > 1 then [1, 2] else [3, 4]) * 2))";
+        let got = evaluate(src, [0.0, 0.5]).unwrap();
+        assert!(got.contains(14.0) && got.width() < 1e-9);
+    }
+
+    #[test]
+    fn series_add_up_the_bounds_of_each_term() {
+        let got = evaluate("sum(k, 1, 4, k * x)", [1.0, 2.0]).unwrap();
+        assert!(got.contains(10.0) && got.contains(20.0));
+        assert!(got.width() < 10.0 + 1e-9);
+
+        let got = evaluate("prod(x, 1, 1 + 2, x)", [5.0, 6.0]).unwrap();
+        assert!(got.contains(6.0) && got.width() < 1e-9);
+
+        assert!(evaluate("sum(k, 1, if x > 1 then 2 else 3, k)", [0.0, 2.0]).is_err());
+    }
+
+    #[test]
+    fn integrals_are_bounded_piece_by_piece() {
+        let got = evaluate("integrate(t * x, t, 0, 1)", [1.0, 2.0]).unwrap();
+        assert!(got.contains(0.5) && got.contains(1.0));
+        assert!(got.width() < 0.6);
+
+        let got = evaluate("integrate(t -> t * t, 0, x)", [1.0, 2.0]).unwrap();
+        assert!(got.contains(1.0 / 3.0) && got.contains(8.0 / 3.0));
+        assert!(got.width() < 4.5);
+
+        let got = evaluate("integrate(1, t, x, 1.5)", [1.0, 2.0]).unwrap();
+        assert!(got.contains(0.5) && got.contains(-0.5));
+    }
+
+    #[test]
+    fn quantities_are_converted_to_si_units() {
+        let got = evaluate("x * 1 km + 500 m", [1.0, 2.0]).unwrap();
+        assert!(got.contains(1500.0) && got.contains(2500.0));
+        assert!(got.width() < 1000.0 + 1e-9);
+
+        assert!(evaluate("x + 1 m", [1.0, 2.0]).is_err());
+    }
+}
@@ -0,0 +1,164 @@
+//! Execute JIT compiled `calc` code.
+//!
+//! # Debugging and Profiling
+//!
+//! When a formula is compiled with [`Compiler::with_debug_info()`], each
+//! instruction carries a DWARF location pointing at the expression it came
+//! from, in a synthetic source file holding the formula (see
+//! [`write_source_file()`]).
+//!
+//! LLVM registers every module it JIT compiles with `gdb`, so breaking in
+//! `calc_main` or an `integrand.N` function shows the offending line of the
+//! formula. Programs with debug info are also registered with LLVM's perf
+//! listener, which writes a `jit-<pid>.dump` under `$JITDUMPDIR/.debug/jit/`
+//! (`/tmp/.debug/jit/` by default) containing the code and line table of
+//! each function. Record with `perf record -k 1` and run `perf inject --jit` on
+//! the result, then `perf report` and `perf annotate` can attribute samples
+//! to the formula's source.
+//!
+//! A failing calculation also reports the location of the offending
+//! expression through [`RuntimeError::span()`], and
+//! [`RuntimeError::source_text()`] gets the corresponding source code.
+//!
+//! [`Compiler::with_debug_info()`]: ../trans/struct.Compiler.html#method.with_debug_info
+//! [`write_source_file()`]: ../trans/fn.write_source_file.html
+//! [`RuntimeError::span()`]: ../backend/enum.RuntimeError.html#method.span
+//! [`RuntimeError::source_text()`]: ../backend/enum.RuntimeError.html#method.source_text
+
+use failure::Error;
+use inkwell::execution_engine::ExecutionEngine;
+use inkwell::targets::{InitializationConfig, Target};
+use inkwell::OptimizationLevel;
+use num_complex::Complex64;
+use std::fmt::{self, Debug, Formatter};
+
+use backend::{self, CalcMain, Executable, CALC_ENTRYPOINT};
+use environment::{self, Environment, HostFunction, CLOSURE_TRAMPOLINE};
+use solve::{self, SOLVER};
+use trans::Compiled;
+
+pub use backend::RuntimeError;
+
+/// A compiled `calc` program which is ready to be executed.
+pub struct Program<'ctx> {
+    ee: ExecutionEngine<'ctx>,
+    // keeps any closures alive for as long as the compiled code can call them
+    env: Environment,
+}
+
+impl<'ctx> Program<'ctx> {
+    /// JIT compile a `Module` generated by the [`Compiler`], linking it
+    /// against the functions registered with the `Environment` it was
+    /// compiled with.
+    ///
+    /// Modules with debug info are registered with `perf` before any code
+    /// is generated for them.
+    ///
+    /// [`Compiler`]: ../trans/struct.Compiler.html
+    pub fn new(compiled: &Compiled<'ctx>) -> Result<Program<'ctx>, Error> {
+        let module = &compiled.module;
+        let env = compiled.environment();
+
+        Target::initialize_native(&InitializationConfig::default())
+            .map_err(|e| format_err!("Unable to initialize the native target: {}", e))?;
+
+        let ee = module
+            .create_jit_execution_engine(OptimizationLevel::Default)
+            .map_err(|e| format_err!("Unable to create the execution engine: {}", e))?;
+
+        if compiled.has_debug_info() {
+            perf::register(&ee);
+        }
+
+        for (name, host) in env.functions() {
+            if let Some(address) = host.extern_address() {
+                if let Some(func) = module.get_function(&HostFunction::symbol(name)) {
+                    ee.add_global_mapping(&func, address);
+                }
+            }
+        }
+
+        if let Some(trampoline) = module.get_function(CLOSURE_TRAMPOLINE) {
+            ee.add_global_mapping(&trampoline, environment::call_closure as *const () as usize);
+        }
+
+        if let Some(solver) = module.get_function(SOLVER) {
+            ee.add_global_mapping(&solver, solve::calc_solve as *const () as usize);
+        }
+
+        Ok(Program {
+            ee,
+            env: env.clone(),
+        })
+    }
+
+    /// Run the program, translating any error status from `calc_main` into a
+    /// `RuntimeError`.
+    ///
+    /// Only the real part of a complex result is returned, use
+    /// `call_complex()` to get both.
+    pub fn call(&self) -> Result<f64, RuntimeError> {
+        self.call_complex().map(|z| z.re)
+    }
+
+    /// Run the program, returning its result as a complex number.
+    pub fn call_complex(&self) -> Result<Complex64, RuntimeError> {
+        let calc_main = unsafe {
+            self.ee
+                .get_function::<CalcMain>(CALC_ENTRYPOINT)
+                .expect("The compiler always emits an entrypoint")
+        };
+
+        backend::call_entrypoint(|result, location| unsafe { calc_main.call(result, location) })
+    }
+}
+
+impl<'ctx> Executable for Program<'ctx> {
+    fn call_complex(&self) -> Result<Complex64, RuntimeError> {
+        Program::call_complex(self)
+    }
+}
+
+impl<'ctx> Debug for Program<'ctx> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Program")
+            .field("env", &self.env)
+            .finish()
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+mod perf {
+    use inkwell::execution_engine::ExecutionEngine;
+    use std::os::raw::c_void;
+
+    extern "C" {
+        fn LLVMCreatePerfJITEventListener() -> *mut c_void;
+
+        // The C API can only attach listeners to ORC, so call
+        // `llvm::MCJIT::RegisterJITEventListener()` directly. `inkwell`
+        // always creates an MCJIT, and `this` is passed as the first
+        // argument under the Itanium ABI.
+        #[link_name = "_ZN4llvm5MCJIT24RegisterJITEventListenerEPNS_16JITEventListenerE"]
+        fn mcjit_register_listener(mcjit: *mut c_void, listener: *mut c_void);
+    }
+
+    /// Have LLVM's (process-wide) perf listener write each function the
+    /// execution engine generates to a jitdump file.
+    pub fn register(ee: &ExecutionEngine) {
+        unsafe {
+            let listener = LLVMCreatePerfJITEventListener();
+            if !listener.is_null() {
+                mcjit_register_listener(ee.as_mut_ptr() as *mut c_void, listener);
+            }
+        }
+    }
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+mod perf {
+    use inkwell::execution_engine::ExecutionEngine;
+
+    /// `perf` only exists on Linux.
+    pub fn register(_: &ExecutionEngine) {}
+}
@@ -3,17 +3,60 @@
 //! To run JIT compiled code, the compiler goes through several phases:
 //!
 //! 1. Parse the source code into an AST (Abstract Syntax Tree)
-//! 2. Translate the AST into its equivalent LLVM IR
-//! 3. JIT compile the LLVM IR
+//! 2. Type check the AST, making any implicit conversions explicit and
+//!    converting quantities with [`units`] to plain numbers
+//! 3. Translate the AST into its equivalent LLVM IR
+//! 4. JIT compile the LLVM IR
+//!
+//! LLVM can be hard to install, so the `llvm` feature can be disabled and
+//! the [`cranelift`] backend used instead, by enabling the `cranelift`
+//! feature. Both backends implement the traits in [`backend`].
+//!
+//! Alternatively, the [`interpreter`] can evaluate a type checked AST using
+//! exact arithmetic, and the [`interval`] module can find guaranteed bounds
+//! on the result of an expression. On hosts without LLVM, the [`bytecode`]
+//! module compiles expressions to a compact bytecode which can be saved and
+//! evaluated later by a small stack machine. For targets which can only
+//! take C, the [`c`] module generates an equivalent C function.
+//!
+//! The [`solve`] module finds the value of a variable which satisfies an
+//! equation, using the same compiled code as the `solve()` built-in.
 //!
 //! [inkwell]: https://github.com/TheDan64/inkwell
+//! [`backend`]: backend/index.html
+//! [`bytecode`]: bytecode/index.html
+//! [`c`]: c/index.html
+//! [`cranelift`]: cranelift/index.html
+//! [`interpreter`]: interpreter/index.html
+//! [`interval`]: interval/index.html
+//! [`solve`]: solve/index.html
+//! [`units`]: units/index.html
 
 #![deny(missing_docs, missing_debug_implementations, missing_copy_implementations)]
 
+#[cfg(feature = "cranelift")]
+extern crate cranelift_codegen;
+#[cfg(feature = "cranelift")]
+extern crate cranelift_frontend;
+#[cfg(feature = "cranelift")]
+extern crate cranelift_module;
+#[cfg(feature = "cranelift")]
+extern crate cranelift_simplejit;
+#[macro_use]
 extern crate failure;
+#[cfg(feature = "llvm")]
 extern crate inkwell;
 extern crate lalrpop_util;
-extern crate regex;
+extern crate num_bigint;
+extern crate num_complex;
+extern crate num_rational;
+extern crate num_traits;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "serde")]
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
 #[macro_use]
 extern crate slog;
 
@@ -21,5 +64,21 @@ extern crate slog;
 #[macro_use]
 extern crate pretty_assertions;
 
+pub mod backend;
+pub mod builtins;
+pub mod bytecode;
+pub mod c;
+#[cfg(feature = "cranelift")]
+pub mod cranelift;
+pub mod environment;
+pub mod interpreter;
+pub mod interval;
+#[cfg(feature = "llvm")]
+pub mod jit;
+pub mod sema;
+#[cfg(any(feature = "llvm", feature = "cranelift"))]
+pub mod solve;
 pub mod syntax;
+#[cfg(feature = "llvm")]
 pub mod trans;
+pub mod units;
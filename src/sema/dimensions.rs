@@ -0,0 +1,610 @@
+use syntax::{
+    Array, Atom, BinaryOp, Cast, Conditional, Expr, FunctionCall, Index, Integral, Let, Op,
+    Series, SeriesKind, Solve, Span, UnaryOp, UnitAnnotation,
+};
+use units::{self, Dimension};
+
+/// Make sure quantities are only combined with compatible units, returning
+/// a copy of the tree with every quantity converted to SI units and its unit
+/// annotation removed.
+///
+/// Anything without a unit (including identifiers) is dimensionless, except
+/// a variable bound by a [`Let`], which has the same dimension as its value.
+/// Addition, subtraction and comparisons need both operands to have the same
+/// dimension, while multiplication and division combine them. Every element
+/// in an array must have the same dimension.
+///
+/// Lambdas must have already been inlined (see [`inline_lambdas()`]).
+///
+/// A sum has the same dimension as its terms, but the dimension of a product
+/// would depend on how many terms there are, so only plain numbers can be
+/// multiplied together. The variable of integration is a plain number, so
+/// an integral has the same dimension as its body. Similarly, the variable
+/// `solve()` finds is a plain number, so it needs a plain number as its
+/// initial guess.
+///
+/// [`inline_lambdas()`]: fn.inline_lambdas.html
+/// [`Let`]: ../syntax/struct.Let.html
+pub fn check_dimensions(expr: &Expr) -> Result<(Expr, Dimension), DimensionError> {
+    let checker = DimensionChecker { scope: Vec::new() };
+    checker.check(expr)
+}
+
+struct DimensionChecker {
+    /// The variables bound by enclosing sums, products, integrals,
+    /// `solve()`s and `Let`s.
+    scope: Vec<(String, Dimension)>,
+}
+
+impl DimensionChecker {
+    fn check(&self, expr: &Expr) -> Result<(Expr, Dimension), DimensionError> {
+        match *expr {
+            Expr::Atom(ref atom, span) => {
+                let dimension = match *atom {
+                    Atom::Ident(ref name) => self.lookup(name).unwrap_or_default(),
+                    _ => Dimension::dimensionless(),
+                };
+                Ok((Expr::Atom(atom.clone(), span), dimension))
+            }
+            Expr::UnitAnnotation(ref annotation) => self.check_unit_annotation(annotation),
+            Expr::BinaryOp(ref op) => self.check_binary_op(op),
+            Expr::UnaryOp(ref op) => {
+                let (value, dimension) = self.check(&op.value)?;
+                let expr = Expr::from(UnaryOp::new(value, op.op)).with_span(op.span);
+                Ok((expr, dimension))
+            }
+            Expr::Conditional(ref cond) => self.check_conditional(cond),
+            Expr::FunctionCall(ref call) => self.check_function_call(call),
+            Expr::Cast(ref cast) => {
+                let (value, dimension) = self.check(&cast.value)?;
+                let expr = Expr::from(Cast::new(value, cast.from, cast.to)).with_span(cast.span);
+                Ok((expr, dimension))
+            }
+            Expr::Array(ref array) => self.check_array(array),
+            Expr::Index(ref index) => {
+                let (array, dimension) = self.check(&index.array)?;
+                let (position, position_dim) = self.check(&index.index)?;
+                if !position_dim.is_dimensionless() {
+                    return Err(DimensionError::DimensionedIndex {
+                        dimension: position_dim,
+                        span: position.span(),
+                    });
+                }
+
+                let expr = Expr::from(Index::new(array, position)).with_span(index.span);
+                Ok((expr, dimension))
+            }
+            Expr::Series(ref series) => self.check_series(series),
+            Expr::Integral(ref integral) => {
+                let lower = self.check_bound(&integral.lower)?;
+                let upper = self.check_bound(&integral.upper)?;
+                let inner = self.with_variable(&integral.variable, Dimension::dimensionless());
+                let (body, dimension) = inner.check(&integral.body)?;
+
+                let integral = Integral {
+                    span: integral.span,
+                    ..Integral::new(body, integral.variable.clone(), lower, upper)
+                };
+                Ok((integral.into(), dimension))
+            }
+            Expr::Solve(ref solve) => {
+                let inner = self.with_variable(&solve.variable, Dimension::dimensionless());
+                let (equation, _) = inner.check(&solve.equation)?;
+                let (guess, dimension) = self.check(&solve.guess)?;
+                if !dimension.is_dimensionless() {
+                    return Err(DimensionError::DimensionedGuess {
+                        dimension,
+                        span: guess.span(),
+                    });
+                }
+
+                let solve = Solve {
+                    span: solve.span,
+                    ..Solve::new(equation, solve.variable.clone(), guess)
+                };
+                Ok((solve.into(), Dimension::dimensionless()))
+            }
+            Expr::Let(ref binding) => self.check_let(binding),
+            Expr::Lambda(_) => unreachable!("Lambdas are inlined before dimension checking"),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Dimension> {
+        self.scope
+            .iter()
+            .rev()
+            .find(|&&(ref variable, _)| variable == name)
+            .map(|&(_, dimension)| dimension)
+    }
+
+    /// Create a checker for the body of something which binds a variable.
+    fn with_variable(&self, name: &str, dimension: Dimension) -> DimensionChecker {
+        let mut scope = self.scope.clone();
+        scope.push((name.to_string(), dimension));
+
+        DimensionChecker { scope }
+    }
+
+    fn check_let(&self, binding: &Let) -> Result<(Expr, Dimension), DimensionError> {
+        let (value, value_dim) = self.check(&binding.value)?;
+        let inner = self.with_variable(&binding.variable, value_dim);
+        let (body, dimension) = inner.check(&binding.body)?;
+
+        let binding = Let {
+            span: binding.span,
+            ..Let::new(binding.variable.clone(), value, body)
+        };
+        Ok((binding.into(), dimension))
+    }
+
+    fn check_series(&self, series: &Series) -> Result<(Expr, Dimension), DimensionError> {
+        let start = self.check_bound(&series.start)?;
+        let end = self.check_bound(&series.end)?;
+        let inner = self.with_variable(&series.variable, Dimension::dimensionless());
+        let (body, dimension) = inner.check(&series.body)?;
+
+        if series.kind == SeriesKind::Product && !dimension.is_dimensionless() {
+            return Err(DimensionError::DimensionedProduct {
+                dimension,
+                span: body.span(),
+            });
+        }
+
+        let series = Series {
+            span: series.span,
+            ..Series::new(series.kind, series.variable.clone(), start, end, body)
+        };
+
+        Ok((series.into(), dimension))
+    }
+
+    fn check_bound(&self, bound: &Expr) -> Result<Expr, DimensionError> {
+        let (bound, dimension) = self.check(bound)?;
+
+        if dimension.is_dimensionless() {
+            Ok(bound)
+        } else {
+            Err(DimensionError::DimensionedBound {
+                dimension,
+                span: bound.span(),
+            })
+        }
+    }
+
+    fn check_array(&self, array: &Array) -> Result<(Expr, Dimension), DimensionError> {
+        let mut elements = Vec::new();
+        let mut first = None;
+
+        for element in &array.elements {
+            let (element, dimension) = self.check(element)?;
+
+            match first {
+                Some(first) if first != dimension => {
+                    return Err(DimensionError::MixedArray {
+                        first,
+                        other: dimension,
+                        span: element.span(),
+                    })
+                }
+                _ => first = Some(dimension),
+            }
+
+            elements.push(element);
+        }
+
+        let expr = Expr::from(Array::new(elements)).with_span(array.span);
+        Ok((expr, first.unwrap_or_default()))
+    }
+
+    /// Convert the quantity to SI units so the annotation can be thrown away.
+    fn check_unit_annotation(
+        &self,
+        annotation: &UnitAnnotation,
+    ) -> Result<(Expr, Dimension), DimensionError> {
+        let (factor, dimension) =
+            units::parse(&annotation.unit).ok_or_else(|| DimensionError::UnknownUnit {
+                unit: annotation.unit.clone(),
+                span: annotation.span,
+            })?;
+
+        let (value, _) = self.check(&annotation.value)?;
+        let unrepresentable = DimensionError::UnrepresentableQuantity {
+            span: annotation.span,
+        };
+
+        let value = match value {
+            _ if factor == 1.0 => value,
+            // compare as i128, because converting back to i64 saturates
+            Expr::Atom(Atom::Integer(n), _) if (n as f64) as i128 != i128::from(n) => {
+                return Err(unrepresentable)
+            }
+            Expr::Atom(Atom::Number(n), _) => Atom::Number(n * factor).into(),
+            Expr::Atom(Atom::Integer(n), _) => Atom::Number(n as f64 * factor).into(),
+            other => BinaryOp::mult(other, Atom::Number(factor).into()).into(),
+        };
+
+        match value {
+            Expr::Atom(Atom::Number(n), _) if !n.is_finite() => return Err(unrepresentable),
+            _ => {}
+        }
+
+        Ok((value.with_span(annotation.span), dimension))
+    }
+
+    fn check_binary_op(&self, op: &BinaryOp) -> Result<(Expr, Dimension), DimensionError> {
+        let (left, left_dim) = self.check(&op.left)?;
+        let (right, right_dim) = self.check(&op.right)?;
+
+        let overflow = DimensionError::ExponentOverflow { span: op.span };
+        let dimension = match op.op {
+            Op::Multiply => left_dim.mul(right_dim).ok_or(overflow)?,
+            Op::Divide | Op::IntegerDivide => left_dim.div(right_dim).ok_or(overflow)?,
+            _ if left_dim != right_dim => {
+                return Err(DimensionError::IncompatibleDimensions {
+                    op: op.op,
+                    left: left_dim,
+                    right: right_dim,
+                    span: op.span,
+                })
+            }
+            _ if op.op.is_comparison() || op.op.is_logical() => Dimension::dimensionless(),
+            _ => left_dim,
+        };
+
+        let expr = Expr::from(BinaryOp::new(left, right, op.op)).with_span(op.span);
+        Ok((expr, dimension))
+    }
+
+    fn check_conditional(&self, cond: &Conditional) -> Result<(Expr, Dimension), DimensionError> {
+        let (condition, _) = self.check(&cond.condition)?;
+        let (if_true, true_dim) = self.check(&cond.if_true)?;
+        let (if_false, false_dim) = self.check(&cond.if_false)?;
+
+        if true_dim != false_dim {
+            return Err(DimensionError::IncompatibleBranches {
+                if_true: true_dim,
+                if_false: false_dim,
+                span: cond.span,
+            });
+        }
+
+        let expr = Expr::from(Conditional::new(condition, if_true, if_false)).with_span(cond.span);
+        Ok((expr, true_dim))
+    }
+
+    /// Most functions only make sense for plain numbers, but a handful of
+    /// built-ins know what to do with units.
+    fn check_function_call(
+        &self,
+        call: &FunctionCall,
+    ) -> Result<(Expr, Dimension), DimensionError> {
+        let mut arguments = Vec::new();
+        let mut dimensions = Vec::new();
+
+        for arg in &call.arguments {
+            let (arg, dimension) = self.check(arg)?;
+            arguments.push(arg);
+            dimensions.push(dimension);
+        }
+
+        let first = dimensions.first().cloned().unwrap_or_default();
+
+        let dimension = match call.name.as_str() {
+            "sqrt" => first.sqrt().ok_or_else(|| DimensionError::OddSquareRoot {
+                dimension: first,
+                span: call.span,
+            })?,
+            "abs" | "floor" | "ceil" | "round" | "re" | "im" | "conj" | "sum" | "mean" => first,
+            "dot" => first
+                .mul(dimensions.get(1).cloned().unwrap_or_default())
+                .ok_or(DimensionError::ExponentOverflow { span: call.span })?,
+            "min" | "max" => {
+                if let Some(&other) = dimensions.iter().find(|&&d| d != first) {
+                    return Err(DimensionError::IncompatibleArguments {
+                        function: call.name.clone(),
+                        first,
+                        other,
+                        span: call.span,
+                    });
+                }
+                first
+            }
+            // only an integer literal exponent tells us the result's dimension
+            "pow" if !first.is_dimensionless() => match call.arguments.get(1) {
+                Some(&Expr::Atom(Atom::Integer(n), _)) if n.abs() <= i64::from(i8::max_value()) => {
+                    first
+                        .powi(n as i8)
+                        .ok_or(DimensionError::ExponentOverflow { span: call.span })?
+                }
+                _ => return Err(dimensioned_argument(call, &arguments[0], first)),
+            },
+            _ => {
+                for (arg, &dimension) in arguments.iter().zip(&dimensions) {
+                    if !dimension.is_dimensionless() {
+                        return Err(dimensioned_argument(call, arg, dimension));
+                    }
+                }
+                Dimension::dimensionless()
+            }
+        };
+
+        let call = FunctionCall {
+            span: call.span,
+            ..FunctionCall::new(call.name.clone(), arguments)
+        };
+
+        Ok((call.into(), dimension))
+    }
+}
+
+fn dimensioned_argument(call: &FunctionCall, arg: &Expr, dimension: Dimension) -> DimensionError {
+    DimensionError::DimensionedArgument {
+        function: call.name.clone(),
+        dimension,
+        span: arg.span(),
+    }
+}
+
+/// The ways dimension checking can fail.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum DimensionError {
+    /// A quantity was annotated with a unit we don't know about.
+    #[fail(display = "Unknown unit \"{}\" at {}", unit, span)]
+    UnknownUnit {
+        /// The unit, as written.
+        unit: String,
+        /// Where the quantity is.
+        span: Span,
+    },
+    /// A binary operator was applied to quantities with different
+    /// dimensions (e.g. `1 m + 2 s`).
+    #[fail(
+        display = "Can't apply \"{}\" to {} and {} at {}",
+        op, left, right, span
+    )]
+    IncompatibleDimensions {
+        /// The operator.
+        op: Op,
+        /// The left operand's dimension.
+        left: Dimension,
+        /// The right operand's dimension.
+        right: Dimension,
+        /// Where the operation is.
+        span: Span,
+    },
+    /// The two branches of an `if` have different dimensions.
+    #[fail(
+        display = "The branches of the conditional at {} have incompatible dimensions ({} and {})",
+        span, if_true, if_false
+    )]
+    IncompatibleBranches {
+        /// The dimension of the "true" branch.
+        if_true: Dimension,
+        /// The dimension of the "false" branch.
+        if_false: Dimension,
+        /// Where the conditional is.
+        span: Span,
+    },
+    /// A function which needs all of its arguments to have the same
+    /// dimension (e.g. `max`) was passed a mixture.
+    #[fail(
+        display = "\"{}\" can't compare {} with {} at {}",
+        function, first, other, span
+    )]
+    IncompatibleArguments {
+        /// The function being called.
+        function: String,
+        /// The first argument's dimension.
+        first: Dimension,
+        /// The dimension which didn't match.
+        other: Dimension,
+        /// Where the function call is.
+        span: Span,
+    },
+    /// Passing a quantity with units to a function which only accepts plain
+    /// numbers (e.g. `sin(3 m)`).
+    #[fail(
+        display = "\"{}\" expects a plain number but found {} at {}",
+        function, dimension, span
+    )]
+    DimensionedArgument {
+        /// The function being called.
+        function: String,
+        /// The argument's dimension.
+        dimension: Dimension,
+        /// Where the argument is.
+        span: Span,
+    },
+    /// An array containing quantities with different dimensions.
+    #[fail(
+        display = "Array elements must all be {} but found {} at {}",
+        first, other, span
+    )]
+    MixedArray {
+        /// The first element's dimension.
+        first: Dimension,
+        /// The dimension which didn't match.
+        other: Dimension,
+        /// Where the offending element is.
+        span: Span,
+    },
+    /// Indexing into an array with a quantity (e.g. `xs[2 m]`).
+    #[fail(
+        display = "An index must be a plain number but found {} at {}",
+        dimension, span
+    )]
+    DimensionedIndex {
+        /// The index's dimension.
+        dimension: Dimension,
+        /// Where the index is.
+        span: Span,
+    },
+    /// A sum, product or integral with a bound like `10 s`.
+    #[fail(
+        display = "Bounds must be plain numbers but found {} at {}",
+        dimension, span
+    )]
+    DimensionedBound {
+        /// The bound's dimension.
+        dimension: Dimension,
+        /// Where the bound is.
+        span: Span,
+    },
+    /// Giving `solve()` a starting point like `10 s`.
+    #[fail(
+        display = "The initial guess must be a plain number but found {} at {}",
+        dimension, span
+    )]
+    DimensionedGuess {
+        /// The guess's dimension.
+        dimension: Dimension,
+        /// Where the guess is.
+        span: Span,
+    },
+    /// Multiplying together terms which have units.
+    #[fail(
+        display = "The terms of a product must be plain numbers but found {} at {}",
+        dimension, span
+    )]
+    DimensionedProduct {
+        /// The dimension of each term.
+        dimension: Dimension,
+        /// Where the body is.
+        span: Span,
+    },
+    /// A dimension with an exponent too large to keep track of (e.g.
+    /// `pow(1 m^2, 100)`).
+    #[fail(display = "The dimension at {} has an exponent which is too large", span)]
+    ExponentOverflow {
+        /// The operation which overflowed.
+        span: Span,
+    },
+    /// A quantity which overflows when it's converted to SI units (e.g.
+    /// `1e308 km`), or an integer which would have to be rounded.
+    #[fail(display = "The quantity at {} can't be represented in SI units", span)]
+    UnrepresentableQuantity {
+        /// Where the quantity is.
+        span: Span,
+    },
+    /// Taking the square root of something like `m^3`.
+    #[fail(display = "Can't take the square root of {} at {}", dimension, span)]
+    OddSquareRoot {
+        /// The dimension of the square root's argument.
+        dimension: Dimension,
+        /// Where the square root is.
+        span: Span,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sema::inline_lambdas;
+    use syntax;
+
+    fn check(src: &str) -> Result<(Expr, Dimension), DimensionError> {
+        let ast = syntax::parse(src).unwrap();
+        let ast = inline_lambdas(&ast).unwrap();
+        check_dimensions(&ast)
+    }
+
+    #[test]
+    fn units_are_converted_and_erased() {
+        let inputs = vec![
+            ("9.81 m/s^2 * 3 s", "m s^-1"),
+            ("1 km + 300 m", "m"),
+            ("sqrt(4 m^2)", "m"),
+            ("if 1 h > 30 min then 2 kg else 5 g", "kg"),
+            ("2 * pi", "dimensionless"),
+            ("3 N / (1 kg)", "m s^-2"),
+            ("pow(2 s, 2)", "s^2"),
+            ("sum([1 m, 2 km] * 2)", "m"),
+            ("[1 s, 2 min][1]", "s"),
+            ("dot([1 N, 2 N], [3 m, 4 m])", "kg m^2 s^-2"),
+            ("sum(i, 1, 3, i * 2 h)", "s"),
+            ("prod(i, 1, 3, 1 h / 1 min)", "dimensionless"),
+            ("sum(map(x -> x * x, [1 m, 2 m]))", "m^2"),
+            ("sum(map(x -> x * x, [1 m, 2 m] * 3))", "m^2"),
+            ("sum(map(x -> x, [1 m, 2 m] + sqrt(4 m^2)))", "m"),
+            ("integrate(x * 3 N, x, 0, 2)", "kg m s^-2"),
+            ("solve(x * 1 m == 2 km, x, 1)", "dimensionless"),
+        ];
+
+        for (src, should_be) in inputs {
+            let (expr, dimension) = check(src).unwrap();
+            assert_eq!(dimension.to_string(), should_be, "{}", src);
+
+            // nothing should be left for later phases to deal with
+            struct NoUnits;
+            impl syntax::visit::Visitor for NoUnits {
+                fn visit_unit_annotation(&mut self, u: &UnitAnnotation) {
+                    panic!("Found a unit annotation: {:?}", u);
+                }
+            }
+            syntax::visit::Visitor::visit_expr(&mut NoUnits, &expr);
+        }
+    }
+
+    #[test]
+    fn compatible_units_are_converted_to_si() {
+        let (got, _) = check("1 km + 300 m").unwrap();
+        let should_be = BinaryOp::add(Atom::Number(1000.0).into(), Atom::Integer(300).into());
+
+        assert_eq!(got.without_spans(), Expr::from(should_be));
+    }
+
+    #[test]
+    fn mismatched_dimensions_are_errors() {
+        let inputs = vec![
+            "1 m + 2 s",
+            "3 kg < 4 m",
+            "if true then 1 m else 1 s",
+            "sin(30 s)",
+            "max(1 m, 2 s)",
+            "sqrt(8 m^3)",
+            "pow(2 m, x)",
+            "[1 m, 2 s]",
+            "[1, 2][1 m]",
+            "sum(i, 1 s, 3, i)",
+            "prod(i, 1, 3, i * 1 kg)",
+            "integrate(x, x, 0 s, 1 s)",
+            "solve(x == 2 m, x, 1)",
+            "solve(x == 2, x, 1 s)",
+        ];
+
+        for src in inputs {
+            assert!(check(src).is_err(), "{}", src);
+        }
+    }
+
+    #[test]
+    fn exponents_which_overflow_are_errors() {
+        let inputs = vec![
+            "pow(2 m, 100) * pow(2 m, 100)",
+            "pow(1 m^2, 100)",
+            "1 / pow(1 m, 127) / 1 m^2",
+        ];
+
+        for src in inputs {
+            match check(src) {
+                Err(DimensionError::ExponentOverflow { .. }) => {}
+                other => panic!("Unexpected result for {}: {:?}", src, other),
+            }
+        }
+    }
+    #[test]
+    fn quantities_which_cant_be_converted_are_errors() {
+        let inputs = vec!["1e308 km", "2 * 1e308 km", "9007199254740993 km"];
+
+        for src in inputs {
+            match check(src) {
+                Err(DimensionError::UnrepresentableQuantity { span }) => {
+                    assert_eq!(span.end, src.len(), "{}", src)
+                }
+                other => panic!("Unexpected result for {}: {:?}", src, other),
+            }
+        }
+
+        assert!(check("9007199254740992 km").is_ok());
+    }
+}
@@ -0,0 +1,425 @@
+use sema::typeck::TypeError;
+use syntax::visit::Visitor;
+use syntax::{
+    Array, Atom, BinaryOp, Cast, Conditional, Expr, FunctionCall, Index, Integral, Lambda, Let,
+    Series, Solve, UnaryOp, UnitAnnotation,
+};
+
+/// Inline every lambda into the built-in it's passed to, returning a copy of
+/// the tree without any `Lambda` nodes.
+///
+/// Lambdas are always written inline, so we know exactly which function each
+/// built-in is given and can substitute the arguments straight into its
+/// body. For example, `map(x -> x * x, [1, 2])` becomes `[1 * 1, 2 * 2]`, and
+/// `integrate(t -> t * t, 0, 1)` becomes `integrate(t * t, t, 0, 1)`.
+/// Likewise, `solve(x -> x * x == 2, 1)` is `solve(x * x == 2, x, 1)`.
+///
+/// Because the parameter is replaced by each argument's expression, it has
+/// the same type and dimension as whatever was passed in. An expression is
+/// only copied into several places when it's a literal or variable. Anything
+/// else is evaluated once and bound to a variable with a [`Let`], otherwise
+/// host functions would be called more often than written and nested
+/// `map()`s would make the tree grow exponentially.
+///
+/// [`Let`]: ../syntax/struct.Let.html
+pub fn inline_lambdas(expr: &Expr) -> Result<Expr, TypeError> {
+    let expr = match *expr {
+        Expr::Atom(..) => expr.clone(),
+        Expr::FunctionCall(ref call) => return inline_function_call(call),
+        Expr::BinaryOp(ref op) => {
+            let left = inline_lambdas(&op.left)?;
+            let right = inline_lambdas(&op.right)?;
+            Expr::from(BinaryOp::new(left, right, op.op)).with_span(op.span)
+        }
+        Expr::UnaryOp(ref op) => {
+            let value = inline_lambdas(&op.value)?;
+            Expr::from(UnaryOp::new(value, op.op)).with_span(op.span)
+        }
+        Expr::Conditional(ref cond) => {
+            let condition = inline_lambdas(&cond.condition)?;
+            let if_true = inline_lambdas(&cond.if_true)?;
+            let if_false = inline_lambdas(&cond.if_false)?;
+            Expr::from(Conditional::new(condition, if_true, if_false)).with_span(cond.span)
+        }
+        Expr::Cast(ref cast) => {
+            let value = inline_lambdas(&cast.value)?;
+            Expr::from(Cast::new(value, cast.from, cast.to)).with_span(cast.span)
+        }
+        Expr::UnitAnnotation(ref annotation) => {
+            let value = inline_lambdas(&annotation.value)?;
+            let annotation = UnitAnnotation {
+                span: annotation.span,
+                ..UnitAnnotation::new(value, annotation.unit.clone())
+            };
+            annotation.into()
+        }
+        Expr::Array(ref array) => {
+            let elements = array
+                .elements
+                .iter()
+                .map(inline_lambdas)
+                .collect::<Result<Vec<_>, _>>()?;
+            Expr::from(Array::new(elements)).with_span(array.span)
+        }
+        Expr::Index(ref index) => {
+            let array = inline_lambdas(&index.array)?;
+            let position = inline_lambdas(&index.index)?;
+            Expr::from(Index::new(array, position)).with_span(index.span)
+        }
+        Expr::Series(ref series) => {
+            let start = inline_lambdas(&series.start)?;
+            let end = inline_lambdas(&series.end)?;
+            let body = inline_lambdas(&series.body)?;
+            let series = Series {
+                span: series.span,
+                ..Series::new(series.kind, series.variable.clone(), start, end, body)
+            };
+            series.into()
+        }
+        Expr::Integral(ref integral) => {
+            let lower = inline_lambdas(&integral.lower)?;
+            let upper = inline_lambdas(&integral.upper)?;
+            let body = inline_lambdas(&integral.body)?;
+            let integral = Integral {
+                span: integral.span,
+                ..Integral::new(body, integral.variable.clone(), lower, upper)
+            };
+            integral.into()
+        }
+        Expr::Solve(ref solve) => {
+            let equation = inline_lambdas(&solve.equation)?;
+            let guess = inline_lambdas(&solve.guess)?;
+            let solve = Solve {
+                span: solve.span,
+                ..Solve::new(equation, solve.variable.clone(), guess)
+            };
+            solve.into()
+        }
+        Expr::Let(ref binding) => {
+            let value = inline_lambdas(&binding.value)?;
+            let body = inline_lambdas(&binding.body)?;
+            let binding = Let {
+                span: binding.span,
+                ..Let::new(binding.variable.clone(), value, body)
+            };
+            binding.into()
+        }
+        // anything which accepts a lambda has already dealt with it
+        Expr::Lambda(ref lambda) => {
+            return Err(TypeError::UnexpectedLambda { span: lambda.span });
+        }
+    };
+
+    Ok(expr)
+}
+
+fn inline_function_call(call: &FunctionCall) -> Result<Expr, TypeError> {
+    // inline from the inside out so nested lambdas are already gone by the
+    // time they get substituted into something else
+    let lambda = match call.arguments.first() {
+        Some(&Expr::Lambda(ref lambda)) => Some(Lambda {
+            body: inline_lambdas(&lambda.body)?,
+            ..(**lambda).clone()
+        }),
+        _ => None,
+    };
+    let skip = if lambda.is_some() { 1 } else { 0 };
+    let arguments = call.arguments
+        .iter()
+        .skip(skip)
+        .map(inline_lambdas)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match (call.name.as_str(), lambda) {
+        ("map", Some(ref f)) if arguments.len() == 1 => inline_map(call, f, &arguments[0]),
+        ("integrate", Some(f)) if arguments.len() == 2 => {
+            let mut bounds = arguments.into_iter();
+            let integral = Integral {
+                span: call.span,
+                ..Integral::new(f.body, f.parameter, bounds.next().unwrap(), bounds.next().unwrap())
+            };
+            Ok(integral.into())
+        }
+        ("solve", Some(f)) if arguments.len() == 1 => {
+            let guess = arguments.into_iter().next().unwrap();
+            let solve = Solve {
+                span: call.span,
+                ..Solve::new(f.body, f.parameter, guess)
+            };
+            Ok(solve.into())
+        }
+        (_, Some(f)) => Err(TypeError::UnexpectedLambda { span: f.span }),
+        (_, None) => {
+            let call = FunctionCall {
+                span: call.span,
+                ..FunctionCall::new(call.name.clone(), arguments)
+            };
+            Ok(call.into())
+        }
+    }
+}
+
+/// `map(f, xs)` is an array literal with the lambda applied to each element.
+fn inline_map(call: &FunctionCall, f: &Lambda, array: &Expr) -> Result<Expr, TypeError> {
+    // only one branch is evaluated, so each gets mapped separately
+    if let Expr::Conditional(ref cond) = *array {
+        let if_true = inline_map(call, f, &cond.if_true)?;
+        let if_false = inline_map(call, f, &cond.if_false)?;
+        let cond = Conditional::new(cond.condition.clone(), if_true, if_false);
+        return Ok(Expr::from(cond).with_span(call.span));
+    }
+
+    let mut shared = Vec::new();
+    let elements = elements(array, &mut shared).ok_or_else(|| TypeError::UnknownLength {
+        function: call.name.clone(),
+        span: array.span(),
+    })?;
+
+    let uses = uses(&f.body, &f.parameter);
+    let elements: Vec<Expr> = elements.iter().map(|e| apply(f, e, uses)).collect();
+    let array = Expr::from(Array::new(elements)).with_span(call.span);
+
+    // the first value to be shared is the outermost binding
+    let expr = shared
+        .into_iter()
+        .rev()
+        .fold(array, |body, (name, value)| {
+            Expr::from(Let::new(name, value, body)).with_span(call.span)
+        });
+    Ok(expr)
+}
+
+/// Call the lambda, binding the argument to its parameter if substituting
+/// it would mean evaluating it more than once.
+fn apply(f: &Lambda, argument: &Expr, uses: usize) -> Expr {
+    if uses > 1 && !is_trivially_copyable(argument) {
+        let binding = Let {
+            span: f.body.span(),
+            ..Let::new(f.parameter.clone(), argument.clone(), f.body.clone())
+        };
+        binding.into()
+    } else {
+        f.apply(argument)
+    }
+}
+
+/// Split an array-valued expression into an expression for each element,
+/// pushing element-wise operations down to the individual elements. Returns
+/// `None` if the expression isn't obviously an array.
+///
+/// Anything which every element needs (e.g. the `g(3)` in `[1, 2] + g(3)`)
+/// is added to `shared`, and the elements use a variable bound to it
+/// instead.
+fn elements(expr: &Expr, shared: &mut Vec<(String, Expr)>) -> Option<Vec<Expr>> {
+    let before = shared.len();
+    let got = split_elements(expr, shared);
+
+    if got.is_none() {
+        shared.truncate(before);
+    }
+    got
+}
+
+fn split_elements(expr: &Expr, shared: &mut Vec<(String, Expr)>) -> Option<Vec<Expr>> {
+    match *expr {
+        Expr::Array(ref array) => Some(array.elements.clone()),
+        Expr::BinaryOp(ref op) => {
+            let (left, right) = match (elements(&op.left, shared), elements(&op.right, shared)) {
+                (Some(left), Some(right)) => (left, right),
+                (Some(left), None) => {
+                    let right = repeat(&op.right, left.len(), shared);
+                    (left, right)
+                }
+                (None, Some(right)) => {
+                    let left = repeat(&op.left, right.len(), shared);
+                    (left, right)
+                }
+                (None, None) => return None,
+            };
+            if left.len() != right.len() {
+                return None;
+            }
+
+            let elements = left
+                .into_iter()
+                .zip(right)
+                .map(|(l, r)| Expr::from(BinaryOp::new(l, r, op.op)).with_span(op.span))
+                .collect();
+            Some(elements)
+        }
+        Expr::Conditional(ref cond) => {
+            let mut in_branches = Vec::new();
+            let if_true = elements(&cond.if_true, &mut in_branches)?;
+            let if_false = elements(&cond.if_false, &mut in_branches)?;
+            // only one branch is evaluated, so nothing in them can be shared
+            if !in_branches.is_empty() || if_true.len() != if_false.len() {
+                return None;
+            }
+
+            let conditions = repeat(&cond.condition, if_true.len(), shared);
+            let elements = conditions
+                .into_iter()
+                .zip(if_true.into_iter().zip(if_false))
+                .map(|(c, (t, f))| Expr::from(Conditional::new(c, t, f)).with_span(cond.span))
+                .collect();
+            Some(elements)
+        }
+        // the result of another map(), so its variable gets shared too
+        Expr::Let(ref binding) => {
+            let variable = share(&binding.value, shared);
+            let body = Lambda::new(binding.variable.clone(), binding.body.clone())
+                .apply(&Atom::Ident(variable).into());
+            elements(&body, shared)
+        }
+        _ => None,
+    }
+}
+
+/// Make `times` copies of an expression, sharing it if it's not trivially
+/// copyable.
+fn repeat(expr: &Expr, times: usize, shared: &mut Vec<(String, Expr)>) -> Vec<Expr> {
+    if times > 1 && !is_trivially_copyable(expr) {
+        let variable = Expr::from(Atom::Ident(share(expr, shared)));
+        vec![variable.with_span(expr.span()); times]
+    } else {
+        vec![expr.clone(); times]
+    }
+}
+
+/// Add a value to the shared values, returning the name of its variable.
+fn share(value: &Expr, shared: &mut Vec<(String, Expr)>) -> String {
+    // a quote can't appear in an identifier, so this won't clash with
+    // anything the user wrote
+    let name = format!("'{}", shared.len());
+    shared.push((name.clone(), value.clone()));
+    name
+}
+
+/// Literals and variables, possibly with units attached.
+fn is_trivially_copyable(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Atom(..) => true,
+        Expr::UnitAnnotation(ref annotation) => is_trivially_copyable(&annotation.value),
+        _ => false,
+    }
+}
+
+/// How many times does the expression use an identifier called `name`?
+///
+/// Uses of a different variable which shadows `name` are counted too, so
+/// this may overestimate.
+fn uses(expr: &Expr, name: &str) -> usize {
+    struct Uses<'a> {
+        name: &'a str,
+        count: usize,
+    }
+
+    impl<'a> Visitor for Uses<'a> {
+        fn visit_atom(&mut self, atom: &Atom) {
+            if let Atom::Ident(ref ident) = *atom {
+                if ident == self.name {
+                    self.count += 1;
+                }
+            }
+        }
+    }
+
+    let mut visitor = Uses { name, count: 0 };
+    visitor.visit_expr(expr);
+    visitor.count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntax;
+
+    fn inline(src: &str) -> Result<Expr, TypeError> {
+        let ast = syntax::parse(src).unwrap();
+        inline_lambdas(&ast)
+    }
+
+    #[test]
+    fn map_applies_the_lambda_to_each_element() {
+        let got = inline("map(x -> x * x, [1, 2 m])").unwrap();
+
+        let square = |element: Expr| BinaryOp::mult(element.clone(), element).into();
+        let two_metres = UnitAnnotation::new(Atom::from(2).into(), "m").into();
+        let should_be = Array::new(vec![square(Atom::from(1).into()), square(two_metres)]);
+
+        assert_eq!(got.without_spans(), Expr::from(should_be));
+
+        let got = inline("map(x -> x * 2, [1, 2] + 3)").unwrap();
+
+        let double = |n: i64| {
+            let element = BinaryOp::add(Atom::from(n).into(), Atom::from(3).into());
+            BinaryOp::mult(element.into(), Atom::from(2).into()).into()
+        };
+        let should_be = Array::new(vec![double(1), double(2)]);
+
+        assert_eq!(got.without_spans(), Expr::from(should_be));
+    }
+
+    #[test]
+    fn values_used_more_than_once_are_bound_to_a_variable() {
+        let call = |name: &str, n: i64| FunctionCall::new(name, vec![Atom::from(n).into()]);
+        let ident = |name: &str| Expr::from(Atom::from(name));
+
+        let got = inline("map(x -> x * x, [f(1), 2])").unwrap();
+
+        let square = BinaryOp::mult(ident("x"), ident("x"));
+        let two_squared = BinaryOp::mult(Atom::from(2).into(), Atom::from(2).into());
+        let should_be = Array::new(vec![
+            Let::new("x", call("f", 1).into(), square.into()).into(),
+            two_squared.into(),
+        ]);
+
+        assert_eq!(got.without_spans(), Expr::from(should_be));
+
+        let got = inline("map(x -> x, [1, 2] + g(3))").unwrap();
+
+        let plus_g = |n: i64| BinaryOp::add(Atom::from(n).into(), ident("'0")).into();
+        let elements = Array::new(vec![plus_g(1), plus_g(2)]);
+        let should_be = Let::new("'0", call("g", 3).into(), elements.into());
+
+        assert_eq!(got.without_spans(), Expr::from(should_be));
+    }
+
+    #[test]
+    fn map_accepts_any_array_expression() {
+        let inputs = vec![
+            "map(x -> x * x, [1, 2] * 3)",
+            "map(x -> x * x, [0.1 + 0.2])",
+            "map(x -> x * x, map(y -> y * y, [1, 2]))",
+            "map(x -> x * x, map(y -> y + 1, [1]) * 2)",
+            "map(x -> x * x, map(y -> y, [1, 2] + f(3)) + g(4))",
+            "map(x -> x, if f(1) > 0 then [1, 2] + f(2) else [3, 4])",
+            "map(x -> x, (if f(1) > 0 then [1, 2] else [3, 4]) * 2)",
+        ];
+
+        for src in inputs {
+            let got = inline(src);
+            assert!(got.is_ok(), "{}: {:?}", src, got);
+        }
+    }
+
+    #[test]
+    fn integrating_a_lambda_binds_its_parameter() {
+        let got = inline("integrate(t -> t * t, 0, 1)").unwrap();
+
+        let square = BinaryOp::mult(Atom::from("t").into(), Atom::from("t").into());
+        let (lower, upper) = (Atom::from(0).into(), Atom::from(1).into());
+        let should_be = Integral::new(square.into(), "t", lower, upper);
+
+        assert_eq!(got.without_spans(), Expr::from(should_be));
+    }
+
+    #[test]
+    fn lambdas_can_only_be_passed_to_higher_order_built_ins() {
+        let inputs = vec!["x -> x", "1 + (x -> x)", "sin(x -> x)", "map(x -> x, 5)"];
+
+        for src in inputs {
+            assert!(inline(src).is_err(), "{}", src);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+//! Semantic analysis.
+//!
+//! Before an AST can be translated it needs to be checked for mistakes the
+//! parser can't catch, like trying to add a number to a boolean. The checks
+//! also make any implicit conversions explicit, so later phases never need
+//! to guess what type a value is.
+//!
+//! Lambdas are inlined into the built-ins they're passed to before anything
+//! else happens. Quantities with physical units are then checked for
+//! dimensional consistency, after which they're converted to SI units and
+//! the units erased.
+
+mod dimensions;
+mod lambdas;
+mod typeck;
+
+pub use self::dimensions::{check_dimensions, DimensionError};
+pub use self::lambdas::inline_lambdas;
+pub use self::typeck::{type_check, TypeError};
@@ -0,0 +1,1006 @@
+use builtins::{self, ComplexSupport};
+use environment::Environment;
+use sema::dimensions::{check_dimensions, DimensionError};
+use sema::lambdas::inline_lambdas;
+use syntax::{
+    Array, Atom, BinaryOp, Cast, Conditional, Expr, FunctionCall, Index, Integral, Let, Op,
+    Series, SeriesKind, Solve, Span, Type, UnaryOp, UnaryOperator,
+};
+
+/// Infer the type of an expression, returning a copy of the tree with a
+/// `Cast` inserted wherever a value needs to be converted.
+///
+/// Integers are implicitly promoted to floats when the two are mixed, and
+/// `/` always does floating point division. Integer division (`//`) and
+/// modulo (`%`) only accept integers.
+///
+/// Lambdas are inlined (see [`inline_lambdas()`]), then any units are
+/// checked and erased (see [`check_dimensions()`]) before types are
+/// inferred.
+///
+/// Arrays may be used in intermediate calculations, but the final result
+/// must be a single value (e.g. by reducing the array with `sum()`).
+///
+/// The variable in a sum or product is an integer which shadows any
+/// constant with the same name inside the body. Likewise, the variable of
+/// integration and the variable being solved for are floats, and a variable
+/// bound by a `Let` has the same type as its value.
+///
+/// [`inline_lambdas()`]: fn.inline_lambdas.html
+/// [`check_dimensions()`]: fn.check_dimensions.html
+pub fn type_check(expr: &Expr, env: &Environment) -> Result<(Expr, Type), TypeError> {
+    let expr = inline_lambdas(expr)?;
+    let (expr, _) = check_dimensions(&expr)?;
+    let checker = TypeChecker {
+        env,
+        scope: Vec::new(),
+    };
+    let (expr, ty) = checker.check(&expr)?;
+
+    if ty.is_array() {
+        return Err(TypeError::ArrayResult { span: expr.span() });
+    }
+
+    Ok((expr, ty))
+}
+
+struct TypeChecker<'env> {
+    env: &'env Environment,
+    /// The variables bound by enclosing sums, products, integrals and
+    /// `Let`s.
+    scope: Vec<(String, Type)>,
+}
+
+impl<'env> TypeChecker<'env> {
+    fn check(&self, expr: &Expr) -> Result<(Expr, Type), TypeError> {
+        match *expr {
+            Expr::Atom(ref atom, span) => self.check_atom(atom, span),
+            Expr::BinaryOp(ref op) => self.check_binary_op(op),
+            Expr::UnaryOp(ref op) => self.check_unary_op(op),
+            Expr::Conditional(ref cond) => self.check_conditional(cond),
+            Expr::FunctionCall(ref call) => self.check_function_call(call),
+            Expr::Cast(ref cast) => {
+                let (value, _) = self.check(&cast.value)?;
+                let expr = Expr::from(Cast::new(value, cast.from, cast.to)).with_span(cast.span);
+                Ok((expr, cast.to))
+            }
+            Expr::UnitAnnotation(_) => unreachable!("Units are erased before type checking"),
+            Expr::Array(ref array) => self.check_array(array),
+            Expr::Index(ref index) => self.check_index(index),
+            Expr::Series(ref series) => self.check_series(series),
+            Expr::Integral(ref integral) => self.check_integral(integral),
+            Expr::Solve(ref solve) => self.check_solve(solve),
+            Expr::Let(ref binding) => self.check_let(binding),
+            Expr::Lambda(_) => unreachable!("Lambdas are inlined before type checking"),
+        }
+    }
+
+    /// Identifiers refer to either a variable bound by an enclosing sum,
+    /// product, integral, `solve()` or `Let`, or a constant (which is replaced with
+    /// its value).
+    fn check_atom(&self, atom: &Atom, span: Span) -> Result<(Expr, Type), TypeError> {
+        let ty = match *atom {
+            Atom::Number(_) => Type::Float,
+            Atom::Integer(_) => Type::Integer,
+            Atom::Imaginary(_) => Type::Complex,
+            Atom::Boolean(_) => Type::Bool,
+            Atom::Ident(ref name) if self.lookup(name).is_some() => self.lookup(name).unwrap(),
+            Atom::Ident(ref name) => {
+                let value = self.env
+                    .constant(name)
+                    .or_else(|| builtins::constant(name).map(|c| c.value))
+                    .ok_or_else(|| TypeError::UnknownIdentifier {
+                        name: name.clone(),
+                        span,
+                    })?;
+
+                return Ok((Expr::Atom(Atom::Number(value), span), Type::Float));
+            }
+        };
+
+        Ok((Expr::Atom(atom.clone(), span), ty))
+    }
+
+    fn lookup(&self, name: &str) -> Option<Type> {
+        self.scope
+            .iter()
+            .rev()
+            .find(|&&(ref variable, _)| variable == name)
+            .map(|&(_, ty)| ty)
+    }
+
+    /// Create a checker for the body of something which binds a variable.
+    fn with_variable(&self, name: &str, ty: Type) -> TypeChecker<'env> {
+        let mut scope = self.scope.clone();
+        scope.push((name.to_string(), ty));
+
+        TypeChecker {
+            env: self.env,
+            scope,
+        }
+    }
+
+    fn check_binary_op(&self, op: &BinaryOp) -> Result<(Expr, Type), TypeError> {
+        let (left, left_ty) = self.check(&op.left)?;
+        let (right, right_ty) = self.check(&op.right)?;
+
+        let invalid = TypeError::InvalidOperands {
+            op: op.op,
+            left: left_ty,
+            right: right_ty,
+            span: op.span,
+        };
+
+        if left_ty.is_array() || right_ty.is_array() {
+            return array_binary_op(op, (left, left_ty), (right, right_ty), invalid);
+        }
+
+        let (operand_ty, result_ty) = match op.op {
+            _ if op.op.is_logical() => {
+                if left_ty != Type::Bool || right_ty != Type::Bool {
+                    return Err(invalid);
+                }
+                (Type::Bool, Type::Bool)
+            }
+            Op::Equal | Op::NotEqual if left_ty == Type::Bool && right_ty == Type::Bool => {
+                (Type::Bool, Type::Bool)
+            }
+            Op::Equal | Op::NotEqual => {
+                let ty = numeric_supertype(left_ty, right_ty).ok_or(invalid)?;
+                (ty, Type::Bool)
+            }
+            _ if op.op.is_comparison() => {
+                // complex numbers aren't ordered
+                let ty = numeric_supertype(left_ty, right_ty)
+                    .filter(|&ty| ty != Type::Complex)
+                    .ok_or(invalid)?;
+                (ty, Type::Bool)
+            }
+            Op::Divide => match numeric_supertype(left_ty, right_ty).ok_or(invalid)? {
+                Type::Complex => (Type::Complex, Type::Complex),
+                _ => (Type::Float, Type::Float),
+            },
+            _ if op.op.is_integer_only() => {
+                if left_ty != Type::Integer || right_ty != Type::Integer {
+                    return Err(invalid);
+                }
+                (Type::Integer, Type::Integer)
+            }
+            _ => {
+                let ty = numeric_supertype(left_ty, right_ty).ok_or(invalid)?;
+                (ty, ty)
+            }
+        };
+
+        let left = coerce(left, left_ty, operand_ty);
+        let right = coerce(right, right_ty, operand_ty);
+        let expr = Expr::from(BinaryOp::new(left, right, op.op)).with_span(op.span);
+
+        Ok((expr, result_ty))
+    }
+
+    fn check_unary_op(&self, op: &UnaryOp) -> Result<(Expr, Type), TypeError> {
+        let (value, ty) = self.check(&op.value)?;
+
+        match op.op {
+            UnaryOperator::Not if ty != Type::Bool => Err(TypeError::InvalidOperand {
+                op: op.op,
+                ty,
+                span: op.span,
+            }),
+            UnaryOperator::Not => {
+                let expr = Expr::from(UnaryOp::new(value, op.op)).with_span(op.span);
+                Ok((expr, Type::Bool))
+            }
+        }
+    }
+
+    fn check_conditional(&self, cond: &Conditional) -> Result<(Expr, Type), TypeError> {
+        let (condition, condition_ty) = self.check(&cond.condition)?;
+        if condition_ty != Type::Bool {
+            return Err(TypeError::NonBooleanCondition {
+                found: condition_ty,
+                span: condition.span(),
+            });
+        }
+
+        let (if_true, true_ty) = self.check(&cond.if_true)?;
+        let (if_false, false_ty) = self.check(&cond.if_false)?;
+
+        let ty = if true_ty == false_ty {
+            true_ty
+        } else {
+            numeric_supertype(true_ty, false_ty).ok_or(TypeError::IncompatibleBranches {
+                if_true: true_ty,
+                if_false: false_ty,
+                span: cond.span,
+            })?
+        };
+
+        let if_true = coerce(if_true, true_ty, ty);
+        let if_false = coerce(if_false, false_ty, ty);
+        let expr = Expr::from(Conditional::new(condition, if_true, if_false)).with_span(cond.span);
+
+        Ok((expr, ty))
+    }
+
+    /// Every element is converted to a float.
+    fn check_array(&self, array: &Array) -> Result<(Expr, Type), TypeError> {
+        if array.elements.is_empty() {
+            return Err(TypeError::EmptyArray { span: array.span });
+        }
+
+        let mut elements = Vec::new();
+
+        for element in &array.elements {
+            let (element, ty) = self.check(element)?;
+            match ty {
+                Type::Integer | Type::Float => elements.push(coerce(element, ty, Type::Float)),
+                _ => {
+                    return Err(TypeError::InvalidElement {
+                        found: ty,
+                        span: element.span(),
+                    })
+                }
+            }
+        }
+
+        let len = elements.len();
+        let expr = Expr::from(Array::new(elements)).with_span(array.span);
+
+        Ok((expr, Type::Array(len)))
+    }
+
+    fn check_index(&self, index: &Index) -> Result<(Expr, Type), TypeError> {
+        let (array, array_ty) = self.check(&index.array)?;
+        let len = match array_ty {
+            Type::Array(len) => len,
+            other => {
+                return Err(TypeError::NotAnArray {
+                    found: other,
+                    span: array.span(),
+                })
+            }
+        };
+
+        let (position, position_ty) = self.check(&index.index)?;
+        if position_ty != Type::Integer {
+            return Err(TypeError::InvalidIndex {
+                found: position_ty,
+                span: position.span(),
+            });
+        }
+
+        // constant indices can be checked now instead of at runtime
+        if let Expr::Atom(Atom::Integer(i), span) = position {
+            if i < 0 || i as u64 >= len as u64 {
+                return Err(TypeError::IndexOutOfBounds {
+                    index: i,
+                    length: len,
+                    span,
+                });
+            }
+        }
+
+        let expr = Expr::from(Index::new(array, position)).with_span(index.span);
+
+        Ok((expr, Type::Float))
+    }
+
+    /// The bounds are checked in the enclosing scope, while the body is
+    /// checked with the variable in scope. Every term is converted to a
+    /// float.
+    fn check_series(&self, series: &Series) -> Result<(Expr, Type), TypeError> {
+        let start = self.check_bound(&series.start)?;
+        let end = self.check_bound(&series.end)?;
+
+        let inner = self.with_variable(&series.variable, Type::Integer);
+        let (body, body_ty) = inner.check(&series.body)?;
+        match body_ty {
+            Type::Integer | Type::Float => {}
+            other => {
+                return Err(TypeError::InvalidTerm {
+                    kind: series.kind,
+                    found: other,
+                    span: body.span(),
+                })
+            }
+        }
+
+        let body = coerce(body, body_ty, Type::Float);
+        let series = Series {
+            span: series.span,
+            ..Series::new(series.kind, series.variable.clone(), start, end, body)
+        };
+
+        Ok((series.into(), Type::Float))
+    }
+
+    fn check_let(&self, binding: &Let) -> Result<(Expr, Type), TypeError> {
+        let (value, value_ty) = self.check(&binding.value)?;
+        let inner = self.with_variable(&binding.variable, value_ty);
+        let (body, ty) = inner.check(&binding.body)?;
+
+        let binding = Let {
+            span: binding.span,
+            ..Let::new(binding.variable.clone(), value, body)
+        };
+
+        Ok((binding.into(), ty))
+    }
+
+    /// The bounds and body of an integral are converted to floats.
+    fn check_integral(&self, integral: &Integral) -> Result<(Expr, Type), TypeError> {
+        let invalid = |found, span| TypeError::InvalidIntegral { found, span };
+        let lower = self.check_real(&integral.lower, invalid)?;
+        let upper = self.check_real(&integral.upper, invalid)?;
+
+        let inner = self.with_variable(&integral.variable, Type::Float);
+        let body = inner.check_real(&integral.body, invalid)?;
+
+        let integral = Integral {
+            span: integral.span,
+            ..Integral::new(body, integral.variable.clone(), lower, upper)
+        };
+
+        Ok((integral.into(), Type::Float))
+    }
+
+    /// Both sides of the equation are converted to floats.
+    fn check_solve(&self, solve: &Solve) -> Result<(Expr, Type), TypeError> {
+        let invalid = |found, span| TypeError::InvalidEquation { found, span };
+        let guess = self.check_real(&solve.guess, invalid)?;
+
+        let sides = match solve.equation {
+            Expr::BinaryOp(ref op) if op.op == Op::Equal => op,
+            ref other => return Err(TypeError::NotAnEquation { span: other.span() }),
+        };
+        let inner = self.with_variable(&solve.variable, Type::Float);
+        let left = inner.check_real(&sides.left, invalid)?;
+        let right = inner.check_real(&sides.right, invalid)?;
+        let equation = Expr::from(BinaryOp::new(left, right, Op::Equal)).with_span(sides.span);
+
+        let solve = Solve {
+            span: solve.span,
+            ..Solve::new(equation, solve.variable.clone(), guess)
+        };
+
+        Ok((solve.into(), Type::Float))
+    }
+
+    /// Check something which must be a real number, converting it to a
+    /// float.
+    fn check_real<F>(&self, expr: &Expr, invalid: F) -> Result<Expr, TypeError>
+    where
+        F: Fn(Type, Span) -> TypeError,
+    {
+        let (expr, ty) = self.check(expr)?;
+
+        match ty {
+            Type::Integer | Type::Float => Ok(coerce(expr, ty, Type::Float)),
+            other => Err(invalid(other, expr.span())),
+        }
+    }
+
+    fn check_bound(&self, bound: &Expr) -> Result<Expr, TypeError> {
+        let (bound, ty) = self.check(bound)?;
+
+        if ty == Type::Integer {
+            Ok(bound)
+        } else {
+            Err(TypeError::InvalidBound {
+                found: ty,
+                span: bound.span(),
+            })
+        }
+    }
+
+    /// Functions are `fn(float...) -> float`, with functions registered with
+    /// the `Environment` taking precedence over built-ins. Some built-ins
+    /// also accept complex numbers, in which case every argument is promoted
+    /// to a complex number.
+    fn check_function_call(&self, call: &FunctionCall) -> Result<(Expr, Type), TypeError> {
+        if self.env.function(&call.name).is_none()
+            && builtins::reduction(&call.name, call.arguments.len()).is_some()
+        {
+            return self.check_reduction(call);
+        }
+
+        // functions without a real implementation only accept complex numbers
+        let (arity, complex, complex_only) = match self.env.function(&call.name) {
+            Some(host) => (host.arity(), ComplexSupport::RealOnly, false),
+            None => builtins::lookup(&call.name)
+                .map(|b| (b.arity, b.complex, b.intrinsic.is_none()))
+                .ok_or_else(|| TypeError::UnknownFunction {
+                    name: call.name.clone(),
+                    span: call.span,
+                })?,
+        };
+
+        if arity != call.arguments.len() {
+            return Err(TypeError::WrongArity {
+                name: call.name.clone(),
+                expected: arity,
+                found: call.arguments.len(),
+                span: call.span,
+            });
+        }
+
+        let mut arguments = Vec::new();
+
+        for arg in &call.arguments {
+            let (arg, ty) = self.check(arg)?;
+            if !ty.is_numeric() {
+                return Err(TypeError::NonNumericArgument {
+                    function: call.name.clone(),
+                    found: ty,
+                    span: arg.span(),
+                });
+            }
+            if ty == Type::Complex && complex == ComplexSupport::RealOnly {
+                return Err(TypeError::ComplexArgument {
+                    function: call.name.clone(),
+                    span: arg.span(),
+                });
+            }
+
+            arguments.push((arg, ty));
+        }
+
+        let is_complex = complex_only || arguments.iter().any(|&(_, ty)| ty == Type::Complex);
+        let (argument_ty, return_ty) = match complex {
+            ComplexSupport::ToReal if is_complex => (Type::Complex, Type::Float),
+            ComplexSupport::ToComplex if is_complex => (Type::Complex, Type::Complex),
+            _ => (Type::Float, Type::Float),
+        };
+
+        let arguments: Vec<Expr> = arguments
+            .into_iter()
+            .map(|(arg, ty)| coerce(arg, ty, argument_ty))
+            .collect();
+
+        let call = FunctionCall {
+            span: call.span,
+            ..FunctionCall::new(call.name.clone(), arguments)
+        };
+
+        Ok((call.into(), return_ty))
+    }
+
+    /// Reductions (e.g. `sum()` and `dot()`) take arrays which all have the
+    /// same length, and return a float.
+    fn check_reduction(&self, call: &FunctionCall) -> Result<(Expr, Type), TypeError> {
+        let mut arguments = Vec::new();
+        let mut expected = None;
+
+        for arg in &call.arguments {
+            let (arg, ty) = self.check(arg)?;
+            let len = match ty {
+                Type::Array(len) => len,
+                other => {
+                    return Err(TypeError::NotAnArray {
+                        found: other,
+                        span: arg.span(),
+                    })
+                }
+            };
+
+            match expected {
+                Some(expected) if expected != len => {
+                    return Err(TypeError::LengthMismatch {
+                        function: call.name.clone(),
+                        expected,
+                        found: len,
+                        span: arg.span(),
+                    })
+                }
+                _ => expected = Some(len),
+            }
+
+            arguments.push(arg);
+        }
+
+        let call = FunctionCall {
+            span: call.span,
+            ..FunctionCall::new(call.name.clone(), arguments)
+        };
+
+        Ok((call.into(), Type::Float))
+    }
+}
+
+/// Arithmetic on arrays is done element-wise, with a number being used for
+/// every element when combined with an array (broadcasting).
+fn array_binary_op(
+    op: &BinaryOp,
+    left: (Expr, Type),
+    right: (Expr, Type),
+    invalid: TypeError,
+) -> Result<(Expr, Type), TypeError> {
+    match op.op {
+        Op::Add | Op::Subtract | Op::Multiply | Op::Divide => {}
+        _ => return Err(invalid),
+    }
+
+    let len = match (left.1, right.1) {
+        (Type::Array(l), Type::Array(r)) if l == r => l,
+        (Type::Array(len), Type::Integer)
+        | (Type::Array(len), Type::Float)
+        | (Type::Integer, Type::Array(len))
+        | (Type::Float, Type::Array(len)) => len,
+        _ => return Err(invalid),
+    };
+
+    let left = broadcast(left);
+    let right = broadcast(right);
+    let expr = Expr::from(BinaryOp::new(left, right, op.op)).with_span(op.span);
+
+    Ok((expr, Type::Array(len)))
+}
+
+/// Make sure a number used alongside an array is a float.
+fn broadcast((expr, ty): (Expr, Type)) -> Expr {
+    if ty.is_array() {
+        expr
+    } else {
+        coerce(expr, ty, Type::Float)
+    }
+}
+
+/// The type both operands should be converted to, if they're numbers.
+fn numeric_supertype(left: Type, right: Type) -> Option<Type> {
+    match (left, right) {
+        (Type::Integer, Type::Integer) => Some(Type::Integer),
+        (Type::Complex, r) if r.is_numeric() => Some(Type::Complex),
+        (l, Type::Complex) if l.is_numeric() => Some(Type::Complex),
+        (l, r) if l.is_numeric() && r.is_numeric() => Some(Type::Float),
+        _ => None,
+    }
+}
+
+fn coerce(expr: Expr, from: Type, to: Type) -> Expr {
+    if from == to {
+        expr
+    } else {
+        Cast::new(expr, from, to).into()
+    }
+}
+
+/// The ways type checking can fail.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum TypeError {
+    /// A binary operator was applied to operands of the wrong type.
+    #[fail(
+        display = "Can't apply \"{}\" to {} and {} at {}",
+        op, left, right, span
+    )]
+    InvalidOperands {
+        /// The operator.
+        op: Op,
+        /// The left operand's type.
+        left: Type,
+        /// The right operand's type.
+        right: Type,
+        /// Where the operation is.
+        span: Span,
+    },
+    /// A unary operator was applied to an operand of the wrong type.
+    #[fail(display = "Can't apply \"{}\" to {} at {}", op, ty, span)]
+    InvalidOperand {
+        /// The operator.
+        op: UnaryOperator,
+        /// The operand's type.
+        ty: Type,
+        /// Where the operation is.
+        span: Span,
+    },
+    /// The condition in an `if` wasn't a `bool`.
+    #[fail(display = "Expected a bool condition but found {} at {}", found, span)]
+    NonBooleanCondition {
+        /// The condition's type.
+        found: Type,
+        /// Where the condition is.
+        span: Span,
+    },
+    /// The two branches of an `if` can't be converted to a common type.
+    #[fail(
+        display = "The branches of the conditional at {} have incompatible types ({} and {})",
+        span, if_true, if_false
+    )]
+    IncompatibleBranches {
+        /// The type of the "true" branch.
+        if_true: Type,
+        /// The type of the "false" branch.
+        if_false: Type,
+        /// Where the conditional is.
+        span: Span,
+    },
+    /// A function was passed something other than a number.
+    #[fail(
+        display = "Expected a number to pass to \"{}\" but found {} at {}",
+        function, found, span
+    )]
+    NonNumericArgument {
+        /// The function being called.
+        function: String,
+        /// The argument's type.
+        found: Type,
+        /// Where the argument is.
+        span: Span,
+    },
+    /// Passing a complex number to a function which only accepts real
+    /// numbers.
+    #[fail(
+        display = "\"{}\" doesn't accept complex numbers (at {})",
+        function, span
+    )]
+    ComplexArgument {
+        /// The function being called.
+        function: String,
+        /// Where the argument is.
+        span: Span,
+    },
+    /// Calling a function which doesn't exist.
+    #[fail(display = "Unknown function \"{}\" at {}", name, span)]
+    UnknownFunction {
+        /// The function's name.
+        name: String,
+        /// Where the function call is.
+        span: Span,
+    },
+    /// Calling a function with the wrong number of arguments.
+    #[fail(
+        display = "\"{}\" expects {} arguments but was called with {} at {}",
+        name, expected, found, span
+    )]
+    WrongArity {
+        /// The function's name.
+        name: String,
+        /// The number of arguments the function accepts.
+        expected: usize,
+        /// The number of arguments provided.
+        found: usize,
+        /// Where the function call is.
+        span: Span,
+    },
+    /// An identifier which doesn't refer to anything.
+    #[fail(display = "Unknown identifier \"{}\" at {}", name, span)]
+    UnknownIdentifier {
+        /// The identifier.
+        name: String,
+        /// Where the identifier is.
+        span: Span,
+    },
+    /// The final result was an array instead of a single value.
+    #[fail(
+        display = "The result at {} is an array, try reducing it with a function like sum()",
+        span
+    )]
+    ArrayResult {
+        /// Where the expression is.
+        span: Span,
+    },
+    /// An array literal without any elements.
+    #[fail(display = "Arrays can't be empty (at {})", span)]
+    EmptyArray {
+        /// Where the array is.
+        span: Span,
+    },
+    /// An array element which isn't a real number.
+    #[fail(
+        display = "Array elements must be real numbers but found {} at {}",
+        found, span
+    )]
+    InvalidElement {
+        /// The element's type.
+        found: Type,
+        /// Where the element is.
+        span: Span,
+    },
+    /// Indexing into (or reducing) something which isn't an array.
+    #[fail(display = "Expected an array but found {} at {}", found, span)]
+    NotAnArray {
+        /// The value's type.
+        found: Type,
+        /// Where the value is.
+        span: Span,
+    },
+    /// Arrays can only be indexed using integers.
+    #[fail(display = "Expected an integer index but found {} at {}", found, span)]
+    InvalidIndex {
+        /// The index's type.
+        found: Type,
+        /// Where the index is.
+        span: Span,
+    },
+    /// A constant index which is past the end of the array.
+    #[fail(
+        display = "Index {} is out of bounds for an array of length {} at {}",
+        index, length, span
+    )]
+    IndexOutOfBounds {
+        /// The index.
+        index: i64,
+        /// The array's length.
+        length: usize,
+        /// Where the index is.
+        span: Span,
+    },
+    /// Passing arrays with different lengths to a reduction like `dot()`.
+    #[fail(
+        display = "\"{}\" expected an array of length {} but found {} at {}",
+        function, expected, found, span
+    )]
+    LengthMismatch {
+        /// The function being called.
+        function: String,
+        /// The length of the first array.
+        expected: usize,
+        /// The length of this array.
+        found: usize,
+        /// Where the array is.
+        span: Span,
+    },
+    /// A lambda which isn't being passed to a built-in like `map()`.
+    #[fail(
+        display = "Functions can only be passed to built-ins like map() (at {})",
+        span
+    )]
+    UnexpectedLambda {
+        /// Where the lambda is.
+        span: Span,
+    },
+    /// A function like `map()` was given something which isn't obviously an
+    /// array, so we don't know how many times to call its lambda.
+    #[fail(
+        display = "\"{}\" needs an array literal or arithmetic on arrays but found {}",
+        function, span
+    )]
+    UnknownLength {
+        /// The function being called.
+        function: String,
+        /// Where the argument is.
+        span: Span,
+    },
+    /// The bounds of a sum or product must be integers.
+    #[fail(display = "Expected an integer bound but found {} at {}", found, span)]
+    InvalidBound {
+        /// The bound's type.
+        found: Type,
+        /// Where the bound is.
+        span: Span,
+    },
+    /// The terms of a sum or product must be real numbers.
+    #[fail(
+        display = "The terms of {}() must be real numbers but found {} at {}",
+        kind, found, span
+    )]
+    InvalidTerm {
+        /// Whether it's a sum or a product.
+        kind: SeriesKind,
+        /// The body's type.
+        found: Type,
+        /// Where the body is.
+        span: Span,
+    },
+    /// The bounds and body of an integral must be real numbers.
+    #[fail(
+        display = "Integrals only work with real numbers but found {} at {}",
+        found, span
+    )]
+    InvalidIntegral {
+        /// The offending type.
+        found: Type,
+        /// Where the expression is.
+        span: Span,
+    },
+    /// Both sides of an equation, and the initial guess, must be real
+    /// numbers.
+    #[fail(
+        display = "solve() only works with real numbers but found {} at {}",
+        found, span
+    )]
+    InvalidEquation {
+        /// The offending type.
+        found: Type,
+        /// Where the expression is.
+        span: Span,
+    },
+    /// Passing `solve()` something other than an equation like `x * x == 2`.
+    #[fail(display = "Expected an equation like \"x * x == 2\" at {}", span)]
+    NotAnEquation {
+        /// Where the expression is.
+        span: Span,
+    },
+    /// The expression's units don't make sense.
+    #[fail(display = "{}", _0)]
+    Dimension(#[cause] DimensionError),
+}
+
+impl From<DimensionError> for TypeError {
+    fn from(other: DimensionError) -> TypeError {
+        TypeError::Dimension(other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntax;
+
+    fn check(src: &str) -> Result<(Expr, Type), TypeError> {
+        let ast = syntax::parse(src).unwrap();
+        type_check(&ast, &Environment::new())
+    }
+
+    #[test]
+    fn infer_the_type_of_simple_expressions() {
+        let inputs = vec![
+            ("1 + 2", Type::Integer),
+            ("1.5 * 2.0", Type::Float),
+            ("1 + 2.5", Type::Float),
+            ("7 / 2", Type::Float),
+            ("7 // 2 % 3", Type::Integer),
+            ("1 < 2", Type::Bool),
+            ("true == (1 > 2)", Type::Bool),
+            ("if true then 1 else 2", Type::Integer),
+            ("if true then 1 else 2.0", Type::Float),
+            ("sin(1)", Type::Float),
+            ("pow(2, 0.5)", Type::Float),
+            ("3 + 4i", Type::Complex),
+            ("2.5 / 1i", Type::Complex),
+            ("1i == 1", Type::Bool),
+            ("abs(3 + 4i)", Type::Float),
+            ("sqrt(0 - 4i)", Type::Complex),
+            ("conj(2)", Type::Complex),
+            ("re(2)", Type::Float),
+            ("2 m * 3 m", Type::Integer),
+            ("3 km + 20 m", Type::Float),
+            ("[1, 2.5][0]", Type::Float),
+            ("sum([1, 2] * 2 + [3, 4])", Type::Float),
+            ("dot([1, 2], 1 / [3, 4])", Type::Float),
+            ("max([1, 2]) + min(1, 2)", Type::Float),
+            ("sum(i, 1, 10, i)", Type::Float),
+            ("prod(k, 1, 3 + 2, k // 2) * sum(pi, 0, 2, pi)", Type::Float),
+            ("sum(n, 1, 3, sum(i, n, 2 * n, 1 / i))", Type::Float),
+            ("sum(map(x -> x // 2, [1, 2]))", Type::Float),
+            ("max(map(x -> x * pi, map(x -> x + 1, [1, 2] * 2)))", Type::Float),
+            ("integrate(x * 2, x, 0, 1)", Type::Float),
+            ("integrate(t -> integrate(x * t, x, 0, t), 0, pi)", Type::Float),
+            ("sum(k, 1, 3, integrate(x, x, 0, k))", Type::Float),
+            ("solve(x * x == 2, x, 1)", Type::Float),
+            ("solve(r -> pow(1 + r, 10) == 2, 0.1) * 100", Type::Float),
+        ];
+
+        for (src, should_be) in inputs {
+            let (_, got) = check(src).unwrap();
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn mixed_arithmetic_inserts_a_cast() {
+        let (got, _) = check("1 + 2.5").unwrap();
+
+        let should_be = BinaryOp::add(
+            Cast::new(Atom::from(1).into(), Type::Integer, Type::Float).into(),
+            Atom::from(2.5).into(),
+        );
+        assert_eq!(got.without_spans(), Expr::from(should_be));
+    }
+
+    #[test]
+    fn adding_a_bool_to_an_integer_is_an_error() {
+        let got = check("2 * (true + 1)").unwrap_err();
+
+        match got {
+            TypeError::InvalidOperands {
+                op,
+                left,
+                right,
+                span,
+            } => {
+                assert_eq!(op, Op::Add);
+                assert_eq!((left, right), (Type::Bool, Type::Integer));
+                assert_eq!((span.start, span.end), (5, 13));
+            }
+            other => panic!("Unexpected error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn host_functions_are_checked_against_their_arity() {
+        let mut env = Environment::new();
+        env.register_closure("interest", 2, |args| args[0] * args[1])
+            .register_closure("sin", 2, |args| args[0] + args[1]);
+
+        let inputs = vec![
+            ("interest(0.05, 10)", true),
+            ("interest(0.05)", false),
+            ("sin(1, 2)", true),
+            ("sin(1)", false),
+        ];
+
+        for (src, ok) in inputs {
+            let ast = syntax::parse(src).unwrap();
+            let got = type_check(&ast, &env);
+            assert_eq!(got.is_ok(), ok, "{}", src);
+        }
+    }
+
+    #[test]
+    fn constants_are_replaced_with_their_value() {
+        let mut env = Environment::new();
+        env.register_constant("rate", 0.25).register_constant("pi", 3.0);
+
+        let ast = syntax::parse("rate * tau + pi").unwrap();
+        let (got, ty) = type_check(&ast, &env).unwrap();
+
+        let should_be = BinaryOp::add(
+            BinaryOp::mult(
+                Atom::from(0.25).into(),
+                Atom::from(2.0 * ::std::f64::consts::PI).into(),
+            ).into(),
+            Atom::from(3.0).into(),
+        );
+        assert_eq!(got.without_spans(), Expr::from(should_be));
+        assert_eq!(ty, Type::Float);
+    }
+
+    #[test]
+    fn detect_other_type_errors() {
+        let inputs = vec![
+            "not 1",
+            "1 and true",
+            "7.5 // 2",
+            "7 % 2.0",
+            "if 1 then 2 else 3",
+            "if true then 2 else false",
+            "sin(true)",
+            "sin(1, 2)",
+            "foo(1)",
+            "x + 1",
+            "1i < 2",
+            "sin(1i)",
+            "7 // 2i",
+            "1 m + 1 s",
+            "[1, 2]",
+            "[]",
+            "[1, true]",
+            "[1, 2][3]",
+            "[1, 2][0.5]",
+            "pi[0]",
+            "[1, 2] + [1, 2, 3]",
+            "[1, 2] < 3",
+            "[1, 2] + 1i",
+            "sin([1, 2])",
+            "sum(3)",
+            "dot([1], [1, 2])",
+            "sum(i, 1.5, 3, i)",
+            "sum(i, 1, true, i)",
+            "sum(i, 1, 3, i < 2)",
+            "prod(i, 1, 3, 1i)",
+            "sum(i, 1, 3, j)",
+            "sum(i, 1, i, i)",
+            "i + sum(i, 1, 2, i)",
+            "map(x -> x, [1, 2])",
+            "sum(map(x -> x // 2, [1, 2] * 1.5))",
+            "sum(map(x -> x < 1, [1, 2]))",
+            "integrate(x, x, 0, 1i)",
+            "integrate(x < 1, x, 0, 1)",
+            "integrate(x -> x // 2, 0, 1)",
+            "integrate(x, x, 0, [1, 2])",
+            "integrate(x, x, 0, x)",
+            "sum(i, 1, 3, integrate(x, x, 0, x // i))",
+            "solve(x * x, x, 1)",
+            "solve(x == 1i, x, 1)",
+            "solve(x == 1, x, true)",
+            "solve(x -> x // 2 == 1, 1)",
+            "solve(x == 1, x, x)",
+        ];
+
+        for src in inputs {
+            assert!(check(src).is_err(), "{}", src);
+        }
+    }
+}
@@ -0,0 +1,359 @@
+//! Find the value of a variable which satisfies an equation.
+//!
+//! Newton's method is tried first, using the derivative of the compiled
+//! equation. If that doesn't converge (e.g. because the derivative is zero,
+//! or couldn't be worked out), we search outwards from the initial guess
+//! for a range where the equation changes sign and narrow it down using
+//! Brent's method.
+//!
+//! The same solver is used by the `solve()` built-in, which calls back into
+//! Rust from JIT compiled code.
+//!
+//! ```rust
+//! use calc::environment::Environment;
+//!
+//! let equation = calc::syntax::parse("pow(1 + rate, 10) == 2").unwrap();
+//! let rate = calc::solve::solve(&equation, "rate", 0.05, &Environment::new()).unwrap();
+//!
+//! assert!((rate - 0.071773).abs() < 1e-6);
+//! ```
+
+use failure::Error;
+#[cfg(feature = "llvm")]
+use inkwell::context::Context;
+use std::f64;
+use std::os::raw::c_void;
+
+use backend::{ErrorLocation, Jit, Status};
+use environment::Environment;
+use sema;
+use syntax::{Atom, Expr, Solve};
+
+/// The symbol LLVM compiled code uses to invoke the solver.
+#[cfg(feature = "llvm")]
+pub(crate) const SOLVER: &str = "calc.solve";
+
+/// Find a value for `variable` which satisfies `equation` (e.g.
+/// `x * x == 2`), starting the search from `guess`.
+///
+/// The equation is JIT compiled with runtime checks enabled, exactly as if
+/// it had been passed to the `solve()` built-in, so it may use anything
+/// registered with the `Environment`. LLVM is used when it's available,
+/// otherwise Cranelift.
+pub fn solve(equation: &Expr, variable: &str, guess: f64, env: &Environment) -> Result<f64, Error> {
+    let span = equation.span();
+    let solve = Solve::new(equation.clone(), variable, Atom::Number(guess).into());
+    let (ast, _) = sema::type_check(&Expr::from(solve).with_span(span), env)?;
+
+    #[cfg(feature = "llvm")]
+    let ctx = Context::create();
+    #[cfg(feature = "llvm")]
+    let jit = ::trans::Compiler::new(&ctx);
+    #[cfg(not(feature = "llvm"))]
+    let jit = ::cranelift::Compiler::new();
+
+    let program = jit.with_runtime_checks(true)
+        .with_environment(env)
+        .build(&ast)?;
+    Ok(program.call()?)
+}
+
+/// The signature of the functions the compiler generates for the residual
+/// of an equation (`left - right`) and its derivative.
+///
+/// They take a pointer to write the result to, a pointer to write the
+/// location of any error to, the variable's value, and a pointer to the
+/// values of any variables from enclosing scopes. Like `calc_main`, the
+/// return value is a [`Status`] code.
+///
+/// [`Status`]: ../backend/enum.Status.html
+pub(crate) type Residual =
+    unsafe extern "C" fn(*mut f64, *mut ErrorLocation, f64, *const c_void) -> u32;
+
+/// The function JIT compiled code calls to solve an equation, writing the
+/// solution to `root` and returning a `Status` code.
+///
+/// An error from the residual is passed straight back to the caller, but an
+/// error from the derivative just means Newton's method can't be used.
+pub(crate) extern "C" fn calc_solve(
+    residual: Residual,
+    derivative: Option<Residual>,
+    captures: *const c_void,
+    guess: f64,
+    root: *mut f64,
+    location: *mut ErrorLocation,
+) -> u32 {
+    let call = |func: Residual, x: f64| {
+        let mut value = 0.0;
+        match unsafe { func(&mut value, location, x, captures) } {
+            0 => Ok(value),
+            status => Err(status),
+        }
+    };
+    let f = |x| call(residual, x);
+    let df = |x| derivative.and_then(|d| call(d, x).ok());
+
+    match find_root(f, df, guess) {
+        Ok(Some(x)) => {
+            unsafe { *root = x };
+            Status::Ok as u32
+        }
+        Ok(None) => Status::NoSolution as u32,
+        Err(status) => status,
+    }
+}
+
+const MAX_ITERATIONS: usize = 100;
+/// How many times the search range is doubled while looking for a sign
+/// change.
+const MAX_EXPANSIONS: usize = 60;
+/// The relative accuracy we aim for.
+const TOLERANCE: f64 = 1e-14;
+
+/// Find a root of `f` near `guess`, where `df` gives the derivative of `f`
+/// (if it's known). Any error from `f` aborts the search.
+fn find_root<F, D, E>(mut f: F, mut df: D, guess: f64) -> Result<Option<f64>, E>
+where
+    F: FnMut(f64) -> Result<f64, E>,
+    D: FnMut(f64) -> Option<f64>,
+{
+    if let Some(root) = newton(&mut f, &mut df, guess)? {
+        return Ok(Some(root));
+    }
+
+    match bracket(&mut f, guess)? {
+        Some((a, b)) => brent(&mut f, a, b),
+        None => Ok(None),
+    }
+}
+
+/// Follow the tangent line until the steps become negligible, giving up if
+/// we land somewhere the function or its slope isn't usable.
+fn newton<F, D, E>(f: &mut F, df: &mut D, guess: f64) -> Result<Option<f64>, E>
+where
+    F: FnMut(f64) -> Result<f64, E>,
+    D: FnMut(f64) -> Option<f64>,
+{
+    let mut x = guess;
+
+    for _ in 0..MAX_ITERATIONS {
+        let fx = f(x)?;
+        if fx == 0.0 {
+            return Ok(Some(x));
+        }
+
+        let slope = match df(x) {
+            Some(slope) if slope.is_finite() && slope != 0.0 => slope,
+            _ => return Ok(None),
+        };
+        let step = fx / slope;
+        let next = x - step;
+
+        if !next.is_finite() {
+            return Ok(None);
+        }
+        if step.abs() <= TOLERANCE * next.abs().max(1.0) {
+            return Ok(Some(next));
+        }
+
+        x = next;
+    }
+
+    Ok(None)
+}
+
+/// Search outwards from `guess` for a range where `f` changes sign.
+fn bracket<F, E>(f: &mut F, guess: f64) -> Result<Option<(f64, f64)>, E>
+where
+    F: FnMut(f64) -> Result<f64, E>,
+{
+    let f_guess = f(guess)?;
+    let mut width = guess.abs().max(1.0) / 100.0;
+
+    for _ in 0..MAX_EXPANSIONS {
+        let (a, b) = (guess - width, guess + width);
+        let (fa, fb) = (f(a)?, f(b)?);
+
+        if changes_sign(fa, f_guess) {
+            return Ok(Some((a, guess)));
+        }
+        if changes_sign(f_guess, fb) {
+            return Ok(Some((guess, b)));
+        }
+        if changes_sign(fa, fb) {
+            return Ok(Some((a, b)));
+        }
+
+        width *= 2.0;
+    }
+
+    Ok(None)
+}
+
+fn changes_sign(a: f64, b: f64) -> bool {
+    a.is_finite() && b.is_finite() && (a <= 0.0) != (b <= 0.0)
+}
+
+/// Brent's method, which combines bisection with inverse quadratic
+/// interpolation so it's both fast and guaranteed to converge.
+fn brent<F, E>(f: &mut F, mut a: f64, mut b: f64) -> Result<Option<f64>, E>
+where
+    F: FnMut(f64) -> Result<f64, E>,
+{
+    let mut fa = f(a)?;
+    let mut fb = f(b)?;
+    let (mut c, mut fc) = (b, fb);
+    let mut d = b - a;
+    let mut e = d;
+
+    for _ in 0..MAX_ITERATIONS {
+        // keep the root between b and c
+        if (fb > 0.0) == (fc > 0.0) {
+            c = a;
+            fc = fa;
+            d = b - a;
+            e = d;
+        }
+        // and make b the best estimate so far
+        if fc.abs() < fb.abs() {
+            a = b;
+            b = c;
+            c = a;
+            fa = fb;
+            fb = fc;
+            fc = fa;
+        }
+
+        let tolerance = 2.0 * f64::EPSILON * b.abs() + 0.5 * TOLERANCE;
+        let midpoint = 0.5 * (c - b);
+        if midpoint.abs() <= tolerance || fb == 0.0 {
+            return Ok(Some(b));
+        }
+
+        if e.abs() >= tolerance && fa.abs() > fb.abs() {
+            // try interpolating
+            let s = fb / fa;
+            let (mut p, mut q) = if a == c {
+                (2.0 * midpoint * s, 1.0 - s)
+            } else {
+                let q = fa / fc;
+                let r = fb / fc;
+                let p = s * (2.0 * midpoint * q * (q - r) - (b - a) * (r - 1.0));
+                (p, (q - 1.0) * (r - 1.0) * (s - 1.0))
+            };
+            if p > 0.0 {
+                q = -q;
+            } else {
+                p = -p;
+            }
+
+            let limit = (3.0 * midpoint * q - (tolerance * q).abs()).min((e * q).abs());
+            if 2.0 * p < limit {
+                e = d;
+                d = p / q;
+            } else {
+                d = midpoint;
+                e = d;
+            }
+        } else {
+            // fall back to bisection
+            d = midpoint;
+            e = d;
+        }
+
+        a = b;
+        fa = fb;
+        b += if d.abs() > tolerance {
+            d
+        } else if midpoint > 0.0 {
+            tolerance
+        } else {
+            -tolerance
+        };
+        fb = f(b)?;
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::RuntimeError;
+    use syntax;
+
+    fn root<F, D>(f: F, df: D, guess: f64) -> Option<f64>
+    where
+        F: Fn(f64) -> f64,
+        D: Fn(f64) -> Option<f64>,
+    {
+        find_root(|x| Ok::<f64, ()>(f(x)), df, guess).unwrap()
+    }
+
+    #[test]
+    fn newtons_method_uses_the_derivative() {
+        let mut calls = 0;
+        let got = find_root(
+            |x| {
+                calls += 1;
+                Ok::<f64, ()>(x * x - 2.0)
+            },
+            |x| Some(2.0 * x),
+            1.0,
+        );
+
+        assert!((got.unwrap().unwrap() - 2.0_f64.sqrt()).abs() < 1e-14);
+        assert!(calls < 10);
+    }
+
+    #[test]
+    fn fall_back_to_brents_method() {
+        let inputs: Vec<(fn(f64) -> f64, f64, f64)> = vec![
+            (|x| x * x * x - 8.0, 0.0, 2.0),
+            (|x| x.floor() + x / 4.0 - 2.6, 0.0, 2.4),
+            (|x| x.cbrt(), 5.0, 0.0),
+        ];
+
+        for (f, guess, should_be) in inputs {
+            let got = root(f, |_| None, guess).unwrap();
+            assert!((got - should_be).abs() < 1e-12, "{} != {}", got, should_be);
+        }
+
+        // Newton's method diverges for the cube root, so Brent's method has
+        // to take over even though the derivative is known
+        let got = root(|x| x.cbrt(), |x| Some(x.cbrt() / (3.0 * x)), 5.0).unwrap();
+        assert!(got.abs() < 1e-12);
+    }
+
+    #[test]
+    fn give_up_when_there_is_no_solution() {
+        assert_eq!(root(|x| x * x + 1.0, |x| Some(2.0 * x), 3.0), None);
+        assert_eq!(root(|x| x.abs() + 1.0, |_| None, 0.0), None);
+
+        let got = find_root(|_| Err("broken"), |_| None, 1.0);
+        assert_eq!(got, Err("broken"));
+    }
+
+    #[test]
+    fn solve_a_parsed_equation() {
+        let mut env = Environment::new();
+        env.register_closure("npv", 1, |args| {
+            let rate = args[0];
+            (1..=5)
+                .map(|year| 300.0 / (1.0 + rate).powi(year))
+                .sum::<f64>()
+                - 1000.0
+        });
+
+        let equation = syntax::parse("npv(r) == 0").unwrap();
+        let got = solve(&equation, "r", 0.1, &env).unwrap();
+        assert!((got - 0.152_382).abs() < 1e-6, "{}", got);
+
+        let equation = syntax::parse("sqrt(x) == 0 - 1").unwrap();
+        let err = solve(&equation, "x", 1.0, &env).unwrap_err();
+        match err.downcast::<RuntimeError>() {
+            Ok(RuntimeError::DomainError { .. }) | Ok(RuntimeError::NoSolution { .. }) => {}
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -1,17 +1,233 @@
+use std::fmt::{self, Display, Formatter};
+
+use syntax::visit::Visitor;
+
+/// The location of a node in the original source text, as a pair of byte
+/// indices.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span {
+    /// The index of the first byte.
+    pub start: usize,
+    /// The index one past the last byte.
+    pub end: usize,
+}
+
+impl Span {
+    /// Create a new `Span`.
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// The type of a value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Type {
+    /// Either `true` or `false`.
+    Bool,
+    /// A 64-bit signed integer.
+    Integer,
+    /// A 64-bit floating point number.
+    Float,
+    /// A complex number made from two 64-bit floats.
+    Complex,
+    /// A fixed-length array of 64-bit floats.
+    Array(usize),
+}
+
+impl Type {
+    /// Is this a numeric type?
+    pub fn is_numeric(&self) -> bool {
+        match *self {
+            Type::Integer | Type::Float | Type::Complex => true,
+            Type::Bool | Type::Array(_) => false,
+        }
+    }
+
+    /// Is this an array type?
+    pub fn is_array(&self) -> bool {
+        match *self {
+            Type::Array(_) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Type::Bool => write!(f, "bool"),
+            Type::Integer => write!(f, "integer"),
+            Type::Float => write!(f, "float"),
+            Type::Complex => write!(f, "complex"),
+            Type::Array(len) => write!(f, "array[{}]", len),
+        }
+    }
+}
+
 /// A `calc` expression.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Expr {
     /// A `FunctionCall` node.
     FunctionCall(FunctionCall),
-    /// An `Atom` node.
-    Atom(Atom),
+    /// An `Atom` node and its location.
+    Atom(Atom, Span),
     /// A `BinaryOp` node.
     BinaryOp(Box<BinaryOp>),
+    /// A `UnaryOp` node.
+    UnaryOp(Box<UnaryOp>),
+    /// A `Conditional` node.
+    Conditional(Box<Conditional>),
+    /// A `Cast` node.
+    Cast(Box<Cast>),
+    /// A `UnitAnnotation` node.
+    UnitAnnotation(Box<UnitAnnotation>),
+    /// An `Array` literal.
+    Array(Array),
+    /// An `Index` node.
+    Index(Box<Index>),
+    /// A `Series` node.
+    Series(Box<Series>),
+    /// A `Lambda` node.
+    Lambda(Box<Lambda>),
+    /// An `Integral` node.
+    Integral(Box<Integral>),
+    /// A `Solve` node.
+    Solve(Box<Solve>),
+    /// A `Let` node.
+    Let(Box<Let>),
+}
+
+impl Expr {
+    /// Where this expression is in the source text.
+    pub fn span(&self) -> Span {
+        match *self {
+            Expr::FunctionCall(ref f) => f.span,
+            Expr::Atom(_, span) => span,
+            Expr::BinaryOp(ref b) => b.span,
+            Expr::UnaryOp(ref u) => u.span,
+            Expr::Conditional(ref c) => c.span,
+            Expr::Cast(ref c) => c.span,
+            Expr::UnitAnnotation(ref u) => u.span,
+            Expr::Array(ref a) => a.span,
+            Expr::Index(ref i) => i.span,
+            Expr::Series(ref s) => s.span,
+            Expr::Lambda(ref l) => l.span,
+            Expr::Integral(ref i) => i.span,
+            Expr::Solve(ref s) => s.span,
+            Expr::Let(ref l) => l.span,
+        }
+    }
+
+    /// Set the location of the outermost node in this expression.
+    pub fn with_span(mut self, span: Span) -> Expr {
+        match self {
+            Expr::FunctionCall(ref mut f) => f.span = span,
+            Expr::Atom(_, ref mut s) => *s = span,
+            Expr::BinaryOp(ref mut b) => b.span = span,
+            Expr::UnaryOp(ref mut u) => u.span = span,
+            Expr::Conditional(ref mut c) => c.span = span,
+            Expr::Cast(ref mut c) => c.span = span,
+            Expr::UnitAnnotation(ref mut u) => u.span = span,
+            Expr::Array(ref mut a) => a.span = span,
+            Expr::Index(ref mut i) => i.span = span,
+            Expr::Series(ref mut s) => s.span = span,
+            Expr::Lambda(ref mut l) => l.span = span,
+            Expr::Integral(ref mut i) => i.span = span,
+            Expr::Solve(ref mut s) => s.span = span,
+            Expr::Let(ref mut l) => l.span = span,
+        }
+
+        self
+    }
+
+    /// Turn a function call into the node for whichever special form it's
+    /// written as (e.g. `sum(k, 1, n, k)` is a `Series`), if any.
+    pub fn from_call(call: FunctionCall) -> Expr {
+        Series::from_call(call)
+            .map(Expr::from)
+            .or_else(|call| Integral::from_call(call).map(Expr::from))
+            .or_else(|call| Solve::from_call(call).map(Expr::from))
+            .unwrap_or_else(Expr::from)
+    }
+
+    /// Reset every span in the tree, so parsed expressions can be compared
+    /// against trees built by hand.
+    #[cfg(test)]
+    pub(crate) fn without_spans(mut self) -> Expr {
+        strip_spans(&mut self);
+        self
+    }
+}
+
+#[cfg(test)]
+fn strip_spans(expr: &mut Expr) {
+    let placeholder = Expr::from(Atom::Boolean(false));
+    *expr = ::std::mem::replace(expr, placeholder).with_span(Span::default());
+
+    match *expr {
+        Expr::Atom(..) => {}
+        Expr::FunctionCall(ref mut f) => {
+            for arg in &mut f.arguments {
+                strip_spans(arg);
+            }
+        }
+        Expr::BinaryOp(ref mut b) => {
+            strip_spans(&mut b.left);
+            strip_spans(&mut b.right);
+        }
+        Expr::UnaryOp(ref mut u) => strip_spans(&mut u.value),
+        Expr::Conditional(ref mut c) => {
+            strip_spans(&mut c.condition);
+            strip_spans(&mut c.if_true);
+            strip_spans(&mut c.if_false);
+        }
+        Expr::Cast(ref mut c) => strip_spans(&mut c.value),
+        Expr::UnitAnnotation(ref mut u) => strip_spans(&mut u.value),
+        Expr::Array(ref mut a) => {
+            for element in &mut a.elements {
+                strip_spans(element);
+            }
+        }
+        Expr::Index(ref mut i) => {
+            strip_spans(&mut i.array);
+            strip_spans(&mut i.index);
+        }
+        Expr::Series(ref mut s) => {
+            strip_spans(&mut s.start);
+            strip_spans(&mut s.end);
+            strip_spans(&mut s.body);
+        }
+        Expr::Lambda(ref mut l) => strip_spans(&mut l.body),
+        Expr::Integral(ref mut i) => {
+            strip_spans(&mut i.lower);
+            strip_spans(&mut i.upper);
+            strip_spans(&mut i.body);
+        }
+        Expr::Solve(ref mut s) => {
+            strip_spans(&mut s.equation);
+            strip_spans(&mut s.guess);
+        }
+        Expr::Let(ref mut l) => {
+            strip_spans(&mut l.value);
+            strip_spans(&mut l.body);
+        }
+    }
 }
 
 impl From<Atom> for Expr {
     fn from(other: Atom) -> Expr {
-        Expr::Atom(other)
+        Expr::Atom(other, Span::default())
     }
 }
 
@@ -27,8 +243,75 @@ impl From<FunctionCall> for Expr {
     }
 }
 
+impl From<UnaryOp> for Expr {
+    fn from(other: UnaryOp) -> Expr {
+        Expr::UnaryOp(Box::new(other))
+    }
+}
+
+impl From<Conditional> for Expr {
+    fn from(other: Conditional) -> Expr {
+        Expr::Conditional(Box::new(other))
+    }
+}
+
+impl From<Cast> for Expr {
+    fn from(other: Cast) -> Expr {
+        Expr::Cast(Box::new(other))
+    }
+}
+
+impl From<UnitAnnotation> for Expr {
+    fn from(other: UnitAnnotation) -> Expr {
+        Expr::UnitAnnotation(Box::new(other))
+    }
+}
+
+impl From<Array> for Expr {
+    fn from(other: Array) -> Expr {
+        Expr::Array(other)
+    }
+}
+
+impl From<Index> for Expr {
+    fn from(other: Index) -> Expr {
+        Expr::Index(Box::new(other))
+    }
+}
+
+impl From<Series> for Expr {
+    fn from(other: Series) -> Expr {
+        Expr::Series(Box::new(other))
+    }
+}
+
+impl From<Lambda> for Expr {
+    fn from(other: Lambda) -> Expr {
+        Expr::Lambda(Box::new(other))
+    }
+}
+
+impl From<Integral> for Expr {
+    fn from(other: Integral) -> Expr {
+        Expr::Integral(Box::new(other))
+    }
+}
+
+impl From<Solve> for Expr {
+    fn from(other: Solve) -> Expr {
+        Expr::Solve(Box::new(other))
+    }
+}
+
+impl From<Let> for Expr {
+    fn from(other: Let) -> Expr {
+        Expr::Let(Box::new(other))
+    }
+}
+
 /// A binary operation.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct BinaryOp {
     /// What kind of operation is this?
     pub op: Op,
@@ -36,12 +319,19 @@ pub struct BinaryOp {
     pub left: Expr,
     /// The right operand.
     pub right: Expr,
+    /// Where the operation is in the source text.
+    pub span: Span,
 }
 
 impl BinaryOp {
     /// Create a new `BinaryOp`.
     pub fn new(left: Expr, right: Expr, op: Op) -> BinaryOp {
-        BinaryOp { left, right, op }
+        BinaryOp {
+            left,
+            right,
+            op,
+            span: Span::default(),
+        }
     }
 
     /// Create an addition operation.
@@ -63,10 +353,32 @@ impl BinaryOp {
     pub fn div(left: Expr, right: Expr) -> BinaryOp {
         BinaryOp::new(left, right, Op::Divide)
     }
+
+    /// Create an integer division operation.
+    pub fn int_div(left: Expr, right: Expr) -> BinaryOp {
+        BinaryOp::new(left, right, Op::IntegerDivide)
+    }
+
+    /// Create a modulo operation.
+    pub fn modulo(left: Expr, right: Expr) -> BinaryOp {
+        BinaryOp::new(left, right, Op::Modulo)
+    }
+
+    /// Create a logical "and" operation.
+    pub fn and(left: Expr, right: Expr) -> BinaryOp {
+        BinaryOp::new(left, right, Op::And)
+    }
+
+    /// Create a logical "or" operation.
+    pub fn or(left: Expr, right: Expr) -> BinaryOp {
+        BinaryOp::new(left, right, Op::Or)
+    }
 }
 
 /// The kind of operation in a `BinaryOp`.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Op {
     /// Addition.
     Add,
@@ -76,13 +388,667 @@ pub enum Op {
     Multiply,
     /// Subtraction.
     Subtract,
+    /// Integer division, rounding towards zero.
+    IntegerDivide,
+    /// The remainder after integer division.
+    Modulo,
+    /// `<`
+    LessThan,
+    /// `<=`
+    LessThanOrEqual,
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `>`
+    GreaterThan,
+    /// `>=`
+    GreaterThanOrEqual,
+    /// Logical "and".
+    And,
+    /// Logical "or".
+    Or,
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let symbol = match *self {
+            Op::Add => "+",
+            Op::Divide => "/",
+            Op::Multiply => "*",
+            Op::Subtract => "-",
+            Op::IntegerDivide => "//",
+            Op::Modulo => "%",
+            Op::LessThan => "<",
+            Op::LessThanOrEqual => "<=",
+            Op::Equal => "==",
+            Op::NotEqual => "!=",
+            Op::GreaterThan => ">",
+            Op::GreaterThanOrEqual => ">=",
+            Op::And => "and",
+            Op::Or => "or",
+        };
+
+        write!(f, "{}", symbol)
+    }
+}
+
+impl Op {
+    /// Is this a comparison operator (e.g. `<` or `==`)?
+    pub fn is_comparison(&self) -> bool {
+        match *self {
+            Op::LessThan
+            | Op::LessThanOrEqual
+            | Op::Equal
+            | Op::NotEqual
+            | Op::GreaterThan
+            | Op::GreaterThanOrEqual => true,
+            _ => false,
+        }
+    }
+
+    /// Does this operator only accept integers?
+    pub fn is_integer_only(&self) -> bool {
+        match *self {
+            Op::IntegerDivide | Op::Modulo => true,
+            _ => false,
+        }
+    }
+
+    /// Is this a logical operator (`and` or `or`)?
+    pub fn is_logical(&self) -> bool {
+        match *self {
+            Op::And | Op::Or => true,
+            _ => false,
+        }
+    }
+}
+
+/// An operation with a single operand.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnaryOp {
+    /// What kind of operation is this?
+    pub op: UnaryOperator,
+    /// The operand.
+    pub value: Expr,
+    /// Where the operation is in the source text.
+    pub span: Span,
+}
+
+impl UnaryOp {
+    /// Create a new `UnaryOp`.
+    pub fn new(value: Expr, op: UnaryOperator) -> UnaryOp {
+        UnaryOp {
+            value,
+            op,
+            span: Span::default(),
+        }
+    }
+
+    /// Create a logical negation.
+    pub fn not(value: Expr) -> UnaryOp {
+        UnaryOp::new(value, UnaryOperator::Not)
+    }
+}
+
+/// The kind of operation in a `UnaryOp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum UnaryOperator {
+    /// Logical negation.
+    Not,
+}
+
+impl Display for UnaryOperator {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            UnaryOperator::Not => write!(f, "not"),
+        }
+    }
+}
+
+/// An `if condition then if_true else if_false` expression.
+///
+/// Only the branch selected by the condition gets evaluated.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Conditional {
+    /// The condition to check.
+    pub condition: Expr,
+    /// The value when the condition is true.
+    pub if_true: Expr,
+    /// The value when the condition is false.
+    pub if_false: Expr,
+    /// Where the conditional is in the source text.
+    pub span: Span,
+}
+
+impl Conditional {
+    /// Create a new `Conditional`.
+    pub fn new(condition: Expr, if_true: Expr, if_false: Expr) -> Conditional {
+        Conditional {
+            condition,
+            if_true,
+            if_false,
+            span: Span::default(),
+        }
+    }
+}
+
+/// An explicit conversion from one type to another.
+///
+/// These can't be written by hand, instead they are inserted by the type
+/// checker wherever a value needs to be converted (e.g. adding an integer to
+/// a float).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Cast {
+    /// The value being converted.
+    pub value: Expr,
+    /// The value's original type.
+    pub from: Type,
+    /// The type to convert to.
+    pub to: Type,
+    /// The location of the value being converted.
+    pub span: Span,
+}
+
+impl Cast {
+    /// Create a new `Cast`, inheriting the location of the value being
+    /// converted.
+    pub fn new(value: Expr, from: Type, to: Type) -> Cast {
+        let span = value.span();
+
+        Cast {
+            value,
+            from,
+            to,
+            span,
+        }
+    }
+}
+
+/// A quantity with a physical unit attached (e.g. `9.81 m/s^2`).
+///
+/// Units only exist until dimension checking, which converts the value to SI
+/// units and replaces the annotation with a plain number.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnitAnnotation {
+    /// The quantity's numeric value.
+    pub value: Expr,
+    /// The unit, as written in the source text (e.g. `km/h`).
+    pub unit: String,
+    /// Where the annotated quantity is in the source text.
+    pub span: Span,
+}
+
+impl UnitAnnotation {
+    /// Create a new `UnitAnnotation`.
+    pub fn new<S: Into<String>>(value: Expr, unit: S) -> UnitAnnotation {
+        UnitAnnotation {
+            value,
+            unit: unit.into(),
+            span: Span::default(),
+        }
+    }
+}
+
+/// An array literal (e.g. `[1, 2, 3]`).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Array {
+    /// The array's elements.
+    pub elements: Vec<Expr>,
+    /// Where the array is in the source text.
+    pub span: Span,
+}
+
+impl Array {
+    /// Create a new `Array`.
+    pub fn new<A>(elements: A) -> Array
+    where
+        A: IntoIterator<Item = Expr>,
+    {
+        Array {
+            elements: elements.into_iter().collect(),
+            span: Span::default(),
+        }
+    }
+}
+
+/// Getting a single element from an array (e.g. `xs[2]`).
+///
+/// Arrays are indexed from zero.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Index {
+    /// The array being indexed.
+    pub array: Expr,
+    /// Which element to get.
+    pub index: Expr,
+    /// Where the indexing operation is in the source text.
+    pub span: Span,
+}
+
+impl Index {
+    /// Create a new `Index`.
+    pub fn new(array: Expr, index: Expr) -> Index {
+        Index {
+            array,
+            index,
+            span: Span::default(),
+        }
+    }
+}
+
+/// A sum or product over a range of integers (e.g. `sum(k, 1, n, 1 / k)`).
+///
+/// The variable is only in scope inside the body, and both bounds are
+/// inclusive. An empty range gives `0` for a sum and `1` for a product.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Series {
+    /// Is this a sum or a product?
+    pub kind: SeriesKind,
+    /// The name of the variable bound inside the body.
+    pub variable: String,
+    /// The variable's first value.
+    pub start: Expr,
+    /// The variable's last value.
+    pub end: Expr,
+    /// The expression for each term.
+    pub body: Expr,
+    /// Where the series is in the source text.
+    pub span: Span,
+}
+
+impl Series {
+    /// Create a new `Series`.
+    pub fn new<S: Into<String>>(
+        kind: SeriesKind,
+        variable: S,
+        start: Expr,
+        end: Expr,
+        body: Expr,
+    ) -> Series {
+        Series {
+            kind,
+            variable: variable.into(),
+            start,
+            end,
+            body,
+            span: Span::default(),
+        }
+    }
+
+    /// Recognise a call to `sum()` or `prod()` which has a variable, two
+    /// bounds and a body, handing the call back if it's something else
+    /// (e.g. summing an array).
+    pub fn from_call(call: FunctionCall) -> Result<Series, FunctionCall> {
+        let kind = SeriesKind::from_name(&call.name);
+        let variable = match call.arguments.first() {
+            Some(&Expr::Atom(Atom::Ident(ref name), _)) => Some(name.clone()),
+            _ => None,
+        };
+
+        match (kind, variable) {
+            (Some(kind), Some(variable)) if call.arguments.len() == 4 => {
+                let span = call.span;
+                let mut args = call.arguments.into_iter().skip(1);
+                let start = args.next().unwrap();
+                let end = args.next().unwrap();
+                let body = args.next().unwrap();
+
+                Ok(Series {
+                    span,
+                    ..Series::new(kind, variable, start, end, body)
+                })
+            }
+            _ => Err(call),
+        }
+    }
+
+    /// Get the body with every use of the variable replaced by `value`.
+    pub fn term(&self, value: i64) -> Expr {
+        let mut body = self.body.clone();
+        substitute(&mut body, &self.variable, &Atom::Integer(value).into());
+        body
+    }
+}
+
+/// A definite integral (e.g. `integrate(x * x, x, 0, 1)`), where the
+/// variable is only in scope inside the body.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Integral {
+    /// The expression being integrated.
+    pub body: Expr,
+    /// The variable of integration.
+    pub variable: String,
+    /// The lower bound.
+    pub lower: Expr,
+    /// The upper bound.
+    pub upper: Expr,
+    /// Where the integral is in the source text.
+    pub span: Span,
+}
+
+impl Integral {
+    /// Create a new `Integral`.
+    pub fn new<S: Into<String>>(body: Expr, variable: S, lower: Expr, upper: Expr) -> Integral {
+        Integral {
+            body,
+            variable: variable.into(),
+            lower,
+            upper,
+            span: Span::default(),
+        }
+    }
+
+    /// Recognise a call to `integrate()` which has a body, a variable and
+    /// two bounds, handing the call back if it's something else.
+    pub fn from_call(call: FunctionCall) -> Result<Integral, FunctionCall> {
+        let variable = match call.arguments.get(1) {
+            Some(&Expr::Atom(Atom::Ident(ref name), _)) => Some(name.clone()),
+            _ => None,
+        };
+
+        match variable {
+            Some(variable) if call.name == "integrate" && call.arguments.len() == 4 => {
+                let span = call.span;
+                let mut args = call.arguments.into_iter();
+                let body = args.next().unwrap();
+                let lower = args.nth(1).unwrap();
+                let upper = args.next().unwrap();
+
+                Ok(Integral {
+                    span,
+                    ..Integral::new(body, variable, lower, upper)
+                })
+            }
+            _ => Err(call),
+        }
+    }
+}
+
+/// Find the value of a variable which satisfies an equation (e.g.
+/// `solve(x * x == 2, x, 1)`), starting the search from an initial guess.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Solve {
+    /// The equation, which should be of the form `left == right`.
+    pub equation: Expr,
+    /// The variable being solved for.
+    pub variable: String,
+    /// Where to start looking for a solution.
+    pub guess: Expr,
+    /// Where the `solve()` is in the source text.
+    pub span: Span,
+}
+
+impl Solve {
+    /// Create a new `Solve`.
+    pub fn new<S: Into<String>>(equation: Expr, variable: S, guess: Expr) -> Solve {
+        Solve {
+            equation,
+            variable: variable.into(),
+            guess,
+            span: Span::default(),
+        }
+    }
+
+    /// Recognise a call to `solve()` which has an equation, a variable and
+    /// a guess, handing the call back if it's something else.
+    pub fn from_call(call: FunctionCall) -> Result<Solve, FunctionCall> {
+        let variable = match call.arguments.get(1) {
+            Some(&Expr::Atom(Atom::Ident(ref name), _)) => Some(name.clone()),
+            _ => None,
+        };
+
+        match variable {
+            Some(variable) if call.name == "solve" && call.arguments.len() == 3 => {
+                let span = call.span;
+                let mut args = call.arguments.into_iter();
+                let equation = args.next().unwrap();
+                let guess = args.nth(1).unwrap();
+
+                Ok(Solve {
+                    span,
+                    ..Solve::new(equation, variable, guess)
+                })
+            }
+            _ => Err(call),
+        }
+    }
+}
+
+/// Evaluate a value once, and bind it to a variable which is only in scope
+/// inside the body.
+///
+/// There's no syntax for this. It's created when inlining a lambda would
+/// otherwise evaluate its argument more than once (e.g. each element of
+/// `map(x -> x * x, [f(1), f(2)])`).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Let {
+    /// The name of the variable bound inside the body.
+    pub variable: String,
+    /// The variable's value.
+    pub value: Expr,
+    /// The expression the variable is used in.
+    pub body: Expr,
+    /// The location of the code this was created from.
+    pub span: Span,
+}
+
+impl Let {
+    /// Create a new `Let`.
+    pub fn new<S: Into<String>>(variable: S, value: Expr, body: Expr) -> Let {
+        Let {
+            variable: variable.into(),
+            value,
+            body,
+            span: Span::default(),
+        }
+    }
+
+    /// Get the body with every use of the variable replaced by the value.
+    pub fn inlined(&self) -> Expr {
+        let mut body = self.body.clone();
+        substitute(&mut body, &self.variable, &self.value);
+        body
+    }
+}
+
+/// An anonymous function with a single parameter (e.g. `x -> x * x`).
+///
+/// Lambdas can only be passed to built-ins like `map()`, and are always
+/// inlined before type checking.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Lambda {
+    /// The parameter's name.
+    pub parameter: String,
+    /// The function's body.
+    pub body: Expr,
+    /// Where the lambda is in the source text.
+    pub span: Span,
+}
+
+impl Lambda {
+    /// Create a new `Lambda`.
+    pub fn new<S: Into<String>>(parameter: S, body: Expr) -> Lambda {
+        Lambda {
+            parameter: parameter.into(),
+            body,
+            span: Span::default(),
+        }
+    }
+
+    /// Call the function by substituting the argument into its body.
+    pub fn apply(&self, argument: &Expr) -> Expr {
+        let mut body = self.body.clone();
+        substitute(&mut body, &self.parameter, argument);
+        body
+    }
+}
+
+/// Replace every free use of the variable, `name`, with `replacement`.
+fn substitute(expr: &mut Expr, name: &str, replacement: &Expr) {
+    let is_variable = match *expr {
+        Expr::Atom(Atom::Ident(ref ident), _) => ident == name,
+        _ => false,
+    };
+
+    if is_variable {
+        let span = expr.span();
+        *expr = replacement.clone().with_span(span);
+        return;
+    }
+
+    match *expr {
+        Expr::Atom(..) => {}
+        Expr::FunctionCall(ref mut f) => {
+            for arg in &mut f.arguments {
+                substitute(arg, name, replacement);
+            }
+        }
+        Expr::BinaryOp(ref mut b) => {
+            substitute(&mut b.left, name, replacement);
+            substitute(&mut b.right, name, replacement);
+        }
+        Expr::UnaryOp(ref mut u) => substitute(&mut u.value, name, replacement),
+        Expr::Conditional(ref mut c) => {
+            substitute(&mut c.condition, name, replacement);
+            substitute(&mut c.if_true, name, replacement);
+            substitute(&mut c.if_false, name, replacement);
+        }
+        Expr::Cast(ref mut c) => substitute(&mut c.value, name, replacement),
+        Expr::UnitAnnotation(ref mut u) => substitute(&mut u.value, name, replacement),
+        Expr::Array(ref mut a) => {
+            for element in &mut a.elements {
+                substitute(element, name, replacement);
+            }
+        }
+        Expr::Index(ref mut i) => {
+            substitute(&mut i.array, name, replacement);
+            substitute(&mut i.index, name, replacement);
+        }
+        Expr::Series(ref mut s) => {
+            substitute(&mut s.start, name, replacement);
+            substitute(&mut s.end, name, replacement);
+            substitute_bound(&mut s.variable, &mut s.body, name, replacement);
+        }
+        Expr::Lambda(ref mut l) => {
+            substitute_bound(&mut l.parameter, &mut l.body, name, replacement)
+        }
+        Expr::Integral(ref mut i) => {
+            substitute(&mut i.lower, name, replacement);
+            substitute(&mut i.upper, name, replacement);
+            substitute_bound(&mut i.variable, &mut i.body, name, replacement);
+        }
+        Expr::Solve(ref mut s) => {
+            substitute(&mut s.guess, name, replacement);
+            substitute_bound(&mut s.variable, &mut s.equation, name, replacement);
+        }
+        Expr::Let(ref mut l) => {
+            substitute(&mut l.value, name, replacement);
+            substitute_bound(&mut l.variable, &mut l.body, name, replacement);
+        }
+    }
+}
+
+/// Substitute into the body of something which binds its own `variable`.
+///
+/// The variable shadows `name` if they're the same. Otherwise it gets
+/// renamed if it would capture an identifier in the replacement.
+fn substitute_bound(variable: &mut String, body: &mut Expr, name: &str, replacement: &Expr) {
+    if variable == name {
+        return;
+    }
+
+    if mentions(replacement, variable) {
+        // a quote can't appear in an identifier, so this won't clash with
+        // anything the user wrote
+        let mut fresh = format!("{}'", variable);
+        while mentions(replacement, &fresh) || mentions(body, &fresh) {
+            fresh.push('\'');
+        }
+
+        substitute(body, variable, &Atom::Ident(fresh.clone()).into());
+        *variable = fresh;
+    }
+
+    substitute(body, name, replacement);
+}
+
+/// Does the expression use an identifier called `name`?
+pub(crate) fn mentions(expr: &Expr, name: &str) -> bool {
+    struct Mentions<'a> {
+        name: &'a str,
+        found: bool,
+    }
+
+    impl<'a> Visitor for Mentions<'a> {
+        fn visit_atom(&mut self, atom: &Atom) {
+            if let Atom::Ident(ref ident) = *atom {
+                self.found |= ident == self.name;
+            }
+        }
+    }
+
+    let mut visitor = Mentions { name, found: false };
+    visitor.visit_expr(expr);
+    visitor.found
+}
+
+/// The different kinds of `Series`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum SeriesKind {
+    /// Add the terms together.
+    Sum,
+    /// Multiply the terms together.
+    Product,
+}
+
+impl SeriesKind {
+    /// Get the `SeriesKind` for a function name, `"sum"` or `"prod"`.
+    pub fn from_name(name: &str) -> Option<SeriesKind> {
+        match name {
+            "sum" => Some(SeriesKind::Sum),
+            "prod" => Some(SeriesKind::Product),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SeriesKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            SeriesKind::Sum => write!(f, "sum"),
+            SeriesKind::Product => write!(f, "prod"),
+        }
+    }
 }
 
 /// The most basic construct in the language.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Atom {
     /// A floating point literal.
     Number(f64),
+    /// An integer literal.
+    Integer(i64),
+    /// An imaginary literal (e.g. `4i`).
+    Imaginary(f64),
+    /// Either `true` or `false`.
+    Boolean(bool),
     /// An identifier (e.g. `foo`).
     Ident(String),
 }
@@ -107,17 +1073,32 @@ impl From<f64> for Atom {
 
 impl From<i32> for Atom {
     fn from(other: i32) -> Atom {
-        Atom::Number(other as f64)
+        Atom::Integer(other.into())
+    }
+}
+
+impl From<i64> for Atom {
+    fn from(other: i64) -> Atom {
+        Atom::Integer(other)
+    }
+}
+
+impl From<bool> for Atom {
+    fn from(other: bool) -> Atom {
+        Atom::Boolean(other)
     }
 }
 
 /// A function call.
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FunctionCall {
     /// The function being called.
     pub name: String,
     /// The list of arguments passed to the function call.
     pub arguments: Vec<Expr>,
+    /// Where the function call is in the source text.
+    pub span: Span,
 }
 
 impl FunctionCall {
@@ -130,6 +1111,7 @@ impl FunctionCall {
         FunctionCall {
             name: name.into(),
             arguments: args.into_iter().collect(),
+            span: Span::default(),
         }
     }
 }
@@ -138,13 +1120,14 @@ impl FunctionCall {
 mod tests {
     use super::*;
     use syntax::grammar;
+    use syntax::lexer::Lexer;
 
     #[test]
     fn parse_a_number_atom() {
         let src = "3.14";
         let should_be = Atom::Number(3.14);
 
-        let got = grammar::AtomParser::new().parse(src).unwrap();
+        let got = grammar::AtomParser::new().parse(Lexer::new(src)).unwrap();
         assert_eq!(got, should_be);
     }
 
@@ -153,29 +1136,165 @@ mod tests {
         let src = "x";
         let should_be = Atom::Ident(String::from(src));
 
-        let got = grammar::AtomParser::new().parse(src).unwrap();
+        let got = grammar::AtomParser::new().parse(Lexer::new(src)).unwrap();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_a_complex_number() {
+        let src = "3 + 4i";
+        let should_be = BinaryOp::add(Atom::Integer(3).into(), Atom::Imaginary(4.0).into());
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_a_quantity_with_units() {
+        let src = "9.81 m/s^2 * 3 s";
+        let should_be = BinaryOp::mult(
+            UnitAnnotation::new(Atom::Number(9.81).into(), "m/s^2").into(),
+            UnitAnnotation::new(Atom::Integer(3).into(), "s").into(),
+        );
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_an_array_and_index_into_it() {
+        let src = "[1, 2.5, x][i + 1]";
+        let should_be = Index::new(
+            Array::new(vec![
+                Atom::from(1).into(),
+                Atom::from(2.5).into(),
+                Atom::from("x").into(),
+            ]).into(),
+            BinaryOp::add(Atom::from("i").into(), Atom::from(1).into()).into(),
+        );
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_a_series() {
+        let src = "sum(k, 1, n, x / k) + sum(xs)";
+        let should_be = BinaryOp::add(
+            Series::new(
+                SeriesKind::Sum,
+                "k",
+                Atom::from(1).into(),
+                Atom::from("n").into(),
+                BinaryOp::div(Atom::from("x").into(), Atom::from("k").into()).into(),
+            ).into(),
+            FunctionCall::new("sum", vec![Atom::from("xs").into()]).into(),
+        );
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_an_integral() {
+        let src = "integrate(x * y, x, 0, 1)";
+        let should_be = Integral::new(
+            BinaryOp::mult(Atom::from("x").into(), Atom::from("y").into()).into(),
+            "x",
+            Atom::from(0).into(),
+            Atom::from(1).into(),
+        );
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
         assert_eq!(got, should_be);
     }
 
+    #[test]
+    fn parse_a_solve() {
+        let src = "solve(x * x == 2, x, 1)";
+        let equation = BinaryOp::new(
+            BinaryOp::mult(Atom::from("x").into(), Atom::from("x").into()).into(),
+            Atom::from(2).into(),
+            Op::Equal,
+        );
+        let should_be = Solve::new(equation.into(), "x", Atom::from(1).into());
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_a_lambda() {
+        let src = "map(x -> x * 2, [1])";
+        let should_be = FunctionCall::new(
+            "map",
+            vec![
+                Lambda::new(
+                    "x",
+                    BinaryOp::mult(Atom::from("x").into(), Atom::from(2).into()).into(),
+                ).into(),
+                Array::new(vec![Atom::from(1).into()]).into(),
+            ],
+        );
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn substitution_avoids_capturing_variables() {
+        // x -> sum(k, 1, x, k * x)
+        let lambda = Lambda::new(
+            "x",
+            Series::new(
+                SeriesKind::Sum,
+                "k",
+                Atom::from(1).into(),
+                Atom::from("x").into(),
+                BinaryOp::mult(Atom::from("k").into(), Atom::from("x").into()).into(),
+            ).into(),
+        );
+
+        let got = lambda.apply(&Atom::from("k").into());
+
+        let should_be = Series::new(
+            SeriesKind::Sum,
+            "k'",
+            Atom::from(1).into(),
+            Atom::from("k").into(),
+            BinaryOp::mult(Atom::from("k'").into(), Atom::from("k").into()).into(),
+        );
+        assert_eq!(got, Expr::from(should_be));
+    }
+
     #[test]
     fn parse_a_multiply() {
         let src = "a * 5";
         let should_be = BinaryOp::mult(
             Atom::Ident(String::from("a")).into(),
-            Atom::Number(5.0).into(),
+            Atom::Integer(5).into(),
         );
         let should_be = Expr::from(should_be);
 
-        let got = grammar::ExprParser::new().parse(src).unwrap();
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
         assert_eq!(got, should_be);
     }
 
     #[test]
     fn parse_a_function_call() {
         let src = "sin(90.0)";
-        let should_be = FunctionCall::new("sin", vec![Expr::Atom(Atom::Number(90.0))]);
+        let argument = Expr::Atom(Atom::Number(90.0), Span::new(4, 8));
+        let mut should_be = FunctionCall::new("sin", vec![argument]);
+        should_be.span = Span::new(0, 9);
 
-        let got = grammar::FunctionCallParser::new().parse(src).unwrap();
+        let got = grammar::FunctionCallParser::new().parse(Lexer::new(src)).unwrap();
         assert_eq!(got, should_be);
     }
 
@@ -194,8 +1313,113 @@ mod tests {
         );
         let should_be = Expr::from(should_be);
 
-        let got = grammar::ExprParser::new().parse(src).unwrap();
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
 
         assert_eq!(got, should_be);
     }
+
+    #[test]
+    fn comparisons_bind_looser_than_arithmetic() {
+        let src = "x + 1 <= 2 * y";
+        let should_be = BinaryOp::new(
+            BinaryOp::add(Atom::from("x").into(), Atom::from(1).into()).into(),
+            BinaryOp::mult(Atom::from(2).into(), Atom::from("y").into()).into(),
+            Op::LessThanOrEqual,
+        );
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_boolean_operators() {
+        let src = "not a or b and c";
+        let should_be = BinaryOp::or(
+            UnaryOp::not(Atom::from("a").into()).into(),
+            BinaryOp::and(Atom::from("b").into(), Atom::from("c").into()).into(),
+        );
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_a_conditional() {
+        let src = "if x > 10000 then x * 0.3 else 0";
+        let should_be = Conditional::new(
+            BinaryOp::new(
+                Atom::from("x").into(),
+                Atom::from(10000).into(),
+                Op::GreaterThan,
+            ).into(),
+            BinaryOp::mult(Atom::from("x").into(), Atom::from(0.3).into()).into(),
+            Atom::from(0).into(),
+        );
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn integer_division_and_modulo_bind_like_multiplication() {
+        let src = "1 + 7 // 2 % 3";
+        let should_be = BinaryOp::add(
+            Atom::from(1).into(),
+            BinaryOp::modulo(
+                BinaryOp::int_div(Atom::from(7).into(), Atom::from(2).into()).into(),
+                Atom::from(3).into(),
+            ).into(),
+        );
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn powers_are_right_associative_calls_to_pow() {
+        let src = "2 * x^3^2";
+        let pow = |a, b| Expr::from(FunctionCall::new("pow", vec![a, b]));
+        let should_be = BinaryOp::mult(
+            Atom::from(2).into(),
+            pow(
+                Atom::Ident(String::from("x")).into(),
+                pow(Atom::from(3).into(), Atom::from(2).into()),
+            ),
+        );
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_boolean_literals() {
+        let src = "true or false";
+        let should_be = BinaryOp::or(Atom::from(true).into(), Atom::from(false).into());
+        let should_be = Expr::from(should_be);
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap().without_spans();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn nodes_record_their_location() {
+        let src = "1 + sin(x) * 2";
+
+        let got = grammar::ExprParser::new().parse(Lexer::new(src)).unwrap();
+
+        let add = match got {
+            Expr::BinaryOp(ref b) => b,
+            ref other => panic!("Expected a binary op, found {:?}", other),
+        };
+        assert_eq!((add.span.start, add.span.end), (0, 14));
+        let mult = add.right.span();
+        assert_eq!((mult.start, mult.end), (4, 14));
+        let one = add.left.span();
+        assert_eq!((one.start, one.end), (0, 1));
+    }
 }
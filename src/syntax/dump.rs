@@ -0,0 +1,533 @@
+//! Print an AST in a human (or machine) readable format, mainly for
+//! debugging the parser.
+//!
+//! ```rust
+//! use calc::syntax::dump::{dump, Format};
+//!
+//! let ast = calc::syntax::parse("1 + 2 * x").unwrap();
+//!
+//! assert_eq!(dump(&ast, Format::SExpr), "(+ 1 (* 2 x))");
+//! ```
+
+use failure::Error;
+use std::fmt::{self, Display, Formatter, Write};
+use std::str::FromStr;
+
+use syntax::ast::{Atom, Expr, Span};
+#[cfg(feature = "serde")]
+use syntax::Versioned;
+
+/// The formats an AST can be printed in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// An indented tree with one node per line.
+    Tree,
+    /// S-expressions, e.g. `(+ 1 (* 2 x))`.
+    SExpr,
+    /// A JSON object per node, with its children nested inside. With the
+    /// `serde` feature this is the same encoding as [`Versioned`], so it can
+    /// be read back in.
+    ///
+    /// [`Versioned`]: ../struct.Versioned.html
+    Json,
+    /// A Graphviz `dot` graph.
+    Dot,
+}
+
+impl Format {
+    /// Every output format.
+    pub const ALL: [Format; 4] = [Format::Tree, Format::SExpr, Format::Json, Format::Dot];
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match *self {
+            Format::Tree => "tree",
+            Format::SExpr => "sexpr",
+            Format::Json => "json",
+            Format::Dot => "dot",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format, Error> {
+        Format::ALL
+            .iter()
+            .cloned()
+            .find(|format| format.to_string() == s)
+            .ok_or_else(|| format_err!("Unknown format, \"{}\"", s))
+    }
+}
+
+/// Print an AST in the requested format.
+pub fn dump(expr: &Expr, format: Format) -> String {
+    let mut buffer = String::new();
+
+    let result = match format {
+        Format::Tree => tree(&mut buffer, None, expr, 0),
+        Format::SExpr => sexpr(&mut buffer, expr),
+        Format::Json => json(&mut buffer, expr),
+        Format::Dot => dot(&mut buffer, expr),
+    };
+    result.expect("Writing to a String never fails");
+
+    buffer
+}
+
+/// A format-independent view of a single AST node.
+struct Node<'a> {
+    /// The type of node (e.g. `"BinaryOp"`).
+    kind: &'static str,
+    /// The most important piece of information about the node (e.g. the
+    /// operator), and the name of the field it comes from. Nodes which are
+    /// identified by their kind alone just have a name for S-expressions.
+    head: (Option<&'static str>, String),
+    /// Any other information which isn't a child node.
+    attributes: Vec<(&'static str, String)>,
+    children: Vec<(&'static str, Child<'a>)>,
+    span: Span,
+}
+
+enum Child<'a> {
+    One(&'a Expr),
+    Many(&'a [Expr]),
+}
+
+impl<'a> Node<'a> {
+    fn new(expr: &'a Expr) -> Node<'a> {
+        use self::Child::{Many, One};
+
+        let node = |kind, head: (Option<&'static str>, String), children| Node {
+            kind,
+            head,
+            attributes: Vec::new(),
+            children,
+            span: expr.span(),
+        };
+
+        match *expr {
+            Expr::Atom(ref atom, _) => node("Atom", (Some("value"), atom_text(atom)), vec![]),
+            Expr::BinaryOp(ref op) => node(
+                "BinaryOp",
+                (Some("op"), op.op.to_string()),
+                vec![("left", One(&op.left)), ("right", One(&op.right))],
+            ),
+            Expr::UnaryOp(ref op) => node(
+                "UnaryOp",
+                (Some("op"), op.op.to_string()),
+                vec![("value", One(&op.value))],
+            ),
+            Expr::FunctionCall(ref call) => node(
+                "FunctionCall",
+                (Some("name"), call.name.clone()),
+                vec![("arguments", Many(&call.arguments))],
+            ),
+            Expr::Conditional(ref cond) => node(
+                "Conditional",
+                (None, String::from("if")),
+                vec![
+                    ("condition", One(&cond.condition)),
+                    ("if_true", One(&cond.if_true)),
+                    ("if_false", One(&cond.if_false)),
+                ],
+            ),
+            Expr::Cast(ref cast) => Node {
+                attributes: vec![("from", cast.from.to_string())],
+                ..node(
+                    "Cast",
+                    (Some("to"), cast.to.to_string()),
+                    vec![("value", One(&cast.value))],
+                )
+            },
+            Expr::UnitAnnotation(ref annotation) => node(
+                "UnitAnnotation",
+                (Some("unit"), annotation.unit.clone()),
+                vec![("value", One(&annotation.value))],
+            ),
+            Expr::Array(ref array) => node(
+                "Array",
+                (None, String::from("array")),
+                vec![("elements", Many(&array.elements))],
+            ),
+            Expr::Index(ref index) => node(
+                "Index",
+                (None, String::from("index")),
+                vec![("array", One(&index.array)), ("index", One(&index.index))],
+            ),
+            Expr::Series(ref series) => Node {
+                attributes: vec![("variable", series.variable.clone())],
+                ..node(
+                    "Series",
+                    (Some("kind"), series.kind.to_string()),
+                    vec![
+                        ("start", One(&series.start)),
+                        ("end", One(&series.end)),
+                        ("body", One(&series.body)),
+                    ],
+                )
+            },
+            Expr::Lambda(ref lambda) => Node {
+                attributes: vec![("parameter", lambda.parameter.clone())],
+                ..node(
+                    "Lambda",
+                    (None, String::from("lambda")),
+                    vec![("body", One(&lambda.body))],
+                )
+            },
+            Expr::Integral(ref integral) => Node {
+                attributes: vec![("variable", integral.variable.clone())],
+                ..node(
+                    "Integral",
+                    (None, String::from("integrate")),
+                    vec![
+                        ("lower", One(&integral.lower)),
+                        ("upper", One(&integral.upper)),
+                        ("body", One(&integral.body)),
+                    ],
+                )
+            },
+            Expr::Solve(ref solve) => Node {
+                attributes: vec![("variable", solve.variable.clone())],
+                ..node(
+                    "Solve",
+                    (None, String::from("solve")),
+                    vec![
+                        ("equation", One(&solve.equation)),
+                        ("guess", One(&solve.guess)),
+                    ],
+                )
+            },
+            Expr::Let(ref binding) => Node {
+                attributes: vec![("variable", binding.variable.clone())],
+                ..node(
+                    "Let",
+                    (None, String::from("let")),
+                    vec![("value", One(&binding.value)), ("body", One(&binding.body))],
+                )
+            },
+        }
+    }
+
+    /// Every child node, in order.
+    fn child_nodes(&self) -> Vec<(&'static str, &'a Expr)> {
+        let mut nodes = Vec::new();
+
+        for &(name, ref child) in &self.children {
+            match *child {
+                Child::One(expr) => nodes.push((name, expr)),
+                Child::Many(exprs) => nodes.extend(exprs.iter().map(|expr| (name, expr))),
+            }
+        }
+
+        nodes
+    }
+}
+
+fn atom_text(atom: &Atom) -> String {
+    match *atom {
+        // always include the decimal point so floats and integers differ
+        Atom::Number(n) => format!("{:?}", n),
+        Atom::Integer(i) => i.to_string(),
+        Atom::Imaginary(n) => format!("{:?}i", n),
+        Atom::Boolean(b) => b.to_string(),
+        Atom::Ident(ref name) => name.clone(),
+    }
+}
+
+fn tree<W: Write>(w: &mut W, field: Option<&str>, expr: &Expr, depth: usize) -> fmt::Result {
+    let node = Node::new(expr);
+
+    write!(w, "{:1$}", "", depth * 2)?;
+    if let Some(field) = field {
+        write!(w, "{}: ", field)?;
+    }
+    write!(w, "{} {}", node.kind, node.head.1)?;
+    for &(name, ref value) in &node.attributes {
+        write!(w, " {}={}", name, value)?;
+    }
+    writeln!(w, " @ {}", node.span)?;
+
+    for (name, child) in node.child_nodes() {
+        tree(w, Some(name), child, depth + 1)?;
+    }
+
+    Ok(())
+}
+
+fn sexpr<W: Write>(w: &mut W, expr: &Expr) -> fmt::Result {
+    let node = Node::new(expr);
+    let children = node.child_nodes();
+
+    if node.kind == "Atom" {
+        return write!(w, "{}", node.head.1);
+    }
+
+    write!(w, "({}", node.head.1)?;
+    for &(_, ref value) in &node.attributes {
+        write!(w, " {}", value)?;
+    }
+    for (_, child) in children {
+        write!(w, " ")?;
+        sexpr(w, child)?;
+    }
+    write!(w, ")")
+}
+
+#[cfg(feature = "serde")]
+fn json<W: Write>(w: &mut W, expr: &Expr) -> fmt::Result {
+    let text = ::serde_json::to_string(&Versioned::new(expr.clone()))
+        .expect("An Expr can always be serialized");
+    w.write_str(&text)
+}
+
+#[cfg(not(feature = "serde"))]
+fn json<W: Write>(w: &mut W, expr: &Expr) -> fmt::Result {
+    let node = Node::new(expr);
+
+    write!(w, "{{\"kind\":{}", json_string(node.kind))?;
+    if let Expr::Atom(ref atom, _) = *expr {
+        write!(w, ",\"value\":{}", json_atom(atom))?;
+    } else if let (Some(name), ref value) = node.head {
+        write!(w, ",{}:{}", json_string(name), json_string(value))?;
+    }
+    for &(name, ref value) in &node.attributes {
+        write!(w, ",{}:{}", json_string(name), json_string(value))?;
+    }
+    write!(w, ",\"span\":[{},{}]", node.span.start, node.span.end)?;
+
+    for &(name, ref child) in &node.children {
+        write!(w, ",{}:", json_string(name))?;
+
+        match *child {
+            Child::One(expr) => json(w, expr)?,
+            Child::Many(exprs) => {
+                write!(w, "[")?;
+                for (i, expr) in exprs.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, ",")?;
+                    }
+                    json(w, expr)?;
+                }
+                write!(w, "]")?;
+            }
+        }
+    }
+
+    write!(w, "}}")
+}
+
+/// Numbers and booleans are written as themselves, and anything else as a
+/// string.
+#[cfg(not(feature = "serde"))]
+fn json_atom(atom: &Atom) -> String {
+    match *atom {
+        Atom::Number(n) if n.is_finite() => format!("{:?}", n),
+        // JSON has no infinity or NaN, so do what serde_json does
+        Atom::Number(_) => String::from("null"),
+        Atom::Integer(i) => i.to_string(),
+        Atom::Boolean(b) => b.to_string(),
+        Atom::Imaginary(_) | Atom::Ident(_) => json_string(&atom_text(atom)),
+    }
+}
+
+/// Quote a string for use in JSON.
+#[cfg(not(feature = "serde"))]
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Quote a string for use in `dot`, which only knows how to escape quotes,
+/// backslashes and newlines.
+fn dot_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c if c.is_control() => quoted.push(' '),
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+fn dot<W: Write>(w: &mut W, expr: &Expr) -> fmt::Result {
+    writeln!(w, "digraph ast {{")?;
+    dot_node(w, expr, &mut 0)?;
+    writeln!(w, "}}")
+}
+
+/// Write the node for `expr` and its children, returning its ID.
+fn dot_node<W: Write>(w: &mut W, expr: &Expr, next_id: &mut usize) -> Result<usize, fmt::Error> {
+    let node = Node::new(expr);
+    let id = *next_id;
+    *next_id += 1;
+
+    let mut label = format!("{}\n{}", node.kind, node.head.1);
+    for &(name, ref value) in &node.attributes {
+        label.push_str(&format!("\n{}={}", name, value));
+    }
+    let shape = if node.kind == "Atom" {
+        "ellipse"
+    } else {
+        "box"
+    };
+    writeln!(
+        w,
+        "    node{} [label={}, shape={}];",
+        id,
+        dot_string(&label),
+        shape
+    )?;
+
+    for (name, child) in node.child_nodes() {
+        let child_id = dot_node(w, child, next_id)?;
+        writeln!(
+            w,
+            "    node{} -> node{} [label=\"{}\"];",
+            id, child_id, name
+        )?;
+    }
+
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syntax;
+
+    fn dump_src(src: &str, format: Format) -> String {
+        dump(&syntax::parse(src).unwrap(), format)
+    }
+
+    #[test]
+    fn s_expressions_show_precedence() {
+        let inputs = vec![
+            ("1 + 2 * 3", "(+ 1 (* 2 3))"),
+            ("(1 + 2) * 3.0", "(* (+ 1 2) 3.0)"),
+            ("1 < 2 and not true", "(and (< 1 2) (not true))"),
+            (
+                "if x then sin(x) else max(1, 2i)",
+                "(if x (sin x) (max 1 2.0i))",
+            ),
+            ("sum(k, 1, 3, k)", "(sum k 1 3 k)"),
+            (
+                "map(x -> x * x, [1, 2])[0]",
+                "(index (map (lambda x (* x x)) (array 1 2)) 0)",
+            ),
+        ];
+
+        for (src, should_be) in inputs {
+            assert_eq!(dump_src(src, Format::SExpr), should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn indented_tree() {
+        let got = dump_src("f(x, 2) - 1", Format::Tree);
+        let should_be = "\
+BinaryOp - @ 0..11
+  left: FunctionCall f @ 0..7
+    arguments: Atom x @ 2..3
+    arguments: Atom 2 @ 5..6
+  right: Atom 1 @ 10..11
+";
+
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde"))]
+    fn json_nests_child_nodes() {
+        let got = dump_src("f(x + 1)", Format::Json);
+        let should_be = concat!(
+            r#"{"kind":"FunctionCall","name":"f","span":[0,8],"arguments":["#,
+            r#"{"kind":"BinaryOp","op":"+","span":[2,7],"#,
+            r#""left":{"kind":"Atom","value":"x","span":[2,3]},"#,
+            r#""right":{"kind":"Atom","value":1,"span":[6,7]}}]}"#,
+        );
+
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde"))]
+    fn json_atoms_keep_their_type() {
+        let inputs = vec![
+            ("1", "1"),
+            ("2.5", "2.5"),
+            ("1e300", "1e300"),
+            ("true", "true"),
+            ("x", r#""x""#),
+            ("2i", r#""2.0i""#),
+        ];
+
+        for (src, value) in inputs {
+            let should_be = format!(
+                r#"{{"kind":"Atom","value":{},"span":[0,{}]}}"#,
+                value,
+                src.len()
+            );
+            assert_eq!(dump_src(src, Format::Json), should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_uses_the_serde_encoding() {
+        let ast = syntax::parse("f(x + 1) * 2.5").unwrap();
+
+        let got = dump(&ast, Format::Json);
+
+        let versioned: syntax::Versioned = ::serde_json::from_str(&got).unwrap();
+        assert_eq!(versioned.expr, ast);
+    }
+
+    #[test]
+    fn graphviz_has_a_node_per_expression() {
+        let got = dump_src("sqrt(2) * x", Format::Dot);
+        let should_be = r#"digraph ast {
+    node0 [label="BinaryOp\n*", shape=box];
+    node1 [label="FunctionCall\nsqrt", shape=box];
+    node2 [label="Atom\n2", shape=ellipse];
+    node1 -> node2 [label="arguments"];
+    node0 -> node1 [label="left"];
+    node3 [label="Atom\nx", shape=ellipse];
+    node0 -> node3 [label="right"];
+}
+"#;
+
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_format_names() {
+        for &format in &Format::ALL {
+            assert_eq!(format.to_string().parse::<Format>().unwrap(), format);
+        }
+
+        assert!("yaml".parse::<Format>().is_err());
+    }
+}
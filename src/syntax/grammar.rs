@@ -1,2894 +0,0 @@
-// auto-generated: "lalrpop 0.15.1"
-use syntax::ast::{Expr, Atom, BinaryOp, FunctionCall};
-#[allow(unused_extern_crates)]
-extern crate lalrpop_util as __lalrpop_util;
-
-#[cfg_attr(rustfmt, rustfmt_skip)]
-mod __parse__Atom {
-    #![allow(non_snake_case, non_camel_case_types, unused_mut, unused_variables, unused_imports, unused_parens)]
-
-    use syntax::ast::{Expr, Atom, BinaryOp, FunctionCall};
-    #[allow(unused_extern_crates)]
-    extern crate lalrpop_util as __lalrpop_util;
-    use super::__intern_token::Token;
-    #[allow(dead_code)]
-    pub enum __Symbol<'input>
-     {
-        Variant0(&'input str),
-        Variant1(Expr),
-        Variant2(::std::vec::Vec<Expr>),
-        Variant3(Atom),
-        Variant4(Vec<Expr>),
-        Variant5(::std::option::Option<Expr>),
-        Variant6(FunctionCall),
-        Variant7(String),
-        Variant8(f64),
-    }
-    const __ACTION: &'static [i8] = &[
-        // State 0
-        0, 0, 0, 0, 0, 0, 0, 5, 6,
-        // State 1
-        0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 2
-        0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 3
-        0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 4
-        0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 5
-        0, 0, 0, 0, 0, 0, 0, 0, 0,
-    ];
-    const __EOF_ACTION: &'static [i8] = &[
-        // State 0
-        0,
-        // State 1
-        -24,
-        // State 2
-        -7,
-        // State 3
-        -6,
-        // State 4
-        -28,
-        // State 5
-        -27,
-    ];
-    const __GOTO: &'static [i8] = &[
-        // State 0
-        0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 4,
-        // State 1
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 2
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 3
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 4
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 5
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    ];
-    fn __expected_tokens(__state: usize) -> Vec<::std::string::String> {
-        const __TERMINAL: &'static [&'static str] = &[
-            r###""(""###,
-            r###"")""###,
-            r###""*""###,
-            r###""+""###,
-            r###"",""###,
-            r###""-""###,
-            r###""/""###,
-            r###"r#"[0-9]+(\\.[0-9]+)?"#"###,
-            r###"r#"[a-zA-Z][a-zA-Z0-9_-]*"#"###,
-        ];
-        __ACTION[(__state * 9)..].iter().zip(__TERMINAL).filter_map(|(&state, terminal)| {
-            if state == 0 {
-                None
-            } else {
-                Some(terminal.to_string())
-            }
-        }).collect()
-    }
-    pub struct AtomParser {
-        builder: super::__intern_token::__MatcherBuilder,
-        _priv: (),
-    }
-
-    impl AtomParser {
-        pub fn new() -> AtomParser {
-            let __builder = super::__intern_token::__MatcherBuilder::new();
-            AtomParser {
-                builder: __builder,
-                _priv: (),
-            }
-        }
-
-        #[allow(dead_code)]
-        pub fn parse<
-            'input,
-        >(
-            &self,
-            input: &'input str,
-        ) -> Result<Atom, __lalrpop_util::ParseError<usize, Token<'input>, &'static str>>
-        {
-            let mut __tokens = self.builder.matcher(input);
-            let mut __states = vec![0_i8];
-            let mut __symbols = vec![];
-            let mut __integer;
-            let mut __lookahead;
-            let __last_location = &mut Default::default();
-            '__shift: loop {
-                __lookahead = match __tokens.next() {
-                    Some(Ok(v)) => v,
-                    None => break '__shift,
-                    Some(Err(e)) => return Err(e),
-                };
-                *__last_location = __lookahead.2.clone();
-                __integer = match __lookahead.1 {
-                    Token(2, _) if true => 0,
-                    Token(3, _) if true => 1,
-                    Token(4, _) if true => 2,
-                    Token(5, _) if true => 3,
-                    Token(6, _) if true => 4,
-                    Token(7, _) if true => 5,
-                    Token(8, _) if true => 6,
-                    Token(0, _) if true => 7,
-                    Token(1, _) if true => 8,
-                    _ => {
-                        let __state = *__states.last().unwrap() as usize;
-                        let __error = __lalrpop_util::ParseError::UnrecognizedToken {
-                            token: Some(__lookahead),
-                            expected: __expected_tokens(__state),
-                        };
-                        return Err(__error);
-                    }
-                };
-                '__inner: loop {
-                    let __state = *__states.last().unwrap() as usize;
-                    let __action = __ACTION[__state * 9 + __integer];
-                    if __action > 0 {
-                        let __symbol = match __integer {
-                            0 => match __lookahead.1 {
-                                Token(2, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            1 => match __lookahead.1 {
-                                Token(3, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            2 => match __lookahead.1 {
-                                Token(4, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            3 => match __lookahead.1 {
-                                Token(5, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            4 => match __lookahead.1 {
-                                Token(6, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            5 => match __lookahead.1 {
-                                Token(7, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            6 => match __lookahead.1 {
-                                Token(8, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            7 => match __lookahead.1 {
-                                Token(0, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            8 => match __lookahead.1 {
-                                Token(1, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            _ => unreachable!(),
-                        };
-                        __states.push(__action - 1);
-                        __symbols.push((__lookahead.0, __symbol, __lookahead.2));
-                        continue '__shift;
-                    } else if __action < 0 {
-                        if let Some(r) = __reduce(input, __action, Some(&__lookahead.0), &mut __states, &mut __symbols, ::std::marker::PhantomData::<()>) {
-                            if r.is_err() {
-                                return r;
-                            }
-                            return Err(__lalrpop_util::ParseError::ExtraToken { token: __lookahead });
-                        }
-                    } else {
-                        let mut __err_lookahead = Some(__lookahead);
-                        let mut __err_integer: Option<usize> = Some(__integer);
-                        let __state = *__states.last().unwrap() as usize;
-                        let __error = __lalrpop_util::ParseError::UnrecognizedToken {
-                            token: __err_lookahead,
-                            expected: __expected_tokens(__state),
-                        };
-                        return Err(__error)
-                    }
-                }
-            }
-            loop {
-                let __state = *__states.last().unwrap() as usize;
-                let __action = __EOF_ACTION[__state];
-                if __action < 0 {
-                    if let Some(r) = __reduce(input, __action, None, &mut __states, &mut __symbols, ::std::marker::PhantomData::<()>) {
-                        return r;
-                    }
-                } else {
-                    let mut __err_lookahead = None;
-                    let mut __err_integer: Option<usize> = None;
-                    let __state = *__states.last().unwrap() as usize;
-                    let __error = __lalrpop_util::ParseError::UnrecognizedToken {
-                        token: __err_lookahead,
-                        expected: __expected_tokens(__state),
-                    };
-                    return Err(__error)
-                }
-            }
-        }
-    }
-    pub(crate) fn __reduce<
-        'input,
-    >(
-        input: &'input str,
-        __action: i8,
-        __lookahead_start: Option<&usize>,
-        __states: &mut ::std::vec::Vec<i8>,
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>,
-        _: ::std::marker::PhantomData<()>,
-    ) -> Option<Result<Atom,__lalrpop_util::ParseError<usize, Token<'input>, &'static str>>>
-    {
-        let (__pop_states, __symbol, __nonterminal) = match -__action {
-            1 => {
-                (|| {
-                    // (<Expr> ",") = Expr, "," => ActionFn(22);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym1.2.clone();
-                    let __nt = super::__action22::<>(input, __sym0, __sym1);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (2, __symbol, 0)
-                })()
-            }
-            2 => {
-                (|| {
-                    // (<Expr> ",")* =  => ActionFn(20);
-                    let __start = __symbols.last().map(|s| s.2.clone()).unwrap_or_default();
-                    let __end = __lookahead_start.cloned().unwrap_or_else(|| __start.clone());
-                    let __nt = super::__action20::<>(input, &__start, &__end);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (0, __symbol, 1)
-                })()
-            }
-            3 => {
-                (|| {
-                    // (<Expr> ",")* = (<Expr> ",")+ => ActionFn(21);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action21::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (1, __symbol, 1)
-                })()
-            }
-            4 => {
-                (|| {
-                    // (<Expr> ",")+ = Expr, "," => ActionFn(25);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym1.2.clone();
-                    let __nt = super::__action25::<>(input, __sym0, __sym1);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (2, __symbol, 2)
-                })()
-            }
-            5 => {
-                (|| {
-                    // (<Expr> ",")+ = (<Expr> ",")+, Expr, "," => ActionFn(26);
-                    let __sym2 = __pop_Variant0(__symbols);
-                    let __sym1 = __pop_Variant1(__symbols);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action26::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (3, __symbol, 2)
-                })()
-            }
-            6 => {
-                (|| {
-                    // Atom = num => ActionFn(13);
-                    let __sym0 = __pop_Variant8(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action13::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant3(__nt), __end);
-                    (1, __symbol, 3)
-                })()
-            }
-            7 => {
-                (|| {
-                    // Atom = ident => ActionFn(14);
-                    let __sym0 = __pop_Variant7(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action14::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant3(__nt), __end);
-                    (1, __symbol, 3)
-                })()
-            }
-            8 => {
-                (|| {
-                    // CommaSeparated<Expr> = Expr => ActionFn(29);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action29::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (1, __symbol, 4)
-                })()
-            }
-            9 => {
-                (|| {
-                    // CommaSeparated<Expr> =  => ActionFn(30);
-                    let __start = __symbols.last().map(|s| s.2.clone()).unwrap_or_default();
-                    let __end = __lookahead_start.cloned().unwrap_or_else(|| __start.clone());
-                    let __nt = super::__action30::<>(input, &__start, &__end);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (0, __symbol, 4)
-                })()
-            }
-            10 => {
-                (|| {
-                    // CommaSeparated<Expr> = (<Expr> ",")+, Expr => ActionFn(31);
-                    let __sym1 = __pop_Variant1(__symbols);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym1.2.clone();
-                    let __nt = super::__action31::<>(input, __sym0, __sym1);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (2, __symbol, 4)
-                })()
-            }
-            11 => {
-                (|| {
-                    // CommaSeparated<Expr> = (<Expr> ",")+ => ActionFn(32);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action32::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (1, __symbol, 4)
-                })()
-            }
-            12 => {
-                (|| {
-                    // Expr = Expr, "+", Factor => ActionFn(3);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action3::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 5)
-                })()
-            }
-            13 => {
-                (|| {
-                    // Expr = Expr, "-", Factor => ActionFn(4);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action4::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 5)
-                })()
-            }
-            14 => {
-                (|| {
-                    // Expr = Factor => ActionFn(5);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action5::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 5)
-                })()
-            }
-            15 => {
-                (|| {
-                    // Expr? = Expr => ActionFn(18);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action18::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant5(__nt), __end);
-                    (1, __symbol, 6)
-                })()
-            }
-            16 => {
-                (|| {
-                    // Expr? =  => ActionFn(19);
-                    let __start = __symbols.last().map(|s| s.2.clone()).unwrap_or_default();
-                    let __end = __lookahead_start.cloned().unwrap_or_else(|| __start.clone());
-                    let __nt = super::__action19::<>(input, &__start, &__end);
-                    let __symbol = (__start, __Symbol::Variant5(__nt), __end);
-                    (0, __symbol, 6)
-                })()
-            }
-            17 => {
-                (|| {
-                    // Factor = Factor, "*", Term => ActionFn(6);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action6::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 7)
-                })()
-            }
-            18 => {
-                (|| {
-                    // Factor = Factor, "/", Term => ActionFn(7);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action7::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 7)
-                })()
-            }
-            19 => {
-                (|| {
-                    // Factor = Term => ActionFn(8);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action8::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 7)
-                })()
-            }
-            20 => {
-                (|| {
-                    // FunctionCall = ident, "(", CommaSeparated<Expr>, ")" => ActionFn(12);
-                    let __sym3 = __pop_Variant0(__symbols);
-                    let __sym2 = __pop_Variant4(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant7(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym3.2.clone();
-                    let __nt = super::__action12::<>(input, __sym0, __sym1, __sym2, __sym3);
-                    let __symbol = (__start, __Symbol::Variant6(__nt), __end);
-                    (4, __symbol, 8)
-                })()
-            }
-            21 => {
-                (|| {
-                    // Term = "(", Expr, ")" => ActionFn(9);
-                    let __sym2 = __pop_Variant0(__symbols);
-                    let __sym1 = __pop_Variant1(__symbols);
-                    let __sym0 = __pop_Variant0(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action9::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 9)
-                })()
-            }
-            22 => {
-                (|| {
-                    // Term = Atom => ActionFn(10);
-                    let __sym0 = __pop_Variant3(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action10::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 9)
-                })()
-            }
-            23 => {
-                (|| {
-                    // Term = FunctionCall => ActionFn(11);
-                    let __sym0 = __pop_Variant6(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action11::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 9)
-                })()
-            }
-            24 => {
-                // __Atom = Atom => ActionFn(2);
-                let __sym0 = __pop_Variant3(__symbols);
-                let __start = __sym0.0.clone();
-                let __end = __sym0.2.clone();
-                let __nt = super::__action2::<>(input, __sym0);
-                return Some(Ok(__nt));
-            }
-            25 => {
-                (|| {
-                    // __Expr = Expr => ActionFn(0);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action0::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 11)
-                })()
-            }
-            26 => {
-                (|| {
-                    // __FunctionCall = FunctionCall => ActionFn(1);
-                    let __sym0 = __pop_Variant6(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action1::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant6(__nt), __end);
-                    (1, __symbol, 12)
-                })()
-            }
-            27 => {
-                (|| {
-                    // ident = r#"[a-zA-Z][a-zA-Z0-9_-]*"# => ActionFn(16);
-                    let __sym0 = __pop_Variant0(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action16::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant7(__nt), __end);
-                    (1, __symbol, 13)
-                })()
-            }
-            28 => {
-                (|| {
-                    // num = r#"[0-9]+(\\.[0-9]+)?"# => ActionFn(15);
-                    let __sym0 = __pop_Variant0(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action15::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant8(__nt), __end);
-                    (1, __symbol, 14)
-                })()
-            }
-            _ => panic!("invalid action code {}", __action)
-        };
-        let __states_len = __states.len();
-        __states.truncate(__states_len - __pop_states);
-        __symbols.push(__symbol);
-        let __state = *__states.last().unwrap() as usize;
-        let __next_state = __GOTO[__state * 15 + __nonterminal] - 1;
-        __states.push(__next_state);
-        None
-    }
-    fn __pop_Variant3<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, Atom, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant3(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant1<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, Expr, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant1(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant6<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, FunctionCall, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant6(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant7<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, String, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant7(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant4<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, Vec<Expr>, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant4(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant8<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, f64, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant8(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant5<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, ::std::option::Option<Expr>, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant5(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant2<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, ::std::vec::Vec<Expr>, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant2(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant0<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, &'input str, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant0(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-}
-pub use self::__parse__Atom::AtomParser;
-
-#[cfg_attr(rustfmt, rustfmt_skip)]
-mod __parse__Expr {
-    #![allow(non_snake_case, non_camel_case_types, unused_mut, unused_variables, unused_imports, unused_parens)]
-
-    use syntax::ast::{Expr, Atom, BinaryOp, FunctionCall};
-    #[allow(unused_extern_crates)]
-    extern crate lalrpop_util as __lalrpop_util;
-    use super::__intern_token::Token;
-    #[allow(dead_code)]
-    pub enum __Symbol<'input>
-     {
-        Variant0(&'input str),
-        Variant1(Expr),
-        Variant2(::std::vec::Vec<Expr>),
-        Variant3(Atom),
-        Variant4(Vec<Expr>),
-        Variant5(::std::option::Option<Expr>),
-        Variant6(FunctionCall),
-        Variant7(String),
-        Variant8(f64),
-    }
-    const __ACTION: &'static [i8] = &[
-        // State 0
-        9, 0, 0, 0, 0, 0, 0, 10, 11,
-        // State 1
-        0, -22, -22, -22, -22, -22, -22, 0, 0,
-        // State 2
-        0, 0, 0, 12, 0, 13, 0, 0, 0,
-        // State 3
-        0, -14, 14, -14, -14, -14, 15, 0, 0,
-        // State 4
-        0, -23, -23, -23, -23, -23, -23, 0, 0,
-        // State 5
-        0, -19, -19, -19, -19, -19, -19, 0, 0,
-        // State 6
-        16, -7, -7, -7, -7, -7, -7, 0, 0,
-        // State 7
-        0, -6, -6, -6, -6, -6, -6, 0, 0,
-        // State 8
-        9, 0, 0, 0, 0, 0, 0, 10, 11,
-        // State 9
-        0, -28, -28, -28, -28, -28, -28, 0, 0,
-        // State 10
-        -27, -27, -27, -27, -27, -27, -27, 0, 0,
-        // State 11
-        9, 0, 0, 0, 0, 0, 0, 10, 11,
-        // State 12
-        9, 0, 0, 0, 0, 0, 0, 10, 11,
-        // State 13
-        9, 0, 0, 0, 0, 0, 0, 10, 11,
-        // State 14
-        9, 0, 0, 0, 0, 0, 0, 10, 11,
-        // State 15
-        9, -9, 0, 0, 0, 0, 0, 10, 11,
-        // State 16
-        0, 25, 0, 12, 0, 13, 0, 0, 0,
-        // State 17
-        0, -12, 14, -12, -12, -12, 15, 0, 0,
-        // State 18
-        0, -13, 14, -13, -13, -13, 15, 0, 0,
-        // State 19
-        0, -17, -17, -17, -17, -17, -17, 0, 0,
-        // State 20
-        0, -18, -18, -18, -18, -18, -18, 0, 0,
-        // State 21
-        9, -11, 0, 0, 0, 0, 0, 10, 11,
-        // State 22
-        0, 27, 0, 0, 0, 0, 0, 0, 0,
-        // State 23
-        0, -8, 0, 12, 28, 13, 0, 0, 0,
-        // State 24
-        0, -21, -21, -21, -21, -21, -21, 0, 0,
-        // State 25
-        0, -10, 0, 12, 29, 13, 0, 0, 0,
-        // State 26
-        0, -20, -20, -20, -20, -20, -20, 0, 0,
-        // State 27
-        -4, -4, 0, 0, 0, 0, 0, -4, -4,
-        // State 28
-        -5, -5, 0, 0, 0, 0, 0, -5, -5,
-    ];
-    const __EOF_ACTION: &'static [i8] = &[
-        // State 0
-        0,
-        // State 1
-        -22,
-        // State 2
-        -25,
-        // State 3
-        -14,
-        // State 4
-        -23,
-        // State 5
-        -19,
-        // State 6
-        -7,
-        // State 7
-        -6,
-        // State 8
-        0,
-        // State 9
-        -28,
-        // State 10
-        -27,
-        // State 11
-        0,
-        // State 12
-        0,
-        // State 13
-        0,
-        // State 14
-        0,
-        // State 15
-        0,
-        // State 16
-        0,
-        // State 17
-        -12,
-        // State 18
-        -13,
-        // State 19
-        -17,
-        // State 20
-        -18,
-        // State 21
-        0,
-        // State 22
-        0,
-        // State 23
-        0,
-        // State 24
-        -21,
-        // State 25
-        0,
-        // State 26
-        -20,
-        // State 27
-        0,
-        // State 28
-        0,
-    ];
-    const __GOTO: &'static [i8] = &[
-        // State 0
-        0, 0, 0, 2, 0, 3, 0, 4, 5, 6, 0, 0, 0, 7, 8,
-        // State 1
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 2
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 3
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 4
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 5
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 6
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 7
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 8
-        0, 0, 0, 2, 0, 17, 0, 4, 5, 6, 0, 0, 0, 7, 8,
-        // State 9
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 10
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 11
-        0, 0, 0, 2, 0, 0, 0, 18, 5, 6, 0, 0, 0, 7, 8,
-        // State 12
-        0, 0, 0, 2, 0, 0, 0, 19, 5, 6, 0, 0, 0, 7, 8,
-        // State 13
-        0, 0, 0, 2, 0, 0, 0, 0, 5, 20, 0, 0, 0, 7, 8,
-        // State 14
-        0, 0, 0, 2, 0, 0, 0, 0, 5, 21, 0, 0, 0, 7, 8,
-        // State 15
-        0, 0, 22, 2, 23, 24, 0, 4, 5, 6, 0, 0, 0, 7, 8,
-        // State 16
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 17
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 18
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 19
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 20
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 21
-        0, 0, 0, 2, 0, 26, 0, 4, 5, 6, 0, 0, 0, 7, 8,
-        // State 22
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 23
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 24
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 25
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 26
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 27
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 28
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    ];
-    fn __expected_tokens(__state: usize) -> Vec<::std::string::String> {
-        const __TERMINAL: &'static [&'static str] = &[
-            r###""(""###,
-            r###"")""###,
-            r###""*""###,
-            r###""+""###,
-            r###"",""###,
-            r###""-""###,
-            r###""/""###,
-            r###"r#"[0-9]+(\\.[0-9]+)?"#"###,
-            r###"r#"[a-zA-Z][a-zA-Z0-9_-]*"#"###,
-        ];
-        __ACTION[(__state * 9)..].iter().zip(__TERMINAL).filter_map(|(&state, terminal)| {
-            if state == 0 {
-                None
-            } else {
-                Some(terminal.to_string())
-            }
-        }).collect()
-    }
-    pub struct ExprParser {
-        builder: super::__intern_token::__MatcherBuilder,
-        _priv: (),
-    }
-
-    impl ExprParser {
-        pub fn new() -> ExprParser {
-            let __builder = super::__intern_token::__MatcherBuilder::new();
-            ExprParser {
-                builder: __builder,
-                _priv: (),
-            }
-        }
-
-        #[allow(dead_code)]
-        pub fn parse<
-            'input,
-        >(
-            &self,
-            input: &'input str,
-        ) -> Result<Expr, __lalrpop_util::ParseError<usize, Token<'input>, &'static str>>
-        {
-            let mut __tokens = self.builder.matcher(input);
-            let mut __states = vec![0_i8];
-            let mut __symbols = vec![];
-            let mut __integer;
-            let mut __lookahead;
-            let __last_location = &mut Default::default();
-            '__shift: loop {
-                __lookahead = match __tokens.next() {
-                    Some(Ok(v)) => v,
-                    None => break '__shift,
-                    Some(Err(e)) => return Err(e),
-                };
-                *__last_location = __lookahead.2.clone();
-                __integer = match __lookahead.1 {
-                    Token(2, _) if true => 0,
-                    Token(3, _) if true => 1,
-                    Token(4, _) if true => 2,
-                    Token(5, _) if true => 3,
-                    Token(6, _) if true => 4,
-                    Token(7, _) if true => 5,
-                    Token(8, _) if true => 6,
-                    Token(0, _) if true => 7,
-                    Token(1, _) if true => 8,
-                    _ => {
-                        let __state = *__states.last().unwrap() as usize;
-                        let __error = __lalrpop_util::ParseError::UnrecognizedToken {
-                            token: Some(__lookahead),
-                            expected: __expected_tokens(__state),
-                        };
-                        return Err(__error);
-                    }
-                };
-                '__inner: loop {
-                    let __state = *__states.last().unwrap() as usize;
-                    let __action = __ACTION[__state * 9 + __integer];
-                    if __action > 0 {
-                        let __symbol = match __integer {
-                            0 => match __lookahead.1 {
-                                Token(2, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            1 => match __lookahead.1 {
-                                Token(3, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            2 => match __lookahead.1 {
-                                Token(4, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            3 => match __lookahead.1 {
-                                Token(5, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            4 => match __lookahead.1 {
-                                Token(6, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            5 => match __lookahead.1 {
-                                Token(7, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            6 => match __lookahead.1 {
-                                Token(8, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            7 => match __lookahead.1 {
-                                Token(0, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            8 => match __lookahead.1 {
-                                Token(1, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            _ => unreachable!(),
-                        };
-                        __states.push(__action - 1);
-                        __symbols.push((__lookahead.0, __symbol, __lookahead.2));
-                        continue '__shift;
-                    } else if __action < 0 {
-                        if let Some(r) = __reduce(input, __action, Some(&__lookahead.0), &mut __states, &mut __symbols, ::std::marker::PhantomData::<()>) {
-                            if r.is_err() {
-                                return r;
-                            }
-                            return Err(__lalrpop_util::ParseError::ExtraToken { token: __lookahead });
-                        }
-                    } else {
-                        let mut __err_lookahead = Some(__lookahead);
-                        let mut __err_integer: Option<usize> = Some(__integer);
-                        let __state = *__states.last().unwrap() as usize;
-                        let __error = __lalrpop_util::ParseError::UnrecognizedToken {
-                            token: __err_lookahead,
-                            expected: __expected_tokens(__state),
-                        };
-                        return Err(__error)
-                    }
-                }
-            }
-            loop {
-                let __state = *__states.last().unwrap() as usize;
-                let __action = __EOF_ACTION[__state];
-                if __action < 0 {
-                    if let Some(r) = __reduce(input, __action, None, &mut __states, &mut __symbols, ::std::marker::PhantomData::<()>) {
-                        return r;
-                    }
-                } else {
-                    let mut __err_lookahead = None;
-                    let mut __err_integer: Option<usize> = None;
-                    let __state = *__states.last().unwrap() as usize;
-                    let __error = __lalrpop_util::ParseError::UnrecognizedToken {
-                        token: __err_lookahead,
-                        expected: __expected_tokens(__state),
-                    };
-                    return Err(__error)
-                }
-            }
-        }
-    }
-    pub(crate) fn __reduce<
-        'input,
-    >(
-        input: &'input str,
-        __action: i8,
-        __lookahead_start: Option<&usize>,
-        __states: &mut ::std::vec::Vec<i8>,
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>,
-        _: ::std::marker::PhantomData<()>,
-    ) -> Option<Result<Expr,__lalrpop_util::ParseError<usize, Token<'input>, &'static str>>>
-    {
-        let (__pop_states, __symbol, __nonterminal) = match -__action {
-            1 => {
-                (|| {
-                    // (<Expr> ",") = Expr, "," => ActionFn(22);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym1.2.clone();
-                    let __nt = super::__action22::<>(input, __sym0, __sym1);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (2, __symbol, 0)
-                })()
-            }
-            2 => {
-                (|| {
-                    // (<Expr> ",")* =  => ActionFn(20);
-                    let __start = __symbols.last().map(|s| s.2.clone()).unwrap_or_default();
-                    let __end = __lookahead_start.cloned().unwrap_or_else(|| __start.clone());
-                    let __nt = super::__action20::<>(input, &__start, &__end);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (0, __symbol, 1)
-                })()
-            }
-            3 => {
-                (|| {
-                    // (<Expr> ",")* = (<Expr> ",")+ => ActionFn(21);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action21::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (1, __symbol, 1)
-                })()
-            }
-            4 => {
-                (|| {
-                    // (<Expr> ",")+ = Expr, "," => ActionFn(25);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym1.2.clone();
-                    let __nt = super::__action25::<>(input, __sym0, __sym1);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (2, __symbol, 2)
-                })()
-            }
-            5 => {
-                (|| {
-                    // (<Expr> ",")+ = (<Expr> ",")+, Expr, "," => ActionFn(26);
-                    let __sym2 = __pop_Variant0(__symbols);
-                    let __sym1 = __pop_Variant1(__symbols);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action26::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (3, __symbol, 2)
-                })()
-            }
-            6 => {
-                (|| {
-                    // Atom = num => ActionFn(13);
-                    let __sym0 = __pop_Variant8(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action13::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant3(__nt), __end);
-                    (1, __symbol, 3)
-                })()
-            }
-            7 => {
-                (|| {
-                    // Atom = ident => ActionFn(14);
-                    let __sym0 = __pop_Variant7(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action14::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant3(__nt), __end);
-                    (1, __symbol, 3)
-                })()
-            }
-            8 => {
-                (|| {
-                    // CommaSeparated<Expr> = Expr => ActionFn(29);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action29::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (1, __symbol, 4)
-                })()
-            }
-            9 => {
-                (|| {
-                    // CommaSeparated<Expr> =  => ActionFn(30);
-                    let __start = __symbols.last().map(|s| s.2.clone()).unwrap_or_default();
-                    let __end = __lookahead_start.cloned().unwrap_or_else(|| __start.clone());
-                    let __nt = super::__action30::<>(input, &__start, &__end);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (0, __symbol, 4)
-                })()
-            }
-            10 => {
-                (|| {
-                    // CommaSeparated<Expr> = (<Expr> ",")+, Expr => ActionFn(31);
-                    let __sym1 = __pop_Variant1(__symbols);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym1.2.clone();
-                    let __nt = super::__action31::<>(input, __sym0, __sym1);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (2, __symbol, 4)
-                })()
-            }
-            11 => {
-                (|| {
-                    // CommaSeparated<Expr> = (<Expr> ",")+ => ActionFn(32);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action32::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (1, __symbol, 4)
-                })()
-            }
-            12 => {
-                (|| {
-                    // Expr = Expr, "+", Factor => ActionFn(3);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action3::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 5)
-                })()
-            }
-            13 => {
-                (|| {
-                    // Expr = Expr, "-", Factor => ActionFn(4);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action4::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 5)
-                })()
-            }
-            14 => {
-                (|| {
-                    // Expr = Factor => ActionFn(5);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action5::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 5)
-                })()
-            }
-            15 => {
-                (|| {
-                    // Expr? = Expr => ActionFn(18);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action18::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant5(__nt), __end);
-                    (1, __symbol, 6)
-                })()
-            }
-            16 => {
-                (|| {
-                    // Expr? =  => ActionFn(19);
-                    let __start = __symbols.last().map(|s| s.2.clone()).unwrap_or_default();
-                    let __end = __lookahead_start.cloned().unwrap_or_else(|| __start.clone());
-                    let __nt = super::__action19::<>(input, &__start, &__end);
-                    let __symbol = (__start, __Symbol::Variant5(__nt), __end);
-                    (0, __symbol, 6)
-                })()
-            }
-            17 => {
-                (|| {
-                    // Factor = Factor, "*", Term => ActionFn(6);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action6::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 7)
-                })()
-            }
-            18 => {
-                (|| {
-                    // Factor = Factor, "/", Term => ActionFn(7);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action7::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 7)
-                })()
-            }
-            19 => {
-                (|| {
-                    // Factor = Term => ActionFn(8);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action8::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 7)
-                })()
-            }
-            20 => {
-                (|| {
-                    // FunctionCall = ident, "(", CommaSeparated<Expr>, ")" => ActionFn(12);
-                    let __sym3 = __pop_Variant0(__symbols);
-                    let __sym2 = __pop_Variant4(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant7(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym3.2.clone();
-                    let __nt = super::__action12::<>(input, __sym0, __sym1, __sym2, __sym3);
-                    let __symbol = (__start, __Symbol::Variant6(__nt), __end);
-                    (4, __symbol, 8)
-                })()
-            }
-            21 => {
-                (|| {
-                    // Term = "(", Expr, ")" => ActionFn(9);
-                    let __sym2 = __pop_Variant0(__symbols);
-                    let __sym1 = __pop_Variant1(__symbols);
-                    let __sym0 = __pop_Variant0(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action9::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 9)
-                })()
-            }
-            22 => {
-                (|| {
-                    // Term = Atom => ActionFn(10);
-                    let __sym0 = __pop_Variant3(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action10::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 9)
-                })()
-            }
-            23 => {
-                (|| {
-                    // Term = FunctionCall => ActionFn(11);
-                    let __sym0 = __pop_Variant6(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action11::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 9)
-                })()
-            }
-            24 => {
-                (|| {
-                    // __Atom = Atom => ActionFn(2);
-                    let __sym0 = __pop_Variant3(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action2::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant3(__nt), __end);
-                    (1, __symbol, 10)
-                })()
-            }
-            25 => {
-                // __Expr = Expr => ActionFn(0);
-                let __sym0 = __pop_Variant1(__symbols);
-                let __start = __sym0.0.clone();
-                let __end = __sym0.2.clone();
-                let __nt = super::__action0::<>(input, __sym0);
-                return Some(Ok(__nt));
-            }
-            26 => {
-                (|| {
-                    // __FunctionCall = FunctionCall => ActionFn(1);
-                    let __sym0 = __pop_Variant6(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action1::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant6(__nt), __end);
-                    (1, __symbol, 12)
-                })()
-            }
-            27 => {
-                (|| {
-                    // ident = r#"[a-zA-Z][a-zA-Z0-9_-]*"# => ActionFn(16);
-                    let __sym0 = __pop_Variant0(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action16::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant7(__nt), __end);
-                    (1, __symbol, 13)
-                })()
-            }
-            28 => {
-                (|| {
-                    // num = r#"[0-9]+(\\.[0-9]+)?"# => ActionFn(15);
-                    let __sym0 = __pop_Variant0(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action15::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant8(__nt), __end);
-                    (1, __symbol, 14)
-                })()
-            }
-            _ => panic!("invalid action code {}", __action)
-        };
-        let __states_len = __states.len();
-        __states.truncate(__states_len - __pop_states);
-        __symbols.push(__symbol);
-        let __state = *__states.last().unwrap() as usize;
-        let __next_state = __GOTO[__state * 15 + __nonterminal] - 1;
-        __states.push(__next_state);
-        None
-    }
-    fn __pop_Variant3<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, Atom, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant3(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant1<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, Expr, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant1(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant6<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, FunctionCall, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant6(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant7<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, String, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant7(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant4<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, Vec<Expr>, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant4(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant8<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, f64, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant8(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant5<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, ::std::option::Option<Expr>, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant5(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant2<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, ::std::vec::Vec<Expr>, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant2(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant0<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, &'input str, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant0(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-}
-pub use self::__parse__Expr::ExprParser;
-
-#[cfg_attr(rustfmt, rustfmt_skip)]
-mod __parse__FunctionCall {
-    #![allow(non_snake_case, non_camel_case_types, unused_mut, unused_variables, unused_imports, unused_parens)]
-
-    use syntax::ast::{Expr, Atom, BinaryOp, FunctionCall};
-    #[allow(unused_extern_crates)]
-    extern crate lalrpop_util as __lalrpop_util;
-    use super::__intern_token::Token;
-    #[allow(dead_code)]
-    pub enum __Symbol<'input>
-     {
-        Variant0(&'input str),
-        Variant1(Expr),
-        Variant2(::std::vec::Vec<Expr>),
-        Variant3(Atom),
-        Variant4(Vec<Expr>),
-        Variant5(::std::option::Option<Expr>),
-        Variant6(FunctionCall),
-        Variant7(String),
-        Variant8(f64),
-    }
-    const __ACTION: &'static [i8] = &[
-        // State 0
-        0, 0, 0, 0, 0, 0, 0, 0, 4,
-        // State 1
-        0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 2
-        5, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 3
-        -27, -27, -27, -27, -27, -27, -27, 0, 0,
-        // State 4
-        15, -9, 0, 0, 0, 0, 0, 16, 4,
-        // State 5
-        15, -11, 0, 0, 0, 0, 0, 16, 4,
-        // State 6
-        0, -22, -22, -22, -22, -22, -22, 0, 0,
-        // State 7
-        0, 18, 0, 0, 0, 0, 0, 0, 0,
-        // State 8
-        0, -8, 0, 19, 20, 21, 0, 0, 0,
-        // State 9
-        0, -14, 22, -14, -14, -14, 23, 0, 0,
-        // State 10
-        0, -23, -23, -23, -23, -23, -23, 0, 0,
-        // State 11
-        0, -19, -19, -19, -19, -19, -19, 0, 0,
-        // State 12
-        5, -7, -7, -7, -7, -7, -7, 0, 0,
-        // State 13
-        0, -6, -6, -6, -6, -6, -6, 0, 0,
-        // State 14
-        15, 0, 0, 0, 0, 0, 0, 16, 4,
-        // State 15
-        0, -28, -28, -28, -28, -28, -28, 0, 0,
-        // State 16
-        0, -10, 0, 19, 25, 21, 0, 0, 0,
-        // State 17
-        0, -20, -20, -20, -20, -20, -20, 0, 0,
-        // State 18
-        15, 0, 0, 0, 0, 0, 0, 16, 4,
-        // State 19
-        -4, -4, 0, 0, 0, 0, 0, -4, -4,
-        // State 20
-        15, 0, 0, 0, 0, 0, 0, 16, 4,
-        // State 21
-        15, 0, 0, 0, 0, 0, 0, 16, 4,
-        // State 22
-        15, 0, 0, 0, 0, 0, 0, 16, 4,
-        // State 23
-        0, 30, 0, 19, 0, 21, 0, 0, 0,
-        // State 24
-        -5, -5, 0, 0, 0, 0, 0, -5, -5,
-        // State 25
-        0, -12, 22, -12, -12, -12, 23, 0, 0,
-        // State 26
-        0, -13, 22, -13, -13, -13, 23, 0, 0,
-        // State 27
-        0, -17, -17, -17, -17, -17, -17, 0, 0,
-        // State 28
-        0, -18, -18, -18, -18, -18, -18, 0, 0,
-        // State 29
-        0, -21, -21, -21, -21, -21, -21, 0, 0,
-    ];
-    const __EOF_ACTION: &'static [i8] = &[
-        // State 0
-        0,
-        // State 1
-        -26,
-        // State 2
-        0,
-        // State 3
-        0,
-        // State 4
-        0,
-        // State 5
-        0,
-        // State 6
-        0,
-        // State 7
-        0,
-        // State 8
-        0,
-        // State 9
-        0,
-        // State 10
-        0,
-        // State 11
-        0,
-        // State 12
-        0,
-        // State 13
-        0,
-        // State 14
-        0,
-        // State 15
-        0,
-        // State 16
-        0,
-        // State 17
-        -20,
-        // State 18
-        0,
-        // State 19
-        0,
-        // State 20
-        0,
-        // State 21
-        0,
-        // State 22
-        0,
-        // State 23
-        0,
-        // State 24
-        0,
-        // State 25
-        0,
-        // State 26
-        0,
-        // State 27
-        0,
-        // State 28
-        0,
-        // State 29
-        0,
-    ];
-    const __GOTO: &'static [i8] = &[
-        // State 0
-        0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 3, 0,
-        // State 1
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 2
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 3
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 4
-        0, 0, 6, 7, 8, 9, 0, 10, 11, 12, 0, 0, 0, 13, 14,
-        // State 5
-        0, 0, 0, 7, 0, 17, 0, 10, 11, 12, 0, 0, 0, 13, 14,
-        // State 6
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 7
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 8
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 9
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 10
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 11
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 12
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 13
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 14
-        0, 0, 0, 7, 0, 24, 0, 10, 11, 12, 0, 0, 0, 13, 14,
-        // State 15
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 16
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 17
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 18
-        0, 0, 0, 7, 0, 0, 0, 26, 11, 12, 0, 0, 0, 13, 14,
-        // State 19
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 20
-        0, 0, 0, 7, 0, 0, 0, 27, 11, 12, 0, 0, 0, 13, 14,
-        // State 21
-        0, 0, 0, 7, 0, 0, 0, 0, 11, 28, 0, 0, 0, 13, 14,
-        // State 22
-        0, 0, 0, 7, 0, 0, 0, 0, 11, 29, 0, 0, 0, 13, 14,
-        // State 23
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 24
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 25
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 26
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 27
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 28
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-        // State 29
-        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-    ];
-    fn __expected_tokens(__state: usize) -> Vec<::std::string::String> {
-        const __TERMINAL: &'static [&'static str] = &[
-            r###""(""###,
-            r###"")""###,
-            r###""*""###,
-            r###""+""###,
-            r###"",""###,
-            r###""-""###,
-            r###""/""###,
-            r###"r#"[0-9]+(\\.[0-9]+)?"#"###,
-            r###"r#"[a-zA-Z][a-zA-Z0-9_-]*"#"###,
-        ];
-        __ACTION[(__state * 9)..].iter().zip(__TERMINAL).filter_map(|(&state, terminal)| {
-            if state == 0 {
-                None
-            } else {
-                Some(terminal.to_string())
-            }
-        }).collect()
-    }
-    pub struct FunctionCallParser {
-        builder: super::__intern_token::__MatcherBuilder,
-        _priv: (),
-    }
-
-    impl FunctionCallParser {
-        pub fn new() -> FunctionCallParser {
-            let __builder = super::__intern_token::__MatcherBuilder::new();
-            FunctionCallParser {
-                builder: __builder,
-                _priv: (),
-            }
-        }
-
-        #[allow(dead_code)]
-        pub fn parse<
-            'input,
-        >(
-            &self,
-            input: &'input str,
-        ) -> Result<FunctionCall, __lalrpop_util::ParseError<usize, Token<'input>, &'static str>>
-        {
-            let mut __tokens = self.builder.matcher(input);
-            let mut __states = vec![0_i8];
-            let mut __symbols = vec![];
-            let mut __integer;
-            let mut __lookahead;
-            let __last_location = &mut Default::default();
-            '__shift: loop {
-                __lookahead = match __tokens.next() {
-                    Some(Ok(v)) => v,
-                    None => break '__shift,
-                    Some(Err(e)) => return Err(e),
-                };
-                *__last_location = __lookahead.2.clone();
-                __integer = match __lookahead.1 {
-                    Token(2, _) if true => 0,
-                    Token(3, _) if true => 1,
-                    Token(4, _) if true => 2,
-                    Token(5, _) if true => 3,
-                    Token(6, _) if true => 4,
-                    Token(7, _) if true => 5,
-                    Token(8, _) if true => 6,
-                    Token(0, _) if true => 7,
-                    Token(1, _) if true => 8,
-                    _ => {
-                        let __state = *__states.last().unwrap() as usize;
-                        let __error = __lalrpop_util::ParseError::UnrecognizedToken {
-                            token: Some(__lookahead),
-                            expected: __expected_tokens(__state),
-                        };
-                        return Err(__error);
-                    }
-                };
-                '__inner: loop {
-                    let __state = *__states.last().unwrap() as usize;
-                    let __action = __ACTION[__state * 9 + __integer];
-                    if __action > 0 {
-                        let __symbol = match __integer {
-                            0 => match __lookahead.1 {
-                                Token(2, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            1 => match __lookahead.1 {
-                                Token(3, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            2 => match __lookahead.1 {
-                                Token(4, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            3 => match __lookahead.1 {
-                                Token(5, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            4 => match __lookahead.1 {
-                                Token(6, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            5 => match __lookahead.1 {
-                                Token(7, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            6 => match __lookahead.1 {
-                                Token(8, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            7 => match __lookahead.1 {
-                                Token(0, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            8 => match __lookahead.1 {
-                                Token(1, __tok0) => __Symbol::Variant0((__tok0)),
-                                _ => unreachable!(),
-                            },
-                            _ => unreachable!(),
-                        };
-                        __states.push(__action - 1);
-                        __symbols.push((__lookahead.0, __symbol, __lookahead.2));
-                        continue '__shift;
-                    } else if __action < 0 {
-                        if let Some(r) = __reduce(input, __action, Some(&__lookahead.0), &mut __states, &mut __symbols, ::std::marker::PhantomData::<()>) {
-                            if r.is_err() {
-                                return r;
-                            }
-                            return Err(__lalrpop_util::ParseError::ExtraToken { token: __lookahead });
-                        }
-                    } else {
-                        let mut __err_lookahead = Some(__lookahead);
-                        let mut __err_integer: Option<usize> = Some(__integer);
-                        let __state = *__states.last().unwrap() as usize;
-                        let __error = __lalrpop_util::ParseError::UnrecognizedToken {
-                            token: __err_lookahead,
-                            expected: __expected_tokens(__state),
-                        };
-                        return Err(__error)
-                    }
-                }
-            }
-            loop {
-                let __state = *__states.last().unwrap() as usize;
-                let __action = __EOF_ACTION[__state];
-                if __action < 0 {
-                    if let Some(r) = __reduce(input, __action, None, &mut __states, &mut __symbols, ::std::marker::PhantomData::<()>) {
-                        return r;
-                    }
-                } else {
-                    let mut __err_lookahead = None;
-                    let mut __err_integer: Option<usize> = None;
-                    let __state = *__states.last().unwrap() as usize;
-                    let __error = __lalrpop_util::ParseError::UnrecognizedToken {
-                        token: __err_lookahead,
-                        expected: __expected_tokens(__state),
-                    };
-                    return Err(__error)
-                }
-            }
-        }
-    }
-    pub(crate) fn __reduce<
-        'input,
-    >(
-        input: &'input str,
-        __action: i8,
-        __lookahead_start: Option<&usize>,
-        __states: &mut ::std::vec::Vec<i8>,
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>,
-        _: ::std::marker::PhantomData<()>,
-    ) -> Option<Result<FunctionCall,__lalrpop_util::ParseError<usize, Token<'input>, &'static str>>>
-    {
-        let (__pop_states, __symbol, __nonterminal) = match -__action {
-            1 => {
-                (|| {
-                    // (<Expr> ",") = Expr, "," => ActionFn(22);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym1.2.clone();
-                    let __nt = super::__action22::<>(input, __sym0, __sym1);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (2, __symbol, 0)
-                })()
-            }
-            2 => {
-                (|| {
-                    // (<Expr> ",")* =  => ActionFn(20);
-                    let __start = __symbols.last().map(|s| s.2.clone()).unwrap_or_default();
-                    let __end = __lookahead_start.cloned().unwrap_or_else(|| __start.clone());
-                    let __nt = super::__action20::<>(input, &__start, &__end);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (0, __symbol, 1)
-                })()
-            }
-            3 => {
-                (|| {
-                    // (<Expr> ",")* = (<Expr> ",")+ => ActionFn(21);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action21::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (1, __symbol, 1)
-                })()
-            }
-            4 => {
-                (|| {
-                    // (<Expr> ",")+ = Expr, "," => ActionFn(25);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym1.2.clone();
-                    let __nt = super::__action25::<>(input, __sym0, __sym1);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (2, __symbol, 2)
-                })()
-            }
-            5 => {
-                (|| {
-                    // (<Expr> ",")+ = (<Expr> ",")+, Expr, "," => ActionFn(26);
-                    let __sym2 = __pop_Variant0(__symbols);
-                    let __sym1 = __pop_Variant1(__symbols);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action26::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant2(__nt), __end);
-                    (3, __symbol, 2)
-                })()
-            }
-            6 => {
-                (|| {
-                    // Atom = num => ActionFn(13);
-                    let __sym0 = __pop_Variant8(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action13::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant3(__nt), __end);
-                    (1, __symbol, 3)
-                })()
-            }
-            7 => {
-                (|| {
-                    // Atom = ident => ActionFn(14);
-                    let __sym0 = __pop_Variant7(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action14::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant3(__nt), __end);
-                    (1, __symbol, 3)
-                })()
-            }
-            8 => {
-                (|| {
-                    // CommaSeparated<Expr> = Expr => ActionFn(29);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action29::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (1, __symbol, 4)
-                })()
-            }
-            9 => {
-                (|| {
-                    // CommaSeparated<Expr> =  => ActionFn(30);
-                    let __start = __symbols.last().map(|s| s.2.clone()).unwrap_or_default();
-                    let __end = __lookahead_start.cloned().unwrap_or_else(|| __start.clone());
-                    let __nt = super::__action30::<>(input, &__start, &__end);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (0, __symbol, 4)
-                })()
-            }
-            10 => {
-                (|| {
-                    // CommaSeparated<Expr> = (<Expr> ",")+, Expr => ActionFn(31);
-                    let __sym1 = __pop_Variant1(__symbols);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym1.2.clone();
-                    let __nt = super::__action31::<>(input, __sym0, __sym1);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (2, __symbol, 4)
-                })()
-            }
-            11 => {
-                (|| {
-                    // CommaSeparated<Expr> = (<Expr> ",")+ => ActionFn(32);
-                    let __sym0 = __pop_Variant2(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action32::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant4(__nt), __end);
-                    (1, __symbol, 4)
-                })()
-            }
-            12 => {
-                (|| {
-                    // Expr = Expr, "+", Factor => ActionFn(3);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action3::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 5)
-                })()
-            }
-            13 => {
-                (|| {
-                    // Expr = Expr, "-", Factor => ActionFn(4);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action4::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 5)
-                })()
-            }
-            14 => {
-                (|| {
-                    // Expr = Factor => ActionFn(5);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action5::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 5)
-                })()
-            }
-            15 => {
-                (|| {
-                    // Expr? = Expr => ActionFn(18);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action18::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant5(__nt), __end);
-                    (1, __symbol, 6)
-                })()
-            }
-            16 => {
-                (|| {
-                    // Expr? =  => ActionFn(19);
-                    let __start = __symbols.last().map(|s| s.2.clone()).unwrap_or_default();
-                    let __end = __lookahead_start.cloned().unwrap_or_else(|| __start.clone());
-                    let __nt = super::__action19::<>(input, &__start, &__end);
-                    let __symbol = (__start, __Symbol::Variant5(__nt), __end);
-                    (0, __symbol, 6)
-                })()
-            }
-            17 => {
-                (|| {
-                    // Factor = Factor, "*", Term => ActionFn(6);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action6::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 7)
-                })()
-            }
-            18 => {
-                (|| {
-                    // Factor = Factor, "/", Term => ActionFn(7);
-                    let __sym2 = __pop_Variant1(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action7::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 7)
-                })()
-            }
-            19 => {
-                (|| {
-                    // Factor = Term => ActionFn(8);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action8::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 7)
-                })()
-            }
-            20 => {
-                (|| {
-                    // FunctionCall = ident, "(", CommaSeparated<Expr>, ")" => ActionFn(12);
-                    let __sym3 = __pop_Variant0(__symbols);
-                    let __sym2 = __pop_Variant4(__symbols);
-                    let __sym1 = __pop_Variant0(__symbols);
-                    let __sym0 = __pop_Variant7(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym3.2.clone();
-                    let __nt = super::__action12::<>(input, __sym0, __sym1, __sym2, __sym3);
-                    let __symbol = (__start, __Symbol::Variant6(__nt), __end);
-                    (4, __symbol, 8)
-                })()
-            }
-            21 => {
-                (|| {
-                    // Term = "(", Expr, ")" => ActionFn(9);
-                    let __sym2 = __pop_Variant0(__symbols);
-                    let __sym1 = __pop_Variant1(__symbols);
-                    let __sym0 = __pop_Variant0(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym2.2.clone();
-                    let __nt = super::__action9::<>(input, __sym0, __sym1, __sym2);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (3, __symbol, 9)
-                })()
-            }
-            22 => {
-                (|| {
-                    // Term = Atom => ActionFn(10);
-                    let __sym0 = __pop_Variant3(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action10::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 9)
-                })()
-            }
-            23 => {
-                (|| {
-                    // Term = FunctionCall => ActionFn(11);
-                    let __sym0 = __pop_Variant6(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action11::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 9)
-                })()
-            }
-            24 => {
-                (|| {
-                    // __Atom = Atom => ActionFn(2);
-                    let __sym0 = __pop_Variant3(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action2::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant3(__nt), __end);
-                    (1, __symbol, 10)
-                })()
-            }
-            25 => {
-                (|| {
-                    // __Expr = Expr => ActionFn(0);
-                    let __sym0 = __pop_Variant1(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action0::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant1(__nt), __end);
-                    (1, __symbol, 11)
-                })()
-            }
-            26 => {
-                // __FunctionCall = FunctionCall => ActionFn(1);
-                let __sym0 = __pop_Variant6(__symbols);
-                let __start = __sym0.0.clone();
-                let __end = __sym0.2.clone();
-                let __nt = super::__action1::<>(input, __sym0);
-                return Some(Ok(__nt));
-            }
-            27 => {
-                (|| {
-                    // ident = r#"[a-zA-Z][a-zA-Z0-9_-]*"# => ActionFn(16);
-                    let __sym0 = __pop_Variant0(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action16::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant7(__nt), __end);
-                    (1, __symbol, 13)
-                })()
-            }
-            28 => {
-                (|| {
-                    // num = r#"[0-9]+(\\.[0-9]+)?"# => ActionFn(15);
-                    let __sym0 = __pop_Variant0(__symbols);
-                    let __start = __sym0.0.clone();
-                    let __end = __sym0.2.clone();
-                    let __nt = super::__action15::<>(input, __sym0);
-                    let __symbol = (__start, __Symbol::Variant8(__nt), __end);
-                    (1, __symbol, 14)
-                })()
-            }
-            _ => panic!("invalid action code {}", __action)
-        };
-        let __states_len = __states.len();
-        __states.truncate(__states_len - __pop_states);
-        __symbols.push(__symbol);
-        let __state = *__states.last().unwrap() as usize;
-        let __next_state = __GOTO[__state * 15 + __nonterminal] - 1;
-        __states.push(__next_state);
-        None
-    }
-    fn __pop_Variant3<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, Atom, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant3(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant1<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, Expr, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant1(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant6<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, FunctionCall, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant6(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant7<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, String, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant7(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant4<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, Vec<Expr>, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant4(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant8<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, f64, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant8(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant5<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, ::std::option::Option<Expr>, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant5(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant2<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, ::std::vec::Vec<Expr>, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant2(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-    fn __pop_Variant0<
-      'input,
-    >(
-        __symbols: &mut ::std::vec::Vec<(usize,__Symbol<'input>,usize)>
-    ) -> (usize, &'input str, usize)
-     {
-        match __symbols.pop().unwrap() {
-            (__l, __Symbol::Variant0(__v), __r) => (__l, __v, __r),
-            _ => panic!("symbol type mismatch")
-        }
-    }
-}
-pub use self::__parse__FunctionCall::FunctionCallParser;
-#[cfg_attr(rustfmt, rustfmt_skip)]
-mod __intern_token {
-    #![allow(unused_imports)]
-    use syntax::ast::{Expr, Atom, BinaryOp, FunctionCall};
-    #[allow(unused_extern_crates)]
-    extern crate lalrpop_util as __lalrpop_util;
-    extern crate regex as __regex;
-    use std::fmt as __fmt;
-
-    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-    pub struct Token<'input>(pub usize, pub &'input str);
-    impl<'a> __fmt::Display for Token<'a> {
-        fn fmt(&self, formatter: &mut __fmt::Formatter) -> Result<(), __fmt::Error> {
-            __fmt::Display::fmt(self.1, formatter)
-        }
-    }
-
-    pub struct __MatcherBuilder {
-        regex_set: __regex::RegexSet,
-        regex_vec: Vec<__regex::Regex>,
-    }
-
-    impl __MatcherBuilder {
-        pub fn new() -> __MatcherBuilder {
-            let __strs: &[&str] = &[
-                "^((?u:[0-9])+((?u:\\.)(?u:[0-9])+)?)",
-                "^((?u:[A-Za-z])(?u:[-0-9A-Z_-_a-z])*)",
-                "^((?u:\\())",
-                "^((?u:\\)))",
-                "^((?u:\\*))",
-                "^((?u:\\+))",
-                "^((?u:,))",
-                "^((?u:\\-))",
-                "^((?u:/))",
-            ];
-            let __regex_set = __regex::RegexSet::new(__strs).unwrap();
-            let __regex_vec = vec![
-                __regex::Regex::new("^((?u:[0-9])+((?u:\\.)(?u:[0-9])+)?)").unwrap(),
-                __regex::Regex::new("^((?u:[A-Za-z])(?u:[-0-9A-Z_-_a-z])*)").unwrap(),
-                __regex::Regex::new("^((?u:\\())").unwrap(),
-                __regex::Regex::new("^((?u:\\)))").unwrap(),
-                __regex::Regex::new("^((?u:\\*))").unwrap(),
-                __regex::Regex::new("^((?u:\\+))").unwrap(),
-                __regex::Regex::new("^((?u:,))").unwrap(),
-                __regex::Regex::new("^((?u:\\-))").unwrap(),
-                __regex::Regex::new("^((?u:/))").unwrap(),
-            ];
-            __MatcherBuilder { regex_set: __regex_set, regex_vec: __regex_vec }
-        }
-        pub fn matcher<'input, 'builder>(&'builder self, s: &'input str) -> __Matcher<'input, 'builder> {
-            __Matcher {
-                text: s,
-                consumed: 0,
-                regex_set: &self.regex_set,
-                regex_vec: &self.regex_vec,
-            }
-        }
-    }
-
-    pub struct __Matcher<'input, 'builder> {
-        text: &'input str,
-        consumed: usize,
-        regex_set: &'builder __regex::RegexSet,
-        regex_vec: &'builder Vec<__regex::Regex>,
-    }
-
-    impl<'input, 'builder> Iterator for __Matcher<'input, 'builder> {
-        type Item = Result<(usize, Token<'input>, usize), __lalrpop_util::ParseError<usize,Token<'input>,&'static str>>;
-
-        fn next(&mut self) -> Option<Self::Item> {
-            let __text = self.text.trim_left();
-            let __whitespace = self.text.len() - __text.len();
-            let __start_offset = self.consumed + __whitespace;
-            if __text.is_empty() {
-                self.text = __text;
-                self.consumed = __start_offset;
-                None
-            } else {
-                let __matches = self.regex_set.matches(__text);
-                if !__matches.matched_any() {
-                    Some(Err(__lalrpop_util::ParseError::InvalidToken {
-                        location: __start_offset,
-                    }))
-                } else {
-                    let mut __longest_match = 0;
-                    let mut __index = 0;
-                    for __i in 0 .. 9 {
-                        if __matches.matched(__i) {
-                            let __match = self.regex_vec[__i].find(__text).unwrap();
-                            let __len = __match.end();
-                            if __len >= __longest_match {
-                                __longest_match = __len;
-                                __index = __i;
-                            }
-                        }
-                    }
-                    let __result = &__text[..__longest_match];
-                    let __remaining = &__text[__longest_match..];
-                    let __end_offset = __start_offset + __longest_match;
-                    self.text = __remaining;
-                    self.consumed = __end_offset;
-                    Some(Ok((__start_offset, Token(__index, __result), __end_offset)))
-                }
-            }
-        }
-    }
-}
-pub use self::__intern_token::Token;
-
-#[allow(unused_variables)]
-fn __action0<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, Expr, usize),
-) -> Expr
-{
-    (__0)
-}
-
-#[allow(unused_variables)]
-fn __action1<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, FunctionCall, usize),
-) -> FunctionCall
-{
-    (__0)
-}
-
-#[allow(unused_variables)]
-fn __action2<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, Atom, usize),
-) -> Atom
-{
-    (__0)
-}
-
-#[allow(unused_variables)]
-fn __action3<
-    'input,
->(
-    input: &'input str,
-    (_, l, _): (usize, Expr, usize),
-    (_, _, _): (usize, &'input str, usize),
-    (_, r, _): (usize, Expr, usize),
-) -> Expr
-{
-    BinaryOp::add(l, r).into()
-}
-
-#[allow(unused_variables)]
-fn __action4<
-    'input,
->(
-    input: &'input str,
-    (_, l, _): (usize, Expr, usize),
-    (_, _, _): (usize, &'input str, usize),
-    (_, r, _): (usize, Expr, usize),
-) -> Expr
-{
-    BinaryOp::sub(l, r).into()
-}
-
-#[allow(unused_variables)]
-fn __action5<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, Expr, usize),
-) -> Expr
-{
-    (__0)
-}
-
-#[allow(unused_variables)]
-fn __action6<
-    'input,
->(
-    input: &'input str,
-    (_, l, _): (usize, Expr, usize),
-    (_, _, _): (usize, &'input str, usize),
-    (_, r, _): (usize, Expr, usize),
-) -> Expr
-{
-    BinaryOp::mult(l, r).into()
-}
-
-#[allow(unused_variables)]
-fn __action7<
-    'input,
->(
-    input: &'input str,
-    (_, l, _): (usize, Expr, usize),
-    (_, _, _): (usize, &'input str, usize),
-    (_, r, _): (usize, Expr, usize),
-) -> Expr
-{
-    BinaryOp::div(l, r).into()
-}
-
-#[allow(unused_variables)]
-fn __action8<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, Expr, usize),
-) -> Expr
-{
-    (__0)
-}
-
-#[allow(unused_variables)]
-fn __action9<
-    'input,
->(
-    input: &'input str,
-    (_, _, _): (usize, &'input str, usize),
-    (_, e, _): (usize, Expr, usize),
-    (_, _, _): (usize, &'input str, usize),
-) -> Expr
-{
-    e
-}
-
-#[allow(unused_variables)]
-fn __action10<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, Atom, usize),
-) -> Expr
-{
-    Expr::Atom(__0)
-}
-
-#[allow(unused_variables)]
-fn __action11<
-    'input,
->(
-    input: &'input str,
-    (_, f, _): (usize, FunctionCall, usize),
-) -> Expr
-{
-    f.into()
-}
-
-#[allow(unused_variables)]
-fn __action12<
-    'input,
->(
-    input: &'input str,
-    (_, i, _): (usize, String, usize),
-    (_, _, _): (usize, &'input str, usize),
-    (_, a, _): (usize, Vec<Expr>, usize),
-    (_, _, _): (usize, &'input str, usize),
-) -> FunctionCall
-{
-    FunctionCall::new(i, a)
-}
-
-#[allow(unused_variables)]
-fn __action13<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, f64, usize),
-) -> Atom
-{
-    Atom::Number(__0)
-}
-
-#[allow(unused_variables)]
-fn __action14<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, String, usize),
-) -> Atom
-{
-    Atom::Ident(__0)
-}
-
-#[allow(unused_variables)]
-fn __action15<
-    'input,
->(
-    input: &'input str,
-    (_, s, _): (usize, &'input str, usize),
-) -> f64
-{
-    s.parse().unwrap()
-}
-
-#[allow(unused_variables)]
-fn __action16<
-    'input,
->(
-    input: &'input str,
-    (_, i, _): (usize, &'input str, usize),
-) -> String
-{
-    i.to_string()
-}
-
-#[allow(unused_variables)]
-fn __action17<
-    'input,
->(
-    input: &'input str,
-    (_, v, _): (usize, ::std::vec::Vec<Expr>, usize),
-    (_, e, _): (usize, ::std::option::Option<Expr>, usize),
-) -> Vec<Expr>
-{
-    match e {
-        None => v,
-        Some(e) => {
-            let mut v = v;
-            v.push(e);
-            v
-        }
-    }
-}
-
-#[allow(unused_variables)]
-fn __action18<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, Expr, usize),
-) -> ::std::option::Option<Expr>
-{
-    Some(__0)
-}
-
-#[allow(unused_variables)]
-fn __action19<
-    'input,
->(
-    input: &'input str,
-    __lookbehind: &usize,
-    __lookahead: &usize,
-) -> ::std::option::Option<Expr>
-{
-    None
-}
-
-#[allow(unused_variables)]
-fn __action20<
-    'input,
->(
-    input: &'input str,
-    __lookbehind: &usize,
-    __lookahead: &usize,
-) -> ::std::vec::Vec<Expr>
-{
-    vec![]
-}
-
-#[allow(unused_variables)]
-fn __action21<
-    'input,
->(
-    input: &'input str,
-    (_, v, _): (usize, ::std::vec::Vec<Expr>, usize),
-) -> ::std::vec::Vec<Expr>
-{
-    v
-}
-
-#[allow(unused_variables)]
-fn __action22<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, Expr, usize),
-    (_, _, _): (usize, &'input str, usize),
-) -> Expr
-{
-    (__0)
-}
-
-#[allow(unused_variables)]
-fn __action23<
-    'input,
->(
-    input: &'input str,
-    (_, __0, _): (usize, Expr, usize),
-) -> ::std::vec::Vec<Expr>
-{
-    vec![__0]
-}
-
-#[allow(unused_variables)]
-fn __action24<
-    'input,
->(
-    input: &'input str,
-    (_, v, _): (usize, ::std::vec::Vec<Expr>, usize),
-    (_, e, _): (usize, Expr, usize),
-) -> ::std::vec::Vec<Expr>
-{
-    { let mut v = v; v.push(e); v }
-}
-
-#[allow(unused_variables)]
-fn __action25<
-    'input,
->(
-    input: &'input str,
-    __0: (usize, Expr, usize),
-    __1: (usize, &'input str, usize),
-) -> ::std::vec::Vec<Expr>
-{
-    let __start0 = __0.0.clone();
-    let __end0 = __1.2.clone();
-    let __temp0 = __action22(
-        input,
-        __0,
-        __1,
-    );
-    let __temp0 = (__start0, __temp0, __end0);
-    __action23(
-        input,
-        __temp0,
-    )
-}
-
-#[allow(unused_variables)]
-fn __action26<
-    'input,
->(
-    input: &'input str,
-    __0: (usize, ::std::vec::Vec<Expr>, usize),
-    __1: (usize, Expr, usize),
-    __2: (usize, &'input str, usize),
-) -> ::std::vec::Vec<Expr>
-{
-    let __start0 = __1.0.clone();
-    let __end0 = __2.2.clone();
-    let __temp0 = __action22(
-        input,
-        __1,
-        __2,
-    );
-    let __temp0 = (__start0, __temp0, __end0);
-    __action24(
-        input,
-        __0,
-        __temp0,
-    )
-}
-
-#[allow(unused_variables)]
-fn __action27<
-    'input,
->(
-    input: &'input str,
-    __0: (usize, ::std::option::Option<Expr>, usize),
-) -> Vec<Expr>
-{
-    let __start0 = __0.0.clone();
-    let __end0 = __0.0.clone();
-    let __temp0 = __action20(
-        input,
-        &__start0,
-        &__end0,
-    );
-    let __temp0 = (__start0, __temp0, __end0);
-    __action17(
-        input,
-        __temp0,
-        __0,
-    )
-}
-
-#[allow(unused_variables)]
-fn __action28<
-    'input,
->(
-    input: &'input str,
-    __0: (usize, ::std::vec::Vec<Expr>, usize),
-    __1: (usize, ::std::option::Option<Expr>, usize),
-) -> Vec<Expr>
-{
-    let __start0 = __0.0.clone();
-    let __end0 = __0.2.clone();
-    let __temp0 = __action21(
-        input,
-        __0,
-    );
-    let __temp0 = (__start0, __temp0, __end0);
-    __action17(
-        input,
-        __temp0,
-        __1,
-    )
-}
-
-#[allow(unused_variables)]
-fn __action29<
-    'input,
->(
-    input: &'input str,
-    __0: (usize, Expr, usize),
-) -> Vec<Expr>
-{
-    let __start0 = __0.0.clone();
-    let __end0 = __0.2.clone();
-    let __temp0 = __action18(
-        input,
-        __0,
-    );
-    let __temp0 = (__start0, __temp0, __end0);
-    __action27(
-        input,
-        __temp0,
-    )
-}
-
-#[allow(unused_variables)]
-fn __action30<
-    'input,
->(
-    input: &'input str,
-    __lookbehind: &usize,
-    __lookahead: &usize,
-) -> Vec<Expr>
-{
-    let __start0 = __lookbehind.clone();
-    let __end0 = __lookahead.clone();
-    let __temp0 = __action19(
-        input,
-        &__start0,
-        &__end0,
-    );
-    let __temp0 = (__start0, __temp0, __end0);
-    __action27(
-        input,
-        __temp0,
-    )
-}
-
-#[allow(unused_variables)]
-fn __action31<
-    'input,
->(
-    input: &'input str,
-    __0: (usize, ::std::vec::Vec<Expr>, usize),
-    __1: (usize, Expr, usize),
-) -> Vec<Expr>
-{
-    let __start0 = __1.0.clone();
-    let __end0 = __1.2.clone();
-    let __temp0 = __action18(
-        input,
-        __1,
-    );
-    let __temp0 = (__start0, __temp0, __end0);
-    __action28(
-        input,
-        __0,
-        __temp0,
-    )
-}
-
-#[allow(unused_variables)]
-fn __action32<
-    'input,
->(
-    input: &'input str,
-    __0: (usize, ::std::vec::Vec<Expr>, usize),
-) -> Vec<Expr>
-{
-    let __start0 = __0.2.clone();
-    let __end0 = __0.2.clone();
-    let __temp0 = __action19(
-        input,
-        &__start0,
-        &__end0,
-    );
-    let __temp0 = (__start0, __temp0, __end0);
-    __action28(
-        input,
-        __0,
-        __temp0,
-    )
-}
-
-pub trait __ToTriple<'input, > {
-    type Error;
-    fn to_triple(value: Self) -> Result<(usize,Token<'input>,usize),Self::Error>;
-}
-
-impl<'input, > __ToTriple<'input, > for (usize, Token<'input>, usize) {
-    type Error = &'static str;
-    fn to_triple(value: Self) -> Result<(usize,Token<'input>,usize),&'static str> {
-        Ok(value)
-    }
-}
-impl<'input, > __ToTriple<'input, > for Result<(usize, Token<'input>, usize),&'static str> {
-    type Error = &'static str;
-    fn to_triple(value: Self) -> Result<(usize,Token<'input>,usize),&'static str> {
-        value
-    }
-}
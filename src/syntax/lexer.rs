@@ -0,0 +1,793 @@
+//! A hand-written lexer which turns source text into a stream of tokens for
+//! the parser.
+//!
+//! Whitespace and comments are skipped entirely. A comment is either a `#`
+//! followed by everything up to the end of the line, or a `/* ... */` block.
+//!
+//! Numeric literals may be written as integers (`42`), decimals (`3.14`,
+//! `.5`), in scientific notation (`6.02e23`, `1e-9`) or as hexadecimal
+//! (`0x1F`). Underscores can be used to separate digits (`1_000_000`). Any
+//! literal with a decimal point or exponent is a float, everything else is a
+//! 64-bit integer.
+//!
+//! A number may be followed by a unit (`9.81 m/s^2`, `3s`). This is only
+//! recognised when the word after the number is a known unit symbol, with
+//! further units joined by `*` or `/` (no spaces) and raised to an integer
+//! power with `^`. Anywhere else `^` is the power operator, so `x^2` and
+//! `2 m^2` both work.
+
+use std::fmt::{self, Display, Formatter};
+use std::iter::Peekable;
+use std::mem;
+use std::str::CharIndices;
+use units;
+
+/// A single token, borrowing from the original source text where possible.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Token<'input> {
+    /// A floating point literal.
+    Number(f64),
+    /// An integer literal.
+    Integer(i64),
+    /// An imaginary literal (e.g. `4i`).
+    Imaginary(f64),
+    /// An identifier (e.g. `foo`).
+    Ident(&'input str),
+    /// The unit attached to a number (e.g. the `m/s^2` in `9.81 m/s^2`).
+    Unit(&'input str),
+    /// `+`
+    Plus,
+    /// `-`
+    Minus,
+    /// `*`
+    Star,
+    /// `/`
+    Slash,
+    /// `//`
+    DoubleSlash,
+    /// `%`
+    Percent,
+    /// `^`
+    Caret,
+    /// `(`
+    OpenParen,
+    /// `)`
+    CloseParen,
+    /// `[`
+    OpenBracket,
+    /// `]`
+    CloseBracket,
+    /// `,`
+    Comma,
+    /// `->`
+    Arrow,
+    /// `<`
+    LessThan,
+    /// `<=`
+    LessThanOrEqual,
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `>`
+    GreaterThan,
+    /// `>=`
+    GreaterThanOrEqual,
+    /// The `and` keyword.
+    And,
+    /// The `or` keyword.
+    Or,
+    /// The `not` keyword.
+    Not,
+    /// The `if` keyword.
+    If,
+    /// The `then` keyword.
+    Then,
+    /// The `else` keyword.
+    Else,
+    /// The `true` keyword.
+    True,
+    /// The `false` keyword.
+    False,
+}
+
+impl<'input> Display for Token<'input> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Integer(n) => write!(f, "{}", n),
+            Token::Imaginary(n) => write!(f, "{}i", n),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Unit(unit) => write!(f, "{}", unit),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::DoubleSlash => write!(f, "//"),
+            Token::Percent => write!(f, "%"),
+            Token::Caret => write!(f, "^"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+            Token::OpenBracket => write!(f, "["),
+            Token::CloseBracket => write!(f, "]"),
+            Token::Comma => write!(f, ","),
+            Token::Arrow => write!(f, "->"),
+            Token::LessThan => write!(f, "<"),
+            Token::LessThanOrEqual => write!(f, "<="),
+            Token::Equal => write!(f, "=="),
+            Token::NotEqual => write!(f, "!="),
+            Token::GreaterThan => write!(f, ">"),
+            Token::GreaterThanOrEqual => write!(f, ">="),
+            Token::And => write!(f, "and"),
+            Token::Or => write!(f, "or"),
+            Token::Not => write!(f, "not"),
+            Token::If => write!(f, "if"),
+            Token::Then => write!(f, "then"),
+            Token::Else => write!(f, "else"),
+            Token::True => write!(f, "true"),
+            Token::False => write!(f, "false"),
+        }
+    }
+}
+
+/// The things which can go wrong while tokenizing.
+#[derive(Debug, Copy, Clone, PartialEq, Fail)]
+pub enum LexError {
+    /// Encountered a character which can't start a token.
+    #[fail(display = "Invalid character {:?} at {}", ch, location)]
+    InvalidCharacter {
+        /// The offending character.
+        ch: char,
+        /// The character's byte index in the source text.
+        location: usize,
+    },
+    /// A `/*` was never closed with a matching `*/`.
+    #[fail(display = "Unterminated block comment starting at {}", start)]
+    UnterminatedComment {
+        /// The byte index of the opening `/*`.
+        start: usize,
+    },
+    /// A numeric literal which doesn't contain any digits (e.g. `0x`).
+    #[fail(display = "Malformed number at {}..{}", start, end)]
+    MalformedNumber {
+        /// The byte index the literal starts at.
+        start: usize,
+        /// The byte index just past the end of the literal.
+        end: usize,
+    },
+    /// A numeric literal which is too large to be represented.
+    #[fail(display = "The number at {}..{} is too large", start, end)]
+    NumberOverflow {
+        /// The byte index the literal starts at.
+        start: usize,
+        /// The byte index just past the end of the literal.
+        end: usize,
+    },
+}
+
+/// A token and the byte indices of its start and end in the source text.
+pub type Spanned<'input> = (usize, Token<'input>, usize);
+
+/// An iterator over the tokens in some source text.
+#[derive(Debug, Clone)]
+pub struct Lexer<'input> {
+    src: &'input str,
+    chars: Peekable<CharIndices<'input>>,
+    follows_number: bool,
+}
+
+impl<'input> Lexer<'input> {
+    /// Create a new `Lexer` for the provided source text.
+    pub fn new(src: &'input str) -> Lexer<'input> {
+        Lexer {
+            src,
+            chars: src.char_indices().peekable(),
+            follows_number: false,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    /// The byte index of the next character, or the end of input.
+    fn position(&mut self) -> usize {
+        let end = self.src.len();
+        self.chars.peek().map(|&(ix, _)| ix).unwrap_or(end)
+    }
+
+    /// Consume characters until reaching the provided byte index.
+    fn advance_to(&mut self, end: usize) {
+        while self.position() < end {
+            self.chars.next();
+        }
+    }
+
+    /// Advance while the predicate holds, returning the index just past the
+    /// last character consumed.
+    fn take_while<P>(&mut self, mut predicate: P) -> usize
+    where
+        P: FnMut(char) -> bool,
+    {
+        while let Some(c) = self.peek() {
+            if !predicate(c) {
+                break;
+            }
+            self.chars.next();
+        }
+
+        self.position()
+    }
+
+    /// Skip over any whitespace and comments, stopping at the start of the
+    /// next token.
+    fn skip_trivia(&mut self) -> Result<(), LexError> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                Some('#') => {
+                    self.take_while(|c| c != '\n');
+                }
+                Some('/') if self.src[self.position()..].starts_with("/*") => {
+                    self.block_comment()?;
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn block_comment(&mut self) -> Result<(), LexError> {
+        let start = self.position();
+        let body = &self.src[start + 2..];
+
+        match body.find("*/") {
+            Some(ix) => {
+                self.advance_to(start + 2 + ix + 2);
+                Ok(())
+            }
+            None => Err(LexError::UnterminatedComment { start }),
+        }
+    }
+
+    fn number(&mut self, start: usize) -> Result<Spanned<'input>, LexError> {
+        let rest = &self.src[start..];
+        if rest.starts_with("0x") || rest.starts_with("0X") {
+            return self.hex_number(start);
+        }
+
+        let bytes = self.src.as_bytes();
+        let mut end = scan_digits(bytes, start, |b| b.is_ascii_digit());
+        let mut is_float = false;
+
+        if bytes.get(end) == Some(&b'.') && is_digit_at(bytes, end + 1) {
+            end = scan_digits(bytes, end + 1, |b| b.is_ascii_digit());
+            is_float = true;
+        }
+
+        if bytes.get(end) == Some(&b'e') || bytes.get(end) == Some(&b'E') {
+            let mut exponent = end + 1;
+            if bytes.get(exponent) == Some(&b'+') || bytes.get(exponent) == Some(&b'-') {
+                exponent += 1;
+            }
+
+            // a lone "e" isn't an exponent, so leave it for the next token
+            if is_digit_at(bytes, exponent) {
+                end = scan_digits(bytes, exponent, |b| b.is_ascii_digit());
+                is_float = true;
+            }
+        }
+
+        // a number immediately followed by an "i" is imaginary
+        let is_imaginary = bytes.get(end) == Some(&b'i')
+            && !bytes
+                .get(end + 1)
+                .map_or(false, |&b| b.is_ascii_alphanumeric() || b == b'_');
+
+        let digits = without_underscores(&self.src[start..end]);
+
+        if is_imaginary {
+            end += 1;
+            self.advance_to(end);
+            let value: f64 = digits
+                .parse()
+                .map_err(|_| LexError::MalformedNumber { start, end })?;
+
+            return if value.is_finite() {
+                Ok((start, Token::Imaginary(value), end))
+            } else {
+                Err(LexError::NumberOverflow { start, end })
+            };
+        }
+
+        self.advance_to(end);
+
+        if !is_float {
+            return match digits.parse() {
+                Ok(value) => Ok((start, Token::Integer(value), end)),
+                Err(_) => Err(LexError::NumberOverflow { start, end }),
+            };
+        }
+
+        let value: f64 = digits
+            .parse()
+            .map_err(|_| LexError::MalformedNumber { start, end })?;
+
+        if value.is_finite() {
+            Ok((start, Token::Number(value), end))
+        } else {
+            Err(LexError::NumberOverflow { start, end })
+        }
+    }
+
+    /// Try to lex the unit after a number, consuming nothing if `start`
+    /// isn't the beginning of a known unit.
+    fn unit(&mut self, start: usize) -> Option<Spanned<'input>> {
+        let bytes = self.src.as_bytes();
+        let mut end = self.unit_symbol_at(start)?;
+
+        loop {
+            if bytes.get(end) == Some(&b'^') {
+                let mut exponent = end + 1;
+                if bytes.get(exponent) == Some(&b'-') {
+                    exponent += 1;
+                }
+                if is_digit_at(bytes, exponent) {
+                    end = scan_digits(bytes, exponent, |b| b.is_ascii_digit());
+                }
+            }
+
+            // only keep going if the separator is followed by another unit
+            match bytes.get(end) {
+                Some(&b'*') | Some(&b'/') => match self.unit_symbol_at(end + 1) {
+                    Some(next) => end = next,
+                    None => break,
+                },
+                _ => break,
+            }
+        }
+
+        self.advance_to(end);
+        Some((start, Token::Unit(&self.src[start..end]), end))
+    }
+
+    /// If a known unit symbol starts at `start`, find where it ends.
+    fn unit_symbol_at(&self, start: usize) -> Option<usize> {
+        let bytes = self.src.as_bytes();
+        let end = bytes[start.min(bytes.len())..]
+            .iter()
+            .position(|&b| !(b.is_ascii_alphanumeric() || b == b'_'))
+            .map(|ix| start + ix)
+            .unwrap_or(bytes.len());
+
+        if start < end && units::lookup(&self.src[start..end]).is_some() {
+            Some(end)
+        } else {
+            None
+        }
+    }
+
+    fn hex_number(&mut self, start: usize) -> Result<Spanned<'input>, LexError> {
+        let digits_start = start + 2;
+        let end = scan_digits(self.src.as_bytes(), digits_start, |b| b.is_ascii_hexdigit());
+        self.advance_to(end);
+
+        let digits = without_underscores(&self.src[digits_start..end]);
+        if digits.is_empty() {
+            return Err(LexError::MalformedNumber { start, end });
+        }
+
+        match i64::from_str_radix(&digits, 16) {
+            Ok(value) => Ok((start, Token::Integer(value), end)),
+            Err(_) => Err(LexError::NumberOverflow { start, end }),
+        }
+    }
+
+    fn identifier(&mut self, start: usize) -> Spanned<'input> {
+        let end = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+
+        let tok = match &self.src[start..end] {
+            "and" => Token::And,
+            "or" => Token::Or,
+            "not" => Token::Not,
+            "if" => Token::If,
+            "then" => Token::Then,
+            "else" => Token::Else,
+            "true" => Token::True,
+            "false" => Token::False,
+            ident => Token::Ident(ident),
+        };
+
+        (start, tok, end)
+    }
+
+    /// Lex an operator which may be followed by `=` (e.g. `<` and `<=`).
+    fn maybe_equals(
+        &mut self,
+        start: usize,
+        single: Token<'input>,
+        double: Token<'input>,
+    ) -> Spanned<'input> {
+        self.chars.next();
+
+        if self.peek() == Some('=') {
+            self.chars.next();
+            (start, double, start + 2)
+        } else {
+            (start, single, start + 1)
+        }
+    }
+}
+
+/// Find the end of a run of digits (as determined by `is_digit`) and
+/// underscores, starting from `start`.
+fn scan_digits<F>(bytes: &[u8], start: usize, is_digit: F) -> usize
+where
+    F: Fn(u8) -> bool,
+{
+    bytes[start..]
+        .iter()
+        .position(|&b| !(is_digit(b) || b == b'_'))
+        .map(|ix| start + ix)
+        .unwrap_or(bytes.len())
+}
+
+fn is_digit_at(bytes: &[u8], ix: usize) -> bool {
+    bytes.get(ix).map(|b| b.is_ascii_digit()).unwrap_or(false)
+}
+
+fn without_underscores(s: &str) -> String {
+    s.chars().filter(|&c| c != '_').collect()
+}
+
+impl<'input> Iterator for Lexer<'input> {
+    type Item = Result<Spanned<'input>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let follows_number = mem::replace(&mut self.follows_number, false);
+
+        if let Err(e) = self.skip_trivia() {
+            // make sure we don't keep reporting the same error forever
+            while self.chars.next().is_some() {}
+            return Some(Err(e));
+        }
+
+        let start = self.position();
+        let c = self.peek()?;
+
+        let leading_dot = c == '.' && is_digit_at(self.src.as_bytes(), start + 1);
+        if c.is_ascii_digit() || leading_dot {
+            let number = self.number(start);
+            self.follows_number = match number {
+                Ok((_, Token::Number(_), _)) | Ok((_, Token::Integer(_), _)) => true,
+                _ => false,
+            };
+            return Some(number);
+        }
+        if follows_number {
+            if let Some(unit) = self.unit(start) {
+                return Some(Ok(unit));
+            }
+        }
+        if c.is_ascii_alphabetic() || c == '_' {
+            return Some(Ok(self.identifier(start)));
+        }
+
+        match c {
+            '<' => {
+                return Some(Ok(self.maybe_equals(
+                    start,
+                    Token::LessThan,
+                    Token::LessThanOrEqual,
+                )))
+            }
+            '>' => {
+                return Some(Ok(self.maybe_equals(
+                    start,
+                    Token::GreaterThan,
+                    Token::GreaterThanOrEqual,
+                )))
+            }
+            '=' if self.src[start..].starts_with("==") => {
+                self.advance_to(start + 2);
+                return Some(Ok((start, Token::Equal, start + 2)));
+            }
+            '!' if self.src[start..].starts_with("!=") => {
+                self.advance_to(start + 2);
+                return Some(Ok((start, Token::NotEqual, start + 2)));
+            }
+            '/' if self.src[start..].starts_with("//") => {
+                self.advance_to(start + 2);
+                return Some(Ok((start, Token::DoubleSlash, start + 2)));
+            }
+            '-' if self.src[start..].starts_with("->") => {
+                self.advance_to(start + 2);
+                return Some(Ok((start, Token::Arrow, start + 2)));
+            }
+            _ => {}
+        }
+
+        let tok = match c {
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '%' => Token::Percent,
+            '^' => Token::Caret,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '[' => Token::OpenBracket,
+            ']' => Token::CloseBracket,
+            ',' => Token::Comma,
+            other => {
+                self.chars.next();
+                return Some(Err(LexError::InvalidCharacter {
+                    ch: other,
+                    location: start,
+                }));
+            }
+        };
+
+        self.chars.next();
+        Some(Ok((start, tok, start + c.len_utf8())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(src: &str) -> Vec<Token> {
+        Lexer::new(src).map(|t| t.unwrap().1).collect()
+    }
+
+    #[test]
+    fn tokenize_a_simple_expression() {
+        let src = "5 + sin(x_1, 2.5)";
+        let should_be = vec![
+            Token::Integer(5),
+            Token::Plus,
+            Token::Ident("sin"),
+            Token::OpenParen,
+            Token::Ident("x_1"),
+            Token::Comma,
+            Token::Number(2.5),
+            Token::CloseParen,
+        ];
+
+        let got = tokens(src);
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn tokenize_an_array_index() {
+        let src = "[1, x][0]";
+        let should_be = vec![
+            Token::OpenBracket,
+            Token::Integer(1),
+            Token::Comma,
+            Token::Ident("x"),
+            Token::CloseBracket,
+            Token::OpenBracket,
+            Token::Integer(0),
+            Token::CloseBracket,
+        ];
+
+        let got = tokens(src);
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn tokenize_a_lambda() {
+        let src = "x->x - 1";
+        let should_be = vec![
+            Token::Ident("x"),
+            Token::Arrow,
+            Token::Ident("x"),
+            Token::Minus,
+            Token::Integer(1),
+        ];
+
+        let got = tokens(src);
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn tokens_know_their_location() {
+        let src = "  foo *3";
+        let should_be = vec![
+            (2, Token::Ident("foo"), 5),
+            (6, Token::Star, 7),
+            (7, Token::Integer(3), 8),
+        ];
+
+        let got: Vec<_> = Lexer::new(src).map(Result::unwrap).collect();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn tokenize_comparisons_and_keywords() {
+        let src = "if a<=b and not c != d then e else f==g";
+        let should_be = vec![
+            Token::If,
+            Token::Ident("a"),
+            Token::LessThanOrEqual,
+            Token::Ident("b"),
+            Token::And,
+            Token::Not,
+            Token::Ident("c"),
+            Token::NotEqual,
+            Token::Ident("d"),
+            Token::Then,
+            Token::Ident("e"),
+            Token::Else,
+            Token::Ident("f"),
+            Token::Equal,
+            Token::Ident("g"),
+        ];
+
+        let got = tokens(src);
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn tell_division_apart_from_integer_division() {
+        let src = "a / b // c % d";
+        let should_be = vec![
+            Token::Ident("a"),
+            Token::Slash,
+            Token::Ident("b"),
+            Token::DoubleSlash,
+            Token::Ident("c"),
+            Token::Percent,
+            Token::Ident("d"),
+        ];
+
+        let got = tokens(src);
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn skip_line_and_block_comments() {
+        let src = "1 # a line comment\n/* a\nblock * comment */ - 2 /**/";
+        let should_be = vec![Token::Integer(1), Token::Minus, Token::Integer(2)];
+
+        let got = tokens(src);
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn invalid_characters_are_reported_with_their_location() {
+        let src = "1 + $";
+        let should_be = LexError::InvalidCharacter {
+            ch: '$',
+            location: 4,
+        };
+
+        let got = Lexer::new(src).last().unwrap().unwrap_err();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn parse_all_the_numeric_literal_forms() {
+        let inputs = vec![
+            ("42", Token::Integer(42)),
+            ("3.14", Token::Number(3.14)),
+            (".5", Token::Number(0.5)),
+            ("1e-9", Token::Number(1e-9)),
+            ("6.02E+23", Token::Number(6.02e23)),
+            ("1_000_000", Token::Integer(1_000_000)),
+            ("1_000.5", Token::Number(1_000.5)),
+            ("0x1F", Token::Integer(31)),
+            ("0xdead_beef", Token::Integer(3735928559)),
+            ("4i", Token::Imaginary(4.0)),
+            ("2.5e3i", Token::Imaginary(2500.0)),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = tokens(src);
+            assert_eq!(got, vec![should_be], "{}", src);
+        }
+    }
+
+    #[test]
+    fn a_trailing_e_is_not_an_exponent() {
+        let src = "2e";
+        let should_be = vec![Token::Integer(2), Token::Ident("e")];
+
+        let got = tokens(src);
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn imaginary_literals_need_a_trailing_i() {
+        let inputs = vec![
+            ("3 + 4i", vec![Token::Integer(3), Token::Plus, Token::Imaginary(4.0)]),
+            ("4in", vec![Token::Integer(4), Token::Ident("in")]),
+            ("4 i", vec![Token::Integer(4), Token::Ident("i")]),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = tokens(src);
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn numbers_can_be_followed_by_units() {
+        let inputs = vec![
+            ("3 s", vec![Token::Integer(3), Token::Unit("s")]),
+            ("3km", vec![Token::Integer(3), Token::Unit("km")]),
+            (
+                "9.81 m/s^2 * 3 s",
+                vec![
+                    Token::Number(9.81),
+                    Token::Unit("m/s^2"),
+                    Token::Star,
+                    Token::Integer(3),
+                    Token::Unit("s"),
+                ],
+            ),
+            ("1 kg*m^-1", vec![Token::Integer(1), Token::Unit("kg*m^-1")]),
+            (
+                "2 m/x",
+                vec![
+                    Token::Integer(2),
+                    Token::Unit("m"),
+                    Token::Slash,
+                    Token::Ident("x"),
+                ],
+            ),
+            ("2 furlongs", vec![Token::Integer(2), Token::Ident("furlongs")]),
+            ("x m", vec![Token::Ident("x"), Token::Ident("m")]),
+            (
+                "2 m^2 * x^2",
+                vec![
+                    Token::Integer(2),
+                    Token::Unit("m^2"),
+                    Token::Star,
+                    Token::Ident("x"),
+                    Token::Caret,
+                    Token::Integer(2),
+                ],
+            ),
+            ("2^3", vec![Token::Integer(2), Token::Caret, Token::Integer(3)]),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = tokens(src);
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn overflowing_literals_are_errors() {
+        let inputs = vec![
+            ("1e999", LexError::NumberOverflow { start: 0, end: 5 }),
+            (
+                "9223372036854775808",
+                LexError::NumberOverflow { start: 0, end: 19 },
+            ),
+            (
+                "0x8000_0000_0000_0000",
+                LexError::NumberOverflow { start: 0, end: 21 },
+            ),
+            ("0x", LexError::MalformedNumber { start: 0, end: 2 }),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = Lexer::new(src).next().unwrap().unwrap_err();
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn unterminated_block_comment() {
+        let src = "1 /* oops";
+        let should_be = LexError::UnterminatedComment { start: 2 };
+
+        let got = Lexer::new(src).last().unwrap().unwrap_err();
+        assert_eq!(got, should_be);
+    }
+}
@@ -3,23 +3,36 @@
 //! The main entry point to the parser is via the [`parse()`] function. This
 //! takes source text and tries to convert it into its AST representation. If
 //! you then want to inspect the parsed program you can use the [`Visitor`]
-//! trait for AST traversal.
+//! trait for AST traversal, or print it with [`dump()`].
 //!
 //! [`parse()`]: fn.parse.html
 //! [`Visitor`]: visit/trait.Visitor.html
+//! [`dump()`]: dump/fn.dump.html
+//!
+//! With the `serde` feature enabled, the AST can be serialized. Wrap it in a
+//! [`Versioned`] so it can be read back safely by later versions of `calc`.
+//!
+//! [`Versioned`]: struct.Versioned.html
 
 mod ast;
+pub mod dump;
 mod grammar;
+pub mod lexer;
+#[cfg(feature = "serde")]
+mod versioned;
 pub mod visit;
 
 pub use self::ast::*;
+#[cfg(feature = "serde")]
+pub use self::versioned::{Versioned, FORMAT_VERSION};
 
 use failure::Error;
+use self::lexer::Lexer;
 
 /// Parse a string into its AST representation.
 pub fn parse(src: &str) -> Result<Expr, Error> {
     grammar::ExprParser::new()
-        .parse(src)
+        .parse(Lexer::new(src))
         .map_err(|e| e.map_token(|tok| tok.to_string()))
         .map_err(Error::from)
 }
@@ -0,0 +1,127 @@
+use serde::de::{Deserialize, Deserializer, Error};
+
+use syntax::ast::Expr;
+
+/// The version of the serialized AST format.
+///
+/// This is bumped whenever a change to the AST would change how it gets
+/// serialized (e.g. adding a field or renaming a variant), so old documents
+/// are rejected instead of being silently misinterpreted.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// An `Expr` tagged with the version of the format it was serialized in.
+///
+/// Store or send this instead of a bare `Expr`. Every node keeps its
+/// `Span`, so a round trip gives back exactly the same tree.
+///
+/// ```rust
+/// # extern crate calc;
+/// # extern crate serde_json;
+/// use calc::syntax::{self, Versioned};
+///
+/// # fn main() {
+/// let ast = syntax::parse("sin(x) * 2").unwrap();
+///
+/// let json = serde_json::to_string(&Versioned::new(ast.clone())).unwrap();
+/// assert!(json.starts_with(r#"{"version":1,"expr":{"binary_op":"#));
+///
+/// let got: Versioned = serde_json::from_str(&json).unwrap();
+/// assert_eq!(got.expr, ast);
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Versioned {
+    #[serde(deserialize_with = "check_version")]
+    version: u32,
+    /// The expression.
+    pub expr: Expr,
+}
+
+impl Versioned {
+    /// Tag an `Expr` with the current `FORMAT_VERSION`.
+    pub fn new(expr: Expr) -> Versioned {
+        Versioned {
+            version: FORMAT_VERSION,
+            expr,
+        }
+    }
+
+    /// The format version this was serialized with.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+impl From<Expr> for Versioned {
+    fn from(other: Expr) -> Versioned {
+        Versioned::new(other)
+    }
+}
+
+fn check_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let version = u32::deserialize(deserializer)?;
+
+    if version == FORMAT_VERSION {
+        Ok(version)
+    } else {
+        Err(D::Error::custom(format!(
+            "Unsupported AST format version {}, expected {}",
+            version, FORMAT_VERSION
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use syntax;
+
+    #[test]
+    fn round_trip_through_json() {
+        let inputs = vec![
+            "1 + 2.5 * x",
+            "if true and not false then 1i else 0x10 // 3",
+            "map(x -> x * x, [1, 2, 3])[0] + 2 kg",
+            "sum(k, 1, 10, k) + integrate(t -> t, 0, 1) + solve(x * x == 2, x, 1)",
+            "0.1 + 1e-300 + 6.02e23",
+        ];
+
+        for src in inputs {
+            let ast = syntax::parse(src).unwrap();
+            let json = serde_json::to_string(&Versioned::new(ast.clone())).unwrap();
+            let got: Versioned = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(got.expr, ast, "{}", src);
+        }
+    }
+
+    #[test]
+    fn the_json_shape_is_stable() {
+        let ast = syntax::parse("f(1)").unwrap();
+        let got = serde_json::to_value(Versioned::new(ast)).unwrap();
+
+        let should_be = json!({
+            "version": 1,
+            "expr": {
+                "function_call": {
+                    "name": "f",
+                    "arguments": [
+                        { "atom": [{ "integer": 1 }, { "start": 2, "end": 3 }] },
+                    ],
+                    "span": { "start": 0, "end": 4 },
+                },
+            },
+        });
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let src =
+            r#"{"version": 2, "expr": {"atom": [{"boolean": true}, {"start": 0, "end": 4}]}}"#;
+        let err = serde_json::from_str::<Versioned>(src).unwrap_err();
+
+        assert!(err.to_string().contains("Unsupported AST format version 2"));
+    }
+}
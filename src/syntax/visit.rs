@@ -8,7 +8,10 @@
 //! Use the `walk_*()` functions to continue traversing the AST in the default
 //! traversal order.
 
-use syntax::ast::{Atom, BinaryOp, Expr, FunctionCall};
+use syntax::ast::{
+    Array, Atom, BinaryOp, Cast, Conditional, Expr, FunctionCall, Index, Integral, Lambda, Let,
+    Series, Solve, UnaryOp, UnitAnnotation,
+};
 
 /// A utility trait for traversing an AST.
 pub trait Visitor {
@@ -27,18 +30,87 @@ pub trait Visitor {
         walk_function_call(self, f);
     }
 
+    /// Visit a unary operation.
+    fn visit_unary_op(&mut self, u: &UnaryOp) {
+        walk_unary_op(self, u);
+    }
+
+    /// Visit a conditional expression.
+    fn visit_conditional(&mut self, c: &Conditional) {
+        walk_conditional(self, c);
+    }
+
+    /// Visit a type conversion.
+    fn visit_cast(&mut self, c: &Cast) {
+        walk_cast(self, c);
+    }
+
+    /// Visit a quantity with units attached.
+    fn visit_unit_annotation(&mut self, u: &UnitAnnotation) {
+        walk_unit_annotation(self, u);
+    }
+
+    /// Visit an array literal.
+    fn visit_array(&mut self, a: &Array) {
+        walk_array(self, a);
+    }
+
+    /// Visit an indexing operation.
+    fn visit_index(&mut self, i: &Index) {
+        walk_index(self, i);
+    }
+
+    /// Visit a sum or product.
+    fn visit_series(&mut self, s: &Series) {
+        walk_series(self, s);
+    }
+
+    /// Visit a lambda.
+    fn visit_lambda(&mut self, l: &Lambda) {
+        walk_lambda(self, l);
+    }
+
+    /// Visit a definite integral.
+    fn visit_integral(&mut self, i: &Integral) {
+        walk_integral(self, i);
+    }
+
+    /// Visit an equation being solved.
+    fn visit_solve(&mut self, s: &Solve) {
+        walk_solve(self, s);
+    }
+
+    /// Visit a variable binding.
+    fn visit_let(&mut self, l: &Let) {
+        walk_let(self, l);
+    }
+
     /// Visit an `Atom`.
     fn visit_atom(&mut self, _atom: &Atom) {}
 }
 
 /// Continue to recursively walk an expression, calling the visitor's
-/// `visit_atom()`, `visit_function_call()`, or `visit_binary_op()` method
-/// depending on what type of `Expr` it is.
+/// `visit_atom()`, `visit_function_call()`, `visit_binary_op()`,
+/// `visit_unary_op()`, `visit_conditional()`, `visit_cast()`,
+/// `visit_unit_annotation()`, `visit_array()`, `visit_index()`,
+/// `visit_series()`, `visit_lambda()`, `visit_integral()`, `visit_solve()`,
+/// or `visit_let()` method depending on what type of `Expr` it is.
 pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, e: &Expr) {
     match *e {
-        Expr::Atom(ref a) => visitor.visit_atom(a),
+        Expr::Atom(ref a, _) => visitor.visit_atom(a),
         Expr::FunctionCall(ref f) => visitor.visit_function_call(f),
         Expr::BinaryOp(ref b) => visitor.visit_binary_op(b),
+        Expr::UnaryOp(ref u) => visitor.visit_unary_op(u),
+        Expr::Conditional(ref c) => visitor.visit_conditional(c),
+        Expr::Cast(ref c) => visitor.visit_cast(c),
+        Expr::UnitAnnotation(ref u) => visitor.visit_unit_annotation(u),
+        Expr::Array(ref a) => visitor.visit_array(a),
+        Expr::Index(ref i) => visitor.visit_index(i),
+        Expr::Series(ref s) => visitor.visit_series(s),
+        Expr::Lambda(ref l) => visitor.visit_lambda(l),
+        Expr::Integral(ref i) => visitor.visit_integral(i),
+        Expr::Solve(ref s) => visitor.visit_solve(s),
+        Expr::Let(ref l) => visitor.visit_let(l),
     }
 }
 
@@ -54,3 +126,69 @@ pub fn walk_function_call<V: Visitor + ?Sized>(visitor: &mut V, f: &FunctionCall
         visitor.visit_expr(arg);
     }
 }
+
+/// Recursively visit a unary operation's operand.
+pub fn walk_unary_op<V: Visitor + ?Sized>(visitor: &mut V, u: &UnaryOp) {
+    visitor.visit_expr(&u.value);
+}
+
+/// Recursively visit a conditional's condition and both of its branches.
+pub fn walk_conditional<V: Visitor + ?Sized>(visitor: &mut V, c: &Conditional) {
+    visitor.visit_expr(&c.condition);
+    visitor.visit_expr(&c.if_true);
+    visitor.visit_expr(&c.if_false);
+}
+
+/// Recursively visit the value being converted.
+pub fn walk_cast<V: Visitor + ?Sized>(visitor: &mut V, c: &Cast) {
+    visitor.visit_expr(&c.value);
+}
+
+/// Recursively visit the annotated value.
+pub fn walk_unit_annotation<V: Visitor + ?Sized>(visitor: &mut V, u: &UnitAnnotation) {
+    visitor.visit_expr(&u.value);
+}
+
+/// Recursively visit each element in the array.
+pub fn walk_array<V: Visitor + ?Sized>(visitor: &mut V, a: &Array) {
+    for element in &a.elements {
+        visitor.visit_expr(element);
+    }
+}
+
+/// Recursively visit the array being indexed, then the index.
+pub fn walk_index<V: Visitor + ?Sized>(visitor: &mut V, i: &Index) {
+    visitor.visit_expr(&i.array);
+    visitor.visit_expr(&i.index);
+}
+
+/// Recursively visit a series' bounds, then its body.
+pub fn walk_series<V: Visitor + ?Sized>(visitor: &mut V, s: &Series) {
+    visitor.visit_expr(&s.start);
+    visitor.visit_expr(&s.end);
+    visitor.visit_expr(&s.body);
+}
+
+/// Recursively visit a lambda's body.
+pub fn walk_lambda<V: Visitor + ?Sized>(visitor: &mut V, l: &Lambda) {
+    visitor.visit_expr(&l.body);
+}
+
+/// Recursively visit an integral's bounds, then its body.
+pub fn walk_integral<V: Visitor + ?Sized>(visitor: &mut V, i: &Integral) {
+    visitor.visit_expr(&i.lower);
+    visitor.visit_expr(&i.upper);
+    visitor.visit_expr(&i.body);
+}
+
+/// Recursively visit the guess, then the equation being solved.
+pub fn walk_solve<V: Visitor + ?Sized>(visitor: &mut V, s: &Solve) {
+    visitor.visit_expr(&s.guess);
+    visitor.visit_expr(&s.equation);
+}
+
+/// Recursively visit the bound value, then the body.
+pub fn walk_let<V: Visitor + ?Sized>(visitor: &mut V, l: &Let) {
+    visitor.visit_expr(&l.value);
+    visitor.visit_expr(&l.body);
+}
@@ -1,22 +1,52 @@
+use failure::Error;
 use inkwell::builder::Builder;
 use inkwell::context::Context;
-use inkwell::module::Module;
-use inkwell::types::FloatType;
-use inkwell::values::{FloatValue, FunctionValue};
+use inkwell::debug_info::{self, AsDIScope, DICompileUnit, DIFile, DIFlags, DIFlagsConstants,
+                          DILocation, DIScope, DWARFEmissionKind, DWARFSourceLanguage,
+                          DebugInfoBuilder};
+use inkwell::module::{FlagBehavior, Module};
+use inkwell::types::{BasicMetadataTypeEnum, BasicTypeEnum, FloatType, IntType, StructType};
+use inkwell::values::{BasicMetadataValueEnum, BasicValueEnum, FloatValue, FunctionValue,
+                      IntValue, PointerValue, StructValue};
+use inkwell::{AddressSpace, FloatPredicate, IntPredicate};
 use slog::{Discard, Logger};
+use std::cell::RefCell;
+use std::env;
 use std::fmt::{self, Debug, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use syntax::{Atom, BinaryOp, Expr, FunctionCall, Op};
-
-/// The signature used for `calc`'s entrypoint, `"calc_main"`.
-pub type CalcMain = unsafe extern "C" fn() -> f64;
-pub const CALC_ENTRYPOINT: &str = "calc_main";
+use backend::quadrature::{GAUSS_WEIGHTS, INTEGRAL_TOLERANCE, KRONROD_NODES, KRONROD_WEIGHTS,
+                          MAX_PANELS};
+use backend::{self, array_length, differentiate, Backend, Jit, Never, Status, CALC_ENTRYPOINT};
+use builtins::{self, Domain};
+use environment::{Environment, HostFunction, CLOSURE_TRAMPOLINE};
+use jit::Program;
+use solve::SOLVER;
+use syntax::{Array, Atom, BinaryOp, Cast, Expr, FunctionCall, Index, Integral, Let, Op,
+             Series, SeriesKind, Solve, Span, Type, UnaryOp, UnaryOperator};
 
 pub struct Compiler<'ctx> {
     ctx: &'ctx Context,
     logger: Logger,
-    builder: Builder,
-    double: FloatType,
+    builder: Builder<'ctx>,
+    module: Module<'ctx>,
+    double: FloatType<'ctx>,
+    int: IntType<'ctx>,
+    boolean: IntType<'ctx>,
+    status: IntType<'ctx>,
+    location: StructType<'ctx>,
+    complex: StructType<'ctx>,
+    runtime_checks: bool,
+    env: Environment,
+    /// The values of the variables bound by any sums, products, integrals or
+    /// equations we're currently inside.
+    scope: RefCell<Vec<(String, BasicValueEnum<'ctx>)>>,
+    /// The names of every function we've generated code for.
+    defined: RefCell<Vec<String>>,
+    debug_info: Option<DebugInfo<'ctx>>,
 }
 
 impl<'ctx> Compiler<'ctx> {
@@ -28,71 +58,1527 @@ impl<'ctx> Compiler<'ctx> {
         let logger = logger.new(o!("phase" => "trans"));
 
         let double = ctx.f64_type();
+        let int = ctx.i64_type();
+        let boolean = ctx.bool_type();
+        let status = ctx.i32_type();
+        let location = ctx.struct_type(&[int.into(), int.into()], false);
+        let complex = ctx.struct_type(&[double.into(), double.into()], false);
 
         let builder = ctx.create_builder();
+        let module = ctx.create_module("calc");
 
         Compiler {
             ctx,
             builder,
             logger,
+            module,
             double,
+            int,
+            boolean,
+            status,
+            location,
+            complex,
+            runtime_checks: false,
+            env: Environment::new(),
+            scope: RefCell::new(Vec::new()),
+            defined: RefCell::new(Vec::new()),
+            debug_info: None,
         }
     }
 
+    /// Guard floating point division and calls to functions which are only
+    /// defined for some inputs (e.g. `sqrt()` and `ln()`), making
+    /// `calc_main` return an error status instead of `inf` or `NaN`.
+    ///
+    /// Integer overflow is always checked, regardless of this setting.
+    pub fn with_runtime_checks(mut self, enabled: bool) -> Compiler<'ctx> {
+        self.runtime_checks = enabled;
+        self
+    }
+
+    /// Let the compiled code call functions registered with an
+    /// `Environment`.
+    pub fn with_environment(mut self, env: &Environment) -> Compiler<'ctx> {
+        self.env = env.clone();
+        self
+    }
+
+    /// Attach DWARF debug info to the generated code, so `gdb` and `perf`
+    /// can point at the part of `src` each instruction came from.
+    ///
+    /// `path` is where the formula's source can be found, usually written
+    /// there by [`write_source_file()`].
+    ///
+    /// [`write_source_file()`]: fn.write_source_file.html
+    pub fn with_debug_info(mut self, path: &Path, src: &str) -> Compiler<'ctx> {
+        let filename = path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let directory = path.parent()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let (builder, unit) = self.module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            &filename,
+            &directory,
+            "calc",
+            false,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+        // without this LLVM throws the debug info away when verifying
+        let version = self.status
+            .const_int(debug_info::debug_metadata_version() as u64, false);
+        self.module
+            .add_basic_value_flag("Debug Info Version", FlagBehavior::Warning, version);
+
+        self.debug_info = Some(DebugInfo {
+            file: unit.get_file(),
+            builder,
+            unit,
+            line_starts: line_starts(src),
+        });
+        self
+    }
+
     /// Compile an AST tree to a LLVM `Module`.
-    pub fn compile(&self, ast: &Expr) -> Module {
-        let mut module = self.ctx.create_module("calc");
+    ///
+    /// The tree must have already been through [`sema::type_check()`] so
+    /// every operation's operands are guaranteed to have the same type.
+    ///
+    /// [`sema::type_check()`]: ../sema/fn.type_check.html
+    pub fn compile(self, ast: &Expr) -> Compiled<'ctx> {
+        self.compile_function(CALC_ENTRYPOINT, ast);
 
-        self.compile_function(&mut module, CALC_ENTRYPOINT, ast);
+        if let Some(ref debug_info) = self.debug_info {
+            debug_info.builder.finalize();
+        }
 
-        module
+        Compiled {
+            module: self.module,
+            env: self.env,
+            functions: self.defined.into_inner(),
+            debug_info: self.debug_info.is_some(),
+        }
     }
 
-    fn compile_function(&self, module: &mut Module, name: &str, body: &Expr) -> FunctionValue {
-        // hard-code all functions to be `fn() -> f64`
-        let sig = self.double.fn_type(&[], false);
-        let func = module.add_function(name, &sig, None);
+    fn compile_function(&self, name: &str, body: &Expr) -> FunctionValue<'ctx> {
+        // hard-code all functions to be `fn(*mut Complex64, *mut ErrorLocation) -> u32`
+        let out = self.complex.ptr_type(AddressSpace::default());
+        let location = self.location.ptr_type(AddressSpace::default());
+        let sig = self.status.fn_type(&[out.into(), location.into()], false);
+        let func = self.module.add_function(name, sig, None);
+        self.defined.borrow_mut().push(name.to_string());
+        self.start_debug_scope(func, body.span());
 
-        let entry = func.append_basic_block("entry");
-        self.builder.position_at_end(&entry);
+        let entry = self.ctx.append_basic_block(func, "entry");
+        self.builder.position_at_end(entry);
 
         let ret = self.compile_expr(body);
+        self.set_debug_location(body.span());
+        let ret = self.to_complex(ret);
 
-        self.builder.build_return(Some(&ret));
+        let out = func.get_nth_param(0).unwrap().into_pointer_value();
+        self.builder.build_store(out, ret);
+        self.builder
+            .build_return(Some(&self.status_code(Status::Ok)));
 
         func
     }
 
-    fn compile_expr(&self, expr: &Expr) -> FloatValue {
-        match *expr {
-            Expr::Atom(ref atom) => self.compile_atom(atom),
-            Expr::BinaryOp(ref op) => self.compile_binary_op(op),
-            Expr::FunctionCall(ref call) => self.compile_function_call(call),
+    fn compile_expr(&self, expr: &Expr) -> BasicValueEnum<'ctx> {
+        match backend::lower(self, expr) {
+            Ok(value) => value,
+            Err(never) => match never {},
         }
     }
 
-    fn compile_atom(&self, atom: &Atom) -> FloatValue {
+    fn compile_atom(&self, atom: &Atom) -> BasicValueEnum<'ctx> {
         match *atom {
-            Atom::Number(n) => self.double.const_float(n),
-            _ => unimplemented!(),
+            Atom::Number(n) => self.double.const_float(n).into(),
+            Atom::Integer(n) => self.int.const_int(n as u64, true).into(),
+            Atom::Imaginary(n) => {
+                let re = self.double.const_float(0.0);
+                let im = self.double.const_float(n);
+                self.complex_value(re, im).into()
+            }
+            Atom::Boolean(b) => self.boolean.const_int(b as u64, false).into(),
+            Atom::Ident(_) => {
+                unreachable!("The type checker inlines everything except bound variables")
+            }
         }
     }
 
-    fn compile_binary_op(&self, op: &BinaryOp) -> FloatValue {
-        let left = self.compile_expr(&op.left);
-        let right = self.compile_expr(&op.right);
+    fn compile_binary_op(
+        &self,
+        op: &BinaryOp,
+        left: BasicValueEnum<'ctx>,
+        right: BasicValueEnum<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        match (left, right) {
+            (BasicValueEnum::FloatValue(l), BasicValueEnum::FloatValue(r)) => {
+                self.float_binary_op(op, l, r)
+            }
+            (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
+                self.int_binary_op(op, l, r).into()
+            }
+            (BasicValueEnum::StructValue(l), BasicValueEnum::StructValue(r)) => {
+                self.complex_binary_op(op, l, r)
+            }
+            (BasicValueEnum::PointerValue(_), _) | (_, BasicValueEnum::PointerValue(_)) => {
+                self.array_binary_op(op, left, right).into()
+            }
+            _ => unreachable!("The type checker ensures both operands have the same type"),
+        }
+    }
+
+    fn float_binary_op(
+        &self,
+        op: &BinaryOp,
+        left: FloatValue<'ctx>,
+        right: FloatValue<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        let predicate = match op.op {
+            Op::Add => return self.builder.build_float_add(left, right, "add").into(),
+            Op::Subtract => return self.builder.build_float_sub(left, right, "sub").into(),
+            Op::Multiply => return self.builder.build_float_mul(left, right, "mul").into(),
+            Op::Divide => {
+                if self.runtime_checks {
+                    let zero = self.double.const_float(0.0);
+                    let is_zero = self.builder
+                        .build_float_compare(FloatPredicate::OEQ, right, zero, "is_zero");
+                    self.bail_if(is_zero, Status::DivideByZero, op.span);
+                }
+
+                return self.builder.build_float_div(left, right, "div").into();
+            }
+            Op::IntegerDivide | Op::Modulo => unreachable!("{} only accepts integers", op.op),
+            Op::LessThan => FloatPredicate::OLT,
+            Op::LessThanOrEqual => FloatPredicate::OLE,
+            Op::Equal => FloatPredicate::OEQ,
+            // true if either side is NaN, the same as `!=` in Rust and C
+            Op::NotEqual => FloatPredicate::UNE,
+            Op::GreaterThan => FloatPredicate::OGT,
+            Op::GreaterThanOrEqual => FloatPredicate::OGE,
+            Op::And | Op::Or => unreachable!("Logical operators only accept bools"),
+        };
+
+        self.builder
+            .build_float_compare(predicate, left, right, "cmp")
+            .into()
+    }
+
+    /// Sums and products are lowered to a counted loop, with the variable
+    /// being the loop counter and a running total kept on the stack.
+    fn compile_series<F>(
+        &self,
+        series: &Series,
+        start: IntValue<'ctx>,
+        end: IntValue<'ctx>,
+        mut term: F,
+    ) -> Result<FloatValue<'ctx>, Never>
+    where
+        F: FnMut(BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, Never>,
+    {
+        let identity = match series.kind {
+            SeriesKind::Sum => self.double.const_float(0.0),
+            SeriesKind::Product => self.double.const_float(1.0),
+        };
+        let total = self.builder.build_alloca(self.double, "total");
+        self.builder.build_store(total, identity);
+
+        let func = self.current_function();
+        let preheader = self.builder.get_insert_block().unwrap();
+        let header = self.ctx.append_basic_block(func, "series");
+        let body = self.ctx.append_basic_block(func, "term");
+        let exit = self.ctx.append_basic_block(func, "after_series");
+
+        self.builder.build_unconditional_branch(header);
+        self.builder.position_at_end(header);
+
+        let phi = self.builder.build_phi(self.int, &series.variable);
+        let counter = phi.as_basic_value().into_int_value();
+        let finished = self.builder
+            .build_int_compare(IntPredicate::SGT, counter, end, "finished");
+        self.builder
+            .build_conditional_branch(finished, exit, body);
+
+        self.builder.position_at_end(body);
+        // anything the body allocates (e.g. arrays) is only needed for one
+        // iteration, so give the stack space back afterwards
+        let stack = self.stack_save();
+        let term = term(counter.into())?.into_float_value();
+        self.set_debug_location(series.span);
+
+        let previous = self.builder
+            .build_load(total, "previous")
+            .into_float_value();
+        let next_total = match series.kind {
+            SeriesKind::Sum => self.builder.build_float_add(previous, term, "sum"),
+            SeriesKind::Product => self.builder.build_float_mul(previous, term, "product"),
+        };
+        self.builder.build_store(total, next_total);
+        self.stack_restore(stack);
+
+        // the body may have appended more blocks, e.g. for runtime checks
+        let latch = self.builder.get_insert_block().unwrap();
+        // stop before incrementing, otherwise `end == i64::MAX` would wrap
+        // around and never finish
+        let last = self.builder
+            .build_int_compare(IntPredicate::EQ, counter, end, "last");
+        let one = self.int.const_int(1, false);
+        let next = self.builder.build_int_add(counter, one, "next");
+        self.builder
+            .build_conditional_branch(last, exit, header);
+
+        phi.add_incoming(&[(&start, preheader), (&next, latch)]);
+
+        self.builder.position_at_end(exit);
+        Ok(self.builder
+            .build_load(total, "total")
+            .into_float_value())
+    }
+
+    /// Integrals use Gauss–Kronrod quadrature. The integrand is compiled to
+    /// its own function, which is evaluated at the 15 Kronrod points of
+    /// each panel, and the number of panels is doubled until the Kronrod
+    /// estimate agrees with the embedded 7-point Gauss estimate. If that
+    /// still hasn't happened with `MAX_PANELS` panels, we bail with
+    /// `Status::NoConvergence`.
+    fn compile_integral(&self, integral: &Integral) -> FloatValue<'ctx> {
+        let lower = self.compile_expr(&integral.lower).into_float_value();
+        let upper = self.compile_expr(&integral.upper).into_float_value();
+        let captures: Vec<BasicValueEnum> = self.scope.borrow().iter().map(|&(_, v)| v).collect();
+        let integrand = self.compile_integrand(integral, &captures);
+        self.set_debug_location(integral.span);
+
+        let out = self.builder.build_alloca(self.double, "integrand_out");
+        let panels = self.builder.build_alloca(self.int, "panels");
+        let kronrod = self.builder.build_alloca(self.double, "kronrod");
+        let error = self.builder.build_alloca(self.double, "error");
+        self.builder
+            .build_store(panels, self.int.const_int(1, false));
+
+        let func = self.current_function();
+        let refine = self.ctx.append_basic_block(func, "refine");
+        let exit = self.ctx.append_basic_block(func, "after_integral");
+
+        self.builder.build_unconditional_branch(refine);
+        self.builder.position_at_end(refine);
+
+        let zero = self.double.const_float(0.0);
+        self.builder.build_store(kronrod, zero);
+        self.builder.build_store(error, zero);
+
+        let n = self.builder
+            .build_load(panels, "n")
+            .into_int_value();
+        let n_float = self.builder
+            .build_signed_int_to_float(n, self.double, "n_float");
+        let width = self.builder.build_float_sub(upper, lower, "width");
+        let step = self.builder.build_float_div(width, n_float, "step");
+        let half = self.builder
+            .build_float_mul(step, self.double.const_float(0.5), "half");
+
+        self.build_loop(n, |i| {
+            let i = self.builder
+                .build_signed_int_to_float(i, self.double, "i");
+            let offset = self.builder.build_float_mul(i, step, "offset");
+            let left = self.builder.build_float_add(lower, offset, "left");
+            let centre = self.builder.build_float_add(left, half, "centre");
+            let f = |x: FloatValue<'ctx>| self.call_integrand(integrand, out, x, &captures);
+
+            let value = f(centre);
+            let mut k = self.builder.build_float_mul(
+                value,
+                self.double.const_float(KRONROD_WEIGHTS[7]),
+                "k",
+            );
+            let mut g = self.builder.build_float_mul(
+                value,
+                self.double.const_float(GAUSS_WEIGHTS[3]),
+                "g",
+            );
+
+            for (j, (&node, &weight)) in KRONROD_NODES.iter().zip(&KRONROD_WEIGHTS).enumerate() {
+                let node = self.double.const_float(node);
+                let dx = self.builder.build_float_mul(half, node, "dx");
+                let below = f(self.builder.build_float_sub(centre, dx, "x"));
+                let above = f(self.builder.build_float_add(centre, dx, "x"));
+                let pair = self.builder.build_float_add(below, above, "pair");
+
+                let weight = self.double.const_float(weight);
+                let term = self.builder.build_float_mul(pair, weight, "term");
+                k = self.builder.build_float_add(k, term, "k");
+
+                // every second Kronrod point is also a Gauss point
+                if j % 2 == 1 {
+                    let weight = self.double.const_float(GAUSS_WEIGHTS[j / 2]);
+                    let term = self.builder.build_float_mul(pair, weight, "term");
+                    g = self.builder.build_float_add(g, term, "g");
+                }
+            }
+
+            let difference = self.builder.build_float_sub(k, g, "difference");
+            let difference = self.call_float_function("llvm.fabs.f64", &[difference]);
+            self.accumulate(kronrod, self.builder.build_float_mul(k, half, "panel"));
+            self.accumulate(error, self.builder.build_float_mul(difference, half, "error"));
+        });
+
+        let total = self.builder
+            .build_load(kronrod, "total")
+            .into_float_value();
+        let error = self.builder
+            .build_load(error, "error")
+            .into_float_value();
+
+        // stop once the error is small relative to the answer
+        let magnitude = self.call_float_function("llvm.fabs.f64", &[total]);
+        let relative = self.builder.build_float_mul(
+            magnitude,
+            self.double.const_float(INTEGRAL_TOLERANCE),
+            "relative",
+        );
+        let tolerance = self.call_float_function(
+            "llvm.maxnum.f64",
+            &[relative, self.double.const_float(INTEGRAL_TOLERANCE)],
+        );
+        let converged = self.builder
+            .build_float_compare(FloatPredicate::OLE, error, tolerance, "converged");
+        let not_converged = self.ctx.append_basic_block(func, "not_converged");
+        self.builder
+            .build_conditional_branch(converged, exit, not_converged);
+
+        self.builder.position_at_end(not_converged);
+        let max_panels = self.int.const_int(MAX_PANELS, false);
+        let exhausted = self.builder
+            .build_int_compare(IntPredicate::SGE, n, max_panels, "exhausted");
+        self.bail_if(exhausted, Status::NoConvergence, integral.span);
+
+        let doubled = self.builder
+            .build_int_mul(n, self.int.const_int(2, false), "doubled");
+        self.builder.build_store(panels, doubled);
+        self.builder.build_unconditional_branch(refine);
+
+        self.builder.position_at_end(exit);
+        total
+    }
+
+    /// Compile an integral's body to a function shaped like `calc_main`,
+    /// except the result is a double and it also takes the variable of
+    /// integration and the value of every variable in scope.
+    fn compile_integrand(
+        &self,
+        integral: &Integral,
+        captures: &[BasicValueEnum<'ctx>],
+    ) -> FunctionValue<'ctx> {
+        let mut params: Vec<BasicTypeEnum<'ctx>> = vec![
+            self.double.ptr_type(AddressSpace::default()).into(),
+            self.location.ptr_type(AddressSpace::default()).into(),
+            self.double.into(),
+        ];
+        params.extend(captures.iter().map(|value| value.get_type()));
+        let params: Vec<BasicMetadataTypeEnum> = params.into_iter().map(Into::into).collect();
+        let sig = self.status.fn_type(&params, false);
+
+        let name = (0..)
+            .map(|i| format!("integrand.{}", i))
+            .find(|name| self.module.get_function(name).is_none())
+            .unwrap();
+        let func = self.module.add_function(&name, sig, None);
+        self.defined.borrow_mut().push(name);
+
+        let caller = self.builder.get_insert_block().unwrap();
+        let caller_location = self.builder.get_current_debug_location();
+        self.start_debug_scope(func, integral.body.span());
+        let entry = self.ctx.append_basic_block(func, "entry");
+        self.builder.position_at_end(entry);
+
+        let mut scope: Vec<(String, BasicValueEnum<'ctx>)> = self.scope
+            .borrow()
+            .iter()
+            .zip(func.get_params().into_iter().skip(3))
+            .map(|(&(ref name, _), param)| (name.clone(), param))
+            .collect();
+        scope.push((integral.variable.clone(), func.get_nth_param(2).unwrap()));
+
+        let outer_scope = self.scope.replace(scope);
+        let value = self.compile_expr(&integral.body);
+        self.scope.replace(outer_scope);
+
+        let out = func.get_nth_param(0).unwrap().into_pointer_value();
+        self.builder.build_store(out, value);
+        self.builder
+            .build_return(Some(&self.status_code(Status::Ok)));
+
+        self.builder.position_at_end(caller);
+        self.restore_debug_location(caller_location);
+        func
+    }
+
+    /// Evaluate the integrand at `x`, passing on any error it returns.
+    fn call_integrand(
+        &self,
+        integrand: FunctionValue<'ctx>,
+        out: PointerValue<'ctx>,
+        x: FloatValue<'ctx>,
+        captures: &[BasicValueEnum<'ctx>],
+    ) -> FloatValue<'ctx> {
+        let func = self.current_function();
+        let location = func.get_nth_param(1).unwrap();
+
+        let mut args: Vec<BasicValueEnum<'ctx>> = vec![out.into(), location, x.into()];
+        args.extend(captures);
+        let args: Vec<BasicMetadataValueEnum> = args.iter().map(|&arg| arg.into()).collect();
+
+        let status = self.builder
+            .build_call(integrand, &args, "status")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        // the integrand has already recorded where it failed
+        self.propagate_failure(status);
+
+        self.builder
+            .build_load(out, "f")
+            .into_float_value()
+    }
+
+    /// Return `status` from the current function if it isn't `Status::Ok`.
+    fn propagate_failure(&self, status: IntValue<'ctx>) {
+        let func = self.current_function();
+        let failed = self.builder.build_int_compare(
+            IntPredicate::NE,
+            status,
+            self.status_code(Status::Ok),
+            "failed",
+        );
+        let bail = self.ctx.append_basic_block(func, "propagate_failure");
+        let next = self.ctx.append_basic_block(func, "continue");
+        self.builder
+            .build_conditional_branch(failed, bail, next);
+        self.builder.position_at_end(bail);
+        self.builder.build_return(Some(&status));
+        self.builder.position_at_end(next);
+    }
+
+    /// Equations are solved by the [`solve`] module at runtime. We compile
+    /// `left - right` (and its derivative, if there is one) to functions the
+    /// solver can call, passing it pointers to them and to a struct holding
+    /// the values of any variables in scope.
+    ///
+    /// [`solve`]: ../solve/index.html
+    fn compile_solve(&self, solve: &Solve) -> FloatValue<'ctx> {
+        let guess = self.compile_expr(&solve.guess).into_float_value();
+        self.set_debug_location(solve.span);
+
+        let captures: Vec<BasicValueEnum> = self.scope.borrow().iter().map(|&(_, v)| v).collect();
+        let types: Vec<BasicTypeEnum> = captures.iter().map(|value| value.get_type()).collect();
+        let capture_type = self.ctx.struct_type(&types, false);
+        let environment = self.builder
+            .build_alloca(capture_type, "captures");
+        for (i, value) in captures.iter().enumerate() {
+            let field = self.builder
+                .build_struct_gep(environment, i as u32, "capture")
+                .unwrap();
+            self.builder.build_store(field, *value);
+        }
+
+        let residual: Expr = match solve.equation {
+            Expr::BinaryOp(ref op) => BinaryOp::sub(op.left.clone(), op.right.clone()).into(),
+            _ => unreachable!("The type checker only accepts equations"),
+        };
+        let derivative = differentiate(&residual, &solve.variable, &self.env);
+
+        let opaque = self.ctx.i8_type().ptr_type(AddressSpace::default());
+        let as_opaque = |func: FunctionValue<'ctx>| -> BasicValueEnum<'ctx> {
+            let address = func.as_global_value().as_pointer_value();
+            self.builder
+                .build_pointer_cast(address, opaque, "function")
+                .into()
+        };
+
+        let residual = self.compile_residual("residual", &residual, solve, capture_type);
+        let derivative = match derivative {
+            Some(ref derivative) => {
+                as_opaque(self.compile_residual("derivative", derivative, solve, capture_type))
+            }
+            None => opaque.const_null().into(),
+        };
+        let environment = self.builder
+            .build_pointer_cast(environment, opaque, "environment");
+        let root = self.builder.build_alloca(self.double, "root");
+        let location = self.current_function().get_nth_param(1).unwrap();
+
+        let args: Vec<BasicValueEnum<'ctx>> = vec![
+            as_opaque(residual),
+            derivative,
+            environment.into(),
+            guess.into(),
+            root.into(),
+            location,
+        ];
+        let args: Vec<BasicMetadataValueEnum> = args.iter().map(|&arg| arg.into()).collect();
+        let status = self.builder
+            .build_call(self.solver(), &args, "status")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+
+        let no_solution = self.builder.build_int_compare(
+            IntPredicate::EQ,
+            status,
+            self.status_code(Status::NoSolution),
+            "no_solution",
+        );
+        self.bail_if(no_solution, Status::NoSolution, solve.span);
+        // any other error came from the residual, which recorded its location
+        self.propagate_failure(status);
+
+        self.builder
+            .build_load(root, "root")
+            .into_float_value()
+    }
+
+    /// Compile a function of the equation's variable with the
+    /// `solve::Residual` signature, unpacking the variables it captures from
+    /// the struct pointed to by its last parameter.
+    fn compile_residual(
+        &self,
+        name: &str,
+        body: &Expr,
+        solve: &Solve,
+        capture_type: StructType<'ctx>,
+    ) -> FunctionValue<'ctx> {
+        let opaque = self.ctx.i8_type().ptr_type(AddressSpace::default());
+        let out = self.double.ptr_type(AddressSpace::default());
+        let location = self.location.ptr_type(AddressSpace::default());
+        let sig = self.status
+            .fn_type(&[out.into(), location.into(), self.double.into(), opaque.into()], false);
+
+        let name = (0..)
+            .map(|i| format!("{}.{}", name, i))
+            .find(|name| self.module.get_function(name).is_none())
+            .unwrap();
+        let func = self.module.add_function(&name, sig, None);
+        self.defined.borrow_mut().push(name);
+
+        let caller = self.builder.get_insert_block().unwrap();
+        let caller_location = self.builder.get_current_debug_location();
+        self.start_debug_scope(func, solve.equation.span());
+        let entry = self.ctx.append_basic_block(func, "entry");
+        self.builder.position_at_end(entry);
+
+        let environment = func.get_nth_param(3).unwrap().into_pointer_value();
+        let environment = self.builder.build_pointer_cast(
+            environment,
+            capture_type.ptr_type(AddressSpace::default()),
+            "captures",
+        );
+        let names: Vec<String> = self.scope
+            .borrow()
+            .iter()
+            .map(|&(ref name, _)| name.clone())
+            .collect();
+        let mut scope = Vec::new();
+        for (i, name) in names.into_iter().enumerate() {
+            let field = self.builder
+                .build_struct_gep(environment, i as u32, "capture")
+                .unwrap();
+            scope.push((name.clone(), self.builder.build_load(field, &name)));
+        }
+        scope.push((solve.variable.clone(), func.get_nth_param(2).unwrap()));
+
+        let outer_scope = self.scope.replace(scope);
+        let value = self.compile_expr(body);
+        self.scope.replace(outer_scope);
+
+        let out = func.get_nth_param(0).unwrap().into_pointer_value();
+        self.builder.build_store(out, value);
+        self.builder
+            .build_return(Some(&self.status_code(Status::Ok)));
+
+        self.builder.position_at_end(caller);
+        self.restore_debug_location(caller_location);
+        func
+    }
+
+    /// Get the `fn(*i8, *i8, *i8, f64, *f64, *ErrorLocation) -> u32` which
+    /// the `Program` maps to the equation solver.
+    fn solver(&self) -> FunctionValue<'ctx> {
+        self.module.get_function(SOLVER).unwrap_or_else(|| {
+            let opaque = self.ctx.i8_type().ptr_type(AddressSpace::default());
+            let root = self.double.ptr_type(AddressSpace::default());
+            let location = self.location.ptr_type(AddressSpace::default());
+            let sig = self.status.fn_type(
+                &[
+                    opaque.into(),
+                    opaque.into(),
+                    opaque.into(),
+                    self.double.into(),
+                    root.into(),
+                    location.into(),
+                ],
+                false,
+            );
+            self.module.add_function(SOLVER, sig, None)
+        })
+    }
+
+    /// Add `value` to the double stored at `total`.
+    fn accumulate(&self, total: PointerValue<'ctx>, value: FloatValue<'ctx>) {
+        let previous = self.builder
+            .build_load(total, "previous")
+            .into_float_value();
+        let next = self.builder.build_float_add(previous, value, "next");
+        self.builder.build_store(total, next);
+    }
+
+    fn stack_save(&self) -> PointerValue<'ctx> {
+        let func = self.module
+            .get_function("llvm.stacksave")
+            .unwrap_or_else(|| {
+                let sig = self.ctx
+                    .i8_type()
+                    .ptr_type(AddressSpace::default())
+                    .fn_type(&[], false);
+                self.module
+                    .add_function("llvm.stacksave", sig, None)
+            });
+
+        self.builder
+            .build_call(func, &[], "stack")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    fn stack_restore(&self, stack: PointerValue<'ctx>) {
+        let func = self.module
+            .get_function("llvm.stackrestore")
+            .unwrap_or_else(|| {
+                let sig = self.ctx
+                    .void_type()
+                    .fn_type(&[stack.get_type().into()], false);
+                self.module
+                    .add_function("llvm.stackrestore", sig, None)
+            });
+
+        self.builder
+            .build_call(func, &[stack.into()], "");
+    }
+
+    /// Element-wise arithmetic, done in a loop. A number used alongside an
+    /// array is combined with every element.
+    fn array_binary_op(
+        &self,
+        op: &BinaryOp,
+        left: BasicValueEnum<'ctx>,
+        right: BasicValueEnum<'ctx>,
+    ) -> PointerValue<'ctx> {
+        let len = array_length(&op.left)
+            .or_else(|| array_length(&op.right))
+            .expect("At least one operand is an array");
+        let result = self.allocate_array(len);
+
+        self.build_loop(self.int.const_int(len as u64, false), |i| {
+            let l = self.element_or_scalar(left, i);
+            let r = self.element_or_scalar(right, i);
+            let value = self.float_binary_op(op, l, r).into_float_value();
+            self.store_element(result, i, value);
+        });
+
+        result
+    }
+
+    fn element_or_scalar(
+        &self,
+        value: BasicValueEnum<'ctx>,
+        index: IntValue<'ctx>,
+    ) -> FloatValue<'ctx> {
+        match value {
+            BasicValueEnum::PointerValue(array) => self.load_element(array, index),
+            other => other.into_float_value(),
+        }
+    }
+
+    /// Arrays are stored as a buffer of doubles on the stack.
+    fn compile_array(&self, elements: &[BasicValueEnum<'ctx>]) -> PointerValue<'ctx> {
+        let buffer = self.allocate_array(elements.len());
+
+        for (i, element) in elements.iter().enumerate() {
+            let index = self.int.const_int(i as u64, false);
+            self.store_element(buffer, index, element.into_float_value());
+        }
+
+        buffer
+    }
+
+    /// Indexing is always bounds checked, regardless of whether runtime
+    /// checks are enabled, because reading past the end of the buffer would
+    /// be undefined behaviour.
+    fn compile_index(
+        &self,
+        index: &Index,
+        array: PointerValue<'ctx>,
+        position: IntValue<'ctx>,
+    ) -> FloatValue<'ctx> {
+        let len = array_length(&index.array)
+            .expect("The type checker ensures only arrays are indexed");
+
+        // negative indices wrap around to huge unsigned numbers, so a single
+        // comparison catches both ends
+        let len = self.int.const_int(len as u64, false);
+        let out_of_bounds = self.builder
+            .build_int_compare(IntPredicate::UGE, position, len, "out_of_bounds");
+        self.bail_if(out_of_bounds, Status::IndexOutOfBounds, index.span);
+
+        self.load_element(array, position)
+    }
+
+    fn allocate_array(&self, len: usize) -> PointerValue<'ctx> {
+        let len = self.int.const_int(len as u64, false);
+        self.builder
+            .build_array_alloca(self.double, len, "array")
+    }
+
+    fn load_element(&self, array: PointerValue<'ctx>, index: IntValue<'ctx>) -> FloatValue<'ctx> {
+        let slot = unsafe { self.builder.build_gep(array, &[index], "slot") };
+        self.builder
+            .build_load(slot, "element")
+            .into_float_value()
+    }
+
+    fn store_element(
+        &self,
+        array: PointerValue<'ctx>,
+        index: IntValue<'ctx>,
+        value: FloatValue<'ctx>,
+    ) {
+        let slot = unsafe { self.builder.build_gep(array, &[index], "slot") };
+        self.builder.build_store(slot, value);
+    }
+
+    /// Emit a loop which runs `body` once for every index in `0..len`.
+    ///
+    /// Arrays are never empty (and integrals have at least one panel), so
+    /// the exit condition is only checked after the first iteration.
+    fn build_loop<F>(&self, len: IntValue<'ctx>, mut body: F)
+    where
+        F: FnMut(IntValue<'ctx>),
+    {
+        let func = self.current_function();
+        let preheader = self.builder.get_insert_block().unwrap();
+        let header = self.ctx.append_basic_block(func, "loop");
+        let exit = self.ctx.append_basic_block(func, "after_loop");
+
+        self.builder.build_unconditional_branch(header);
+        self.builder.position_at_end(header);
+
+        let phi = self.builder.build_phi(self.int, "index");
+        let index = phi.as_basic_value().into_int_value();
+        body(index);
+
+        // the body may have appended more blocks (e.g. for runtime checks)
+        let latch = self.builder.get_insert_block().unwrap();
+        let one = self.int.const_int(1, false);
+        let next = self.builder.build_int_add(index, one, "next_index");
+        let done = self.builder
+            .build_int_compare(IntPredicate::EQ, next, len, "done");
+        self.builder
+            .build_conditional_branch(done, exit, header);
+
+        let zero = self.int.const_int(0, false);
+        phi.add_incoming(&[(&zero, preheader), (&next, latch)]);
+
+        self.builder.position_at_end(exit);
+    }
+
+    /// Complex arithmetic, done component-wise on `{double, double}`
+    /// structs.
+    fn complex_binary_op(
+        &self,
+        op: &BinaryOp,
+        left: StructValue<'ctx>,
+        right: StructValue<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        let (a, b) = self.complex_parts(left);
+        let (c, d) = self.complex_parts(right);
+
+        let (re, im) = match op.op {
+            Op::Add => (
+                self.builder.build_float_add(a, c, "re"),
+                self.builder.build_float_add(b, d, "im"),
+            ),
+            Op::Subtract => (
+                self.builder.build_float_sub(a, c, "re"),
+                self.builder.build_float_sub(b, d, "im"),
+            ),
+            Op::Multiply => {
+                // (a + bi)(c + di) = (ac - bd) + (ad + bc)i
+                let ac = self.builder.build_float_mul(a, c, "ac");
+                let bd = self.builder.build_float_mul(b, d, "bd");
+                let ad = self.builder.build_float_mul(a, d, "ad");
+                let bc = self.builder.build_float_mul(b, c, "bc");
+                (
+                    self.builder.build_float_sub(ac, bd, "re"),
+                    self.builder.build_float_add(ad, bc, "im"),
+                )
+            }
+            Op::Divide => {
+                // (a + bi)/(c + di) = ((ac + bd) + (bc - ad)i) / (c² + d²)
+                let cc = self.builder.build_float_mul(c, c, "cc");
+                let dd = self.builder.build_float_mul(d, d, "dd");
+                let denominator = self.builder.build_float_add(cc, dd, "denominator");
+
+                if self.runtime_checks {
+                    let zero = self.double.const_float(0.0);
+                    let re_is_zero = self.builder
+                        .build_float_compare(FloatPredicate::OEQ, c, zero, "re_is_zero");
+                    let im_is_zero = self.builder
+                        .build_float_compare(FloatPredicate::OEQ, d, zero, "im_is_zero");
+                    let is_zero = self.builder
+                        .build_and(re_is_zero, im_is_zero, "is_zero");
+                    self.bail_if(is_zero, Status::DivideByZero, op.span);
+                }
+
+                let ac = self.builder.build_float_mul(a, c, "ac");
+                let bd = self.builder.build_float_mul(b, d, "bd");
+                let bc = self.builder.build_float_mul(b, c, "bc");
+                let ad = self.builder.build_float_mul(a, d, "ad");
+                let re = self.builder.build_float_add(ac, bd, "re");
+                let im = self.builder.build_float_sub(bc, ad, "im");
+                (
+                    self.builder.build_float_div(re, denominator, "re"),
+                    self.builder.build_float_div(im, denominator, "im"),
+                )
+            }
+            Op::Equal | Op::NotEqual => {
+                let re_equal = self.builder
+                    .build_float_compare(FloatPredicate::OEQ, a, c, "re_equal");
+                let im_equal = self.builder
+                    .build_float_compare(FloatPredicate::OEQ, b, d, "im_equal");
+                let equal = self.builder.build_and(re_equal, im_equal, "equal");
+
+                return match op.op {
+                    Op::Equal => equal.into(),
+                    _ => self.builder.build_not(equal, "not_equal").into(),
+                };
+            }
+            other => unreachable!("The type checker doesn't allow {} on complex numbers", other),
+        };
+
+        self.complex_value(re, im).into()
+    }
+
+    fn complex_value(&self, re: FloatValue<'ctx>, im: FloatValue<'ctx>) -> StructValue<'ctx> {
+        let value = self.complex.get_undef();
+        let value = self.builder
+            .build_insert_value(value, re, 0, "with_re")
+            .unwrap();
+        self.builder
+            .build_insert_value(value, im, 1, "complex")
+            .unwrap()
+            .into_struct_value()
+    }
+
+    fn complex_parts(&self, value: StructValue<'ctx>) -> (FloatValue<'ctx>, FloatValue<'ctx>) {
+        let re = self.builder
+            .build_extract_value(value, 0, "re")
+            .unwrap()
+            .into_float_value();
+        let im = self.builder
+            .build_extract_value(value, 1, "im")
+            .unwrap()
+            .into_float_value();
+        (re, im)
+    }
+
+    /// Operations on both integers and bools, which LLVM represents as
+    /// 64-bit and 1-bit integers respectively.
+    ///
+    /// Integer arithmetic is checked, bailing out of `calc_main` with an
+    /// error status on overflow instead of silently wrapping.
+    fn int_binary_op(
+        &self,
+        op: &BinaryOp,
+        left: IntValue<'ctx>,
+        right: IntValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        let predicate = match op.op {
+            Op::Add | Op::Subtract | Op::Multiply => {
+                return self.checked_arithmetic(op, left, right)
+            }
+            Op::IntegerDivide | Op::Modulo => return self.checked_division(op, left, right),
+            Op::And | Op::Or => unreachable!("Logical operators are lowered to conditionals"),
+            Op::Divide => unreachable!("Division is always done using floats"),
+            Op::LessThan => IntPredicate::SLT,
+            Op::LessThanOrEqual => IntPredicate::SLE,
+            Op::Equal => IntPredicate::EQ,
+            Op::NotEqual => IntPredicate::NE,
+            Op::GreaterThan => IntPredicate::SGT,
+            Op::GreaterThanOrEqual => IntPredicate::SGE,
+        };
+
+        self.builder
+            .build_int_compare(predicate, left, right, "cmp")
+    }
+
+    /// Call one of the `llvm.*.with.overflow.i64` intrinsics, returning
+    /// early if the operation overflowed.
+    fn checked_arithmetic(
+        &self,
+        op: &BinaryOp,
+        left: IntValue<'ctx>,
+        right: IntValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        let intrinsic = match op.op {
+            Op::Add => "llvm.sadd.with.overflow.i64",
+            Op::Subtract => "llvm.ssub.with.overflow.i64",
+            Op::Multiply => "llvm.smul.with.overflow.i64",
+            other => unreachable!("There is no overflow intrinsic for {}", other),
+        };
+
+        let func = self.module.get_function(intrinsic).unwrap_or_else(|| {
+            let result = self.ctx
+                .struct_type(&[self.int.into(), self.boolean.into()], false);
+            let sig = result.fn_type(&[self.int.into(), self.int.into()], false);
+            self.module.add_function(intrinsic, sig, None)
+        });
+
+        let result = self.builder
+            .build_call(func, &[left.into(), right.into()], "checked")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_struct_value();
+
+        let value = self.builder
+            .build_extract_value(result, 0, "value")
+            .unwrap()
+            .into_int_value();
+        let overflowed = self.builder
+            .build_extract_value(result, 1, "overflowed")
+            .unwrap()
+            .into_int_value();
+
+        self.bail_if(overflowed, Status::IntegerOverflow, op.span);
+
+        value
+    }
+
+    /// Integer division and modulo, guarding against the cases LLVM leaves
+    /// undefined (dividing by zero and `i64::MIN // -1`).
+    fn checked_division(
+        &self,
+        op: &BinaryOp,
+        left: IntValue<'ctx>,
+        right: IntValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        let zero = self.int.const_int(0, false);
+        let is_zero = self.builder
+            .build_int_compare(IntPredicate::EQ, right, zero, "is_zero");
+        self.bail_if(is_zero, Status::DivideByZero, op.span);
+
+        let min = self.int.const_int(::std::i64::MIN as u64, true);
+        let minus_one = self.int.const_int(-1_i64 as u64, true);
+        let is_min = self.builder
+            .build_int_compare(IntPredicate::EQ, left, min, "is_min");
+        let is_minus_one = self.builder
+            .build_int_compare(IntPredicate::EQ, right, minus_one, "is_minus_one");
+        let overflows = self.builder
+            .build_and(is_min, is_minus_one, "overflows");
+        self.bail_if(overflows, Status::IntegerOverflow, op.span);
 
         match op.op {
-            Op::Add => self.builder.build_float_add(&left, &right, "add"),
-            Op::Subtract => self.builder.build_float_sub(&left, &right, "sub"),
-            Op::Multiply => self.builder.build_float_mul(&left, &right, "mul"),
-            Op::Divide => self.builder.build_float_div(&left, &right, "div"),
+            Op::IntegerDivide => self.builder
+                .build_int_signed_div(left, right, "div"),
+            _ => self.builder.build_int_signed_rem(left, right, "rem"),
+        }
+    }
+
+    /// Return `status` from the current function when `condition` is true,
+    /// recording the offending expression's location. Otherwise continue in
+    /// a fresh basic block.
+    fn bail_if(&self, condition: IntValue<'ctx>, status: Status, span: Span) {
+        let func = self.current_function();
+        let bail = self.ctx.append_basic_block(func, "bail");
+        let next = self.ctx.append_basic_block(func, "continue");
+
+        self.builder
+            .build_conditional_branch(condition, bail, next);
+
+        self.builder.position_at_end(bail);
+        let location = func.get_nth_param(1).unwrap().into_pointer_value();
+        let start = self.builder
+            .build_struct_gep(location, 0, "start")
+            .unwrap();
+        self.builder
+            .build_store(start, self.int.const_int(span.start as u64, false));
+        let end = self.builder
+            .build_struct_gep(location, 1, "end")
+            .unwrap();
+        self.builder
+            .build_store(end, self.int.const_int(span.end as u64, false));
+        self.builder
+            .build_return(Some(&self.status_code(status)));
+
+        self.builder.position_at_end(next);
+    }
+
+    fn status_code(&self, status: Status) -> IntValue<'ctx> {
+        self.status.const_int(status as u64, false)
+    }
+
+    fn current_function(&self) -> FunctionValue<'ctx> {
+        self.builder
+            .get_insert_block()
+            .and_then(|bb| bb.get_parent())
+            .expect("Expressions are always compiled inside a function")
+    }
+
+    /// Give a newly defined function its own `DISubprogram`, starting at
+    /// `span`. Any instructions emitted after this are attributed to it.
+    fn start_debug_scope(&self, func: FunctionValue<'ctx>, span: Span) {
+        let debug_info = match self.debug_info {
+            Some(ref debug_info) => debug_info,
+            None => return,
+        };
+
+        let (line, _) = debug_info.line_and_column(span.start);
+        let ty = debug_info
+            .builder
+            .create_subroutine_type(debug_info.file, None, &[], DIFlags::ZERO);
+        let name = func.get_name().to_string_lossy();
+        let subprogram = debug_info.builder.create_function(
+            debug_info.unit.as_debug_info_scope(),
+            &name,
+            None,
+            debug_info.file,
+            line,
+            ty,
+            false,
+            true,
+            line,
+            DIFlags::ZERO,
+            false,
+        );
+        func.set_subprogram(subprogram);
+
+        let location = debug_info.location(self.ctx, span, subprogram.as_debug_info_scope());
+        self.builder.set_current_debug_location(location);
+    }
+
+    /// Attribute the instructions emitted from now on to the code at `span`.
+    fn set_debug_location(&self, span: Span) {
+        if let Some(ref debug_info) = self.debug_info {
+            let scope = self.current_function()
+                .get_subprogram()
+                .expect("Every function we define has a subprogram")
+                .as_debug_info_scope();
+            let location = debug_info.location(self.ctx, span, scope);
+            self.builder.set_current_debug_location(location);
+        }
+    }
+
+    /// Go back to the debug location the builder had before it was used to
+    /// generate another function.
+    fn restore_debug_location(&self, location: Option<DILocation<'ctx>>) {
+        if let Some(location) = location {
+            self.builder.set_current_debug_location(location);
+        }
+    }
+
+    /// Lower a conditional to a diamond of basic blocks joined by a phi node,
+    /// so only the branch which was selected gets evaluated.
+    fn compile_conditional<T, F>(
+        &self,
+        condition: IntValue<'ctx>,
+        if_true: T,
+        if_false: F,
+    ) -> Result<BasicValueEnum<'ctx>, Never>
+    where
+        T: FnOnce() -> Result<BasicValueEnum<'ctx>, Never>,
+        F: FnOnce() -> Result<BasicValueEnum<'ctx>, Never>,
+    {
+        let func = self.current_function();
+
+        let true_block = self.ctx.append_basic_block(func, "if_true");
+        let false_block = self.ctx.append_basic_block(func, "if_false");
+        let merge = self.ctx.append_basic_block(func, "merge");
+
+        self.builder
+            .build_conditional_branch(condition, true_block, false_block);
+
+        // Compiling a branch may append more blocks (e.g. nested
+        // conditionals), so the phi's incoming edges need to come from
+        // whichever block each branch finished in.
+        self.builder.position_at_end(true_block);
+        let true_value = if_true()?;
+        let true_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(merge);
+
+        self.builder.position_at_end(false_block);
+        let false_value = if_false()?;
+        let false_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(merge);
+
+        self.builder.position_at_end(merge);
+        let phi = self.builder
+            .build_phi(true_value.get_type(), "if_result");
+        phi.add_incoming(&[(&true_value, true_end), (&false_value, false_end)]);
+
+        Ok(phi.as_basic_value())
+    }
+
+    fn compile_cast(&self, cast: &Cast, value: BasicValueEnum<'ctx>) -> BasicValueEnum<'ctx> {
+        match (cast.from, cast.to) {
+            (from, to) if from == to => value,
+            (Type::Integer, Type::Float) => self.builder
+                .build_signed_int_to_float(value.into_int_value(), self.double, "int_to_float")
+                .into(),
+            (Type::Bool, Type::Float) => self.builder
+                .build_unsigned_int_to_float(value.into_int_value(), self.double, "bool_to_float")
+                .into(),
+            (Type::Bool, Type::Integer) => self.builder
+                .build_int_z_extend(value.into_int_value(), self.int, "bool_to_int")
+                .into(),
+            (Type::Float, Type::Integer) => self.builder
+                .build_float_to_signed_int(value.into_float_value(), self.int, "float_to_int")
+                .into(),
+            (_, Type::Complex) => self.to_complex(value).into(),
+            (from, to) => unreachable!("Can't convert a {} to a {}", from, to),
+        }
+    }
+
+    /// Convert a value to a complex number, which is how the final result is
+    /// returned from `calc_main`.
+    fn to_complex(&self, value: BasicValueEnum<'ctx>) -> StructValue<'ctx> {
+        let re = match value {
+            BasicValueEnum::StructValue(z) => return z,
+            BasicValueEnum::FloatValue(f) => f,
+            BasicValueEnum::IntValue(i) if i.get_type().get_bit_width() == 1 => self.builder
+                .build_unsigned_int_to_float(i, self.double, "bool_to_float"),
+            BasicValueEnum::IntValue(i) => self.builder
+                .build_signed_int_to_float(i, self.double, "int_to_float"),
+            other => unreachable!("calc never produces a {:?}", other),
+        };
+
+        self.complex_value(re, self.double.const_float(0.0))
+    }
+
+    /// Built-in functions are implemented using LLVM intrinsics, with
+    /// arguments outside the function's domain being caught when runtime
+    /// checks are enabled.
+    fn compile_function_call(
+        &self,
+        call: &FunctionCall,
+        args: Vec<BasicValueEnum<'ctx>>,
+    ) -> BasicValueEnum<'ctx> {
+        match args.first() {
+            Some(&BasicValueEnum::StructValue(z)) => {
+                return self.complex_function_call(&call.name, z)
+            }
+            Some(&BasicValueEnum::PointerValue(_)) => {
+                return self.compile_reduction(call, &args).into()
+            }
+            _ => {}
+        }
+
+        let args: Vec<FloatValue> = args.into_iter().map(|arg| arg.into_float_value()).collect();
+
+        if let Some(host) = self.env.function(&call.name) {
+            return self.call_host_function(call, host, &args).into();
+        }
+
+        let builtin = builtins::lookup(&call.name)
+            .expect("The type checker ensures only known functions are called");
+        let intrinsic = builtin
+            .intrinsic
+            .expect("The type checker promotes the arguments of complex-only functions");
+
+        if self.runtime_checks {
+            self.check_domain(builtin.domain, args[0], call.span);
         }
+
+        self.call_float_function(intrinsic, &args).into()
     }
 
-    fn compile_function_call(&self, call: &FunctionCall) -> FloatValue {
-        unimplemented!()
+    /// Built-ins which accept a complex number are written in terms of real
+    /// intrinsics and functions from libm.
+    fn complex_function_call(&self, name: &str, z: StructValue<'ctx>) -> BasicValueEnum<'ctx> {
+        let (re, im) = self.complex_parts(z);
+
+        match name {
+            "re" => re.into(),
+            "im" => im.into(),
+            "conj" => {
+                let im = self.builder.build_float_neg(im, "conj");
+                self.complex_value(re, im).into()
+            }
+            "abs" => self.call_float_function("hypot", &[re, im]).into(),
+            "arg" => self.call_float_function("atan2", &[im, re]).into(),
+            "exp" => {
+                // e^(a + bi) = e^a (cos b + i sin b)
+                let magnitude = self.call_float_function("llvm.exp.f64", &[re]);
+                let cos = self.call_float_function("llvm.cos.f64", &[im]);
+                let sin = self.call_float_function("llvm.sin.f64", &[im]);
+                let re = self.builder.build_float_mul(magnitude, cos, "re");
+                let im = self.builder.build_float_mul(magnitude, sin, "im");
+                self.complex_value(re, im).into()
+            }
+            "sqrt" => {
+                // the principal square root, which has a non-negative real
+                // part and an imaginary part with the same sign as the input
+                let two = self.double.const_float(2.0);
+                let modulus = self.call_float_function("hypot", &[re, im]);
+
+                let sum = self.builder.build_float_add(modulus, re, "sum");
+                let half_sum = self.builder.build_float_div(sum, two, "half_sum");
+                let new_re = self.call_float_function("llvm.sqrt.f64", &[half_sum]);
+
+                let difference = self.builder.build_float_sub(modulus, re, "difference");
+                let half_difference = self.builder
+                    .build_float_div(difference, two, "half_difference");
+                let magnitude = self.call_float_function("llvm.sqrt.f64", &[half_difference]);
+                let new_im = self.call_float_function("llvm.copysign.f64", &[magnitude, im]);
+
+                self.complex_value(new_re, new_im).into()
+            }
+            other => unreachable!("The type checker ensures {}() accepts complex numbers", other),
+        }
     }
+
+    /// Reductions loop over their arguments, keeping a running total on the
+    /// stack.
+    fn compile_reduction(
+        &self,
+        call: &FunctionCall,
+        args: &[BasicValueEnum<'ctx>],
+    ) -> FloatValue<'ctx> {
+        let len = array_length(&call.arguments[0])
+            .expect("The type checker ensures reductions are given arrays");
+        let array = args[0].into_pointer_value();
+
+        let initial = match call.name.as_str() {
+            "min" | "max" => self.load_element(array, self.int.const_int(0, false)),
+            _ => self.double.const_float(0.0),
+        };
+        let accumulator = self.builder.build_alloca(self.double, "accumulator");
+        self.builder.build_store(accumulator, initial);
+
+        self.build_loop(self.int.const_int(len as u64, false), |i| {
+            let total = self.builder
+                .build_load(accumulator, "total")
+                .into_float_value();
+            let element = self.load_element(array, i);
+
+            let total = match call.name.as_str() {
+                "sum" | "mean" => self.builder.build_float_add(total, element, "total"),
+                "min" => self.call_float_function("llvm.minnum.f64", &[total, element]),
+                "max" => self.call_float_function("llvm.maxnum.f64", &[total, element]),
+                "dot" => {
+                    let other = self.load_element(args[1].into_pointer_value(), i);
+                    let product = self.builder.build_float_mul(element, other, "product");
+                    self.builder.build_float_add(total, product, "total")
+                }
+                other => unreachable!("Unknown reduction, {}()", other),
+            };
+
+            self.builder.build_store(accumulator, total);
+        });
+
+        let total = self.builder
+            .build_load(accumulator, "total")
+            .into_float_value();
+
+        if call.name == "mean" {
+            let len = self.double.const_float(len as f64);
+            self.builder.build_float_div(total, len, "mean")
+        } else {
+            total
+        }
+    }
+
+    fn call_float_function(&self, name: &str, args: &[FloatValue<'ctx>]) -> FloatValue<'ctx> {
+        let func = self.float_function(name, args.len());
+        let args: Vec<BasicMetadataValueEnum> = args.iter().map(|&arg| arg.into()).collect();
+
+        self.builder
+            .build_call(func, &args, name)
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_float_value()
+    }
+
+    /// Host functions are declared as external symbols which the [`Program`]
+    /// maps to their address. Closures are called indirectly through a
+    /// trampoline which receives a pointer to the closure and its arguments,
+    /// and returns `Status::HostFunctionPanicked` if the closure panics.
+    ///
+    /// The closure's address is baked into the generated code, so the
+    /// [`Compiled`] module keeps the `Environment` it came from alive.
+    ///
+    /// [`Program`]: ../jit/struct.Program.html
+    /// [`Compiled`]: struct.Compiled.html
+    fn call_host_function(
+        &self,
+        call: &FunctionCall,
+        host: &HostFunction,
+        args: &[FloatValue<'ctx>],
+    ) -> FloatValue<'ctx> {
+        let closure = match host.closure_address() {
+            Some(closure) => closure,
+            None => {
+                let func = self.float_function(&HostFunction::symbol(&call.name), host.arity());
+                let args: Vec<BasicMetadataValueEnum> =
+                    args.iter().map(|&arg| arg.into()).collect();
+
+                return self.builder
+                    .build_call(func, &args, &call.name)
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+                    .into_float_value();
+            }
+        };
+
+        let len = self.int.const_int(args.len() as u64, false);
+        let buffer = self.builder
+            .build_array_alloca(self.double, len, "args");
+
+        for (i, arg) in args.iter().enumerate() {
+            let index = self.int.const_int(i as u64, false);
+            let slot = unsafe { self.builder.build_gep(buffer, &[index], "arg") };
+            self.builder.build_store(slot, *arg);
+        }
+
+        let closure = self.int.const_int(closure as u64, false);
+        let out = self.builder.build_alloca(self.double, "closure_out");
+        let args: [BasicMetadataValueEnum; 4] =
+            [closure.into(), buffer.into(), len.into(), out.into()];
+        let status = self.builder
+            .build_call(self.closure_trampoline(), &args, &call.name)
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+
+        let panicked = self.builder.build_int_compare(
+            IntPredicate::NE,
+            status,
+            self.status_code(Status::Ok),
+            "panicked",
+        );
+        self.bail_if(panicked, Status::HostFunctionPanicked, call.span);
+
+        self.builder
+            .build_load(out, "result")
+            .into_float_value()
+    }
+
+    /// Get the `fn(i64, *const f64, i64, *mut f64) -> u32` used to invoke
+    /// closures.
+    fn closure_trampoline(&self) -> FunctionValue<'ctx> {
+        self.module
+            .get_function(CLOSURE_TRAMPOLINE)
+            .unwrap_or_else(|| {
+                let args = self.double.ptr_type(AddressSpace::default());
+                let sig = self.status
+                    .fn_type(&[self.int.into(), args.into(), self.int.into(), args.into()], false);
+                self.module
+                    .add_function(CLOSURE_TRAMPOLINE, sig, None)
+            })
+    }
+
+    fn check_domain(&self, domain: Domain, value: FloatValue<'ctx>, span: Span) {
+        let zero = self.double.const_float(0.0);
+
+        let predicate = match domain {
+            Domain::Any => return,
+            Domain::NonNegative => FloatPredicate::OLT,
+            Domain::Positive => FloatPredicate::OLE,
+        };
+
+        let outside = self.builder
+            .build_float_compare(predicate, value, zero, "outside_domain");
+        self.bail_if(outside, Status::DomainError, span);
+    }
+
+    /// Get a `fn(f64, ...) -> f64` function from the module, declaring it if
+    /// necessary.
+    fn float_function(&self, name: &str, arity: usize) -> FunctionValue<'ctx> {
+        self.module.get_function(name).unwrap_or_else(|| {
+            let params: Vec<BasicMetadataTypeEnum> = (0..arity)
+                .map(|_| self.double.into())
+                .collect();
+            let sig = self.double.fn_type(&params, false);
+            self.module.add_function(name, sig, None)
+        })
+    }
+}
+
+/// Write a formula to its own file in the temporary directory, so debuggers
+/// and profilers have a source file to show alongside its debug info.
+pub fn write_source_file(src: &str) -> Result<PathBuf, Error> {
+    static FORMULAS: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = env::temp_dir().join(format!("calc-{}", process::id()));
+    fs::create_dir_all(&dir)?;
+
+    let n = FORMULAS.fetch_add(1, Ordering::SeqCst);
+    let path = dir.join(format!("formula-{}.calc", n));
+    fs::write(&path, src)?;
+
+    Ok(path)
+}
+
+/// The state needed to attach DWARF debug info to the generated code.
+#[derive(Debug)]
+struct DebugInfo<'ctx> {
+    builder: DebugInfoBuilder<'ctx>,
+    unit: DICompileUnit<'ctx>,
+    file: DIFile<'ctx>,
+    /// The byte offset of the start of each line in the source code.
+    line_starts: Vec<usize>,
+}
+
+impl<'ctx> DebugInfo<'ctx> {
+    /// Convert a byte offset to a 1-based line and column.
+    fn line_and_column(&self, offset: usize) -> (u32, u32) {
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        let column = offset - self.line_starts[line];
+
+        (line as u32 + 1, column as u32 + 1)
+    }
+
+    fn location(&self, ctx: &'ctx Context, span: Span, scope: DIScope<'ctx>) -> DILocation<'ctx> {
+        let (line, column) = self.line_and_column(span.start);
+        self.builder
+            .create_debug_location(ctx, line, column, scope, None)
+    }
+}
+
+fn line_starts(src: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(src.match_indices('\n').map(|(i, _)| i + 1));
+    starts
 }
 
 impl<'ctx> Debug for Compiler<'ctx> {
@@ -100,35 +1586,223 @@ impl<'ctx> Debug for Compiler<'ctx> {
         f.debug_struct("Compiler")
             .field("ctx", self.ctx)
             .field("logger", &self.logger)
+            .field("module", &self.module)
             .field("double", &self.double)
+            .field("int", &self.int)
+            .field("boolean", &self.boolean)
+            .field("status", &self.status)
+            .field("location", &self.location)
+            .field("complex", &self.complex)
+            .field("runtime_checks", &self.runtime_checks)
+            .field("env", &self.env)
+            .field("scope", &self.scope)
+            .field("defined", &self.defined)
+            .field("debug_info", &self.debug_info)
             .finish()
     }
 }
 
+/// Each operation is translated to LLVM IR at the builder's current
+/// position.
+impl<'ctx> Backend for Compiler<'ctx> {
+    type Value = BasicValueEnum<'ctx>;
+    type Error = Never;
+
+    fn scope(&self) -> &RefCell<Vec<(String, BasicValueEnum<'ctx>)>> {
+        &self.scope
+    }
+
+    fn atom(&self, atom: &Atom, _: Span) -> Result<BasicValueEnum<'ctx>, Never> {
+        Ok(self.compile_atom(atom))
+    }
+
+    fn binary_op(
+        &self,
+        op: &BinaryOp,
+        left: BasicValueEnum<'ctx>,
+        right: BasicValueEnum<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>, Never> {
+        self.set_debug_location(op.span);
+        Ok(self.compile_binary_op(op, left, right))
+    }
+
+    fn unary_op(
+        &self,
+        op: &UnaryOp,
+        value: BasicValueEnum<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>, Never> {
+        let value = value.into_int_value();
+        self.set_debug_location(op.span);
+
+        match op.op {
+            UnaryOperator::Not => Ok(self.builder.build_not(value, "not").into()),
+        }
+    }
+
+    fn cast(
+        &self,
+        cast: &Cast,
+        value: BasicValueEnum<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>, Never> {
+        self.set_debug_location(cast.span);
+        Ok(self.compile_cast(cast, value))
+    }
+
+    fn call(
+        &self,
+        call: &FunctionCall,
+        args: Vec<BasicValueEnum<'ctx>>,
+    ) -> Result<BasicValueEnum<'ctx>, Never> {
+        self.set_debug_location(call.span);
+        Ok(self.compile_function_call(call, args))
+    }
+
+    fn conditional<T, F>(
+        &self,
+        condition: BasicValueEnum<'ctx>,
+        if_true: T,
+        if_false: F,
+    ) -> Result<BasicValueEnum<'ctx>, Never>
+    where
+        T: FnOnce() -> Result<BasicValueEnum<'ctx>, Never>,
+        F: FnOnce() -> Result<BasicValueEnum<'ctx>, Never>,
+    {
+        self.compile_conditional(condition.into_int_value(), if_true, if_false)
+    }
+
+    fn series<F>(
+        &self,
+        series: &Series,
+        start: BasicValueEnum<'ctx>,
+        end: BasicValueEnum<'ctx>,
+        term: F,
+    ) -> Result<BasicValueEnum<'ctx>, Never>
+    where
+        F: FnMut(BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, Never>,
+    {
+        let (start, end) = (start.into_int_value(), end.into_int_value());
+        self.set_debug_location(series.span);
+        self.compile_series(series, start, end, term)
+            .map(Into::into)
+    }
+
+    /// An LLVM value is only calculated once, wherever it's used.
+    fn bind<F>(
+        &self,
+        _: &Let,
+        value: BasicValueEnum<'ctx>,
+        body: F,
+    ) -> Result<BasicValueEnum<'ctx>, Never>
+    where
+        F: FnOnce(BasicValueEnum<'ctx>) -> Result<BasicValueEnum<'ctx>, Never>,
+    {
+        body(value)
+    }
+
+    fn array(
+        &self,
+        array: &Array,
+        elements: Vec<BasicValueEnum<'ctx>>,
+    ) -> Result<BasicValueEnum<'ctx>, Never> {
+        self.set_debug_location(array.span);
+        Ok(self.compile_array(&elements).into())
+    }
+
+    fn index(
+        &self,
+        index: &Index,
+        array: BasicValueEnum<'ctx>,
+        position: BasicValueEnum<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>, Never> {
+        let (array, position) = (array.into_pointer_value(), position.into_int_value());
+        self.set_debug_location(index.span);
+        Ok(self.compile_index(index, array, position).into())
+    }
+
+    fn integral(&self, integral: &Integral) -> Result<BasicValueEnum<'ctx>, Never> {
+        self.set_debug_location(integral.span);
+        Ok(self.compile_integral(integral).into())
+    }
+
+    fn solve(&self, solve: &Solve) -> Result<BasicValueEnum<'ctx>, Never> {
+        self.set_debug_location(solve.span);
+        Ok(self.compile_solve(solve).into())
+    }
+}
+
+/// Compile the expression and JIT compile the resulting `Module`.
+impl<'ctx> Jit for Compiler<'ctx> {
+    type Program = Program<'ctx>;
+
+    fn build(self, ast: &Expr) -> Result<Program<'ctx>, Error> {
+        Program::new(&self.compile(ast))
+    }
+}
+
+/// The `Module` generated by the [`Compiler`], along with the host
+/// functions its code calls.
+///
+/// The generated code calls closures through their address, so they need to
+/// outlive the module. Keeping the `Environment` the module was compiled
+/// against means the [`Program`] can hold onto the same closures, and knows
+/// where every host function the module declares can be found.
+///
+/// [`Compiler`]: struct.Compiler.html
+/// [`Program`]: ../jit/struct.Program.html
+#[derive(Debug)]
+pub struct Compiled<'ctx> {
+    /// The generated LLVM IR.
+    pub module: Module<'ctx>,
+    env: Environment,
+    functions: Vec<String>,
+    debug_info: bool,
+}
+
+impl<'ctx> Compiled<'ctx> {
+    /// The host functions the generated code may call.
+    pub fn environment(&self) -> &Environment {
+        &self.env
+    }
+
+    /// The names of the functions defined in the module, i.e. `calc_main`
+    /// and any integrands, residuals and derivatives it uses.
+    pub fn functions(&self) -> &[String] {
+        &self.functions
+    }
+
+    /// Was the module compiled with DWARF debug info?
+    pub fn has_debug_info(&self) -> bool {
+        self.debug_info
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use backend::{CalcMain, ErrorLocation};
     use inkwell::targets::{InitializationConfig, Target};
     use inkwell::values::InstructionOpcode;
     use inkwell::OptimizationLevel;
+    use jit::{Program, RuntimeError};
+    use num_complex::Complex64;
 
     #[test]
     fn compile_a_single_instruction() {
         Target::initialize_native(&InitializationConfig::default()).unwrap();
 
         let should_be = 3.14;
-        let src = Expr::Atom(Atom::Number(should_be));
+        let src = Expr::Atom(Atom::Number(should_be), Span::default());
 
         let ctx = Context::create();
-        let got = Compiler::new(&ctx).compile(&src);
+        let got = Compiler::new(&ctx).compile(&src).module;
 
         let sig = ctx.f32_type().fn_type(&[], false);
-        let _func = got.add_function("dummy", &sig, None);
+        let _func = got.add_function("dummy", sig, None);
 
         let calc_main = got.get_function("calc_main").unwrap();
         assert_eq!(calc_main.count_basic_blocks(), 1);
 
-        let entry = calc_main.get_entry_basic_block().unwrap();
+        let entry = calc_main.get_first_basic_block().unwrap();
         let last_inst = entry.get_last_instruction().unwrap();
 
         assert_eq!(last_inst.get_opcode(), InstructionOpcode::Return);
@@ -139,27 +1813,33 @@ mod tests {
         unsafe {
             let func = ee.get_function::<CalcMain>("calc_main").unwrap();
 
-            let got = func();
-            assert_eq!(got, should_be);
+            let mut got = Complex64::new(0.0, 0.0);
+            let mut location = ErrorLocation::default();
+            let status = func.call(&mut got, &mut location);
+            assert_eq!(Status::from_code(status), Some(Status::Ok));
+            assert_eq!(got, Complex64::new(should_be, 0.0));
         }
     }
 
-    fn execute(src: &str) -> f64 {
-        Target::initialize_native(&InitializationConfig::default()).unwrap();
-
+    fn try_execute_with(src: &str, env: &Environment) -> Result<f64, RuntimeError> {
         let ast = ::syntax::parse(src).unwrap();
+        let (ast, _) = ::sema::type_check(&ast, env).unwrap();
         let ctx = Context::create();
-        let module = Compiler::new(&ctx).compile(&ast);
+        let compiled = Compiler::new(&ctx)
+            .with_runtime_checks(true)
+            .with_environment(env)
+            .compile(&ast);
 
-        let ee = module
-            .create_jit_execution_engine(OptimizationLevel::None)
-            .unwrap();
+        let program = Program::new(&compiled).unwrap();
+        program.call()
+    }
 
-        unsafe {
-            let func = ee.get_function::<CalcMain>("calc_main").unwrap();
+    fn try_execute(src: &str) -> Result<f64, RuntimeError> {
+        try_execute_with(src, &Environment::new())
+    }
 
-            func()
-        }
+    fn execute(src: &str) -> f64 {
+        try_execute(src).unwrap()
     }
 
     #[test]
@@ -177,6 +1857,458 @@ mod tests {
         }
     }
 
+    #[test]
+    fn execute_comparisons_and_boolean_logic() {
+        let inputs = vec![
+            ("1 < 2", 1.0),
+            ("2 <= 1", 0.0),
+            ("3 == 3", 1.0),
+            ("3 != 3", 0.0),
+            ("1 < 2 and 2 > 3", 0.0),
+            ("1 < 2 or 2 > 3", 1.0),
+            ("1 > 2 and 1 // 0 == 0", 0.0),
+            ("1 < 2 or 1 // 0 == 0", 1.0),
+            ("not 1 >= 2", 1.0),
+            ("true != false", 1.0),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = execute(src);
+            assert_eq!(got, should_be, "{}", src);
+        }
+
+        let mut env = Environment::new();
+        env.register_constant("nan", ::std::f64::NAN);
+        let inputs = vec![("nan == nan", 0.0), ("nan != nan", 1.0), ("nan < 1", 0.0)];
+
+        for (src, should_be) in inputs {
+            let got = try_execute_with(src, &env).unwrap();
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn integer_arithmetic_stays_exact() {
+        let inputs = vec![
+            ("9007199254740993 - 9007199254740992", 1.0),
+            ("3 * 4 + 0.5", 12.5),
+            ("7 / 2", 3.5),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = execute(src);
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn execute_integer_division_and_modulo() {
+        let inputs = vec![("7 // 2", 3.0), ("(0 - 7) // 2", -3.0), ("(0 - 7) % 3", -1.0)];
+
+        for (src, should_be) in inputs {
+            let got = execute(src);
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn integer_overflow_is_reported() {
+        let inputs = vec![
+            "9223372036854775807 + 1",
+            "(0 - 9223372036854775807) - 2",
+            "4294967296 * 4294967296",
+            "(0 - 9223372036854775807 - 1) // (0 - 1)",
+        ];
+
+        for src in inputs {
+            match try_execute(src) {
+                Err(RuntimeError::IntegerOverflow { .. }) => {}
+                other => panic!("Expected an overflow for {:?}, found {:?}", src, other),
+            }
+        }
+    }
+
+    #[test]
+    fn runtime_errors_know_which_expression_failed() {
+        let inputs = vec![
+            ("1 + 1 // 0", Status::DivideByZero, "1 // 0"),
+            ("1 % (2 - 2)", Status::DivideByZero, "1 % (2 - 2)"),
+            ("2 * (100 / 0)", Status::DivideByZero, "100 / 0"),
+            ("sqrt(4) + sqrt(0 - 1)", Status::DomainError, "sqrt(0 - 1)"),
+            ("ln(0.0)", Status::DomainError, "ln(0.0)"),
+            ("[1, 2][1 + 1]", Status::IndexOutOfBounds, "[1, 2][1 + 1]"),
+            ("[1, 2][0 - 1]", Status::IndexOutOfBounds, "[1, 2][0 - 1]"),
+        ];
+
+        for (src, status, culprit) in inputs {
+            let err = try_execute(src).unwrap_err();
+
+            assert_eq!(err.status(), Some(status), "{}", src);
+            assert_eq!(err.source_text(src), Some(culprit), "{}", src);
+        }
+    }
+
+    #[test]
+    fn execute_builtin_functions() {
+        let inputs = vec![
+            ("sqrt(16)", 4.0),
+            ("abs(1 - 3)", 2.0),
+            ("pow(2, 10)", 1024.0),
+            ("max(1, 2.5)", 2.5),
+            ("floor(2.7) + ceil(2.1)", 5.0),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = execute(src);
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn constants_are_inlined() {
+        let mut env = Environment::new();
+        env.register_constant("radius", 2.0);
+
+        let got = try_execute_with("pi * radius * radius", &env).unwrap();
+        assert_eq!(got, ::std::f64::consts::PI * 4.0);
+
+        let ast = ::syntax::parse("e").unwrap();
+        let (ast, _) = ::sema::type_check(&ast, &env).unwrap();
+        let ctx = Context::create();
+        let module = Compiler::new(&ctx).compile(&ast).module;
+
+        let calc_main = module.get_function(CALC_ENTRYPOINT).unwrap();
+        let entry = calc_main.get_first_basic_block().unwrap();
+        let store = entry.get_first_instruction().unwrap();
+        assert_eq!(store.get_opcode(), InstructionOpcode::Store);
+    }
+
+    #[test]
+    fn unchecked_mode_follows_ieee_754() {
+        let ast = ::syntax::parse("1 / 0.0 + sqrt(0 - 1)").unwrap();
+        let (ast, _) = ::sema::type_check(&ast, &Environment::new()).unwrap();
+        let ctx = Context::create();
+        let compiled = Compiler::new(&ctx).compile(&ast);
+
+        let got = Program::new(&compiled)
+            .unwrap()
+            .call()
+            .unwrap();
+        assert!(got.is_nan());
+    }
+
+    extern "C" fn compound_interest(principal: f64, rate: f64, years: f64) -> f64 {
+        principal * (1.0 + rate).powf(years)
+    }
+
+    #[test]
+    fn call_an_extern_host_function() {
+        let mut env = Environment::new();
+        env.register_function(
+            "compound",
+            compound_interest as extern "C" fn(f64, f64, f64) -> f64,
+        );
+
+        let got = try_execute_with("compound(100, 0.1, 2) - 100", &env).unwrap();
+
+        assert!((got - 21.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn call_a_host_closure() {
+        let scale = 3.0;
+        let mut env = Environment::new();
+        env.register_closure("scaled_sum", 2, move |args| scale * (args[0] + args[1]))
+            .register_closure("sqrt", 1, |_| 42.0);
+
+        assert_eq!(try_execute_with("scaled_sum(1, 2.5)", &env).unwrap(), 10.5);
+        assert_eq!(try_execute_with("sqrt(16)", &env).unwrap(), 42.0);
+    }
+
+    #[test]
+    fn mapping_over_closure_calls_only_calls_each_once() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let calls = Rc::new(Cell::new(0));
+        let counter = Rc::clone(&calls);
+        let mut env = Environment::new();
+        env.register_closure("f", 1, move |args| {
+            counter.set(counter.get() + 1);
+            args[0]
+        });
+
+        let inputs = vec![
+            ("sum(map(x -> x * 2, map(y -> y + 1, [f(1), f(2)] * 3)))", 22.0, 2),
+            ("sum(map(x -> x * x, [f(1), f(2)] + f(3)))", 41.0, 3),
+            ("sum(map(x -> x * x, map(y -> y * y, [f(1), 2] * f(2))))", 272.0, 2),
+        ];
+
+        for (src, should_be, expected_calls) in inputs {
+            calls.set(0);
+            let got = try_execute_with(src, &env).unwrap();
+
+            assert_eq!(got, should_be, "{}", src);
+            assert_eq!(calls.get(), expected_calls, "{}", src);
+        }
+    }
+
+    #[test]
+    fn closures_outlive_the_environment_they_were_registered_with() {
+        let ctx = Context::create();
+        let compiled = {
+            let offset = vec![1.0, 2.0];
+            let mut env = Environment::new();
+            env.register_closure("offset", 1, move |args| args[0] + offset[1]);
+
+            let ast = ::syntax::parse("offset(40)").unwrap();
+            let (ast, _) = ::sema::type_check(&ast, &env).unwrap();
+            Compiler::new(&ctx).with_environment(&env).compile(&ast)
+        };
+
+        assert_eq!(Program::new(&compiled).unwrap().call().unwrap(), 42.0);
+    }
+
+    #[test]
+    fn panicking_closures_are_an_error() {
+        let mut env = Environment::new();
+        env.register_closure("oops", 1, |_| panic!("Oops"));
+
+        let src = "1 + oops(2)";
+        let err = try_execute_with(src, &env).unwrap_err();
+
+        assert_eq!(err.status(), Some(Status::HostFunctionPanicked));
+        assert_eq!(err.source_text(src), Some("oops(2)"));
+    }
+
+    fn execute_complex(src: &str) -> Complex64 {
+        let ast = ::syntax::parse(src).unwrap();
+        let env = Environment::new();
+        let (ast, _) = ::sema::type_check(&ast, &env).unwrap();
+        let ctx = Context::create();
+        let compiled = Compiler::new(&ctx)
+            .with_runtime_checks(true)
+            .compile(&ast);
+
+        let program = Program::new(&compiled).unwrap();
+        program.call_complex().unwrap()
+    }
+
+    #[test]
+    fn execute_complex_arithmetic() {
+        let inputs = vec![
+            ("3 + 4i", Complex64::new(3.0, 4.0)),
+            ("(1 + 2i) * (3 - 1i)", Complex64::new(5.0, 5.0)),
+            ("(1 + 1i) / (1 - 1i)", Complex64::new(0.0, 1.0)),
+            ("conj(2 + 3i) + re(1i) + im(1i)", Complex64::new(3.0, -3.0)),
+            ("sqrt(0 - 4i * 1i) + sqrt(2i)", Complex64::new(3.0, 1.0)),
+            ("abs(3 + 4i) + arg(1i) * 0", Complex64::new(5.0, 0.0)),
+            ("if 1i * 1i == 0 - 1 then 1i else 0", Complex64::new(0.0, 1.0)),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = execute_complex(src);
+            assert!((got - should_be).norm() < 1e-12, "{} gave {}", src, got);
+        }
+
+        let got = execute_complex("exp(1i * pi) + 1");
+        assert!(got.norm() < 1e-12);
+    }
+
+    #[test]
+    fn complex_division_by_zero_is_reported() {
+        let err = try_execute("abs(1i / (0 * 1i))").unwrap_err();
+        assert_eq!(err.status(), Some(Status::DivideByZero));
+    }
+
+    #[test]
+    fn conditionals_use_basic_blocks_and_a_phi() {
+        let src = ::syntax::parse("if 1 < 2 then 10 else 20").unwrap();
+        let (src, _) = ::sema::type_check(&src, &Environment::new()).unwrap();
+
+        let ctx = Context::create();
+        let module = Compiler::new(&ctx).compile(&src).module;
+
+        let calc_main = module.get_function("calc_main").unwrap();
+        assert_eq!(calc_main.count_basic_blocks(), 4);
+    }
+
+    #[test]
+    fn arrays_are_lowered_to_loops() {
+        let inputs = vec![
+            ("[1, 2, 3][1]", 2.0),
+            ("([1, 2, 3] + [10, 20, 30])[2]", 33.0),
+            ("(2 * [1.5, 2.5])[1]", 5.0),
+            ("sum([1, 2, 3] / 2)", 3.0),
+            ("mean([1, 2, 3, 4])", 2.5),
+            ("min([3, 1, 2]) + max([3, 1, 2])", 4.0),
+            ("dot([1, 2, 3], [4, 5, 6])", 32.0),
+            ("sum(if 1 < 2 then [1, 2] else [3, 4])", 3.0),
+            ("sum(map(x -> x * x, [1, 2, 3]) + 1)", 17.0),
+            ("sum(map(x -> x * x, [1, 2, 3] + 1))", 29.0),
+            ("sum(map(x -> x * 2, [1, 2, 3] + 1))", 18.0),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = execute(src);
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
+    #[test]
+    fn series_are_lowered_to_counted_loops() {
+        let inputs = vec![
+            ("sum(i, 1, 4, i)", 10.0),
+            ("prod(k, 1, 5, k)", 120.0),
+            ("sum(i, 5, 1, i) + prod(i, 5, 1, i)", 1.0),
+            ("sum(n, 1, 3, sum(i, 1, n, i))", 10.0),
+            ("sum(i, 1, 2, sum(i, 1, 3, i))", 12.0),
+            ("sum(i, 0, 2, [1, 2, 4][i] * i)", 10.0),
+            ("sum(i, 0, 100000, [1, 2][i % 2])", 150001.0),
+            ("sum(i, 9223372036854775806, 9223372036854775807, 1)", 2.0),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = execute(src);
+            assert_eq!(got, should_be, "{}", src);
+        }
+
+        // e = 1/0! + 1/1! + 1/2! + ...
+        let got = execute("sum(k, 0, 20, 1 / prod(j, 1, k, j))");
+        assert!((got - ::std::f64::consts::E).abs() < 1e-12);
+    }
+
+    #[test]
+    fn integrands_are_compiled_to_their_own_function() {
+        let inputs = vec![
+            ("integrate(x * x, x, 0, 3)", 9.0),
+            ("integrate(t -> sin(t), 0, pi)", 2.0),
+            ("integrate(x, x, 1, 0)", -0.5),
+            ("integrate(integrate(x * y, y, 0, 1), x, 0, 2)", 1.0),
+            ("sum(k, 1, 3, integrate(pow(x, k), x, 0, 1))", 1.0 / 2.0 + 1.0 / 3.0 + 1.0 / 4.0),
+            ("integrate(exp(0 - x * x), x, 0 - 10, 10)", ::std::f64::consts::PI.sqrt()),
+            ("integrate(abs(x), x, 0 - 1, 2)", 2.5),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = execute(src);
+            assert!((got - should_be).abs() < 1e-8, "{} = {}", src, got);
+        }
+
+        let src = "1 + integrate(sqrt(x), x, 0 - 1, 1)";
+        let err = try_execute(src).unwrap_err();
+        assert_eq!(err.status(), Some(Status::DomainError));
+        assert_eq!(err.source_text(src), Some("sqrt(x)"));
+
+        let src = "2 * integrate(1 / x, x, 0, 1)";
+        let err = try_execute(src).unwrap_err();
+        assert_eq!(err.status(), Some(Status::NoConvergence));
+        assert_eq!(err.source_text(src), Some("integrate(1 / x, x, 0, 1)"));
+    }
+
+    #[test]
+    fn solve_equations_at_runtime() {
+        let inputs = vec![
+            ("solve(x * x == 2, x, 1)", 2.0_f64.sqrt()),
+            ("solve(x -> cos(x) == x, 1)", 0.739_085_133_215_160_6),
+            ("solve(floor(x) + x / 4 == 2.6, x, 0)", 2.4),
+            ("sum(k, 1, 3, solve(x * k == 1, x, 5))", 1.0 + 1.0 / 2.0 + 1.0 / 3.0),
+            ("solve(integrate(t, t, 0, x) == 8, x, 1)", 4.0),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = execute(src);
+            assert!((got - should_be).abs() < 1e-10, "{} = {}", src, got);
+        }
+
+        let src = "1 + solve(x * x == 0 - 1, x, 1)";
+        let err = try_execute(src).unwrap_err();
+        assert_eq!(err.status(), Some(Status::NoSolution));
+        assert_eq!(err.source_text(src), Some("solve(x * x == 0 - 1, x, 1)"));
+    }
+
+    #[test]
+    fn debug_info_points_back_at_the_source() {
+        let src = "integrate(x * x, x, 0, 1)\n    + solve(x * x == 2, x, 1)\n    + sqrt(16)";
+        let ast = ::syntax::parse(src).unwrap();
+        let (ast, _) = ::sema::type_check(&ast, &Environment::new()).unwrap();
+        let ctx = Context::create();
+        let compiled = Compiler::new(&ctx)
+            .with_debug_info(Path::new("/tmp/calc/formula.calc"), src)
+            .compile(&ast);
+
+        assert!(compiled.has_debug_info());
+        compiled.module.verify().unwrap();
+
+        let names = ["calc_main", "integrand.0", "residual.0", "derivative.0"];
+        assert_eq!(compiled.functions(), &names[..]);
+        for name in &names {
+            let func = compiled.module.get_function(name).unwrap();
+            assert!(func.get_subprogram().is_some(), "{}", name);
+        }
+
+        let ir = compiled.module.print_to_string().to_string();
+        let should_contain = [
+            r#"!DIFile(filename: "formula.calc", directory: "/tmp/calc")"#,
+            r#"!DISubprogram(name: "integrand.0""#,
+            // the `x * x` being integrated
+            "!DILocation(line: 1, column: 11,",
+            // the call to `sqrt()`
+            "!DILocation(line: 3, column: 7,",
+        ];
+        for needle in &should_contain {
+            assert!(ir.contains(needle), "{} isn't in {}", needle, ir);
+        }
+    }
+
+    #[test]
+    fn programs_with_debug_info_are_registered_with_perf() {
+        // LLVM's perf listener is only created once, and this is the only
+        // test which uses it
+        let dir = env::temp_dir().join(format!("calc-jitdump-{}", process::id()));
+        env::set_var("JITDUMPDIR", &dir);
+
+        let src = "integrate(x * x, x, 0, 3)";
+        let path = write_source_file(src).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), src);
+
+        let ast = ::syntax::parse(src).unwrap();
+        let (ast, _) = ::sema::type_check(&ast, &Environment::new()).unwrap();
+        let ctx = Context::create();
+        let compiled = Compiler::new(&ctx)
+            .with_debug_info(&path, src)
+            .compile(&ast);
+        let program = Program::new(&compiled).unwrap();
+        assert!((program.call().unwrap() - 9.0).abs() < 1e-8);
+
+        let jitdump = format!("jit-{}.dump", process::id());
+        let dump = fs::read_dir(dir.join(".debug").join("jit"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path().join(&jitdump))
+            .find(|dump| dump.exists())
+            .expect("LLVM should have written a jitdump");
+        let dump = String::from_utf8_lossy(&fs::read(dump).unwrap()).into_owned();
+
+        for needle in &["calc_main", "integrand.0", path.to_str().unwrap()] {
+            assert!(dump.contains(needle), "{} isn't in the jitdump", needle);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn execute_a_piecewise_formula() {
+        let inputs = vec![
+            ("if 5000 > 18200 then 1 else if 5000 > 5000 then 2 else 3", 3.0),
+            ("if 20000 > 18200 then (20000 - 18200) * 0.19 else 0", 1800.0 * 0.19),
+        ];
+
+        for (src, should_be) in inputs {
+            let got = execute(src);
+            assert_eq!(got, should_be, "{}", src);
+        }
+    }
+
     #[test]
     fn execute_a_more_complex_statement() {
         let src = "5 * (100 + 3) / 9 - 2.5";
@@ -0,0 +1,163 @@
+use failure::Error;
+use inkwell::module::Module;
+use inkwell::passes::{PassManager, PassManagerBuilder};
+use inkwell::targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target,
+                       TargetMachine};
+use inkwell::OptimizationLevel;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The kinds of output which can be generated from a compiled `Module`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Emit {
+    /// Human-readable LLVM IR (`*.ll`).
+    Ir,
+    /// LLVM bitcode (`*.bc`).
+    Bitcode,
+    /// Assembly for the host machine (`*.s`).
+    Assembly,
+    /// An object file for the host machine (`*.o`).
+    Object,
+}
+
+impl Emit {
+    /// Every kind of output, in the order they're usually generated.
+    pub const ALL: [Emit; 4] = [Emit::Ir, Emit::Bitcode, Emit::Assembly, Emit::Object];
+
+    /// The file extension conventionally used for this kind of output.
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            Emit::Ir => "ll",
+            Emit::Bitcode => "bc",
+            Emit::Assembly => "s",
+            Emit::Object => "o",
+        }
+    }
+}
+
+impl Display for Emit {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let name = match *self {
+            Emit::Ir => "ir",
+            Emit::Bitcode => "bc",
+            Emit::Assembly => "asm",
+            Emit::Object => "obj",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Emit {
+    type Err = Error;
+
+    /// Parse the names used by `--emit`, i.e. `ir`, `bc`, `asm` or `obj`.
+    fn from_str(s: &str) -> Result<Emit, Error> {
+        Emit::ALL
+            .iter()
+            .cloned()
+            .find(|emit| emit.to_string() == s)
+            .ok_or_else(|| format_err!("Unknown output kind, \"{}\"", s))
+    }
+}
+
+/// Run LLVM's standard optimisation pipeline over a `Module`, the same
+/// passes `clang` would use at the equivalent `-O` level.
+pub fn optimize(module: &Module, level: OptimizationLevel) {
+    let builder = PassManagerBuilder::create();
+    builder.set_optimization_level(level);
+
+    let passes = PassManager::create(());
+    builder.populate_module_pass_manager(&passes);
+    passes.run_on(module);
+}
+
+/// Write a `Module` to `path` in the requested format. Assembly and object
+/// files are generated for the host machine.
+pub fn write_output(module: &Module, emit: Emit, path: &Path) -> Result<(), Error> {
+    match emit {
+        Emit::Ir => fs::write(path, module.print_to_string().to_string())?,
+        Emit::Bitcode => {
+            if !module.write_bitcode_to_path(path) {
+                return Err(format_err!("Unable to write bitcode to {}", path.display()));
+            }
+        }
+        Emit::Assembly => native_machine()?
+            .write_to_file(module, FileType::Assembly, path)
+            .map_err(|e| format_err!("Unable to write assembly: {}", e))?,
+        Emit::Object => native_machine()?
+            .write_to_file(module, FileType::Object, path)
+            .map_err(|e| format_err!("Unable to write an object file: {}", e))?,
+    }
+
+    Ok(())
+}
+
+fn native_machine() -> Result<TargetMachine, Error> {
+    Target::initialize_native(&InitializationConfig::default())
+        .map_err(|e| format_err!("Unable to initialize the native target: {}", e))?;
+
+    let triple = TargetMachine::get_default_triple();
+    let target = Target::from_triple(&triple)
+        .map_err(|e| format_err!("Unable to find a target for {}: {}", triple, e))?;
+
+    target
+        .create_target_machine(
+            &triple,
+            "generic",
+            "",
+            OptimizationLevel::Default,
+            RelocMode::PIC,
+            CodeModel::Default,
+        )
+        .ok_or_else(|| format_err!("Unable to create a target machine for {}", triple))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use environment::Environment;
+    use inkwell::context::Context;
+    use std::env;
+    use trans::Compiler;
+
+    #[test]
+    fn parse_output_kinds() {
+        for &emit in &Emit::ALL {
+            assert_eq!(emit.to_string().parse::<Emit>().unwrap(), emit);
+        }
+
+        assert!("exe".parse::<Emit>().is_err());
+    }
+
+    #[test]
+    fn write_each_kind_of_output() {
+        let ast = ::syntax::parse("if 2 > 1 then sqrt(16) else 0").unwrap();
+        let (ast, _) = ::sema::type_check(&ast, &Environment::new()).unwrap();
+        let ctx = Context::create();
+        let module = Compiler::new(&ctx).compile(&ast).module;
+
+        let dir = env::temp_dir().join(format!("calc-emit-{}", ::std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |emit: Emit| dir.join(format!("main.{}", emit.extension()));
+
+        write_output(&module, Emit::Ir, &path(Emit::Ir)).unwrap();
+        let before = fs::read_to_string(path(Emit::Ir)).unwrap();
+        assert!(before.contains("define i32 @calc_main"));
+        assert!(before.contains("phi"));
+
+        // constant folding gets rid of the branch
+        optimize(&module, OptimizationLevel::Aggressive);
+        write_output(&module, Emit::Ir, &path(Emit::Ir)).unwrap();
+        let after = fs::read_to_string(path(Emit::Ir)).unwrap();
+        assert!(!after.contains("phi"));
+
+        for &emit in &[Emit::Bitcode, Emit::Assembly, Emit::Object] {
+            write_output(&module, emit, &path(emit)).unwrap();
+            assert!(fs::metadata(path(emit)).unwrap().len() > 0, "{}", emit);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,22 +3,31 @@
 #![allow(missing_docs)]
 
 mod compiler;
+mod emit;
 
-pub use self::compiler::Compiler;
+pub use self::compiler::{write_source_file, Compiled, Compiler};
+pub use self::emit::{optimize, write_output, Emit};
+pub use backend::{CalcMain, ErrorLocation, Status, CALC_ENTRYPOINT};
 
 use syntax::Expr;
 use inkwell::context::Context;
-use inkwell::module::Module;
 use failure::Error;
 use slog::Logger;
 
-use syntax::visit::Visitor;
+use environment::Environment;
+use sema;
 
-pub fn translate(ast: &Expr, ctx: &Context, logger: &Logger) -> Result<Module, Error> {
+pub fn translate<'ctx>(
+    ast: &Expr,
+    env: &Environment,
+    ctx: &'ctx Context,
+    logger: &Logger,
+) -> Result<Compiled<'ctx>, Error> {
     info!(logger, "Starting the compilation phase");
 
-    let c = Compiler::new_with_logger(ctx, logger);
-    c.compile(ast);
+    let (ast, ty) = sema::type_check(ast, env)?;
+    debug!(logger, "Type checking succeeded"; "type" => ty.to_string());
 
-    unimplemented!()
+    let c = Compiler::new_with_logger(ctx, logger).with_environment(env);
+    Ok(c.compile(&ast))
 }
@@ -0,0 +1,247 @@
+//! The physical units `calc` knows about.
+//!
+//! Every unit is defined as a multiple of the SI base units, so compatible
+//! units (e.g. `km` and `m`) can be converted between automatically.
+
+use std::fmt::{self, Display, Formatter};
+
+/// The names of the SI base units, in the same order as a `Dimension`'s
+/// exponents.
+const BASE_UNITS: [&str; 7] = ["kg", "m", "s", "A", "K", "mol", "cd"];
+
+/// The exponents of each SI base unit (mass, length, time, current,
+/// temperature, amount of substance and luminous intensity).
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Dimension {
+    exponents: [i8; 7],
+}
+
+impl Dimension {
+    /// A dimensionless quantity (i.e. a plain number).
+    pub fn dimensionless() -> Dimension {
+        Dimension::default()
+    }
+
+    /// Is this a plain number?
+    pub fn is_dimensionless(&self) -> bool {
+        *self == Dimension::dimensionless()
+    }
+
+    /// The dimension of the product of two quantities, or `None` if an
+    /// exponent is too large to represent.
+    pub fn mul(&self, other: Dimension) -> Option<Dimension> {
+        self.combine(other, |a, b| a.checked_add(b))
+    }
+
+    /// The dimension of the quotient of two quantities, or `None` if an
+    /// exponent is too large to represent.
+    pub fn div(&self, other: Dimension) -> Option<Dimension> {
+        self.combine(other, |a, b| a.checked_sub(b))
+    }
+
+    /// Raise a quantity to an integer power, or `None` if an exponent is too
+    /// large to represent.
+    pub fn powi(&self, n: i8) -> Option<Dimension> {
+        self.combine(Dimension::default(), |a, _| a.checked_mul(n))
+    }
+
+    /// The dimension of a quantity's square root, if every exponent is even.
+    pub fn sqrt(&self) -> Option<Dimension> {
+        if self.exponents.iter().all(|e| e % 2 == 0) {
+            self.combine(Dimension::default(), |a, _| Some(a / 2))
+        } else {
+            None
+        }
+    }
+
+    fn combine<F>(&self, other: Dimension, func: F) -> Option<Dimension>
+    where
+        F: Fn(i8, i8) -> Option<i8>,
+    {
+        let mut exponents = [0; 7];
+        for i in 0..exponents.len() {
+            exponents[i] = func(self.exponents[i], other.exponents[i])?;
+        }
+        Some(Dimension { exponents })
+    }
+}
+
+impl Display for Dimension {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.is_dimensionless() {
+            return write!(f, "dimensionless");
+        }
+
+        let mut first = true;
+        for (name, &exponent) in BASE_UNITS.iter().zip(self.exponents.iter()) {
+            if exponent == 0 {
+                continue;
+            }
+            if !first {
+                write!(f, " ")?;
+            }
+            first = false;
+
+            if exponent == 1 {
+                write!(f, "{}", name)?;
+            } else {
+                write!(f, "{}^{}", name, exponent)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A unit, defined as a multiple of the SI base units.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Unit {
+    /// The unit's symbol (e.g. `km`).
+    pub symbol: &'static str,
+    /// How many of the corresponding SI unit there are in one of these.
+    pub factor: f64,
+    /// What the unit measures.
+    pub dimension: Dimension,
+}
+
+macro_rules! unit {
+    ($symbol:expr, $factor:expr, $mass:expr, $length:expr, $time:expr) => {
+        unit!($symbol, $factor, $mass, $length, $time, 0, 0)
+    };
+    ($symbol:expr, $factor:expr, $mass:expr, $length:expr, $time:expr, $current:expr) => {
+        unit!($symbol, $factor, $mass, $length, $time, $current, 0)
+    };
+    (
+        $symbol:expr,
+        $factor:expr,
+        $mass:expr,
+        $length:expr,
+        $time:expr,
+        $current:expr,
+        $temperature:expr
+    ) => {
+        Unit {
+            symbol: $symbol,
+            factor: $factor,
+            dimension: Dimension {
+                exponents: [$mass, $length, $time, $current, $temperature, 0, 0],
+            },
+        }
+    };
+}
+
+/// Every unit which can be used in a `calc` expression.
+pub const UNITS: &[Unit] = &[
+    // length
+    unit!("m", 1.0, 0, 1, 0),
+    unit!("km", 1000.0, 0, 1, 0),
+    unit!("cm", 0.01, 0, 1, 0),
+    unit!("mm", 0.001, 0, 1, 0),
+    // mass
+    unit!("kg", 1.0, 1, 0, 0),
+    unit!("g", 0.001, 1, 0, 0),
+    // time
+    unit!("s", 1.0, 0, 0, 1),
+    unit!("ms", 0.001, 0, 0, 1),
+    unit!("min", 60.0, 0, 0, 1),
+    unit!("h", 3600.0, 0, 0, 1),
+    // volume
+    unit!("L", 0.001, 0, 3, 0),
+    // electromagnetism
+    unit!("A", 1.0, 0, 0, 0, 1),
+    unit!("C", 1.0, 0, 0, 1, 1),
+    unit!("V", 1.0, 1, 2, -3, -1),
+    unit!("ohm", 1.0, 1, 2, -3, -2),
+    // temperature
+    unit!("K", 1.0, 0, 0, 0, 0, 1),
+    // derived mechanical units
+    unit!("Hz", 1.0, 0, 0, -1),
+    unit!("N", 1.0, 1, 1, -2),
+    unit!("Pa", 1.0, 1, -1, -2),
+    unit!("J", 1.0, 1, 2, -2),
+    unit!("W", 1.0, 1, 2, -3),
+];
+
+/// Find the unit with this symbol.
+pub fn lookup(symbol: &str) -> Option<&'static Unit> {
+    UNITS.iter().find(|u| u.symbol == symbol)
+}
+
+/// Parse a compound unit like `kg*m/s^2`, returning its conversion factor
+/// to SI units and its dimension.
+///
+/// Units are separated by `*` or `/`, with everything after the first `/`
+/// being in the denominator, and may be raised to an integer power using
+/// `^`.
+pub fn parse(text: &str) -> Option<(f64, Dimension)> {
+    let mut factor = 1.0;
+    let mut dimension = Dimension::dimensionless();
+    let mut in_denominator = false;
+    let mut rest = text;
+
+    loop {
+        let end = rest.find(|c| c == '*' || c == '/').unwrap_or(rest.len());
+        let (term, tail) = rest.split_at(end);
+
+        let (symbol, power) = match term.find('^') {
+            Some(ix) => (&term[..ix], term[ix + 1..].parse::<i8>().ok()?),
+            None => (term, 1),
+        };
+        let unit = lookup(symbol)?;
+        let power = if in_denominator {
+            power.checked_neg()?
+        } else {
+            power
+        };
+
+        factor *= unit.factor.powi(i32::from(power));
+        dimension = dimension.mul(unit.dimension.powi(power)?)?;
+
+        if tail.is_empty() {
+            return Some((factor, dimension));
+        }
+        in_denominator = in_denominator || tail.starts_with('/');
+        rest = &tail[1..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn length_per_time(length: i8, time: i8) -> Dimension {
+        Dimension {
+            exponents: [0, length, time, 0, 0, 0, 0],
+        }
+    }
+
+    #[test]
+    fn parse_compound_units() {
+        let newton = lookup("N").unwrap().dimension;
+        let inputs = vec![
+            ("m", 1.0, length_per_time(1, 0)),
+            ("km/h", 1000.0 / 3600.0, length_per_time(1, -1)),
+            ("kg*m/s^2", 1.0, newton),
+            ("g*cm/s*s", 1e-5, newton),
+            ("m^-1", 1.0, length_per_time(-1, 0)),
+        ];
+
+        for (src, factor, dimension) in inputs {
+            let got = parse(src).unwrap();
+            assert!((got.0 - factor).abs() < 1e-12, "{}", src);
+            assert_eq!(got.1, dimension, "{}", src);
+        }
+
+        assert!(parse("furlong").is_none());
+        assert!(parse("m^x").is_none());
+        assert!(parse("m^100*m^100").is_none());
+        assert!(parse("s/m^-128").is_none());
+    }
+
+    #[test]
+    fn display_a_dimension() {
+        let acceleration = parse("m/s^2").unwrap().1;
+        assert_eq!(acceleration.to_string(), "m s^-2");
+        assert_eq!(Dimension::dimensionless().to_string(), "dimensionless");
+    }
+}